@@ -1,96 +1,137 @@
 // git-enhancer/src/ai_explainer.rs
-use crate::ai_utils::{
-    ChatMessage, OpenAIChatCompletionResponse, OpenAIChatRequest, clean_ai_output,
-};
+//! Explanations of git commands and of their output.
+
+use crate::ai_utils::{AiReply, ChatMessage, OpenAIChatRequest, clean_ai_output, send_chat_request};
 use crate::config::AppConfig;
 use crate::errors::AIError;
+use crate::post_generate;
+use crate::remotes;
+use crate::untrusted::{self, UNTRUSTED_CONTENT_RULES};
+use serde::Deserialize;
+
+const DIFF_EXPLANATION_REQUEST: &str = "Explain what the changes in the diff below do, and why they \
+    might have been made. Go file by file where that helps.";
+
+/// Characters of a diff or of a command's output sent for an explanation
+const MAX_DIFF_CHARS: usize = 60_000;
+
+/// How much the reader of an explanation already knows about git
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExplanationLevel {
+    /// Analogies, no unexplained jargon, and warnings about dangerous flags
+    Beginner,
+    /// The explanation prompt as written
+    #[default]
+    Normal,
+    /// Terse and technical, skipping the basics
+    Expert,
+}
+
+impl ExplanationLevel {
+    /// Parses the value of `--level` / `[explain] level`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "beginner" => Some(ExplanationLevel::Beginner),
+            "normal" => Some(ExplanationLevel::Normal),
+            "expert" => Some(ExplanationLevel::Expert),
+            _ => None,
+        }
+    }
+
+    /// The name used by `--level`, `[explain] level` and the `{{level}}` placeholder
+    pub fn name(self) -> &'static str {
+        match self {
+            ExplanationLevel::Beginner => "beginner",
+            ExplanationLevel::Normal => "normal",
+            ExplanationLevel::Expert => "expert",
+        }
+    }
+
+    /// Instructions added to the system prompt for this level, if any
+    fn instructions(self) -> Option<&'static str> {
+        match self {
+            ExplanationLevel::Beginner => Some(
+                "The reader is new to git. Explain every git term you use the first time it \
+                 appears, prefer everyday analogies to internals, and clearly warn about any \
+                 option or step that can discard work or rewrite shared history, saying how to \
+                 undo it or check first.",
+            ),
+            ExplanationLevel::Normal => None,
+            ExplanationLevel::Expert => Some(
+                "The reader is an experienced git user. Be terse and technical: skip basic \
+                 concepts and analogies, and focus on exact semantics, edge cases, interactions \
+                 with other options and relevant configuration.",
+            ),
+        }
+    }
+}
 
 /// Helper function to execute the AI request and process the response.
 async fn execute_ai_request(
     config: &AppConfig,
     messages: Vec<ChatMessage>,
-) -> Result<String, AIError> {
+    includes_repo_content: bool,
+) -> Result<AiReply, AIError> {
+    let parameters = config.model_parameters("explain");
+    // Explanations of commands carry the command and its output; the context
+    // for a push (remotes and branch names), `--context` and explained diffs
+    // come from the repository
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, includes_repo_content)?;
+    let api_url = parameters.api_url;
     let request_payload = OpenAIChatRequest {
-        model: config.ai.model_name.clone(),
+        model: parameters.model,
         messages,
-        temperature: Some(config.ai.temperature), // Using temperature from AI config
+        temperature: Some(parameters.temperature), // [explain] override or the [ai] default
         stream: false,
+        max_tokens: parameters.max_tokens,
     };
 
-    if let Ok(json_string) = serde_json::to_string_pretty(&request_payload) {
-        tracing::debug!(
-            "Sending JSON payload to AI for explanation:\n{}",
-            json_string
-        );
+    let response_data = send_chat_request(config, &api_url, &request_payload).await?;
+    if let Some(choice) = response_data.choices.first() {
+        let original_content = &choice.message.content;
+        if original_content.trim().is_empty() {
+            tracing::warn!("AI explainer returned an empty message content.");
+            Err(AIError::EmptyMessage)
+        } else {
+            let cleaned_content = clean_ai_output(original_content);
+            tracing::debug!(
+                "Cleaned AI explanation received: \"{}\"",
+                cleaned_content.chars().take(100).collect::<String>()
+            ); // Log snippet
+            Ok(AiReply {
+                text: post_generate::apply(config.post_generate_cmd("explain"), "explain", &cleaned_content)?,
+                model: response_data.model.clone(),
+                usage: Some(response_data.usage.clone()),
+            })
+        }
     } else {
-        tracing::warn!("Failed to serialize AI request payload for debugging.");
+        tracing::warn!("No choices found in AI explainer response.");
+        Err(AIError::NoChoiceInResponse)
     }
+}
 
-    let client = reqwest::Client::new();
-    let mut request_builder = client.post(&config.ai.api_url);
-
-    // Add Authorization header if api_key is present
-    if let Some(api_key) = &config.ai.api_key {
-        if !api_key.is_empty() {
-            tracing::debug!("Using API key for AI explanation request.");
-            request_builder = request_builder.bearer_auth(api_key);
-        }
+/// Builds the system prompt for explanations, adding the reader's level, the
+/// glossary and the preferred output language if set.
+fn explanation_system_prompt(config: &AppConfig) -> String {
+    let mut prompt = config.system_prompt("explanation");
+    if let Some(instructions) = config.explain.level.instructions() {
+        prompt.push_str("\n\n");
+        prompt.push_str(instructions);
     }
-
-    let openai_response = request_builder
-        .json(&request_payload)
-        .send()
-        .await
-        .map_err(|e| {
-            tracing::error!("AI explainer request failed during send: {}", e);
-            // This error could be a network issue, DNS resolution failure, etc.
-            // AIError::RequestFailed is a general error for reqwest issues.
-            // AIError::ExplainerNetworkError could be used if a more specific categorization is needed
-            // and can be reliably determined from `e`.
-            AIError::RequestFailed(e)
-        })?;
-
-    if !openai_response.status().is_success() {
-        let status_code = openai_response.status();
-        let body = openai_response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Failed to read error body from AI response".to_string());
-        tracing::error!(
-            "AI explainer API request failed with status {}: {}",
-            status_code,
-            body
-        );
-        return Err(AIError::ApiResponseError(status_code, body));
-    }
-
-    // Successfully received a response, now parse it.
-    match openai_response.json::<OpenAIChatCompletionResponse>().await {
-        Ok(response_data) => {
-            if let Some(choice) = response_data.choices.get(0) {
-                let original_content = &choice.message.content;
-                if original_content.trim().is_empty() {
-                    tracing::warn!("AI explainer returned an empty message content.");
-                    Err(AIError::EmptyMessage)
-                } else {
-                    let cleaned_content = clean_ai_output(original_content);
-                    tracing::debug!(
-                        "Cleaned AI explanation received: \"{}\"",
-                        cleaned_content.chars().take(100).collect::<String>()
-                    ); // Log snippet
-                    Ok(cleaned_content)
-                }
-            } else {
-                tracing::warn!("No choices found in AI explainer response.");
-                Err(AIError::NoChoiceInResponse)
-            }
-        }
-        Err(e) => {
-            tracing::error!("Failed to parse JSON response from AI explainer: {}", e);
-            // This error occurs if the response body is not valid JSON matching OpenAIChatCompletionResponse
-            Err(AIError::ResponseParseFailed(e))
-        }
+    if config.explain.glossary {
+        prompt.push_str("\n\n");
+        prompt.push_str(&config.glossary.prompt_instructions());
     }
+    if let Some(language) = &config.explain.language {
+        prompt.push_str(&format!(
+            "\n\nAlways write your entire explanation in {}.",
+            language
+        ));
+    }
+    prompt
 }
 
 /// Takes the raw output from a Git command (typically its help text)
@@ -98,13 +139,12 @@ async fn execute_ai_request(
 pub async fn explain_git_command_output(
     config: &AppConfig,
     command_output: &str,
-) -> Result<String, AIError> {
+) -> Result<AiReply, AIError> {
     if command_output.trim().is_empty() {
         // This is not an error, but a valid case where there's nothing to explain.
-        return Ok("The command produced no output for the AI to explain. \
+        return Ok(AiReply::local("The command produced no output for the AI to explain. \
             It might be a command that doesn't print to stdout/stderr on success, \
-            or it requires specific conditions to produce output."
-            .to_string());
+            or it requires specific conditions to produce output."));
     }
 
     tracing::debug!(
@@ -112,10 +152,7 @@ pub async fn explain_git_command_output(
         command_output.chars().take(200).collect::<String>()
     );
 
-    let system_prompt_content = config.prompts.get("explanation").cloned().unwrap_or_else(|| {
-        tracing::warn!("Explanation prompt not found in config, using empty string");
-        "".to_string()
-    });
+    let system_prompt_content = explanation_system_prompt(config);
 
     let messages = vec![
         ChatMessage {
@@ -128,27 +165,94 @@ pub async fn explain_git_command_output(
         },
     ];
 
-    match execute_ai_request(config, messages).await {
-        Ok(ai_explanation) => {
-            let formatted_output = format!(
-                "## Original Output\n\n```text\n{}\n```\n\n## AI Explanation\n\n{}",
-                command_output, ai_explanation
-            );
-            Ok(formatted_output)
-        }
+    match execute_ai_request(config, messages, false).await {
+        Ok(ai_explanation) => Ok(with_original_output(command_output, ai_explanation)),
         Err(e) => Err(e),
     }
 }
 
+/// Returns an AI-generated explanation of what `command_parts` printed when it
+/// was run. Unlike help text, the output comes from the repository.
+pub async fn explain_command_run(
+    config: &AppConfig,
+    command_parts: &[String],
+    command_output: &str,
+) -> Result<AiReply, AIError> {
+    if command_output.trim().is_empty() {
+        return Ok(AiReply::local("The command printed nothing to explain."));
+    }
+    let command = format!("git {}", command_parts.join(" "));
+    tracing::debug!("Requesting AI explanation for the output of: {}", command);
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: format!("{}\n\n{}", explanation_system_prompt(config), UNTRUSTED_CONTENT_RULES),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "Explain the output below of `{}`: what it says about the repository, and what to do \
+                 about anything it reports.\n\n{}{}",
+                command,
+                untrusted::background(&config.context).unwrap_or_default(),
+                untrusted::fence("output", &truncated(command_output, "[output truncated]"))
+            ),
+        },
+    ];
+    let ai_explanation = execute_ai_request(config, messages, true).await?;
+    Ok(with_original_output(command_output, ai_explanation))
+}
+
+/// `explanation` after the output it explains
+fn with_original_output(command_output: &str, explanation: AiReply) -> AiReply {
+    let text = format!(
+        "## Original Output\n\n```text\n{}\n```\n\n## AI Explanation\n\n{}",
+        command_output, explanation.text
+    );
+    AiReply { text, ..explanation }
+}
+
+/// `text` cut to `MAX_DIFF_CHARS`, ending with `note` when it was cut
+fn truncated(text: &str, note: &str) -> String {
+    match text.char_indices().nth(MAX_DIFF_CHARS) {
+        Some((end, _)) => format!("{}\n{}", &text[..end], note),
+        None => text.to_string(),
+    }
+}
+
+/// Returns an AI-generated explanation of the changes in `diff`.
+pub async fn explain_diff(config: &AppConfig, diff: &str) -> Result<AiReply, AIError> {
+    if diff.trim().is_empty() {
+        return Ok(AiReply::local("There are no changes to explain."));
+    }
+    let diff = truncated(diff, "[diff truncated]");
+    let messages = vec![
+        ChatMessage {
+            role: "system".to_string(),
+            content: format!("{}\n\n{}", explanation_system_prompt(config), UNTRUSTED_CONTENT_RULES),
+        },
+        ChatMessage {
+            role: "user".to_string(),
+            content: format!(
+                "{}\n\n{}{}",
+                DIFF_EXPLANATION_REQUEST,
+                untrusted::background(&config.context).unwrap_or_default(),
+                untrusted::fence("diff", &diff)
+            ),
+        },
+    ];
+    execute_ai_request(config, messages, true).await
+}
+
 /// Takes a Git command (as a sequence of its parts/arguments)
 /// and returns an AI-generated explanation of what that command does.
 pub async fn explain_git_command(
     config: &AppConfig,
     command_parts: &[String],
-) -> Result<String, AIError> {
+) -> Result<AiReply, AIError> {
     if command_parts.is_empty() {
         // This is not an error from AI's perspective but an invalid input to this function.
-        return Ok("No command parts provided for the AI to explain.".to_string());
+        return Ok(AiReply::local("No command parts provided for the AI to explain."));
     }
 
     let command_to_explain = format!("git {}", command_parts.join(" "));
@@ -157,12 +261,23 @@ pub async fn explain_git_command(
         command_to_explain
     );
 
-    let user_message_content = command_to_explain;
+    let mut user_message_content = command_to_explain;
+    // Which remote and branch a push affects depends on the repository
+    let push_context = remotes::push_context(command_parts);
+    if let Some(context) = &push_context {
+        user_message_content.push_str("\n\n");
+        user_message_content.push_str(context);
+    }
+    let background = untrusted::background(&config.context);
+    if let Some(background) = &background {
+        user_message_content.push_str("\n\n");
+        user_message_content.push_str(background.trim_end());
+    }
 
-    let system_prompt_content = config.prompts.get("explanation").cloned().unwrap_or_else(|| {
-        tracing::warn!("Explanation prompt not found in config, using empty string");
-        "".to_string()
-    });
+    let mut system_prompt_content = explanation_system_prompt(config);
+    if background.is_some() {
+        system_prompt_content.push_str(&format!("\n\n{}", UNTRUSTED_CONTENT_RULES));
+    }
 
     let messages = vec![
         ChatMessage {
@@ -175,5 +290,20 @@ pub async fn explain_git_command(
         },
     ];
 
-    execute_ai_request(config, messages).await
+    let includes_repo_content = push_context.is_some() || background.is_some();
+    execute_ai_request(config, messages, includes_repo_content).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explanation_level_names() {
+        assert_eq!(ExplanationLevel::from_name("beginner"), Some(ExplanationLevel::Beginner));
+        assert_eq!(ExplanationLevel::from_name("expert"), Some(ExplanationLevel::Expert));
+        assert_eq!(ExplanationLevel::from_name("guru"), None);
+        // The configured prompt is used unchanged at the default level
+        assert_eq!(ExplanationLevel::default().instructions(), None);
+    }
 }
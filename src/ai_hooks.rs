@@ -0,0 +1,218 @@
+// git-enhancer/src/ai_hooks.rs
+//! `[hooks]`: commands that see every AI request and reply.
+//!
+//! `pre_ai` runs before a prompt is sent and `post_ai` after the reply comes
+//! back, for every AI feature, so an organisation can put its own checks in
+//! one place, e.g. a DLP scanner. Administrators can force them on users with
+//! `[locked.hooks]` in the policy file. Each hook runs with `sh -c`, gets a
+//! JSON object on stdin and prints one on stdout:
+//!
+//! ```text
+//! pre_ai stdin:   {"hook": "pre_ai", "api_url": ..., "model": ..., "messages": [{"role": ..., "content": ...}]}
+//! post_ai stdin:  the same, with "hook": "post_ai" and "response": "<the reply's text>"
+//! stdout:         {"action": "allow"}  or  {"action": "deny", "reason": "..."}
+//! ```
+//!
+//! An allowing `pre_ai` hook may return changed `messages`, and a `post_ai`
+//! hook a changed `response`, to be used instead. Printing nothing allows the
+//! request unchanged. A denial, a non-zero exit status or output that isn't
+//! such an object stops the request, so a broken scanner never lets it
+//! through. Denials are recorded in the audit log.
+//!
+//! Embedding requests go through `pre_ai` as well, each input as a `user`
+//! message; a hook changing them has to return one message per input.
+//! `post_ai` doesn't run for them, as their reply is vectors, not text.
+
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+use crate::ai_utils::{ChatMessage, EmbeddingRequest, OpenAIChatCompletionResponse, OpenAIChatRequest};
+use crate::audit;
+use crate::errors::AIError;
+use crate::post_generate;
+
+// AI 请求前后运行的钩子命令的配置
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct HooksConfig {
+    /// Runs before a prompt is sent; may deny it or change its messages
+    #[serde(default)]
+    pub pre_ai: Option<String>,
+    /// Runs after a reply is received; may deny it or change its text
+    #[serde(default)]
+    pub post_ai: Option<String>,
+}
+
+/// What a hook is given
+#[derive(Serialize, Debug)]
+struct HookInput<'a> {
+    hook: &'static str,
+    api_url: &'a str,
+    model: &'a str,
+    messages: &'a [ChatMessage],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<&'a str>,
+}
+
+/// What a hook answers
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct HookOutput {
+    #[serde(default)]
+    action: HookAction,
+    reason: Option<String>,
+    messages: Option<Vec<ChatMessage>>,
+    response: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum HookAction {
+    #[default]
+    Allow,
+    Deny,
+}
+
+/// `request` as the `pre_ai` hook allows it to be sent
+pub fn before_request<'a>(
+    hooks: &HooksConfig,
+    api_url: &str,
+    request: &'a OpenAIChatRequest,
+) -> Result<Cow<'a, OpenAIChatRequest>, AIError> {
+    let Some(command) = hook_command(&hooks.pre_ai) else {
+        return Ok(Cow::Borrowed(request));
+    };
+    let input = HookInput { hook: "pre_ai", api_url, model: &request.model, messages: &request.messages, response: None };
+    match run_hook(command, &input)?.messages {
+        Some(messages) => Ok(Cow::Owned(OpenAIChatRequest { messages, ..request.clone() })),
+        None => Ok(Cow::Borrowed(request)),
+    }
+}
+
+/// The embeddings `request` as the `pre_ai` hook allows it to be sent
+pub fn before_embedding_request<'a>(
+    hooks: &HooksConfig,
+    api_url: &str,
+    request: &'a EmbeddingRequest,
+) -> Result<Cow<'a, EmbeddingRequest>, AIError> {
+    let Some(command) = hook_command(&hooks.pre_ai) else {
+        return Ok(Cow::Borrowed(request));
+    };
+    let messages: Vec<ChatMessage> = request
+        .input
+        .iter()
+        .map(|input| ChatMessage { role: "user".to_string(), content: input.clone() })
+        .collect();
+    let input = HookInput { hook: "pre_ai", api_url, model: &request.model, messages: &messages, response: None };
+    match run_hook(command, &input)?.messages {
+        Some(changed) if changed.len() != messages.len() => Err(AIError::HookFailed {
+            hook: "pre_ai".to_string(),
+            reason: format!("it returned {} messages for {} embedding inputs", changed.len(), messages.len()),
+        }),
+        Some(changed) => Ok(Cow::Owned(EmbeddingRequest {
+            model: request.model.clone(),
+            input: changed.into_iter().map(|message| message.content).collect(),
+        })),
+        None => Ok(Cow::Borrowed(request)),
+    }
+}
+
+/// `response` to `request` as the `post_ai` hook allows it to be used
+pub fn after_response(
+    hooks: &HooksConfig,
+    api_url: &str,
+    request: &OpenAIChatRequest,
+    mut response: OpenAIChatCompletionResponse,
+) -> Result<OpenAIChatCompletionResponse, AIError> {
+    let Some(command) = hook_command(&hooks.post_ai) else {
+        return Ok(response);
+    };
+    let Some(choice) = response.choices.first_mut() else {
+        return Ok(response);
+    };
+    let input = HookInput {
+        hook: "post_ai",
+        api_url,
+        model: &request.model,
+        messages: &request.messages,
+        response: Some(&choice.message.content),
+    };
+    if let Some(text) = run_hook(command, &input)?.response {
+        choice.message.content = text;
+    }
+    Ok(response)
+}
+
+fn hook_command(command: &Option<String>) -> Option<&str> {
+    command.as_deref().filter(|command| !command.trim().is_empty())
+}
+
+/// Runs the hook `command` on `input`, failing unless it allows the request
+fn run_hook(command: &str, input: &HookInput) -> Result<HookOutput, AIError> {
+    let hook = input.hook;
+    let failed = |reason: String| AIError::HookFailed { hook: hook.to_string(), reason };
+    let stdin = serde_json::to_string(input).map_err(|e| failed(e.to_string()))?;
+    tracing::debug!("Running the {} hook `{}`", hook, command);
+    let stdout = post_generate::run_script(None, command, &[("GITIE_HOOK", hook)], &stdin).map_err(failed)?;
+    let output = parse_output(&stdout).map_err(failed)?;
+    if output.action == HookAction::Deny {
+        let reason = output.reason.unwrap_or_else(|| "no reason given".to_string());
+        audit::record("hook-denied", &format!("{} hook for model {}: {}", hook, input.model, reason));
+        return Err(AIError::BlockedByHook { hook: hook.to_string(), reason });
+    }
+    Ok(output)
+}
+
+fn parse_output(stdout: &str) -> Result<HookOutput, String> {
+    if stdout.trim().is_empty() {
+        return Ok(HookOutput::default());
+    }
+    serde_json::from_str(stdout).map_err(|e| format!("its output is not a hook answer: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> OpenAIChatRequest {
+        OpenAIChatRequest {
+            model: "m".to_string(),
+            messages: vec![ChatMessage { role: "user".to_string(), content: "+password=hunter2".to_string() }],
+            temperature: None,
+            stream: false,
+            max_tokens: None,
+        }
+    }
+
+    #[test]
+    fn test_pre_ai_hook_can_allow_change_or_deny() {
+        let request = request();
+        let hooks = |command: &str| HooksConfig { pre_ai: Some(command.to_string()), post_ai: None };
+        assert!(matches!(before_request(&HooksConfig::default(), "u", &request), Ok(Cow::Borrowed(_))));
+        assert!(matches!(before_request(&hooks("cat >/dev/null"), "u", &request), Ok(Cow::Borrowed(_))));
+
+        let redact = r#"cat >/dev/null; echo '{"messages": [{"role": "user", "content": "+password=***"}]}'"#;
+        let changed = before_request(&hooks(redact), "u", &request).unwrap();
+        assert_eq!(changed.messages[0].content, "+password=***");
+
+        let deny = r#"grep -q hunter2 && echo '{"action": "deny", "reason": "secret in prompt"}'"#;
+        match before_request(&hooks(deny), "u", &request) {
+            Err(AIError::BlockedByHook { hook, reason }) => assert_eq!((hook.as_str(), reason.as_str()), ("pre_ai", "secret in prompt")),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(before_request(&hooks("exit 1"), "u", &request), Err(AIError::HookFailed { .. })));
+        assert!(matches!(before_request(&hooks("echo ok"), "u", &request), Err(AIError::HookFailed { .. })));
+    }
+
+    #[test]
+    fn test_pre_ai_hook_sees_embedding_inputs() {
+        let request = EmbeddingRequest { model: "e".to_string(), input: vec!["a".to_string(), "hunter2".to_string()] };
+        let hooks = |command: &str| HooksConfig { pre_ai: Some(command.to_string()), post_ai: None };
+        let deny = r#"grep -q hunter2 && echo '{"action": "deny", "reason": "secret"}'"#;
+        assert!(matches!(before_embedding_request(&hooks(deny), "u", &request), Err(AIError::BlockedByHook { .. })));
+
+        let redact = r#"cat >/dev/null; echo '{"messages": [{"role": "user", "content": "a"}, {"role": "user", "content": "***"}]}'"#;
+        assert_eq!(before_embedding_request(&hooks(redact), "u", &request).unwrap().input, ["a", "***"]);
+        let dropped = r#"cat >/dev/null; echo '{"messages": [{"role": "user", "content": "a"}]}'"#;
+        assert!(matches!(before_embedding_request(&hooks(dropped), "u", &request), Err(AIError::HookFailed { .. })));
+    }
+}
@@ -1,6 +1,30 @@
+//! Requests to OpenAI-compatible chat and embedding endpoints.
+//!
+//! Every AI feature goes through `send_chat_request`, `send_json_request` or
+//! `send_embedding_request`, which add authentication, retries, timeouts and
+//! Ctrl-C cancellation, and clean up what the model returns.
+
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::Poll;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::Semaphore;
+
+use crate::ai_hooks;
+use crate::audit;
+use crate::clock;
+use crate::config::{AppConfig, RetryConfig, TimeoutConfig};
+use crate::errors::{AIError, error_chain_text};
+use crate::interrupt::CancellationToken;
+use crate::progress::Spinner;
+use crate::ui;
+use crate::usage_stats;
 
 /// Represents a chat message with a role and content
 /// 
@@ -18,13 +42,15 @@ pub struct OpenAIChatRequest {
     pub messages: Vec<ChatMessage>,
     pub temperature: Option<f32>, // Temperature is typically an optional top-level parameter in the OpenAI API
     pub stream: bool,
-    // You can add other OpenAI-supported options here, such as top_p, max_tokens, etc.
-    // pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    // You can add other OpenAI-supported options here, such as top_p.
     // pub top_p: Option<f32>,
 }
 
 /// Represents a choice in the OpenAI API response
 #[derive(Deserialize, Debug, Clone)]
+#[allow(dead_code)] // Mirrors the API schema; not every field is consumed yet
 pub struct OpenAIChoice {
     pub index: u32,
     pub message: ChatMessage,
@@ -33,15 +59,25 @@ pub struct OpenAIChoice {
 }
 
 /// Represents token usage information in the OpenAI API response
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct OpenAIUsage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
 }
 
+impl std::ops::AddAssign for OpenAIUsage {
+    /// Adds up the usage of several requests
+    fn add_assign(&mut self, other: OpenAIUsage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
 /// Represents the complete response structure from the OpenAI chat completion API
 #[derive(Deserialize, Debug, Clone)]
+#[allow(dead_code)] // Mirrors the API schema; not every field is consumed yet
 pub struct OpenAIChatCompletionResponse {
     pub id: String,
     pub object: String,
@@ -52,24 +88,595 @@ pub struct OpenAIChatCompletionResponse {
     pub usage: OpenAIUsage,
 }
 
-/// Removes <think>...</think> tags and their content from a given string.
+/// Cleaned text of an AI reply together with what produced it
+#[derive(Debug, Clone, PartialEq)]
+pub struct AiReply {
+    pub text: String,
+    /// The model as reported by the API; empty when no request was made
+    pub model: String,
+    pub usage: Option<OpenAIUsage>,
+}
+
+impl AiReply {
+    /// A reply produced locally, without asking the model
+    pub fn local(text: impl Into<String>) -> Self {
+        AiReply { text: text.into(), model: String::new(), usage: None }
+    }
+}
+
+/// Sends a chat completion request to `api_url`, authenticating with that endpoint's key.
 ///
-/// The (?s) flag allows . to match newlines, in case <think> tags span multiple lines.
-/// The regex pattern is compiled once using lazy_static for better performance
-/// since this function might be called frequently.
+/// Applies the `[ai.timeouts]` settings to the HTTP client and retries failures
+/// matching `[ai.retry].retry_on` with exponential backoff. Ctrl-C and `--timeout`
+/// abort the request, retries included, and print a `[cancelled]` marker. The
+/// `[hooks]` commands see the request before it is sent and the reply after.
+pub async fn send_chat_request(
+    config: &AppConfig,
+    api_url: &str,
+    request: &OpenAIChatRequest,
+) -> Result<OpenAIChatCompletionResponse, AIError> {
+    let request = ai_hooks::before_request(&config.hooks, api_url, request)?;
+    let request = request.as_ref();
+    if let Ok(json_string) = serde_json::to_string_pretty(request) {
+        tracing::debug!("Sending JSON payload to AI:\n{}", json_string);
+    }
+
+    let client = http_client(&config.ai.timeouts)?;
+    let spinner = Spinner::start(&request.model);
+    let mut cancellation = CancellationToken::register();
+    let deadline = config.ai.timeouts.deadline;
+    let started = Instant::now();
+    // Dropping the unfinished request closes its connection
+    let result = tokio::select! {
+        result = send_with_retries(&client, config, api_url, &request.model, request, Some(&spinner)) => result,
+        _ = cancellation.cancelled() => Err(AIError::Cancelled),
+        _ = sleep_until_deadline(deadline) => Err(AIError::DeadlineExceeded(deadline.unwrap_or_default())),
+    };
+    usage_stats::record_ai_request(config, &request.model, started.elapsed(), result.is_ok());
+    if matches!(result, Err(AIError::Cancelled | AIError::DeadlineExceeded(_))) {
+        drop(spinner);
+        // Responses are not streamed, so there is no partial output to show before it
+        eprintln!("[cancelled]");
+    }
+    ai_hooks::after_response(&config.hooks, api_url, request, result?)
+}
+
+/// Request body for an OpenAI-compatible embeddings endpoint
+#[derive(Serialize, Debug, Clone)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+    #[serde(default)]
+    index: Option<usize>,
+}
+
+/// Sends an embeddings request, returning one vector per input in input order.
+///
+/// Retries, timeouts, Ctrl-C and `--timeout` work as for `send_chat_request`,
+/// but no spinner is shown: callers batching many requests report their own
+/// progress. The `pre_ai` hook sees the inputs before they are sent.
+pub async fn send_embedding_request(
+    config: &AppConfig,
+    api_url: &str,
+    request: &EmbeddingRequest,
+) -> Result<Vec<Vec<f32>>, AIError> {
+    let request = ai_hooks::before_embedding_request(&config.hooks, api_url, request)?;
+    let request = request.as_ref();
+    let client = http_client(&config.ai.timeouts)?;
+    let mut cancellation = CancellationToken::register();
+    let deadline = config.ai.timeouts.deadline;
+    let response: EmbeddingResponse = tokio::select! {
+        result = send_with_retries(&client, config, api_url, &request.model, request, None) => result?,
+        _ = cancellation.cancelled() => return Err(AIError::Cancelled),
+        _ = sleep_until_deadline(deadline) => return Err(AIError::DeadlineExceeded(deadline.unwrap_or_default())),
+    };
+    let mut data = response.data;
+    if data.len() != request.input.len() {
+        return Err(AIError::EmbeddingCountMismatch { expected: request.input.len(), received: data.len() });
+    }
+    data.sort_by_key(|item| item.index);
+    Ok(data.into_iter().map(|item| item.embedding).collect())
+}
+
+/// Sent after a reply that should have been JSON but didn't parse
+const JSON_REPAIR_PROMPT: &str = "Your previous reply could not be parsed as JSON. Reformat it \
+    strictly as JSON: reply with the JSON value only, without code fences, comments or any other \
+    text, and keep the content unchanged.";
+
+/// Sends `request`, whose prompt asks for a JSON reply, and parses the reply as `T`.
+///
+/// Small local models often wrap JSON in prose or get its syntax wrong. A reply
+/// that doesn't parse is followed up with a request to reformat it strictly as
+/// JSON, up to `[ai.retry] json_repairs` times, and each failure is recorded in
+/// the audit log. The returned `AiReply` holds the text that parsed.
+pub async fn send_json_request<T: DeserializeOwned>(
+    config: &AppConfig,
+    api_url: &str,
+    request: &OpenAIChatRequest,
+) -> Result<(T, AiReply), AIError> {
+    let mut request = request.clone();
+    let mut attempt = 0;
+    loop {
+        let response = send_chat_request(config, api_url, &request).await?;
+        let choice = response.choices.first().ok_or(AIError::NoChoiceInResponse)?;
+        let text = clean_ai_output(&choice.message.content);
+        if text.trim().is_empty() {
+            return Err(AIError::EmptyMessage);
+        }
+        match serde_json::from_str::<T>(extract_json(&text)) {
+            Ok(value) => {
+                let reply = AiReply { text, model: response.model.clone(), usage: Some(response.usage.clone()) };
+                return Ok((value, reply));
+            }
+            Err(e) => {
+                attempt += 1;
+                tracing::debug!("AI reply is not valid JSON ({}):\n{}", e, text);
+                audit::record(
+                    "malformed-json",
+                    &format!("model {}, attempt {}: {}: {}", response.model, attempt, e, text),
+                );
+                if attempt > config.ai.retry.json_repairs {
+                    return Err(AIError::MalformedJson { attempts: attempt, error: e.to_string() });
+                }
+                request.messages.push(ChatMessage { role: "assistant".to_string(), content: text });
+                request.messages.push(ChatMessage { role: "user".to_string(), content: JSON_REPAIR_PROMPT.to_string() });
+            }
+        }
+    }
+}
+
+/// The JSON value in `text`, without code fences or prose around it.
+///
+/// Falls back to the whole text, so the parse error describes what was there.
+fn extract_json(text: &str) -> &str {
+    let start = text.find(['{', '[']);
+    let end = text.rfind(['}', ']']);
+    match (start, end) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => text.trim(),
+    }
+}
+
+/// Sleeps for `deadline` seconds, or forever without one
+async fn sleep_until_deadline(deadline: Option<u64>) {
+    match deadline {
+        Some(seconds) => tokio::time::sleep(Duration::from_secs(seconds)).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn send_with_retries<B: Serialize, R: DeserializeOwned>(
+    client: &reqwest::Client,
+    config: &AppConfig,
+    api_url: &str,
+    model: &str,
+    payload: &B,
+    spinner: Option<&Spinner>,
+) -> Result<R, AIError> {
+    let retry = &config.ai.retry;
+    let mut attempt = 1;
+    loop {
+        match post_once(client, config, api_url, model, payload).await {
+            Err(e) if attempt < retry.max_attempts && is_retryable(retry, &e) => {
+                let mut delay = retry_delay(retry, attempt);
+                // Waiting less than the API asked for would only be rejected again
+                if let AIError::RateLimited { retry_after: Some(seconds), .. } = &e {
+                    delay = delay.max(Duration::from_secs(*seconds));
+                }
+                if let Some(spinner) = spinner {
+                    spinner.clear();
+                }
+                ui::status(&format!(
+                    "AI request failed (attempt {}/{}): {}. Retrying in {}ms",
+                    attempt,
+                    retry.max_attempts,
+                    e,
+                    delay.as_millis()
+                ));
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Sends a request once, without a spinner, status messages or retries.
+///
+/// For optional extras running alongside other output, where a failure is
+/// simply dropped.
+pub async fn send_background_chat_request(
+    config: &AppConfig,
+    api_url: &str,
+    request: &OpenAIChatRequest,
+) -> Result<OpenAIChatCompletionResponse, AIError> {
+    let client = http_client(&config.ai.timeouts)?;
+    let request = ai_hooks::before_request(&config.hooks, api_url, request)?;
+    let response = send_chat_request_once(&client, config, api_url, &request).await?;
+    ai_hooks::after_response(&config.hooks, api_url, &request, response)
+}
+
+/// Runs `task` for every item with at most `limit` of them in flight at once,
+/// returning the results in the order of `items`.
+///
+/// For commands that send one AI request per file, commit or chunk: the
+/// requests overlap instead of waiting for each other, while `limit` (usually
+/// `ai.max_concurrency`) keeps the endpoint's rate limits in mind. Tasks run on
+/// the current task, so they may borrow from the caller.
+pub async fn run_concurrently<T, R, Fut>(limit: usize, items: impl IntoIterator<Item = T>, task: impl Fn(T) -> Fut) -> Vec<R>
+where
+    Fut: Future<Output = R>,
+{
+    let semaphore = Semaphore::new(limit.max(1));
+    let (semaphore, task) = (&semaphore, &task);
+    let mut pending: Vec<Pin<Box<dyn Future<Output = R> + '_>>> = items
+        .into_iter()
+        .map(|item| {
+            Box::pin(async move {
+                // The semaphore is never closed, so acquiring only waits
+                let _permit = semaphore.acquire().await.ok();
+                task(item).await
+            }) as Pin<Box<dyn Future<Output = R> + '_>>
+        })
+        .collect();
+    let mut results: Vec<Option<R>> = pending.iter().map(|_| None).collect();
+    std::future::poll_fn(|cx| {
+        let mut done = true;
+        for (future, result) in pending.iter_mut().zip(results.iter_mut()) {
+            if result.is_none() {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(value) => *result = Some(value),
+                    Poll::Pending => done = false,
+                }
+            }
+        }
+        if done { Poll::Ready(()) } else { Poll::Pending }
+    })
+    .await;
+    results.into_iter().flatten().collect()
+}
+
+/// What an endpoint answered to a plain GET
+pub struct EndpointProbe {
+    pub status: reqwest::StatusCode,
+    /// Seconds the local clock is ahead of the server's, if it sent a `Date` header
+    pub clock_skew: Option<i64>,
+}
+
+/// Connects to `api_url` without sending a prompt, to check that it can be
+/// reached and its certificate is trusted. Any HTTP status counts as reachable.
+pub async fn probe_endpoint(config: &AppConfig, api_url: &str) -> Result<EndpointProbe, AIError> {
+    let client = http_client(&config.ai.timeouts)?;
+    let response = client.get(api_url).send().await.map_err(classify_request_error)?;
+    Ok(EndpointProbe {
+        status: response.status(),
+        clock_skew: clock::skew_from_date_header(response.headers(), SystemTime::now()),
+    })
+}
+
+/// The HTTP clients shared by the AI requests of the process, one per set of
+/// timeouts (connect, total, stream idle)
+static HTTP_CLIENTS: Mutex<BTreeMap<(u64, u64, u64), reqwest::Client>> = Mutex::new(BTreeMap::new());
+
+/// Returns the shared HTTP client for `timeouts`, building it on first use.
+///
+/// Reusing one client keeps connections to the endpoints open and skips the TLS
+/// handshake for every request after the first. Configurations with other
+/// timeouts, e.g. those of a long-running server's requests, get a client of
+/// their own. Proxies are taken from the standard `HTTPS_PROXY`, `HTTP_PROXY`
+/// and `NO_PROXY` environment variables.
+fn http_client(timeouts: &TimeoutConfig) -> Result<reqwest::Client, AIError> {
+    let key = (timeouts.connect, timeouts.total, timeouts.stream_idle);
+    let mut clients = HTTP_CLIENTS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+    let client = build_http_client(timeouts)?;
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+/// Connects to the endpoint of `task` ahead of its first request, in the background.
+///
+/// Long-running servers call this at startup so the first request doesn't wait
+/// for the TCP and TLS handshakes. Nothing is sent but a `HEAD` request, and
+/// nothing at all when the policy doesn't allow the endpoint.
+pub fn warm_up(config: &AppConfig, task: &str) {
+    let parameters = config.model_parameters(task);
+    if config.policy.check_request(&parameters.api_url, &parameters.model, false).is_err() {
+        return;
+    }
+    let Ok(client) = http_client(&config.ai.timeouts) else {
+        return;
+    };
+    tokio::spawn(async move {
+        if let Err(e) = client.head(&parameters.api_url).send().await {
+            tracing::debug!("Could not connect to {} ahead of time: {}", parameters.api_url, e);
+        }
+    });
+}
+
+fn build_http_client(timeouts: &TimeoutConfig) -> Result<reqwest::Client, AIError> {
+    reqwest::Client::builder()
+        .connect_timeout(Duration::from_secs(timeouts.connect))
+        .timeout(Duration::from_secs(timeouts.total))
+        .read_timeout(Duration::from_secs(timeouts.stream_idle))
+        .build()
+        .map_err(AIError::RequestFailed)
+}
+
+async fn send_chat_request_once(
+    client: &reqwest::Client,
+    config: &AppConfig,
+    api_url: &str,
+    request: &OpenAIChatRequest,
+) -> Result<OpenAIChatCompletionResponse, AIError> {
+    post_once(client, config, api_url, &request.model, request).await
+}
+
+/// Posts `payload` to `api_url` once and parses the response, for requests to `model`.
+async fn post_once<B: Serialize, R: DeserializeOwned>(
+    client: &reqwest::Client,
+    config: &AppConfig,
+    api_url: &str,
+    model: &str,
+    payload: &B,
+) -> Result<R, AIError> {
+    let mut request_builder = client.post(api_url);
+    if let Some(api_key) = config.ai.api_key_for(api_url) {
+        request_builder = request_builder.bearer_auth(api_key);
+    }
+
+    let response = request_builder
+        .json(payload)
+        .send()
+        .await
+        .map_err(classify_request_error)?;
+
+    if !response.status().is_success() {
+        let status_code = response.status();
+        let skew = clock::skew_from_date_header(response.headers(), SystemTime::now());
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok());
+        let body = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Failed to read error body from AI response".to_string());
+        tracing::debug!("AI API request failed with status {}: {}", status_code, body);
+        let error = classify_status(status_code, retry_after, body, model);
+        // A wrong clock invalidates signed requests, whatever error the API reports for it
+        if let Some(skew_seconds) = skew.filter(|skew| skew.abs() > clock::MAX_CLOCK_SKEW_SECS) {
+            return Err(AIError::ClockSkew { skew_seconds, cause: Box::new(error) });
+        }
+        return Err(error);
+    }
+
+    response
+        .json::<R>()
+        .await
+        .map_err(|e| if e.is_timeout() { AIError::Timeout(e) } else { AIError::ResponseParseFailed(e) })
+}
+
+/// Tells apart the transport failures callers handle differently.
+///
+/// reqwest only flags timeouts and connection failures, so the cause is looked
+/// up in the chain of underlying errors.
+fn classify_request_error(error: reqwest::Error) -> AIError {
+    if error.is_timeout() {
+        return AIError::Timeout(error);
+    }
+    let mut source: Option<&(dyn std::error::Error + 'static)> = std::error::Error::source(&error);
+    let mut refused = false;
+    while let Some(cause) = source {
+        if cause
+            .downcast_ref::<std::io::Error>()
+            .is_some_and(|e| e.kind() == std::io::ErrorKind::ConnectionRefused)
+        {
+            refused = true;
+        }
+        source = cause.source();
+    }
+    let details = error_chain_text(&error);
+    if refused || details.contains("connection refused") {
+        AIError::ConnectionRefused(error)
+    } else if ["dns error", "failed to lookup address", "name or service not known"]
+        .iter()
+        .any(|text| details.contains(text))
+    {
+        AIError::Dns(error)
+    } else if ["certificate", "tls", "ssl", "handshake"].iter().any(|text| details.contains(text)) {
+        AIError::Tls(error)
+    } else {
+        AIError::RequestFailed(error)
+    }
+}
+
+/// Maps an unsuccessful response to the variant for its cause.
+///
+/// Context length and missing models are reported with generic statuses, so
+/// the body (an OpenAI or Ollama style error) is checked for them.
+fn classify_status(status: reqwest::StatusCode, retry_after: Option<u64>, body: String, model: &str) -> AIError {
+    let lowercase = body.to_lowercase();
+    match status.as_u16() {
+        401 | 403 => AIError::AuthFailed(status, body),
+        429 => AIError::RateLimited { retry_after, body },
+        400 | 413 | 422
+            if ["context_length_exceeded", "context length", "context window", "too many tokens"]
+                .iter()
+                .any(|text| lowercase.contains(text)) =>
+        {
+            AIError::ContextLengthExceeded(body)
+        }
+        400 | 404 if lowercase.contains("model_not_found") || (lowercase.contains("model") && lowercase.contains("not found")) => {
+            AIError::ModelNotFound { model: model.to_string(), body }
+        }
+        _ => AIError::ApiResponseError(status, body),
+    }
+}
+
+/// Decides whether a failed request matches one of the configured `retry_on` kinds.
+fn is_retryable(retry: &RetryConfig, error: &AIError) -> bool {
+    let matches = |kind: &str| retry.retry_on.iter().any(|k| k.eq_ignore_ascii_case(kind));
+    match error {
+        AIError::Timeout(_) => matches("timeout"),
+        AIError::ConnectionRefused(_) | AIError::Dns(_) => matches("connect"),
+        AIError::RequestFailed(e) => e.is_connect() && matches("connect"),
+        AIError::RateLimited { .. } => matches("429"),
+        AIError::AuthFailed(status, _) => matches(status.as_str()),
+        AIError::ApiResponseError(status, _) => {
+            matches(status.as_str()) || (status.is_server_error() && matches("5xx"))
+        }
+        _ => false,
+    }
+}
+
+/// Exponential backoff: `base_delay_ms`, then twice that, and so on.
+fn retry_delay(retry: &RetryConfig, attempt: u32) -> Duration {
+    let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+    Duration::from_millis(retry.base_delay_ms.saturating_mul(factor))
+}
+
+// The (?s) flag allows . to match newlines, in case <think> tags span multiple lines.
+// The regex pattern is compiled once using lazy_static for better performance
+// since this function might be called frequently.
 lazy_static! {
     static ref RE_THINK_TAGS: Regex = Regex::new(r"(?s)<think>.*?</think>").unwrap();
+    /// CSI (`ESC [ ... m`), OSC (`ESC ] ... BEL` or `ESC ] ... ESC \`), and other escape sequences
+    static ref RE_ESCAPE_SEQUENCES: Regex = Regex::new(
+        r"\x1b\[[0-?]*[ -/]*[@-~]|\x1b\][^\x07\x1b]*(?:\x07|\x1b\\)?|\x1b[ -/]*[0-~]?|\u{9b}[0-?]*[ -/]*[@-~]"
+    )
+    .unwrap();
 }
 
+/// Removes <think>...</think> tags and their content from a given string, and
+/// makes the rest safe to print and to commit.
+///
+/// A confused or malicious model could emit escape sequences that recolor the
+/// terminal, rewrite its title, hide text or move the cursor over earlier
+/// output. Escape sequences are removed, as are all other control characters
+/// except newlines and tabs, and the bidirectional overrides that make text
+/// display in a different order than it reads.
 pub fn clean_ai_output(text: &str) -> String {
     // Using the pre-compiled regex pattern for better performance
-    RE_THINK_TAGS.replace_all(text, "").into_owned()
+    let without_thinking = RE_THINK_TAGS.replace_all(text, "");
+    RE_ESCAPE_SEQUENCES
+        .replace_all(&without_thinking, "")
+        .chars()
+        .filter(|c| matches!(c, '\n' | '\t') || !(c.is_control() || is_bidi_control(*c)))
+        .collect()
+}
+
+/// Unicode characters that override the display order of the text around them
+fn is_bidi_control(c: char) -> bool {
+    matches!(c, '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}')
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn test_run_concurrently_bounds_tasks_and_keeps_order() {
+        let (running, most) = (std::cell::Cell::new(0), std::cell::Cell::new(0));
+        let results = run_concurrently(2, [30u64, 10, 20, 5], |delay| {
+            let (running, most) = (&running, &most);
+            async move {
+                running.set(running.get() + 1);
+                most.set(most.get().max(running.get()));
+                tokio::time::sleep(Duration::from_millis(delay)).await;
+                running.set(running.get() - 1);
+                delay * 2
+            }
+        })
+        .await;
+        assert_eq!(results, vec![60, 20, 40, 10]);
+        assert_eq!(most.get(), 2);
+    }
+
+    #[test]
+    fn test_is_retryable_matches_configured_statuses() {
+        let retry = RetryConfig::default();
+        let too_many = classify_status(reqwest::StatusCode::TOO_MANY_REQUESTS, None, String::new(), "m");
+        let bad_gateway = classify_status(reqwest::StatusCode::BAD_GATEWAY, None, String::new(), "m");
+        let unauthorized = classify_status(reqwest::StatusCode::UNAUTHORIZED, None, String::new(), "m");
+        assert!(is_retryable(&retry, &too_many));
+        assert!(is_retryable(&retry, &bad_gateway));
+        assert!(!is_retryable(&retry, &unauthorized));
+        assert!(!is_retryable(&retry, &AIError::EmptyMessage));
+
+        let only_429 = RetryConfig { retry_on: vec!["429".to_string()], ..RetryConfig::default() };
+        assert!(is_retryable(&only_429, &too_many));
+        assert!(!is_retryable(&only_429, &bad_gateway));
+    }
+
+    #[test]
+    fn test_extract_json_strips_fences_and_prose() {
+        assert_eq!(
+            extract_json("Sure! Here it is:\n```json\n{\"fix\": \"Fixes a typo\"}\n```\nAnything else?"),
+            "{\"fix\": \"Fixes a typo\"}"
+        );
+        assert_eq!(extract_json("[1, 2]"), "[1, 2]");
+        assert_eq!(extract_json(" no json here "), "no json here");
+    }
+
+    #[test]
+    fn test_classify_status_by_cause() {
+        let classify = |status: u16, body: &str| {
+            classify_status(reqwest::StatusCode::from_u16(status).unwrap(), Some(3), body.to_string(), "qwen3")
+        };
+        assert!(matches!(classify(403, "forbidden"), AIError::AuthFailed(..)));
+        assert!(matches!(classify(429, ""), AIError::RateLimited { retry_after: Some(3), .. }));
+        assert!(matches!(
+            classify(400, r#"{"error":{"code":"context_length_exceeded","message":"too long"}}"#),
+            AIError::ContextLengthExceeded(_)
+        ));
+        assert!(matches!(
+            classify(404, r#"{"error":"model \"qwen3\" not found, try pulling it first"}"#),
+            AIError::ModelNotFound { .. }
+        ));
+        assert!(matches!(classify(404, "no such route"), AIError::ApiResponseError(..)));
+        assert!(matches!(classify(500, "model crashed"), AIError::ApiResponseError(..)));
+    }
+
+    #[test]
+    fn test_classify_request_error_detects_refused_connections() {
+        // A port that was just free has nothing listening on it
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let error = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            reqwest::Client::new().get(format!("http://127.0.0.1:{}", port)).send().await.unwrap_err()
+        });
+        assert!(matches!(classify_request_error(error), AIError::ConnectionRefused(_)));
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_per_attempt() {
+        let retry = RetryConfig { base_delay_ms: 250, ..RetryConfig::default() };
+        assert_eq!(retry_delay(&retry, 1), Duration::from_millis(250));
+        assert_eq!(retry_delay(&retry, 2), Duration::from_millis(500));
+        assert_eq!(retry_delay(&retry, 3), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn test_clean_ai_output_strips_terminal_control() {
+        assert_eq!(
+            clean_ai_output("\x1b[31mfeat\x1b[0m: add\tlogin\x1b]0;pwned\x07\r\n\x1b]8;;https://x\x1b\\link\x1b]8;;\x1b\\ \x07\x08\u{9b}2J\u{202e}txt.exe"),
+            "feat: add\tlogin\nlink txt.exe"
+        );
+    }
+
     #[test]
     fn test_clean_ai_output_no_tags() {
         let input = "This is a normal commit message.";
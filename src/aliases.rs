@@ -0,0 +1,163 @@
+// git-enhancer/src/aliases.rs
+//! Command aliases.
+//!
+//! `[aliases]` maps a word to the command line it stands for:
+//!
+//! ```toml
+//! [aliases]
+//! cm = "commit --ai"
+//! wip = "commit -a -m 'work in progress'"
+//! ```
+//!
+//! The command word is expanded before the command line is parsed, so
+//! `git-enhancer --json cm -a` runs `commit --ai -a` with `--json`. Values are
+//! split into words like a shell would, with single and double quotes. An
+//! alias may use another one; a word is never expanded inside its own
+//! expansion, so `commit = "commit --ai"` makes `--ai` the default.
+//!
+//! git's own `alias.*` entries are respected too: a word that git has an
+//! alias for is passed to git even when git-enhancer has a subcommand of
+//! that name, so `git config alias.next ...` keeps working. git ignores
+//! aliases for its own commands, and so does git-enhancer for `commit`.
+
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use crate::config::{self, ConfigPaths};
+
+/// git commands that git-enhancer extends; git never expands an alias of theirs
+const GIT_COMMANDS: &[&str] = &["commit"];
+
+/// Where command words are looked up
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Aliases {
+    /// `[aliases]` from the configuration
+    pub configured: BTreeMap<String, String>,
+    /// Whether to consult git's `alias.*` entries
+    pub git_config: bool,
+}
+
+impl Aliases {
+    /// The aliases of the current user and repository.
+    ///
+    /// Read before the configuration is loaded, so a configuration that
+    /// doesn't load only means no `[aliases]`.
+    pub fn from_env() -> Self {
+        let configured = config::peek_setting(&ConfigPaths::from_env(), "aliases")
+            .and_then(|value| value.try_into().ok())
+            .unwrap_or_default();
+        Aliases { configured, git_config: true }
+    }
+
+    /// `command` with its first word expanded
+    pub fn expand(&self, mut command: Vec<String>) -> Result<Vec<String>, String> {
+        let mut expanded = Vec::new();
+        while let Some(name) = command.first()
+            && !expanded.contains(name)
+            && let Some(value) = self.configured.get(name)
+        {
+            let words = split_words(value).map_err(|e| format!("bad alias '{}': {}", name, e))?;
+            if words.is_empty() {
+                return Err(format!("alias '{}' is empty", name));
+            }
+            expanded.push(command.remove(0));
+            command.splice(0..0, words);
+        }
+        Ok(command)
+    }
+
+    /// Whether git expands `name` as an alias of its own
+    pub fn is_git_alias(&self, name: &str) -> bool {
+        if !self.git_config || GIT_COMMANDS.contains(&name) {
+            return false;
+        }
+        Command::new("git")
+            .args(["config", "--get", &format!("alias.{}", name)])
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+}
+
+/// `text` split into words as a shell would, honoring single quotes, double
+/// quotes and backslashes.
+fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_default();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err("unterminated quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_default().push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => word.get_or_insert_default().push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_expand_aliases() {
+        let aliases = Aliases {
+            configured: [
+                ("cm", "commit --ai"),
+                ("wip", "commit -a -m 'work in progress'"),
+                ("commit", "commit --no-verify"),
+                ("save", "wip --quiet"),
+                ("broken", "log \"--grep=x"),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect(),
+            git_config: false,
+        };
+        assert_eq!(aliases.expand(to_args(&["cm", "-a"])), Ok(to_args(&["commit", "--no-verify", "--ai", "-a"])));
+        assert_eq!(
+            aliases.expand(to_args(&["save"])),
+            Ok(to_args(&["commit", "--no-verify", "-a", "-m", "work in progress", "--quiet"]))
+        );
+        assert_eq!(aliases.expand(to_args(&["status", "cm"])), Ok(to_args(&["status", "cm"])));
+        assert!(aliases.expand(to_args(&["broken"])).is_err());
+        assert!(!aliases.is_git_alias("next"));
+
+        assert_eq!(split_words(r#"log  --format="%h \"%s\"" a\ b ''"#), Ok(to_args(&["log", "--format=%h \"%s\"", "a b", ""])));
+    }
+}
@@ -0,0 +1,38 @@
+// git-enhancer/src/audit.rs
+//! The audit log.
+//!
+//! Events worth looking into after the fact, such as model replies that had to
+//! be repaired, are appended as one tab-separated line each (`<unix time>`,
+//! `<event>`, `<details>`) to `audit.log` under `~/.local/share/gitie` (the
+//! platform's data directory). Recording never fails the command.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::state_file::FileLock;
+
+/// Appends `event` with `details` to the audit log.
+pub fn record(event: &str, details: &str) {
+    if let Err(e) = append(event, details) {
+        tracing::debug!("Could not write to the audit log: {}", e);
+    }
+}
+
+fn append(event: &str, details: &str) -> std::io::Result<()> {
+    let dir = dirs::data_dir()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no data directory"))?
+        .join("gitie");
+    fs::create_dir_all(&dir)?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let path = dir.join("audit.log");
+    let _lock = FileLock::acquire(&path)?;
+    let mut log = OpenOptions::new().create(true).append(true).open(&path)?;
+    // One write per entry, so entries of concurrent processes can't interleave
+    log.write_all(format!("{}\t{}\t{}\n", timestamp, event, format_details(details)).as_bytes())
+}
+
+/// `details` on one line, so every entry stays a single line
+fn format_details(details: &str) -> String {
+    details.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t")
+}
@@ -0,0 +1,375 @@
+//! The `branches-cleanup` subcommand.
+
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::ai_utils::{AiReply, ChatMessage, OpenAIChatRequest, send_json_request};
+use crate::cli::BranchesCleanupArgs;
+use crate::config::AppConfig;
+use crate::errors::{AIError, AppError, GitError};
+use crate::git_commands::{execute_git_command_and_capture_output, passthrough_to_git};
+use crate::git_context::GitContext;
+use crate::output;
+use crate::prompt;
+use crate::ui;
+
+const BRANCH_SUMMARY_PROMPT: &str = "Below are local git branches that are about to be cleaned up, \
+    each with its most recent commit subjects. Reply with a JSON object that maps every branch name \
+    to a summary saying in at most fifteen words what the branch contained, for example \
+    {\"feature/login\": \"Adds the login form and session handling\"}. Only use the information \
+    given. Reply with the JSON object only.";
+
+/// Commit subjects listed per branch
+const MAX_SUBJECTS_PER_BRANCH: usize = 10;
+
+/// Why a branch is offered for deletion
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum CleanupReason {
+    /// Fully merged into the base branch
+    Merged,
+    /// Its upstream branch was deleted on the remote
+    Gone,
+}
+
+/// A local branch that can be deleted
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct CleanupCandidate {
+    name: String,
+    reason: CleanupReason,
+    upstream: Option<String>,
+    /// Date of the last commit, relative to now
+    last_commit: String,
+    /// Commits not in the base branch; only `gone` branches have any
+    unmerged: usize,
+    /// Commit subjects of the work the branch carried, newest first
+    subjects: Vec<String>,
+    /// What the branch contained, written by the model
+    summary: Option<String>,
+}
+
+/// One line of `git for-each-ref` output for a local branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LocalBranch {
+    name: String,
+    upstream: Option<String>,
+    /// The upstream no longer exists on the remote (as of the last fetch)
+    gone: bool,
+    /// Checked out in this or another worktree
+    checked_out: bool,
+    last_commit: String,
+}
+
+/// `git for-each-ref` format matching `parse_local_branches`
+const BRANCH_FORMAT: &str = "%(refname:short)%00%(upstream:short)%00%(upstream:track)%00%(worktreepath)%00%(committerdate:relative)";
+
+/// Handles the `branches-cleanup` subcommand
+///
+/// Which branches are merged or gone comes from git; the AI only describes
+/// what each one contained. Nothing is deleted without confirmation at a terminal.
+///
+/// # Arguments
+///
+/// * `args` - Branches cleanup arguments from CLI
+/// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub async fn handle_branches_cleanup(args: BranchesCleanupArgs, config: &AppConfig, git_context: &GitContext) -> Result<(), AppError> {
+    git_context.require_repository()?;
+    let base = match args.base {
+        Some(base) => base,
+        None => default_branch(config)?,
+    };
+    let branches = parse_local_branches(&git(&["for-each-ref", &format!("--format={}", BRANCH_FORMAT), "refs/heads"])?);
+    let merged: Vec<String> = git(&["branch", "--merged", &base, "--format=%(refname:short)"])?
+        .lines()
+        .map(str::to_string)
+        .collect();
+    let mut protected = config.guard.shared_branches.clone();
+    protected.push(base.clone());
+    let mut candidates = select_candidates(&branches, &merged, &protected);
+    for candidate in &mut candidates {
+        fill_in_commits(candidate, &base)?;
+    }
+
+    let reply = if candidates.is_empty() {
+        AiReply::local(format!("No merged or gone branches to clean up (base: {}).", base))
+    } else {
+        let described = match summarize(config, &candidates).await {
+            Ok((mut summaries, reply)) => {
+                for candidate in &mut candidates {
+                    candidate.summary = summaries.remove(&candidate.name);
+                }
+                reply
+            }
+            Err(AIError::Cancelled) => return Err(AIError::Cancelled.into()),
+            Err(e) => {
+                ui::warning(&format!("could not describe the branches: {}", e));
+                AiReply::local("")
+            }
+        };
+        AiReply { text: format_candidates(&candidates, &base), ..described }
+    };
+    let data = serde_json::json!({ "base": base, "branches": candidates });
+    output::print_ai_reply_with_data(&config.output, "branches-cleanup", &reply, Some(&data), 0)?;
+
+    if candidates.is_empty() || config.output.format.is_machine_readable() || !prompt::is_interactive() {
+        return Ok(());
+    }
+    let selected = loop {
+        let Some(answer) = prompt::input("Delete which branches? (numbers such as 1 3-5, all, or Enter for none)")? else {
+            return Ok(());
+        };
+        match parse_selection(&answer, candidates.len()) {
+            Ok(selected) => break selected,
+            Err(message) => eprintln!("{}", message),
+        }
+    };
+    if selected.is_empty() {
+        return Ok(());
+    }
+    let selected: Vec<&CleanupCandidate> = selected.into_iter().map(|index| &candidates[index]).collect();
+    for candidate in selected.iter().filter(|candidate| candidate.unmerged > 0) {
+        ui::warning(&format!(
+            "'{}' has {} commit(s) that are not in {}; after deleting it they are only reachable through the reflog",
+            candidate.name, candidate.unmerged, base
+        ));
+    }
+    if !prompt::confirm(&format!("Delete {} branch(es)?", selected.len()), false)? {
+        return Ok(());
+    }
+    // Merging was checked against the base branch; `-d` would check against HEAD instead
+    let mut delete = vec!["branch".to_string(), "-D".to_string()];
+    delete.extend(selected.iter().map(|candidate| candidate.name.clone()));
+    passthrough_to_git(&delete)?;
+    Ok(())
+}
+
+/// The remote's default branch (`origin/HEAD`), else the first existing `[guard] shared_branches` entry
+pub(crate) fn default_branch(config: &AppConfig) -> Result<String, AppError> {
+    if let Ok(remote_head) = git(&["symbolic-ref", "--short", "-q", "refs/remotes/origin/HEAD"]) {
+        let remote_head = remote_head.trim().to_string();
+        // Prefer the local copy: that is where merges happen
+        let local = remote_head.trim_start_matches("origin/");
+        if git(&["rev-parse", "--verify", "-q", &format!("refs/heads/{}", local)]).is_ok() {
+            return Ok(local.to_string());
+        }
+        return Ok(remote_head);
+    }
+    config
+        .guard
+        .shared_branches
+        .iter()
+        .find(|branch| git(&["rev-parse", "--verify", "-q", &format!("refs/heads/{}", branch)]).is_ok())
+        .cloned()
+        .ok_or_else(|| AppError::Generic("could not tell which branch is the default one; pass --base <branch>".to_string()))
+}
+
+/// Parses `git for-each-ref --format=BRANCH_FORMAT refs/heads` output.
+fn parse_local_branches(output: &str) -> Vec<LocalBranch> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\0');
+            let name = fields.next().filter(|name| !name.is_empty())?;
+            let upstream = fields.next().unwrap_or_default();
+            let track = fields.next().unwrap_or_default();
+            let worktree = fields.next().unwrap_or_default();
+            Some(LocalBranch {
+                name: name.to_string(),
+                upstream: (!upstream.is_empty()).then(|| upstream.to_string()),
+                gone: track == "[gone]",
+                checked_out: !worktree.is_empty(),
+                last_commit: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Merged and gone branches, leaving out checked out and `protected` ones.
+fn select_candidates(branches: &[LocalBranch], merged: &[String], protected: &[String]) -> Vec<CleanupCandidate> {
+    let mut candidates: Vec<CleanupCandidate> = branches
+        .iter()
+        .filter(|branch| !branch.checked_out && !protected.contains(&branch.name))
+        .filter_map(|branch| {
+            let reason = if merged.contains(&branch.name) {
+                CleanupReason::Merged
+            } else if branch.gone {
+                CleanupReason::Gone
+            } else {
+                return None;
+            };
+            Some(CleanupCandidate {
+                name: branch.name.clone(),
+                reason,
+                upstream: branch.upstream.clone(),
+                last_commit: branch.last_commit.clone(),
+                unmerged: 0,
+                subjects: Vec::new(),
+                summary: None,
+            })
+        })
+        .collect();
+    // Merged ones first: they are the safe ones to delete
+    candidates.sort_by_key(|candidate| candidate.reason == CleanupReason::Gone);
+    candidates
+}
+
+/// Adds the commits the branch carried and, for gone branches, how many are not in `base`.
+fn fill_in_commits(candidate: &mut CleanupCandidate, base: &str) -> Result<(), AppError> {
+    let max_count = format!("-n{}", MAX_SUBJECTS_PER_BRANCH);
+    let subjects = match candidate.reason {
+        CleanupReason::Merged => {
+            // The branch's work is what its merge commit brought into the base branch
+            let merges = git(&["rev-list", "--ancestry-path", "--merges", "--reverse", &format!("{}..{}", candidate.name, base)])?;
+            match merges.lines().next() {
+                Some(merge) => git(&["log", "--format=%s", &max_count, &format!("{}^1..{}", merge, candidate.name)])?,
+                // Fast-forwarded or rebased: only the tip is known to be the branch's own
+                None => git(&["log", "--format=%s", "-n1", &candidate.name])?,
+            }
+        }
+        CleanupReason::Gone => {
+            let range = format!("{}..{}", base, candidate.name);
+            candidate.unmerged = git(&["rev-list", "--count", &range])?.trim().parse().unwrap_or(0);
+            git(&["log", "--format=%s", &max_count, &range])?
+        }
+    };
+    candidate.subjects = subjects.lines().map(str::to_string).collect();
+    Ok(())
+}
+
+fn format_candidates(candidates: &[CleanupCandidate], base: &str) -> String {
+    let mut text = String::new();
+    for (index, candidate) in candidates.iter().enumerate() {
+        let state = match candidate.reason {
+            CleanupReason::Merged => format!("merged into {}", base),
+            CleanupReason::Gone if candidate.unmerged > 0 => {
+                format!("upstream gone, {} commit(s) not in {}", candidate.unmerged, base)
+            }
+            CleanupReason::Gone => "upstream gone".to_string(),
+        };
+        let about = candidate
+            .summary
+            .as_deref()
+            .or(candidate.subjects.first().map(String::as_str))
+            .unwrap_or("no commits of its own");
+        text.push_str(&format!(
+            "{}. {} ({}, last commit {})\n   {}\n",
+            index + 1,
+            candidate.name,
+            state,
+            candidate.last_commit,
+            about
+        ));
+    }
+    text
+}
+
+/// Parses answers like `1 3-5`, `2,4` or `all` into indices into a list of `count` entries.
+fn parse_selection(answer: &str, count: usize) -> Result<Vec<usize>, String> {
+    let answer = answer.trim();
+    if answer.eq_ignore_ascii_case("all") {
+        return Ok((0..count).collect());
+    }
+    let mut selected = Vec::new();
+    for part in answer.split([' ', ',']).filter(|part| !part.is_empty()) {
+        let invalid = || format!("'{}' is not a number or range between 1 and {}", part, count);
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        let first: usize = first.trim().parse().map_err(|_| invalid())?;
+        let last: usize = last.trim().parse().map_err(|_| invalid())?;
+        if first == 0 || first > last || last > count {
+            return Err(invalid());
+        }
+        for number in first..=last {
+            if !selected.contains(&(number - 1)) {
+                selected.push(number - 1);
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// Asks the model for a one-line description of each branch, by branch name.
+async fn summarize(
+    config: &AppConfig,
+    candidates: &[CleanupCandidate],
+) -> Result<(HashMap<String, String>, AiReply), AIError> {
+    let parameters = config.model_parameters("explain");
+    // Branch names and commit subjects are repository content
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, true)?;
+    let mut branches = String::new();
+    for candidate in candidates {
+        branches.push_str(&format!("\n## {}\n", candidate.name));
+        for subject in &candidate.subjects {
+            branches.push_str(&format!("- {}\n", subject));
+        }
+    }
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: BRANCH_SUMMARY_PROMPT.to_string() },
+            ChatMessage { role: "user".to_string(), content: branches },
+        ],
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    send_json_request(config, &parameters.api_url, &request).await
+}
+
+/// The stdout of a successful `git <args>`
+fn git(args: &[&str]) -> Result<String, AppError> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let output = execute_git_command_and_capture_output(&args)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: format!("git {}", args.join(" ")),
+            status_code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+        .into());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_candidates_skips_checked_out_and_protected_branches() {
+        let output = "main\0origin/main\0\0/repo\0now\n\
+                      feature\0origin/feature\0[gone]\0\x002 days ago\n\
+                      done\0\0\0\x003 weeks ago\n\
+                      wip\0origin/wip\0[ahead 2]\0\x001 hour ago\n\
+                      other-worktree\0\0\0/repo-2\0now\n\
+                      master\0\0\0\0a year ago\n";
+        let branches = parse_local_branches(output);
+        assert_eq!(branches[1].upstream.as_deref(), Some("origin/feature"));
+        assert!(branches[1].gone && !branches[1].checked_out);
+
+        let merged = ["main", "done", "other-worktree", "master"].map(String::from);
+        let candidates = select_candidates(&branches, &merged, &["main".to_string(), "master".to_string()]);
+        assert_eq!(
+            candidates.iter().map(|c| (c.name.as_str(), c.reason)).collect::<Vec<_>>(),
+            vec![("done", CleanupReason::Merged), ("feature", CleanupReason::Gone)]
+        );
+    }
+
+    #[test]
+    fn test_parse_selection() {
+        assert_eq!(parse_selection("1 3-4", 5), Ok(vec![0, 2, 3]));
+        assert_eq!(parse_selection("2,2", 3), Ok(vec![1]));
+        assert_eq!(parse_selection("all", 2), Ok(vec![0, 1]));
+        assert_eq!(parse_selection("", 2), Ok(vec![]));
+        assert!(parse_selection("0", 2).is_err());
+        assert!(parse_selection("2-5", 3).is_err());
+        assert!(parse_selection("x", 3).is_err());
+    }
+}
@@ -0,0 +1,147 @@
+// git-enhancer/src/capture.rs
+//! Capturing command output that may not fit in memory.
+//!
+//! Diffs and logs of large repositories can run into gigabytes. A `SpillBuffer`
+//! keeps the first part of such output in memory and writes the rest to a
+//! temporary file, so consumers can read all of it as a stream without holding
+//! it in memory at once.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Distinguishes the spill files of one process
+static SPILL_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Output kept in memory up to a limit and on disk beyond it
+pub struct SpillBuffer {
+    memory: Vec<u8>,
+    memory_limit: usize,
+    spill: Option<SpillFile>,
+    total_bytes: u64,
+}
+
+/// A temporary file, removed on drop
+struct SpillFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl SpillBuffer {
+    pub fn new(memory_limit: usize) -> Self {
+        SpillBuffer { memory: Vec::new(), memory_limit, spill: None, total_bytes: 0 }
+    }
+
+    /// Size of everything written, in bytes
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes
+    }
+
+    /// Whether the output went over the memory limit
+    pub fn spilled(&self) -> bool {
+        self.spill.is_some()
+    }
+
+    /// Everything written, from memory and then from disk
+    pub fn reader(&mut self) -> io::Result<impl BufRead + '_> {
+        let spilled: Box<dyn Read> = match &mut self.spill {
+            Some(spill) => {
+                spill.writer.flush()?;
+                Box::new(File::open(&spill.path)?)
+            }
+            None => Box::new(io::empty()),
+        };
+        Ok(BufReader::new(self.memory.as_slice().chain(spilled)))
+    }
+
+    /// The output as text while it is small enough to have stayed in memory
+    pub fn text(&self) -> Option<String> {
+        (!self.spilled()).then(|| String::from_utf8_lossy(&self.memory).into_owned())
+    }
+}
+
+impl Write for SpillBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let in_memory = buf.len().min(self.memory_limit - self.memory.len());
+        self.memory.extend_from_slice(&buf[..in_memory]);
+        if in_memory < buf.len() {
+            if self.spill.is_none() {
+                let path = std::env::temp_dir().join(format!(
+                    "gitie-output-{}-{}",
+                    std::process::id(),
+                    SPILL_COUNTER.fetch_add(1, Ordering::Relaxed)
+                ));
+                tracing::debug!("Output is larger than {} bytes, spilling to {}", self.memory_limit, path.display());
+                let writer = BufWriter::new(File::create(&path)?);
+                self.spill = Some(SpillFile { path, writer });
+            }
+            if let Some(spill) = &mut self.spill {
+                spill.writer.write_all(&buf[in_memory..])?;
+            }
+        }
+        self.total_bytes += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.spill {
+            Some(spill) => spill.writer.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Output of a command whose stdout went into a `SpillBuffer`
+pub struct StreamedOutput {
+    pub stdout: SpillBuffer,
+    pub stderr: String,
+    pub status: ExitStatus,
+}
+
+/// Runs `command`, streaming its stdout into a `SpillBuffer` with `memory_limit`
+pub fn stream_output(command: &mut Command, memory_limit: usize) -> io::Result<StreamedOutput> {
+    let mut child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    // Read concurrently, so a command writing a lot to stderr doesn't block on it
+    let mut stderr_pipe = child.stderr.take().expect("stderr is piped");
+    let stderr_reader = std::thread::spawn(move || {
+        let mut stderr = Vec::new();
+        let _ = stderr_pipe.read_to_end(&mut stderr);
+        stderr
+    });
+    let mut stdout = SpillBuffer::new(memory_limit);
+    let copied = io::copy(&mut child.stdout.take().expect("stdout is piped"), &mut stdout);
+    let status = child.wait()?;
+    let stderr = stderr_reader.join().unwrap_or_default();
+    copied?;
+    stdout.flush()?;
+    Ok(StreamedOutput { stdout, stderr: String::from_utf8_lossy(&stderr).into_owned(), status })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spill_buffer_reads_back_memory_and_disk() {
+        let mut buffer = SpillBuffer::new(8);
+        buffer.write_all(b"first line\n").unwrap();
+        buffer.write_all(b"second line\n").unwrap();
+        assert!(buffer.spilled());
+        assert_eq!(buffer.total_bytes(), 23);
+        assert_eq!(buffer.text(), None);
+        let lines: Vec<String> = buffer.reader().unwrap().lines().map(Result::unwrap).collect();
+        assert_eq!(lines, vec!["first line", "second line"]);
+
+        let mut small = SpillBuffer::new(64);
+        small.write_all(b"fits").unwrap();
+        assert_eq!(small.text().as_deref(), Some("fits"));
+    }
+}
@@ -1,10 +1,21 @@
-use clap::Parser;
+//! Command-line arguments: git-enhancer's global options and subcommands,
+//! parsed by clap, and the split of a command line between them and git.
+
+use clap::error::ErrorKind;
+use clap::{Arg, ArgAction, Args, Command, CommandFactory, Parser, Subcommand};
+use crate::aliases::Aliases;
+use crate::output::ColorMode;
+use crate::ui::Verbosity;
 
 /// Defines the command-line arguments specific to `git-enhancer`'s own subcommands.
-/// This is typically used after determining that the invocation is not a global AI explanation request.
+/// `parse_command_line` uses it for commands whose first word names one of
+/// them (and isn't a git alias); all other commands go to git.
 #[derive(Parser, Debug)]
-#[clap(author="Huchen", version="0.1.0", about="Enhances Git with AI support for subcommands.", long_about=None, name = "git-enhancer-subcommand-parser")]
+#[clap(author="Huchen", version="0.1.0", about="Enhances Git with AI support for subcommands.", long_about=None, name = "git-enhancer")]
 pub struct GitEnhancerArgs {
+    #[clap(flatten)]
+    pub global: GlobalArgs,
+
     #[clap(subcommand)]
     pub command: EnhancerSubCommand,
 }
@@ -14,33 +25,1181 @@ pub struct GitEnhancerArgs {
 pub enum EnhancerSubCommand {
     /// Handle git commit operation, potentially with AI assistance for message generation.
     #[clap(alias = "cm")]
-    Commit(CommitArgs),
+    Commit(Box<CommitArgs>),
+    /// Inspect or modify the git-enhancer configuration file.
+    ///
+    /// Named so that `config` stays git's own command.
+    #[clap(name = "gitie-config")]
+    Config(ConfigArgs),
+    /// Print a prioritized checklist of what to do next in this repository.
+    Next(NextArgs),
+    /// Summarize each contributor's work over a time range with AI.
+    TeamSummary(TeamSummaryArgs),
+    /// List merged and gone branches, describe them with AI and delete the selected ones.
+    BranchesCleanup(BranchesCleanupArgs),
+    /// Check git, the configuration, the AI endpoints and the system clock.
+    Doctor,
+    /// Find commits by what their messages mean, using AI embeddings.
+    Search(SearchArgs),
+    /// Summarize a range of commits with AI, however long it is.
+    SummarizeLog(LogSummaryArgs),
+    /// Write release notes for a range of commits with AI.
+    Changelog(LogSummaryArgs),
+    /// Run an AI task defined in ~/.config/gitie/tasks/, or list them.
+    Run(RunTaskArgs),
+    /// Write a pull request title and description for the current branch with AI.
+    PrDescribe(PrDescribeArgs),
+    /// Export the recorded AI interactions, with secrets redacted, as a dataset.
+    ExportHistory(ExportHistoryArgs),
+    /// Serve git context and AI features to other tools over stdio.
+    Serve(ServeArgs),
+    /// Show the local usage statistics: commands, AI acceptance and latency.
+    Stats,
     // Future: Add(AddArgs)
-    // Future: Config(ConfigArgs)
 }
 
 /// Arguments for the `commit` subcommand.
+///
+/// Besides `--ai` and `--ticket`, these are `git commit`'s own options, so
+/// that `commit --ai` takes every command line `git commit` does. git gets
+/// them as they were given (`git_args`), together with any option git-enhancer
+/// doesn't know, in its place.
 #[derive(Parser, Debug, Clone)]
+#[clap(args_override_self = true)]
 pub struct CommitArgs {
     /// Use AI to generate the commit message (specific to the `commit` subcommand).
-    #[clap(long)]
+    #[clap(long, conflicts_with_all = ["message", "file", "reuse_message", "reedit_message"])]
     pub ai: bool,
 
+    /// Ticket the change is for (e.g. `PROJ-123` or `#45`); found in the branch name by default.
+    #[clap(long, requires = "ai")]
+    pub ticket: Option<String>,
+
     /// Automatically stage all tracked, modified files before commit (like git commit -a).
     #[clap(short = 'a', long = "all")]
     pub auto_stage: bool,
 
-    /// Pass a message directly to the commit.
+    /// Choose the changes to commit interactively (like git commit -p).
+    #[clap(short, long)]
+    pub patch: bool,
+
+    /// Pass a message directly to the commit; several become separate paragraphs.
+    #[clap(short, long, allow_hyphen_values = true)]
+    pub message: Vec<String>,
+
+    /// Take the message from a file, `-` for stdin (like git commit -F).
+    #[clap(short = 'F', long, value_name = "file")]
+    pub file: Option<String>,
+
+    /// Reuse the message and authorship of a commit (like git commit -C).
+    #[clap(short = 'C', long, value_name = "commit")]
+    pub reuse_message: Option<String>,
+
+    /// Like --reuse-message, but open the editor on the message (like git commit -c).
+    #[clap(short = 'c', long, value_name = "commit")]
+    pub reedit_message: Option<String>,
+
+    /// Make a fixup! commit for `git rebase --autosquash` (like git commit --fixup).
+    #[clap(long, value_name = "commit")]
+    pub fixup: Option<String>,
+
+    /// Make a squash! commit for `git rebase --autosquash` (like git commit --squash).
+    #[clap(long, value_name = "commit")]
+    pub squash: Option<String>,
+
+    /// Take over authorship of a reused or amended commit (like git commit --reset-author).
+    #[clap(long)]
+    pub reset_author: bool,
+
+    /// Start the editor with the contents of a file (like git commit -t).
+    #[clap(short, long, value_name = "file")]
+    pub template: Option<String>,
+
+    /// Add a Signed-off-by trailer (like git commit -s).
+    #[clap(short, long, overrides_with = "no_signoff")]
+    pub signoff: bool,
+
+    /// Don't add a Signed-off-by trailer, overriding an earlier --signoff or commit.signoff.
+    #[clap(long, overrides_with = "signoff")]
+    pub no_signoff: bool,
+
+    /// Add a trailer such as `Reviewed-by: Name <email>` (like git commit --trailer).
+    #[clap(long, value_name = "token=value")]
+    pub trailer: Vec<String>,
+
+    /// Bypass the pre-commit and commit-msg hooks (like git commit --no-verify).
+    #[clap(short = 'n', long, overrides_with = "verify")]
+    pub no_verify: bool,
+
+    /// Run the pre-commit and commit-msg hooks, overriding an earlier --no-verify.
+    #[clap(long, overrides_with = "no_verify")]
+    pub verify: bool,
+
+    /// Bypass the post-rewrite hook (like git commit --no-post-rewrite).
+    #[clap(long)]
+    pub no_post_rewrite: bool,
+
+    /// Allow a commit that changes nothing (like git commit --allow-empty).
+    #[clap(long)]
+    pub allow_empty: bool,
+
+    /// Allow a commit with an empty message (like git commit --allow-empty-message).
+    #[clap(long)]
+    pub allow_empty_message: bool,
+
+    /// How to clean up the message: strip, whitespace, verbatim, scissors or default.
+    #[clap(long, value_name = "mode")]
+    pub cleanup: Option<String>,
+
+    /// Open the editor on the message, even one given with -m or -F (like git commit -e).
+    #[clap(short, long, overrides_with = "no_edit")]
+    pub edit: bool,
+
+    /// Use the message as it is, e.g. the previous one with --amend (like git commit --no-edit).
+    #[clap(long, overrides_with = "edit")]
+    pub no_edit: bool,
+
+    /// Replace the tip of the current branch with a new commit (like git commit --amend).
+    #[clap(long)]
+    pub amend: bool,
+
+    /// Also stage the given paths before committing (like git commit -i).
+    #[clap(short, long)]
+    pub include: bool,
+
+    /// Commit only the given paths (like git commit -o).
+    #[clap(short, long)]
+    pub only: bool,
+
+    /// Read the paths to commit from a file, `-` for stdin (like git commit --pathspec-from-file).
+    #[clap(long, value_name = "file")]
+    pub pathspec_from_file: Option<String>,
+
+    /// Paths in --pathspec-from-file are separated by NUL characters.
+    #[clap(long)]
+    pub pathspec_file_nul: bool,
+
+    /// Show untracked files in the status: no, normal or all (like git commit -u).
+    #[clap(short, long, value_name = "mode", num_args = 0..=1, require_equals = true, default_missing_value = "all")]
+    pub untracked_files: Option<String>,
+
+    /// Show the diff in the commit message template; twice for unstaged changes too.
+    #[clap(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Suppress the commit summary (like git commit -q).
+    #[clap(short, long)]
+    pub quiet: bool,
+
+    /// Show what would be committed without committing (like git commit --dry-run).
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Include the output of `git status` in the message template.
+    #[clap(long, overrides_with = "no_status")]
+    pub status: bool,
+
+    /// Leave the output of `git status` out of the message template.
+    #[clap(long, overrides_with = "status")]
+    pub no_status: bool,
+
+    /// GPG-sign the commit, with the default key or this one (like git commit -S).
+    #[clap(short = 'S', long, value_name = "keyid", num_args = 0..=1, require_equals = true, default_missing_value = "")]
+    pub gpg_sign: Option<String>,
+
+    /// Don't GPG-sign the commit, overriding commit.gpgSign or an earlier -S.
+    #[clap(long)]
+    pub no_gpg_sign: bool,
+
+    /// Override the commit author, as `Name <email>` or a pattern (like git commit --author).
+    #[clap(long, value_name = "author")]
+    pub author: Option<String>,
+
+    /// Override the author date (like git commit --date).
+    #[clap(long, value_name = "date")]
+    pub date: Option<String>,
+
+    /// With --dry-run, show the status in short format.
+    #[clap(long)]
+    pub short: bool,
+
+    /// With --dry-run, show the branch and tracking information.
+    #[clap(long)]
+    pub branch: bool,
+
+    /// With --dry-run, show the status in a machine-readable format.
+    #[clap(long)]
+    pub porcelain: bool,
+
+    /// With --dry-run, show the status in long format (the default).
+    #[clap(long)]
+    pub long: bool,
+
+    /// With --short or --porcelain, end entries with NUL.
+    #[clap(short = 'z', long)]
+    pub null: bool,
+
+    /// Paths to commit: their working tree content, staged or not.
+    #[clap(value_name = "pathspec")]
+    pub pathspecs: Vec<String>,
+
+    /// The arguments for `git commit`: everything given except git-enhancer's
+    /// own options, verbatim and in order.
+    #[clap(skip)]
+    pub git_args: Vec<String>,
+
+    /// `git_args` without the pathspecs.
+    #[clap(skip)]
+    pub git_options: Vec<String>,
+}
+
+/// Arguments for the `next` subcommand.
+#[derive(Parser, Debug, Clone)]
+pub struct NextArgs {
+    /// Have the AI reword the checklist; the steps themselves always come from git.
+    #[clap(long)]
+    pub ai: bool,
+}
+
+/// Which commits a history command covers, for `summarize-log`, `changelog`
+/// and `team-summary`; checked by `history_selection`.
+#[derive(Args, Debug, Clone, Default, PartialEq)]
+pub struct HistoryArgs {
+    /// Commits to cover, e.g. `v1.2.0..HEAD`, `main...feature` or a branch and its history.
+    #[clap(value_name = "rev-range")]
+    pub range: Option<String>,
+
+    /// Only commits after this date, in any form `git log --since` accepts.
+    #[clap(long, value_name = "date")]
+    pub since: Option<String>,
+
+    /// Only commits before this date, in any form `git log --until` accepts.
+    #[clap(long, value_name = "date")]
+    pub until: Option<String>,
+
+    /// Only commits whose author matches this pattern; repeat for several authors.
+    #[clap(long, value_name = "pattern")]
+    pub author: Vec<String>,
+
+    /// Only commits that touch these paths, given after `--` as for `git log`.
+    #[clap(last = true, value_name = "path")]
+    pub paths: Vec<String>,
+}
+
+/// Arguments for the `team-summary` subcommand.
+///
+/// Without a range or `--since`, covers the last two weeks of HEAD's history.
+#[derive(Parser, Debug, Clone)]
+pub struct TeamSummaryArgs {
+    #[clap(flatten)]
+    pub history: HistoryArgs,
+}
+
+/// Arguments for the `branches-cleanup` subcommand.
+#[derive(Parser, Debug, Clone)]
+pub struct BranchesCleanupArgs {
+    /// Branch that merged branches were merged into; defaults to the remote's default branch.
+    #[clap(long)]
+    pub base: Option<String>,
+}
+
+/// Arguments for the `search` subcommand.
+#[derive(Parser, Debug, Clone)]
+pub struct SearchArgs {
+    /// What to look for, in plain words.
+    pub query: Vec<String>,
+
+    /// Number of commits to show.
+    #[clap(short = 'n', long, default_value_t = 10)]
+    pub limit: usize,
+
+    /// Only bring the index up to date, e.g. from a post-commit hook.
+    #[clap(long)]
+    pub update: bool,
+}
+
+/// Arguments for the `summarize-log` and `changelog` subcommands.
+///
+/// The range defaults to all of HEAD's history for `summarize-log`, and to the
+/// commits since the latest tag for `changelog`.
+#[derive(Parser, Debug, Clone)]
+pub struct LogSummaryArgs {
+    #[clap(flatten)]
+    pub history: HistoryArgs,
+
+    /// Commits summarized per request; longer ranges are summarized in chunks
+    /// whose summaries are then combined.
+    #[clap(long, default_value_t = 100)]
+    pub chunk_size: usize,
+}
+
+/// Arguments for the `run` subcommand.
+#[derive(Parser, Debug, Clone)]
+pub struct RunTaskArgs {
+    /// Name of the task file, without `.toml`. Lists the tasks when omitted.
+    pub task: Option<String>,
+
+    /// Arguments for the task: the range for `log` tasks, and `{{args}}` in its prompt.
+    #[clap(allow_hyphen_values = true, trailing_var_arg = true)]
+    pub args: Vec<String>,
+}
+
+/// Arguments for the `pr-describe` subcommand.
+#[derive(Parser, Debug, Clone)]
+pub struct PrDescribeArgs {
+    /// Branch the pull request merges into; defaults to the remote's default branch.
+    #[clap(long)]
+    pub base: Option<String>,
+
+    /// Push the branch if needed and open the pull request on the forge hosting the remote.
+    #[clap(long)]
+    pub create: bool,
+
+    /// Label for the pull request; repeat the option or separate labels with commas.
+    #[clap(long = "label", value_delimiter = ',', requires = "create")]
+    pub labels: Vec<String>,
+
+    /// User to request a review from; repeat the option or separate names with commas.
+    #[clap(long = "reviewer", value_delimiter = ',', requires = "create")]
+    pub reviewers: Vec<String>,
+
+    /// Open the pull request as a draft.
+    #[clap(long, requires = "create")]
+    pub draft: bool,
+
+    /// Ticket the branch is for (e.g. `PROJ-123` or `#45`); found in the branch name by default.
+    #[clap(long)]
+    pub ticket: Option<String>,
+}
+
+/// Arguments for the `export-history` subcommand.
+#[derive(Parser, Debug, Clone)]
+pub struct ExportHistoryArgs {
+    /// Output format: `jsonl`, one interaction per line.
+    #[clap(long, default_value = "jsonl", value_parser = ["jsonl"])]
+    pub format: String,
+}
+
+/// Arguments for the `serve` subcommand.
+#[derive(Parser, Debug, Clone)]
+#[clap(group = clap::ArgGroup::new("protocol").required(true))]
+pub struct ServeArgs {
+    /// Speak the Model Context Protocol, for AI assistants and IDE agents.
+    #[clap(long, group = "protocol")]
+    pub mcp: bool,
+
+    /// Speak the editor protocol (JSON-RPC), for editor plugins.
+    #[clap(long, group = "protocol")]
+    pub stdio: bool,
+}
+
+/// Arguments for the `gitie-config` subcommand.
+#[derive(Parser, Debug, Clone)]
+pub struct ConfigArgs {
+    #[clap(subcommand)]
+    pub action: ConfigAction,
+}
+
+/// Actions available under the `gitie-config` subcommand.
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigAction {
+    /// Open the user configuration file in your editor and validate it on save.
+    Edit,
+    /// Load and validate the effective configuration, then print the resolved values.
+    Check,
+    /// Set a single value (e.g. `ai.temperature 0.3`), preserving comments and formatting.
+    Set {
+        /// Dotted configuration key, such as `ai.model_name`.
+        key: String,
+        /// New value. Parsed as a TOML literal when possible, otherwise used as a string.
+        value: String,
+    },
+}
+
+/// git-enhancer's own options, given before the command, e.g.
+/// `git-enhancer -C repo --json --ai log`.
+///
+/// Options marked `global` are also accepted after a git-enhancer subcommand
+/// (`commit --ai --json`); after a git command only the `TRAILING_FLAGS` are,
+/// see `parse_command_line`.
+#[derive(Args, Debug, Default, Clone, PartialEq)]
+pub struct GlobalArgs {
+    /// Run the command, then explain its output with AI.
+    #[clap(long, visible_alias = "explain-output")]
+    pub ai: bool,
+
+    /// Explain what the command does with AI, without running it.
+    #[clap(long, conflicts_with = "ai")]
+    pub explain: bool,
+
+    /// Pass the command to git untouched, whatever the configuration or other options say.
+    #[clap(long)]
+    pub no_ai: bool,
+
+    /// Print the result as a JSON record.
+    #[clap(long, global = true)]
+    pub json: bool,
+
+    /// Print the result as `key value` lines.
+    #[clap(long)]
+    pub porcelain: bool,
+
+    /// Offer to run the commands suggested by an explanation.
+    #[clap(long)]
+    pub run: bool,
+
+    /// Define jargon the first time an explanation uses it.
+    #[clap(long)]
+    pub glossary: bool,
+
+    /// Run commands that can lose work without asking.
+    #[clap(long, global = true)]
+    pub force_yes: bool,
+
+    /// Write the AI result to a file instead of stdout (`-` is stdout).
+    #[clap(short = 'o', long)]
+    pub output: Option<String>,
+
+    /// Append to the `--output` file instead of replacing it.
+    #[clap(long)]
+    pub append: bool,
+
+    /// Seconds the AI requests of this run may take, retries included.
+    #[clap(long)]
+    pub timeout: Option<String>,
+
+    /// Also put the AI result on the clipboard.
+    #[clap(long, global = true)]
+    pub copy: bool,
+
+    /// Model, or model alias, to use instead of the configured ones.
+    #[clap(long, global = true, value_name = "name-or-alias")]
+    pub model: Option<String>,
+
+    /// Sampling temperature to use instead of the configured ones.
+    #[clap(long, global = true)]
+    pub temperature: Option<f32>,
+
+    /// Language of explanations.
+    #[clap(long, global = true)]
+    pub lang: Option<String>,
+
+    /// Explanation level: beginner, normal or expert.
+    #[clap(long, global = true)]
+    pub level: Option<String>,
+
+    /// File with the system prompt to use instead of the configured one.
+    #[clap(long, global = true)]
+    pub prompt_file: Option<String>,
+
+    /// System prompt to use instead of the configured one.
+    #[clap(long, global = true)]
+    pub system_prompt: Option<String>,
+
+    /// Directory the model is limited to.
+    #[clap(long, global = true)]
+    pub scope: Option<String>,
+
+    /// Background for the model, e.g. why the change was made; repeatable, `-` reads it from stdin.
+    #[clap(long, global = true, value_name = "text", allow_hyphen_values = true)]
+    pub context: Vec<String>,
+
+    /// Use the default answer to every question.
+    #[clap(long)]
+    pub no_input: bool,
+
+    /// Answer yes to confirmations.
+    #[clap(long)]
+    pub yes: bool,
+
+    /// When to use colors; a bare `--color` means `always`.
+    #[clap(
+        long,
+        value_name = "when",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "always",
+        value_parser = ["auto", "always", "never"]
+    )]
+    pub color: Option<String>,
+
+    /// Only print errors.
     #[clap(short, long)]
-    pub message: Option<String>,
+    pub quiet: bool,
+
+    /// Log more; repeat for more detail.
+    #[clap(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Run as if started in this directory; each is relative to the previous one.
+    #[clap(short = 'C', value_name = "path")]
+    pub directories: Vec<String>,
+
+    /// The repository's `.git` directory, exported as `GIT_DIR`.
+    #[clap(long, value_name = "path")]
+    pub git_dir: Option<String>,
+
+    /// The working tree, exported as `GIT_WORK_TREE`.
+    #[clap(long, value_name = "path")]
+    pub work_tree: Option<String>,
+
+    /// Use this configuration file instead of ~/.config/gitie/config.toml, exported as `GITIE_CONFIG`.
+    #[clap(long, value_name = "path")]
+    pub config: Option<String>,
+}
+
+impl GlobalArgs {
+    pub fn verbosity(&self) -> Verbosity {
+        Verbosity { quiet: self.quiet, verbose: self.verbose }
+    }
+
+    pub fn color_mode(&self) -> Option<ColorMode> {
+        self.color.as_deref().and_then(ColorMode::from_name)
+    }
+
+    /// Whether any option that affects AI features or output was given, as
+    /// opposed to ones that only set up the process (verbosity, color,
+    /// answers, the repository).
+    pub fn affects_features(&self) -> bool {
+        let process_only = GlobalArgs {
+            quiet: self.quiet,
+            verbose: self.verbose,
+            color: self.color.clone(),
+            no_input: self.no_input,
+            yes: self.yes,
+            directories: self.directories.clone(),
+            git_dir: self.git_dir.clone(),
+            work_tree: self.work_tree.clone(),
+            config: self.config.clone(),
+            ..GlobalArgs::default()
+        };
+        *self != process_only
+    }
+
+    /// Adds the `global` options given after a git-enhancer subcommand
+    fn merge(&mut self, later: GlobalArgs) {
+        self.json |= later.json;
+        self.force_yes |= later.force_yes;
+        self.copy |= later.copy;
+        self.context.extend(later.context);
+        if later.temperature.is_some() {
+            self.temperature = later.temperature;
+        }
+        let values = [
+            (&mut self.model, later.model),
+            (&mut self.lang, later.lang),
+            (&mut self.level, later.level),
+            (&mut self.prompt_file, later.prompt_file),
+            (&mut self.system_prompt, later.system_prompt),
+            (&mut self.scope, later.scope),
+        ];
+        for (slot, value) in values {
+            if value.is_some() {
+                *slot = value;
+            }
+        }
+    }
+}
+
+/// Parses `GlobalArgs` on their own
+#[derive(Parser, Debug)]
+#[clap(name = "git-enhancer", no_binary_name = true, disable_help_flag = true, disable_version_flag = true)]
+struct GlobalArgsParser {
+    #[clap(flatten)]
+    global: GlobalArgs,
+}
+
+/// A command line taken apart: git-enhancer's options, git's options and the command.
+#[derive(Debug)]
+pub struct CommandLine {
+    pub global: GlobalArgs,
+    /// git's own options before the command, e.g. `-c key=value` or `--no-pager`
+    pub git_options: Vec<String>,
+    /// The command and its arguments, exactly as given
+    pub command: Vec<String>,
+    pub kind: CommandKind,
+}
+
+/// What a command line asks for
+#[derive(Debug)]
+pub enum CommandKind {
+    /// One of git-enhancer's subcommands
+    Enhancer(EnhancerSubCommand),
+    /// `-h`/`--help` for a command, or for git itself
+    Help,
+    /// Anything else, which git runs
+    Git,
+}
+
+impl CommandLine {
+    /// The arguments to give git: its own options, then the command
+    pub fn git_args(&self) -> Vec<String> {
+        self.git_options.iter().chain(&self.command).cloned().collect()
+    }
+}
+
+/// git's leading options that take their value as the next argument
+const GIT_OPTIONS_WITH_VALUE: &[&str] = &["-c", "--namespace", "--config-env"];
+
+/// git-enhancer options that are also recognized at the end of a git command,
+/// e.g. `status --ai`; git has no options with these names.
+const TRAILING_FLAGS: &[&str] = &["--ai", "--explain-output", "--explain", "--json", "--force-yes"];
+
+/// The options that ask for an explanation
+const EXPLAIN_FLAGS: &[&str] = &["--ai", "--explain-output", "--explain"];
+
+/// Takes a command line apart.
+///
+/// The leading options up to the command are split between git-enhancer
+/// (`GlobalArgs`) and git (everything else, e.g. `-c` or `--no-pager`). The
+/// command itself is kept verbatim: a git-enhancer subcommand is parsed by
+/// clap, and a git command goes to git untouched except for trailing
+/// `TRAILING_FLAGS`. So `commit -m --ai` is a message, and `log --grep=--ai`
+/// searches for one.
+///
+/// The command word is expanded with `aliases` first, and a word git has an
+/// alias for is git's even when it names a git-enhancer subcommand.
+///
+/// A help request (`-h`/`--help`) only shows git's help for the command, so
+/// `--ai` or `--explain` anywhere in it asks for an explanation. A lone `-v`
+/// is `git -v` (git's version).
+pub fn parse_command_line(args: &[String], aliases: &Aliases) -> Result<CommandLine, clap::Error> {
+    let (mut ours, git_options, i) = split_leading_options(args);
+    let global = GlobalArgsParser::try_parse_from(&ours)?.global;
+    if global.no_ai {
+        // Not even aliases or trailing flags: git gets exactly what was typed
+        return Ok(CommandLine { global, git_options, command: args[i..].to_vec(), kind: CommandKind::Git });
+    }
+    let mut command = aliases
+        .expand(args[i..].to_vec())
+        .map_err(|message| clap::Error::raw(ErrorKind::InvalidValue, format!("{}\n", message)))?;
+
+    let subcommand = command.first().and_then(|name| {
+        let subcommand = GitEnhancerArgs::command().find_subcommand(name)?.get_name().to_string();
+        (!aliases.is_git_alias(name)).then_some(subcommand)
+    });
+    let mut kind = CommandKind::Git;
+    let mut later_global = None;
+    if let Some(subcommand) = subcommand {
+        let mut parser_args = vec!["git-enhancer".to_string(), command[0].clone()];
+        let commit_arguments = (subcommand == "commit").then(|| sort_commit_arguments(&command[1..]));
+        match &commit_arguments {
+            Some(sorted) => parser_args.extend_from_slice(&sorted.for_clap),
+            None => parser_args.extend_from_slice(&command[1..]),
+        }
+        match GitEnhancerArgs::try_parse_from(&parser_args) {
+            Ok(parsed) => {
+                let mut parsed_command = parsed.command;
+                if let (EnhancerSubCommand::Commit(commit_args), Some(sorted)) = (&mut parsed_command, commit_arguments) {
+                    if !commit_args.ai && !parsed.global.context.is_empty() {
+                        let message = "'--context' is only used by 'commit --ai'\n".to_string();
+                        return Err(clap::Error::raw(ErrorKind::MissingRequiredArgument, message));
+                    }
+                    // git would only reject it once the message has been generated
+                    if commit_args.ai
+                        && let Some(misplaced) = sorted.unknown.iter().find(|arg| global_option(&GlobalArgsParser::command(), arg).is_some())
+                    {
+                        let message = format!("'{}' is an option of git-enhancer; give it before the command\n", misplaced);
+                        return Err(clap::Error::raw(ErrorKind::UnknownArgument, message));
+                    }
+                    commit_args.git_args = sorted.git_args;
+                    commit_args.git_options = sorted.git_options;
+                }
+                later_global = Some(parsed.global);
+                kind = CommandKind::Enhancer(parsed_command);
+            }
+            Err(e) if e.kind() == ErrorKind::DisplayHelp => kind = CommandKind::Help,
+            // Asking a subcommand for AI with arguments it doesn't take is a
+            // mistake; anything else is left to git, e.g. `commit -v`
+            Err(e) if subcommand_flag(&parser_args, "ai") => return Err(e),
+            Err(_) => {}
+        }
+    } else {
+        let end = command.iter().position(|arg| arg == "--").unwrap_or(command.len());
+        let options = if command.is_empty() { &git_options[..] } else { &command[..end] };
+        if options.iter().any(|arg| arg == "-h" || arg == "--help") {
+            kind = CommandKind::Help;
+        } else if end == command.len() {
+            while command.len() > 1 && TRAILING_FLAGS.contains(&command[command.len() - 1].as_str()) {
+                ours.push(command.pop().unwrap());
+            }
+        }
+    }
+    if matches!(kind, CommandKind::Help) {
+        let end = command.iter().position(|arg| arg == "--").unwrap_or(command.len());
+        let mut index = 0;
+        command.retain(|arg| {
+            index += 1;
+            let explain = index <= end && EXPLAIN_FLAGS.contains(&arg.as_str());
+            if explain {
+                ours.push(arg.clone());
+            }
+            !explain
+        });
+    }
+
+    let mut global = GlobalArgsParser::try_parse_from(&ours)?.global;
+    if let Some(later) = later_global {
+        global.merge(later);
+    }
+    Ok(CommandLine { global, git_options, command, kind })
+}
+
+/// git-enhancer's options before the command, for what has to be settled
+/// before the command line can be taken apart, such as `--config`.
+pub fn leading_global_args(args: &[String]) -> Result<GlobalArgs, clap::Error> {
+    let (ours, _, _) = split_leading_options(args);
+    Ok(GlobalArgsParser::try_parse_from(&ours)?.global)
+}
+
+/// The leading options split into git-enhancer's and git's, and the index of
+/// the command.
+fn split_leading_options(args: &[String]) -> (Vec<String>, Vec<String>, usize) {
+    let global_command = GlobalArgsParser::command();
+    let mut ours = Vec::new();
+    let mut git_options = Vec::new();
+    let mut i = 0;
+    while i < args.len() && args[i].starts_with('-') && args[i] != "--" && args != ["-v"] {
+        let arg = &args[i];
+        match global_option(&global_command, arg) {
+            Some(option) => {
+                ours.push(arg.clone());
+                let inline = arg.contains('=') || (!arg.starts_with("--") && arg.len() > 2);
+                if option.get_action().takes_values() && !inline && i + 1 < args.len() {
+                    let next = &args[i + 1];
+                    if !option.is_require_equals_set() {
+                        ours.push(next.clone());
+                        i += 1;
+                    } else if option.get_possible_values().iter().any(|value| value.matches(next, false)) {
+                        // `--color auto`, as git accepts it
+                        *ours.last_mut().unwrap() = format!("{}={}", arg, next);
+                        i += 1;
+                    }
+                }
+            }
+            None => {
+                git_options.push(arg.clone());
+                if GIT_OPTIONS_WITH_VALUE.contains(&arg.as_str()) && i + 1 < args.len() {
+                    git_options.push(args[i + 1].clone());
+                    i += 1;
+                }
+            }
+        }
+        i += 1;
+    }
+    (ours, git_options, i)
+}
+
+/// The arguments of `commit`, sorted between clap and `git commit`
+#[derive(Debug, Default)]
+struct CommitArguments {
+    /// What clap parses into `CommitArgs`
+    for_clap: Vec<String>,
+    /// `CommitArgs::git_args`
+    git_args: Vec<String>,
+    /// `CommitArgs::git_options`
+    git_options: Vec<String>,
+    /// The options among them that `commit` doesn't know
+    unknown: Vec<String>,
+}
+
+/// Sorts the arguments of `commit` (after the word itself) between clap and
+/// git: git-enhancer's own options only go to clap, options it doesn't know
+/// only to git, and everything else to both.
+///
+/// git gets the arguments verbatim. clap gets each option in its long form
+/// with an attached value (`-am x` becomes `--all --message=x`), so git's
+/// forms that clap doesn't take, such as `-Skeyid`, parse too. The value of
+/// an unknown option has to be attached (`--option=value`); otherwise it is
+/// taken for a path.
+fn sort_commit_arguments(args: &[String]) -> CommitArguments {
+    let mut command = GitEnhancerArgs::command();
+    command.build();
+    let commit = command.find_subcommand("commit").expect("commit is a subcommand");
+    let is_own = |option: &Arg| option.is_global_set() || ["ai", "ticket"].contains(&option.get_id().as_str());
 
-    /// Allow all other flags and arguments to be passed through to the underlying `git commit`.
-    #[clap(allow_hyphen_values = true, last = true)]
-    pub passthrough_args: Vec<String>,
+    let mut sorted = CommitArguments::default();
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+        if arg == "--" || !arg.starts_with('-') || arg == "-" {
+            // After `--` everything is a path
+            let end = if arg == "--" { args.len() } else { i + 1 };
+            sorted.for_clap.extend_from_slice(&args[i..end]);
+            sorted.git_args.extend_from_slice(&args[i..end]);
+            i = end;
+            continue;
+        }
+        let Some((options, values)) = commit_option(commit, arg, args.get(i + 1)) else {
+            sorted.git_args.push(arg.clone());
+            sorted.git_options.push(arg.clone());
+            sorted.unknown.push(arg.clone());
+            i += 1;
+            continue;
+        };
+        if !options.iter().any(|(option, _)| is_own(option)) {
+            sorted.git_args.extend_from_slice(&args[i..=i + values]);
+            sorted.git_options.extend_from_slice(&args[i..=i + values]);
+        }
+        sorted.for_clap.extend(options.into_iter().map(|(_, normalized)| normalized));
+        i += 1 + values;
+    }
+    sorted
 }
 
-/// Checks if a slice of string arguments contains "-h" or "--help".
-#[inline]
-pub fn args_contain_help(args: &[String]) -> bool {
-    args.iter().any(|arg| arg == "-h" || arg == "--help")
+/// The options of `commit` that `arg` stands for, each with its normalized
+/// form, and whether `next` is the value of the last one (0 or 1); `None`
+/// when `arg` isn't an option `commit` knows.
+fn commit_option<'a>(commit: &'a Command, arg: &str, next: Option<&String>) -> Option<(Vec<(&'a Arg, String)>, usize)> {
+    let normalized = |option: &Arg, value: Option<&str>| {
+        let name = match option.get_long() {
+            Some(long) => format!("--{}", long),
+            None => format!("-{}", option.get_short().unwrap_or_default()),
+        };
+        value.map_or(name.clone(), |value| format!("{}={}", name, value))
+    };
+    let needs_value = |option: &Arg| option.get_num_args().is_some_and(|range| range.min_values() > 0);
+
+    if let Some(long) = arg.strip_prefix("--") {
+        let (name, inline) = long.split_once('=').map_or((long, None), |(name, value)| (name, Some(value)));
+        let option = commit
+            .get_arguments()
+            .find(|option| option.get_long_and_visible_aliases().is_some_and(|names| names.contains(&name)))?;
+        if !option.get_action().takes_values() {
+            // `--amend=x` is left for clap to reject
+            return Some((vec![(option, arg.to_string())], 0));
+        }
+        return match (inline, next) {
+            (Some(value), _) => Some((vec![(option, normalized(option, Some(value)))], 0)),
+            (None, Some(next)) if needs_value(option) => Some((vec![(option, normalized(option, Some(next)))], 1)),
+            (None, _) => Some((vec![(option, normalized(option, None))], 0)),
+        };
+    }
+
+    let shorts: Vec<char> = arg.strip_prefix('-')?.chars().collect();
+    let mut options = Vec::new();
+    for (j, &short) in shorts.iter().enumerate() {
+        let option = commit.get_arguments().find(|option| option.get_short() == Some(short))?;
+        if !option.get_action().takes_values() {
+            options.push((option, normalized(option, None)));
+            continue;
+        }
+        // The rest of the argument is the value, as in `-mMessage` or `-Skeyid`
+        let rest: String = shorts[j + 1..].iter().collect();
+        return Some(match next {
+            _ if !rest.is_empty() => {
+                options.push((option, normalized(option, Some(&rest))));
+                (options, 0)
+            }
+            Some(next) if needs_value(option) => {
+                options.push((option, normalized(option, Some(next))));
+                (options, 1)
+            }
+            _ => {
+                options.push((option, normalized(option, None)));
+                (options, 0)
+            }
+        });
+    }
+    Some((options, 0))
+}
+
+/// The global option `arg` is, when it is one of git-enhancer's. Short options
+/// may be combined (`-vv`, `-qv`) when they take no value.
+fn global_option<'a>(command: &'a Command, arg: &str) -> Option<&'a Arg> {
+    if let Some(long) = arg.strip_prefix("--") {
+        let name = long.split_once('=').map_or(long, |(name, _)| name);
+        return command
+            .get_arguments()
+            .find(|option| option.get_long_and_visible_aliases().is_some_and(|names| names.contains(&name)));
+    }
+    let shorts: Vec<char> = arg.strip_prefix('-')?.chars().collect();
+    let options: Vec<&Arg> = shorts
+        .iter()
+        .map(|&short| command.get_arguments().find(|option| option.get_short() == Some(short)))
+        .collect::<Option<_>>()?;
+    match options.as_slice() {
+        [option] => Some(option),
+        combined if combined.iter().all(|option| !option.get_action().takes_values()) => combined.first().copied(),
+        _ => None,
+    }
+}
+
+/// Whether a git-enhancer subcommand line that clap rejected still sets the
+/// subcommand's boolean option `id`, e.g. `ai` in `commit --ai -S`.
+fn subcommand_flag(parser_args: &[String], id: &str) -> bool {
+    let Ok(matches) = GitEnhancerArgs::command().ignore_errors(true).try_get_matches_from(parser_args) else {
+        return false;
+    };
+    matches.subcommand().is_some_and(|(_, sub_matches)| {
+        sub_matches.try_get_one::<bool>(id).ok().flatten().copied().unwrap_or(false)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn parse_command_line_without_aliases(args: &[String]) -> Result<CommandLine, clap::Error> {
+        parse_command_line(args, &Aliases::default())
+    }
+
+    fn parse(args: &[&str]) -> CommandLine {
+        parse_command_line(&to_args(args), &Aliases::default()).unwrap()
+    }
+
+    fn commit_args(line: CommandLine) -> CommitArgs {
+        match line.kind {
+            CommandKind::Enhancer(EnhancerSubCommand::Commit(commit_args)) => *commit_args,
+            other => panic!("expected the commit subcommand, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_leading_options_are_split_between_git_enhancer_and_git() {
+        let line = parse(&["-C", "repo", "-c", "color.ui=never", "--git-dir=.git", "-vv", "-C", "sub", "--no-pager", "--ai", "log", "-C", "-v"]);
+        assert_eq!(line.global.directories, to_args(&["repo", "sub"]));
+        assert_eq!(line.global.git_dir.as_deref(), Some(".git"));
+        assert_eq!(line.global.verbosity(), Verbosity { quiet: false, verbose: 2 });
+        assert!(line.global.ai);
+        assert_eq!(line.git_options, to_args(&["-c", "color.ui=never", "--no-pager"]));
+        assert_eq!(line.command, to_args(&["log", "-C", "-v"]));
+        assert!(matches!(line.kind, CommandKind::Git));
+
+        let line = parse(&["-o", "notes.md", "--append", "--lang=en", "--ai", "diff", "--output=x", "--append"]);
+        assert_eq!((line.global.output.as_deref(), line.global.append), (Some("notes.md"), true));
+        assert_eq!(line.global.lang.as_deref(), Some("en"));
+        assert_eq!(line.command, to_args(&["diff", "--output=x", "--append"]));
+
+        // `git -v` prints git's version
+        let line = parse(&["-v"]);
+        assert_eq!((line.global.verbose, line.git_args()), (0, to_args(&["-v"])));
+
+        let leading = leading_global_args(&to_args(&["--config", "ci.toml", "-c", "a=b", "status", "--json"])).unwrap();
+        assert_eq!((leading.config.as_deref(), leading.json), (Some("ci.toml"), false));
+
+        assert!(parse_command_line_without_aliases(&to_args(&["--output"])).is_err());
+        assert!(parse_command_line_without_aliases(&to_args(&["-C"])).is_err());
+    }
+
+    #[test]
+    fn test_color_option_forms() {
+        assert_eq!(parse(&["--color=never", "log", "--color"]).global.color_mode(), Some(ColorMode::Never));
+        let line = parse(&["--color", "auto", "status"]);
+        assert_eq!((line.global.color_mode(), line.command), (Some(ColorMode::Auto), to_args(&["status"])));
+        let line = parse(&["--color", "status"]);
+        assert_eq!((line.global.color_mode(), line.command), (Some(ColorMode::Always), to_args(&["status"])));
+        assert!(parse_command_line_without_aliases(&to_args(&["--color=sometimes", "status"])).is_err());
+    }
+
+    #[test]
+    fn test_arguments_of_subcommands_are_parsed_by_clap() {
+        let line = parse(&["commit", "-m", "--ai"]);
+        assert!(!line.global.ai);
+        let commit = commit_args(line);
+        assert!(!commit.ai);
+        assert_eq!(commit.message, to_args(&["--ai"]));
+
+        // Global options may follow the subcommand
+        let line = parse(&["--temperature", "0.2", "commit", "--ai", "--json", "--scope", "web", "--model", "fast"]);
+        assert!(line.global.json && !line.global.ai);
+        assert_eq!((line.global.model.as_deref(), line.global.temperature), (Some("fast"), Some(0.2)));
+        assert_eq!(line.global.scope.as_deref(), Some("web"));
+        let commit = commit_args(line);
+        assert!(commit.ai && commit.git_args.is_empty());
+
+        // A leading --ai explains the command of the same name
+        let line = parse(&["--ai", "commit", "-m", "A message"]);
+        assert!(line.global.ai);
+        assert_eq!(line.git_args(), to_args(&["commit", "-m", "A message"]));
+
+        // Arguments the subcommand doesn't take are git's, unless AI was asked for
+        assert!(matches!(parse(&["config", "user.name", "Me"]).kind, CommandKind::Git));
+        // `config` is git's, even for what `gitie-config` does
+        for git_config in [&["config", "edit"][..], &["config", "set", "ai.temperature", "0.3"], &["config", "check"]] {
+            assert!(matches!(parse(git_config).kind, CommandKind::Git), "{:?} should go to git", git_config);
+        }
+        assert!(matches!(parse(&["gitie-config", "edit"]).kind, CommandKind::Enhancer(EnhancerSubCommand::Config(_))));
+        assert!(matches!(parse(&["next", "--bogus"]).kind, CommandKind::Git));
+        assert!(parse_command_line_without_aliases(&to_args(&["next", "--ai", "--bogus"])).is_err());
+        assert!(parse_command_line_without_aliases(&to_args(&["commit", "--ai", "-m", "Mine"])).is_err());
+    }
+
+    #[test]
+    fn test_aliases_are_expanded_before_parsing() {
+        let aliases = Aliases {
+            configured: [("cm".to_string(), "commit --ai".to_string()), ("st".to_string(), "status -s".to_string())].into(),
+            git_config: false,
+        };
+        let line = parse_command_line(&to_args(&["--json", "cm", "-a"]), &aliases).unwrap();
+        assert!(line.global.json);
+        let commit = commit_args(line);
+        assert!(commit.ai && commit.auto_stage);
+        let line = parse_command_line(&to_args(&["st", "--ai"]), &aliases).unwrap();
+        assert!(line.global.ai);
+        assert_eq!(line.command, to_args(&["status", "-s"]));
+    }
+
+    #[test]
+    fn test_git_commands_are_kept_verbatim_except_trailing_flags() {
+        let line = parse(&["status", "--ai"]);
+        assert!(line.global.ai);
+        assert_eq!(line.command, to_args(&["status"]));
+
+        let line = parse(&["log", "--grep=--ai", "--oneline", "--json"]);
+        assert!(line.global.json && !line.global.ai);
+        assert_eq!(line.command, to_args(&["log", "--grep=--ai", "--oneline"]));
+
+        let line = parse(&["log", "-1", "--explain-output"]);
+        assert!(line.global.ai && !line.global.explain);
+        assert_eq!(line.command, to_args(&["log", "-1"]));
+        assert!(parse_command_line_without_aliases(&to_args(&["--explain", "status"])).is_ok_and(|line| line.global.explain && !line.global.ai));
+        assert!(parse_command_line_without_aliases(&to_args(&["--ai", "--explain", "status"])).is_err());
+
+        // After `--` everything is a path
+        let line = parse(&["log", "--", "--ai"]);
+        assert!(!line.global.ai);
+        assert_eq!(line.command, to_args(&["log", "--", "--ai"]));
+
+        let line = parse(&["branch", "--copy", "old", "new"]);
+        assert!(!line.global.copy);
+        assert_eq!(line.command, to_args(&["branch", "--copy", "old", "new"]));
+    }
+
+    #[test]
+    fn test_no_ai_leaves_the_command_to_git() {
+        let aliases = Aliases { configured: [("st".to_string(), "status --ai".to_string())].into(), git_config: false };
+        for command in [&["st", "--json"][..], &["commit", "--ai", "--bogus"], &["log", "--help", "--ai"]] {
+            let mut args = to_args(&["--no-ai", "--ai", "-c", "x.y=z"]);
+            args.extend(to_args(command));
+            let line = parse_command_line(&args, &aliases).unwrap();
+            assert!(line.global.no_ai && matches!(line.kind, CommandKind::Git));
+            assert_eq!(line.git_args()[2..], to_args(command)[..]);
+        }
+    }
+
+    #[test]
+    fn test_help_requests() {
+        let cases: [(&[&str], &[&str]); 3] = [
+            (&["commit", "--help", "--ai"], &["commit", "--help"]),
+            (&["--ai", "commit", "--help"], &["commit", "--help"]),
+            (&["commit", "--ai", "-h"], &["commit", "-h"]),
+        ];
+        for (args, git_args) in cases {
+            let line = parse(args);
+            assert!(matches!(line.kind, CommandKind::Help) && line.global.ai, "{:?}", args);
+            assert_eq!(line.git_args(), to_args(git_args));
+        }
+
+        let line = parse(&["status", "--short", "--help", "--ai"]);
+        assert!(matches!(line.kind, CommandKind::Help) && line.global.ai);
+        assert_eq!(line.command, to_args(&["status", "--short", "--help"]));
+
+        let line = parse(&["--ai", "--help"]);
+        assert!(matches!(line.kind, CommandKind::Help) && line.global.ai);
+        assert_eq!(line.git_args(), to_args(&["--help"]));
+
+        assert!(matches!(parse(&["log", "--", "--help"]).kind, CommandKind::Git));
+    }
+
+    #[test]
+    fn test_commit_takes_the_options_of_git_commit() {
+        let commit = commit_args(parse(&["commit", "--ai", "-asS", "--author", "Me <me@example.com>", "--date=now", "--no-edit", "--amend"]));
+        assert!(commit.ai && commit.auto_stage && commit.signoff && commit.amend && commit.no_edit);
+        assert_eq!(commit.gpg_sign.as_deref(), Some(""));
+        assert_eq!((commit.author.as_deref(), commit.date.as_deref()), (Some("Me <me@example.com>"), Some("now")));
+        assert_eq!(commit.git_args, to_args(&["-asS", "--author", "Me <me@example.com>", "--date=now", "--no-edit", "--amend"]));
+
+        // Values attached the way git takes them
+        let commit = commit_args(parse(&["commit", "-SABCD", "-mFirst", "-m", "Second", "-uno", "--fixup", "HEAD~2", "-p"]));
+        assert_eq!(commit.gpg_sign.as_deref(), Some("ABCD"));
+        assert_eq!(commit.message, to_args(&["First", "Second"]));
+        assert_eq!((commit.untracked_files.as_deref(), commit.fixup.as_deref()), (Some("no"), Some("HEAD~2")));
+        assert!(commit.patch && !commit.ai);
+
+        // Options git-enhancer doesn't know stay in their place; its own are git-enhancer's
+        let line = parse(&["--context", "why", "commit", "src/a.rs", "--ai", "--no-such-option", "-n", "--ticket", "PROJ-1", "--json", "--context", "-", "docs", "--", "-odd-name"]);
+        assert_eq!(line.global.context, to_args(&["why", "-"]));
+        let commit = commit_args(line);
+        assert_eq!(commit.ticket.as_deref(), Some("PROJ-1"));
+        assert_eq!(commit.pathspecs, to_args(&["src/a.rs", "docs", "-odd-name"]));
+        assert_eq!(commit.git_args, to_args(&["src/a.rs", "--no-such-option", "-n", "docs", "--", "-odd-name"]));
+        assert_eq!(commit.git_options, to_args(&["--no-such-option", "-n"]));
+
+        // The last of --verify / --no-verify wins, as in git
+        let commit = commit_args(parse(&["commit", "--no-verify", "--verify", "-v", "-v", "-s", "-s"]));
+        assert!(commit.verify && !commit.no_verify && commit.signoff);
+        assert_eq!(commit.verbose, 2);
+        assert!(parse_command_line_without_aliases(&to_args(&["commit", "--ai", "--author"])).is_err());
+        assert!(parse_command_line_without_aliases(&to_args(&["commit", "--ai", "--yes"])).is_err());
+        assert!(parse_command_line_without_aliases(&to_args(&["commit", "-m", "Mine", "--context", "why"])).is_err());
+        assert!(commit_args(parse(&["commit", "--ai", "-q", "-v"])).quiet);
+    }
+
+    #[test]
+    fn test_nothing_after_double_dash_is_an_option_of_git_enhancer() {
+        let line = parse(&["log", "--oneline", "--", "--ai", "--json"]);
+        assert!(matches!(line.kind, CommandKind::Git) && !line.global.ai && !line.global.json);
+        assert_eq!(line.command, to_args(&["log", "--oneline", "--", "--ai", "--json"]));
+        let line = parse(&["--explain", "log", "--", "--ai"]);
+        assert!(line.global.explain && !line.global.ai);
+        let line = parse(&["log", "-h", "--", "--explain"]);
+        assert!(matches!(line.kind, CommandKind::Help) && !line.global.explain);
+        assert_eq!(line.command, to_args(&["log", "-h", "--", "--explain"]));
+        // The separator itself is git's: `git-enhancer -- x` is `git -- x`
+        assert_eq!(parse(&["--", "--ai"]).command, to_args(&["--", "--ai"]));
+
+        let line = parse(&["commit", "-m", "Mine", "--", "--ai", "--context", "-a"]);
+        assert!(line.global.context.is_empty());
+        let commit = commit_args(line);
+        assert!(!commit.ai && !commit.auto_stage);
+        assert_eq!(commit.pathspecs, to_args(&["--ai", "--context", "-a"]));
+        assert_eq!(commit.git_args, to_args(&["-m", "Mine", "--", "--ai", "--context", "-a"]));
+        let line = parse(&["commit", "--ai", "--", "--json", "-a", "--"]);
+        assert!(!line.global.json);
+        let commit = commit_args(line);
+        assert_eq!((commit.git_options.len(), commit.pathspecs), (0, to_args(&["--json", "-a", "--"])));
+        // As in git, an option's value may look like the separator
+        let line = parse(&["commit", "--ai", "--context", "--", "file"]);
+        assert_eq!(line.global.context, to_args(&["--"]));
+        assert_eq!(commit_args(line).pathspecs, to_args(&["file"]));
+
+        // Paths of history commands come after `--`, whatever they look like
+        let line = parse(&["summarize-log", "v1.0..HEAD", "--since", "monday", "--", "--since", "src/a b.rs"]);
+        match line.kind {
+            CommandKind::Enhancer(EnhancerSubCommand::SummarizeLog(args)) => {
+                assert_eq!(args.history.since.as_deref(), Some("monday"));
+                assert_eq!(args.history.paths, to_args(&["--since", "src/a b.rs"]));
+            }
+            other => panic!("expected summarize-log, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_quoted_arguments_stay_whole() {
+        // What the shell passed as one word is one argument, options in it included
+        let commit = commit_args(parse(&["commit", "-m", "--ai", "-m", "Fix -- and --json handling"]));
+        assert!(!commit.ai);
+        assert_eq!(commit.message, to_args(&["--ai", "Fix -- and --json handling"]));
+        assert_eq!(commit.git_args, to_args(&["-m", "--ai", "-m", "Fix -- and --json handling"]));
+        let line = parse(&["--context", "--json", "--context=a -- b", "commit", "--ai", "--", "my file.rs"]);
+        assert_eq!((line.global.context.clone(), line.global.json), (to_args(&["--json", "a -- b"]), false));
+        assert_eq!(commit_args(line).git_args, to_args(&["--", "my file.rs"]));
+        let line = parse(&["log", "--grep=--ai", "--format=%s -- %an", "--ai"]);
+        assert!(line.global.ai);
+        assert_eq!(line.command, to_args(&["log", "--grep=--ai", "--format=%s -- %an"]));
+
+        let aliases = Aliases {
+            configured: [("wip".to_string(), "commit -m 'wip -- --ai' --".to_string())].into_iter().collect(),
+            git_config: false,
+        };
+        let line = parse_command_line(&to_args(&["wip", "--json"]), &aliases).unwrap();
+        assert!(!line.global.json);
+        let commit = commit_args(line);
+        assert_eq!((commit.message, commit.pathspecs), (to_args(&["wip -- --ai"]), to_args(&["--json"])));
+    }
 }
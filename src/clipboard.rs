@@ -0,0 +1,77 @@
+// git-enhancer/src/clipboard.rs
+//! Puts generated text on the system clipboard for `--copy`.
+//!
+//! Local clipboard tools are tried first. Over SSH they would fill the remote
+//! machine's clipboard, so there the OSC 52 escape sequence is used instead,
+//! which asks the user's terminal emulator to set its clipboard.
+
+use base64::Engine;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// Clipboard programs in the order they are tried, with their arguments
+const CLIPBOARD_TOOLS: &[(&str, &[&str])] = &[
+    ("pbcopy", &[]),
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("clip.exe", &[]),
+];
+
+/// Copies `text` to the clipboard, returning how it was done.
+pub fn copy(text: &str) -> io::Result<&'static str> {
+    let over_ssh = std::env::var_os("SSH_TTY").is_some() || std::env::var_os("SSH_CONNECTION").is_some();
+    if !over_ssh {
+        for (program, args) in CLIPBOARD_TOOLS {
+            match copy_with(program, args, text) {
+                Ok(()) => return Ok(program),
+                Err(e) => tracing::debug!("Clipboard tool {} unavailable: {}", program, e),
+            }
+        }
+    }
+    if !io::stderr().is_terminal() {
+        return Err(io::Error::other(
+            "no clipboard tool found (pbcopy, wl-copy, xclip, xsel) and stderr is not a terminal",
+        ));
+    }
+    let mut stderr = io::stderr().lock();
+    stderr.write_all(osc52_sequence(text).as_bytes())?;
+    stderr.flush()?;
+    Ok("OSC 52")
+}
+
+fn copy_with(program: &str, args: &[&str], text: &str) -> io::Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(text.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("{} exited with {}", program, status)))
+    }
+}
+
+/// The escape sequence that sets the terminal's clipboard (`c`) to `text`.
+fn osc52_sequence(text: &str) -> String {
+    format!(
+        "\x1b]52;c;{}\x07",
+        base64::engine::general_purpose::STANDARD.encode(text)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_sequence_encodes_text() {
+        assert_eq!(osc52_sequence("git status"), "\x1b]52;c;Z2l0IHN0YXR1cw==\x07");
+    }
+}
@@ -0,0 +1,85 @@
+// git-enhancer/src/clock.rs
+//! Detecting a wrong system clock.
+//!
+//! Certificates are only accepted within their validity period and signed API
+//! requests carry a timestamp, so a clock that is off by more than a few minutes
+//! makes requests fail with confusing TLS or authentication errors. HTTP
+//! responses carry the server's time in their `Date` header, which is compared
+//! with the local clock here.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Difference to the server's clock beyond which the local clock is reported as wrong
+pub const MAX_CLOCK_SKEW_SECS: i64 = 5 * 60;
+
+/// How to fix a wrong clock, for error hints and `doctor`
+pub const SYNC_CLOCK_HINT: &str = "Certificates and signed requests are only accepted with a correct \
+    clock. Turn on time synchronization (e.g. `sudo timedatectl set-ntp true`, or \"Set time \
+    automatically\" in the system settings) and try again.";
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// How far the local clock is ahead of the server's `Date` header (negative when behind),
+/// in seconds, or `None` when the header is missing or unreadable.
+pub fn skew_from_date_header(headers: &reqwest::header::HeaderMap, now: SystemTime) -> Option<i64> {
+    let date = headers.get(reqwest::header::DATE)?.to_str().ok()?;
+    let server = parse_http_date(date)?;
+    let local = now.duration_since(UNIX_EPOCH).ok()?.as_secs() as i64;
+    Some(local - server)
+}
+
+/// `skew` seconds as "2 hours ahead of" or "10 minutes behind"
+pub fn describe_skew(skew: i64) -> String {
+    let seconds = skew.unsigned_abs();
+    let amount = match seconds {
+        0..120 => format!("{} seconds", seconds),
+        120..7_200 => format!("{} minutes", seconds / 60),
+        7_200..172_800 => format!("{} hours", seconds / 3_600),
+        _ => format!("{} days", seconds / 86_400),
+    };
+    format!("{} {}", amount, if skew > 0 { "ahead of" } else { "behind" })
+}
+
+/// Seconds since the epoch for an HTTP date such as `Sun, 06 Nov 1994 08:49:37 GMT`
+fn parse_http_date(value: &str) -> Option<i64> {
+    let mut fields = value.split_whitespace().skip(1);
+    let day: i64 = fields.next()?.parse().ok()?;
+    let month_name = fields.next()?;
+    let month = MONTHS.iter().position(|month| *month == month_name)? as i64 + 1;
+    let year: i64 = fields.next()?.parse().ok()?;
+    let mut time = fields.next()?.split(':').map(|part| part.parse::<i64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+    if fields.next() != Some("GMT") {
+        return None;
+    }
+    Some(days_from_civil(year, month, day) * 86_400 + hours * 3_600 + minutes * 60 + seconds)
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_skew_from_date_header() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784_111_777));
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert(reqwest::header::DATE, "Sun, 06 Nov 1994 08:49:37 GMT".parse().unwrap());
+        let two_hours_late = UNIX_EPOCH + Duration::from_secs(784_111_777 + 7_300);
+        assert_eq!(skew_from_date_header(&headers, two_hours_late), Some(7_300));
+        assert_eq!(describe_skew(7_300), "2 hours ahead of");
+        assert_eq!(describe_skew(-600), "10 minutes behind");
+    }
+}
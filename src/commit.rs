@@ -0,0 +1,241 @@
+// git-enhancer/src/commit.rs
+//! Commit message generation as a library call, with a stable API.
+//!
+//! CI bots and pre-commit frameworks that want git-enhancer's commit messages
+//! can depend on this crate instead of running `git-enhancer commit --ai` and
+//! parsing its output. [`generate`] writes a message for the changes staged in
+//! a repository, exactly as `commit --ai` would, and returns it without
+//! committing:
+//!
+//! ```no_run
+//! use git_enhancer::commit::{self, GenerateOptions};
+//!
+//! let draft = commit::generate("path/to/repo", GenerateOptions::new().ticket("PROJ-123"))?;
+//! println!("{}", draft.subject());
+//! # Ok::<(), git_enhancer::errors::AppError>(())
+//! ```
+//!
+//! Unlike the rest of the crate, this module follows semantic versioning:
+//! what it exports only changes incompatibly in a major release. Its structs
+//! are `#[non_exhaustive]`, so fields can be added in minor releases; build
+//! options with [`GenerateOptions::new`] and its setters.
+//!
+//! git runs in the repository's directory; the working directory of the
+//! process is left alone, so calls for different repositories can run at the
+//! same time.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::ai_utils::OpenAIUsage;
+use crate::commit_commands::generate_staged_message;
+use crate::config::{AppConfig, ConfigPaths};
+use crate::errors::AppError;
+use crate::git_context::GitContext;
+
+/// How to generate a message
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct GenerateOptions {
+    /// The ticket the change is for, e.g. `PROJ-123`; by default it is found
+    /// in the branch name, as with `commit --ai`
+    pub ticket: Option<String>,
+    /// The configuration to use instead of loading `~/.config/gitie` and the
+    /// repository's `gitie.*` git settings
+    pub config: Option<Config>,
+}
+
+impl GenerateOptions {
+    /// The defaults: the user's configuration, the ticket from the branch name
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the ticket the change is for
+    pub fn ticket(mut self, ticket: impl Into<String>) -> Self {
+        self.ticket = Some(ticket.into());
+        self
+    }
+
+    /// Sets the configuration to use
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+}
+
+/// A loaded configuration, to reuse across calls
+#[derive(Debug, Clone)]
+pub struct Config {
+    app: AppConfig,
+}
+
+impl Config {
+    /// Loads `path` the way `--config` does: `commit-prompt` and
+    /// `explanation-prompt` are read from next to it when present, and the
+    /// system policy applies. Unlike `--config`, `gitie.*` git settings are
+    /// not layered on top, so the file alone decides.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AppError> {
+        let path = path.as_ref();
+        let paths = ConfigPaths {
+            user_config_dir: path.parent().map_or_else(PathBuf::new, Path::to_path_buf),
+            git_config: false,
+            config_file: Some(path.to_path_buf()),
+            ..ConfigPaths::from_env()
+        };
+        Ok(Config { app: AppConfig::load_from(paths)? })
+    }
+}
+
+/// A generated commit message, not committed
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommitDraft {
+    /// The whole message: the subject line, then the body after a blank line
+    pub message: String,
+    /// The model as reported by the API
+    pub model: String,
+    /// Tokens used, when the API reported them
+    pub usage: Option<Usage>,
+}
+
+/// Tokens a generation used, as reported by the API
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    /// Tokens in the prompt
+    pub prompt_tokens: u32,
+    /// Tokens in the reply
+    pub completion_tokens: u32,
+    /// Both together
+    pub total_tokens: u32,
+}
+
+impl From<OpenAIUsage> for Usage {
+    fn from(usage: OpenAIUsage) -> Self {
+        Usage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
+}
+
+impl CommitDraft {
+    /// The first line of the message
+    pub fn subject(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+
+    /// The message after the subject line and the blank lines following it
+    pub fn body(&self) -> &str {
+        self.message
+            .split_once('\n')
+            .map_or("", |(_, rest)| rest.trim_start_matches(['\n', '\r']))
+    }
+}
+
+/// Generates a commit message for the changes staged in the repository at
+/// `repo_path`, blocking until it is written.
+///
+/// Fails on any thread inside a Tokio runtime context, where blocking on a
+/// second runtime would panic. That includes `spawn_blocking` threads, which
+/// can't be told apart from worker threads; there, use
+/// `Handle::current().block_on(commit::generate_async(..))`, and
+/// [`generate_async`] in async code.
+///
+/// # Errors
+///
+/// Fails like `commit --ai` does, e.g. with [`crate::errors::GitError::NoStagedChanges`]
+/// when nothing is staged, or with an [`AppError::AI`] when the model can't be reached.
+pub fn generate(repo_path: impl AsRef<Path>, options: GenerateOptions) -> Result<CommitDraft, AppError> {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(AppError::Generic(
+            "commit::generate can't run inside a Tokio runtime; use commit::generate_async there".to_string(),
+        ));
+    }
+    runtime()?.block_on(generate_async(repo_path, options))
+}
+
+/// Generates a commit message for the changes staged in the repository at `repo_path`.
+///
+/// The async form of [`generate`], for callers running a Tokio runtime.
+pub async fn generate_async(repo_path: impl AsRef<Path>, options: GenerateOptions) -> Result<CommitDraft, AppError> {
+    let repo_path = repo_path.as_ref();
+    let git_context = GitContext::in_dir(repo_path);
+    git_context.require_repository()?;
+    let config = match options.config {
+        Some(config) => config.app,
+        None => AppConfig::load_for_repository(repo_path)?,
+    };
+    let reply = generate_staged_message(&config, &git_context, options.ticket.as_deref()).await?;
+    Ok(CommitDraft { message: reply.text, model: reply.model, usage: reply.usage.map(Usage::from) })
+}
+
+/// The runtime [`generate`] blocks on, kept for the life of the process so
+/// pooled connections to the model stay usable from one call to the next
+fn runtime() -> Result<&'static tokio::runtime::Runtime, AppError> {
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| AppError::Io("starting the async runtime".to_string(), e))?;
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::GitError;
+    use std::fs;
+    use std::process::Command;
+
+    fn draft(message: &str) -> CommitDraft {
+        CommitDraft { message: message.to_string(), model: "model".to_string(), usage: None }
+    }
+
+    #[test]
+    fn test_subject_and_body() {
+        assert_eq!(draft("").subject(), "");
+        assert_eq!(draft("").body(), "");
+        assert_eq!(draft("Fix typo").subject(), "Fix typo");
+        assert_eq!(draft("Fix typo").body(), "");
+        assert_eq!(draft("Fix typo\n").body(), "");
+        assert_eq!(draft("Fix typo\n\n\nIn the README").body(), "In the README");
+        assert_eq!(draft("Fix typo\r\n\r\nIn the README").subject(), "Fix typo");
+        assert_eq!(draft("Fix typo\r\n\r\nIn the README").body(), "In the README");
+    }
+
+    #[tokio::test]
+    async fn test_generate_refuses_to_block_inside_a_runtime() {
+        let refused = |result: Result<CommitDraft, AppError>| {
+            matches!(result, Err(AppError::Generic(message)) if message.contains("generate_async"))
+        };
+        assert!(refused(generate(".", GenerateOptions::new())));
+        let from_blocking_thread = tokio::task::spawn_blocking(|| generate(".", GenerateOptions::new())).await.unwrap();
+        assert!(refused(from_blocking_thread));
+    }
+
+    #[tokio::test]
+    async fn test_generate_async_with_nothing_staged() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/test_temp_data/commit");
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(dir.join("repo")).unwrap();
+        let init = Command::new("git").arg("-C").arg(dir.join("repo")).args(["init", "-q"]).output().unwrap();
+        assert!(init.status.success());
+        fs::write(dir.join("commit-prompt"), "Write a commit message.").unwrap();
+        fs::write(dir.join("explanation-prompt"), "Explain.").unwrap();
+        fs::write(dir.join("ci.toml"), "[ai]\napi_url = \"http://127.0.0.1:9/v1/chat/completions\"\nmodel_name = \"m\"\n").unwrap();
+        let config = Config::from_file(dir.join("ci.toml")).unwrap();
+        // Fails before any request is sent
+        match generate_async(dir.join("repo"), GenerateOptions::new().config(config)).await {
+            Err(AppError::Git(GitError::NoStagedChanges)) => {}
+            other => panic!("expected NoStagedChanges, got {:?}", other),
+        }
+    }
+}
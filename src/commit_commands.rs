@@ -1,11 +1,32 @@
+//! The `commit` subcommand: AI-generated commit messages, and plain commits
+//! passed through to git.
+
+use crate::audit;
 use crate::cli::CommitArgs;
 use crate::config::AppConfig;
+use crate::diff::{self, FileDiff, FileStatus, HunkLine};
 use crate::errors::{AppError, GitError, AIError};
-use crate::git_commands::map_output_to_git_command_error;
-use crate::ai_utils::{OpenAIChatCompletionResponse, OpenAIChatRequest, ChatMessage, clean_ai_output};
+use crate::git_backend::{open_backend, GitBackend};
+use crate::interaction_log::{self, Decision, Interaction};
+use crate::interrupt::exit_code;
+use crate::issue_tracker::{self, Ticket};
+use crate::large_files;
+use crate::notify;
+use crate::output::{self, ResultRecord};
+use crate::post_generate;
+use crate::prompt::{self, Choice};
+use crate::scope::{self, Scope};
+use crate::ui;
+use crate::untrusted;
+use crate::usage_stats::{self, Event};
+use crate::types::{GitStatus, InProgressOperation, RepositoryState};
+use crate::git_commands::{execute_git_command_and_capture_output, map_output_to_git_command_error, run_attached_to_terminal, run_in_foreground, DiffSource};
+use crate::git_context::GitContext;
+use crate::ai_utils::{AiReply, OpenAIChatCompletionResponse, OpenAIChatRequest, ChatMessage, clean_ai_output, send_chat_request};
 
-use std::process::Command as StdCommand;
-use tracing;
+use std::path::Path;
+use std::process::{Command as StdCommand, Stdio};
+use std::time::Instant;
 
 /// Handles a standard git commit by passing through to git
 ///
@@ -18,33 +39,18 @@ use tracing;
 ///
 /// * `Result<(), AppError>` - Success or an error
 pub async fn handle_commit_passthrough(args: CommitArgs, context_msg: String) -> Result<(), AppError> { 
-    tracing::info!("Commit passthrough {}: msg: {:?}, args: {:?}", context_msg, args.message, args.passthrough_args);
+    tracing::info!("Commit passthrough {}: args: {:?}", context_msg, args.git_args);
     let mut cmd_builder = StdCommand::new("git");
-    cmd_builder.arg("commit");
-    
-    // Add -a/--all flag if auto_stage is set
-    if args.auto_stage {
-        cmd_builder.arg("-a");
-    }
-    
-    if let Some(message) = &args.message {
-        cmd_builder.arg("-m").arg(message);
-    }
-    
-    // Add remaining args, but exclude -a and --all if auto_stage is true
-    for arg in &args.passthrough_args {
-        if !(args.auto_stage && (arg == "-a" || arg == "--all" || (arg.starts_with('-') && !arg.starts_with("--") && arg.contains('a')))) {
-            cmd_builder.arg(arg);
-        }
-    }
-    let cmd_desc = format!("commit (passthrough {}) args: {:?}", context_msg, args.passthrough_args);
-    let status = cmd_builder.status()
+    // Exactly as given, so options git-enhancer doesn't know keep their place
+    cmd_builder.arg("commit").args(&args.git_args);
+    let cmd_desc = format!("commit (passthrough {}) args: {:?}", context_msg, args.git_args);
+    let status = run_attached_to_terminal(&mut cmd_builder)
         .map_err(|e| AppError::Io(format!("Failed git {}", cmd_desc), e))?;
     if !status.success() {
-        tracing::error!("Passthrough git {} failed with status {}", cmd_desc, status);
+        tracing::debug!("Passthrough git {} failed with status {}", cmd_desc, status);
         return Err(AppError::Git(GitError::PassthroughFailed {
             command: format!("git {}", cmd_desc),
-            status_code: status.code(),
+            status_code: exit_code(status),
         }));
     }
     tracing::info!("Passthrough git {} initiated/completed successfully.", cmd_desc);
@@ -57,13 +63,29 @@ pub async fn handle_commit_passthrough(args: CommitArgs, context_msg: String) ->
 ///
 /// * `args` - Commit arguments from CLI
 /// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
 ///
 /// # Returns
 ///
 /// * `Result<(), AppError>` - Success or an error
-pub async fn handle_commit(args: CommitArgs, config: &AppConfig) -> Result<(), AppError> {
+pub async fn handle_commit(args: CommitArgs, config: &AppConfig, git_context: &GitContext) -> Result<(), AppError> {
     if args.ai {
         tracing::info!("AI commit: Attempting to generate message...");
+        git_context.require_repository()?;
+
+        let parameters = config.model_parameters("commit");
+        config.policy.check_request(&parameters.api_url, &parameters.model, true)?;
+
+        // `git commit <paths>` commits the working tree content of just those paths
+        let pathspecs = &args.pathspecs;
+        if !pathspecs.is_empty() {
+            if args.auto_stage {
+                return Err(AppError::Generic(format!("paths '{}' with -a does not make sense", pathspecs.join(" "))));
+            }
+            if args.include {
+                return Err(AppError::Generic("--include with paths is not supported by commit --ai; stage the files and omit the paths".to_string()));
+            }
+        }
         
         // Handle auto-staging functionality
         if args.auto_stage {
@@ -72,86 +94,1103 @@ pub async fn handle_commit(args: CommitArgs, config: &AppConfig) -> Result<(), A
                 .map_err(|e| AppError::Io("Failed to auto stage changes".to_string(), e))?;
             
             if !add_result.status.success() {
-                tracing::error!("Failed to auto-stage changes with git add -u");
+                tracing::debug!("Failed to auto-stage changes with git add -u");
                 return Err(map_output_to_git_command_error("git add -u", add_result).into());
             }
         }
         
-        let diff_out = StdCommand::new("git").arg("diff").arg("--staged").output()
-            .map_err(|e| AppError::Git(GitError::DiffError(e)))?;
-        if !diff_out.status.success() {
-            tracing::error!("Error getting git diff. Is anything staged for commit?");
-            return Err(map_output_to_git_command_error("git diff --staged", diff_out).into());
-        }
-        let diff = String::from_utf8_lossy(&diff_out.stdout);
-        if diff.trim().is_empty() {
+        let backend = open_backend();
+        let state = backend.repository_state()?;
+        let mut status = backend.status()?;
+        check_repository_state(&state, &status)?;
+        // With paths, the commit takes them from the working tree instead of the index
+        if pathspecs.is_empty() && large_files::advise(config, git_context, backend.as_ref(), &status).await? {
+            status = backend.status()?;
+        }
+        let mut diff_options = config.commit.diff_options();
+        let source = if pathspecs.is_empty() {
+            check_partially_staged_files(&status)?;
+            // Explicit paths already say what the commit is about
+            if let Some(scope) = scope::resolve(git_context, config.scope.as_deref())? {
+                check_staged_files_in_scope(&scope, &status)?;
+                diff_options.pathspecs = vec![scope.pathspec()];
+            }
+            DiffSource::Staged
+        } else {
+            // Staged or not, the listed paths are committed as they are in the working tree
+            diff_options.pathspecs = pathspecs.clone();
+            DiffSource::Head
+        };
+        let diff = backend
+            .diff(source, &diff_options)
+            .inspect_err(|_| tracing::debug!("Error getting git diff. Is anything staged for commit?"))?;
+        // Concluding a merge is a valid commit even when the result matches HEAD
+        if diff.trim().is_empty() && state.operation != Some(InProgressOperation::Merge) {
             tracing::info!("AI commit: No staged changes. Checking for --allow-empty.");
-            if args.passthrough_args.contains(&"--allow-empty".to_string()) {
-                let passthrough_commit_args = CommitArgs {
-                     ai: false, 
-                     auto_stage: args.auto_stage,
-                     message: None, 
-                     passthrough_args: args.passthrough_args.clone(),
-                 };
-                return handle_commit_passthrough(passthrough_commit_args, "(AI commit with --allow-empty and no diff)".to_string()).await;
+            if args.allow_empty {
+                return handle_commit_passthrough(args, "(AI commit with --allow-empty and no diff)".to_string()).await;
             } else {
                 return Err(AppError::Git(GitError::NoStagedChanges));
             }
         }
         tracing::debug!("Staged changes for AI:\n{}", diff);
-        let user_prompt = format!("Git diff:\n{}\nGenerate commit message.", diff.trim());
-        let messages = vec![
-            ChatMessage { 
-                role: "system".to_string(), 
-                content: config.prompts.get("commit").cloned().unwrap_or_else(|| {
-                    tracing::warn!("Commit prompt not found in config, using empty string");
-                    "".to_string()
-                }) 
-            },
-            ChatMessage { role: "user".to_string(), content: user_prompt },
-        ];
-        let req_payload = OpenAIChatRequest { model: config.ai.model_name.clone(), messages, temperature: Some(config.ai.temperature), stream: false };
-        if let Ok(json_str) = serde_json::to_string_pretty(&req_payload) { tracing::debug!("AI req:\n{}", json_str); }
-        
-        let client = reqwest::Client::new();
-        let mut builder = client.post(&config.ai.api_url);
-        if let Some(key) = &config.ai.api_key { builder = builder.bearer_auth(key); }
-        let ai_resp = builder.json(&req_payload).send().await.map_err(AIError::RequestFailed)?;
-        
-        if !ai_resp.status().is_success() {
-            let code = ai_resp.status();
-            let body = ai_resp.text().await.unwrap_or_else(|_| "<no body>".into());
-            tracing::error!("AI API request failed with status {}: {}", code, body);
-            return Err(AppError::AI(AIError::ApiResponseError(code, body)));
+        let ticket = issue_tracker::ticket_context(config, git_context, args.ticket.as_deref()).await;
+        let messages = commit_prompt_messages(&diff, &state, backend.as_ref(), config, ticket.as_ref());
+        let api_url = parameters.api_url;
+        let req_payload = OpenAIChatRequest { model: parameters.model, messages, temperature: Some(parameters.temperature), stream: false, max_tokens: parameters.max_tokens };
+        let started = Instant::now();
+        let machine_output = config.output.format.is_machine_readable();
+        let generated = request_commit_message(config, git_context, &api_url, &req_payload, &diff).await;
+        let (mut final_msg, resp_data) = match generated {
+            Ok(generated) => generated,
+            // The staged work is still committed, with a message written by hand
+            Err(e) if !machine_output && prompt::is_interactive() && !matches!(e, AIError::Cancelled) => {
+                ui::warning(&format!("could not generate a commit message: {}", e));
+                if let Some(hint) = e.hint() {
+                    ui::hint(&hint);
+                }
+                return commit_in_editor(&args, source, pathspecs, &e);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let completion = final_msg.clone();
+        if config.commit.provenance_trailer {
+            final_msg = append_trailer(&final_msg, &format!("AI-assisted-by: gitie ({})", resp_data.model));
+        }
+        tracing::info!("AI Message:\n---\n{}\n---", final_msg);
+        if config.output.copy {
+            output::copy_to_clipboard(&final_msg);
         }
-        let resp_data = ai_resp.json::<OpenAIChatCompletionResponse>().await.map_err(AIError::ResponseParseFailed)?;
-        let ai_msg = resp_data.choices.get(0).map_or("", |c| &c.message.content);
-        let final_msg = clean_ai_output(ai_msg).trim().to_string();
+        if let Some(file) = &config.output.file {
+            output::write_to_file(file, &final_msg)?;
+        }
+        if !machine_output {
+            notify::if_slow(&config.notify, started.elapsed(), "Commit message ready");
+        }
+        // Scripts reading a record get no questions
+        let open_editor = !machine_output
+            && review_commit_message(&final_msg, source, pathspecs).inspect_err(|_| {
+                let interaction = Interaction::new("commit", &resp_data.model, &req_payload.messages, &completion, Decision::Rejected);
+                interaction_log::record(config, &interaction);
+                usage_stats::record(config, Event::Suggestion { task: "commit".to_string(), decision: Decision::Rejected });
+            })?;
 
-        if final_msg.is_empty() { 
-            tracing::error!("AI returned an empty message.");
-            return Err(AppError::AI(AIError::EmptyMessage)); 
+        let mut message_args = vec!["-m", final_msg.as_str()];
+        if open_editor {
+            message_args.push("-e");
         }
-        tracing::info!("AI Message:\n---\n{}\n---", final_msg);
+        let mut cmd_builder = commit_command(&message_args, &args, pathspecs);
 
-        let mut cmd_builder = StdCommand::new("git");
-        cmd_builder.arg("commit").arg("-m").arg(&final_msg);
-        
-        // Filter out -a and --all from passthrough_args if auto_stage=true
-        for p_arg in &args.passthrough_args {
-            if p_arg != "-a" && p_arg != "--all" && !(p_arg.starts_with('-') && !p_arg.starts_with("--") && p_arg.contains('a')) {
-                cmd_builder.arg(p_arg);
-            }
+        // Only the diff gathering above is captured; the commit itself may need the
+        // terminal for hooks, GPG pinentry or an editor opened by `-e`
+        let status = if machine_output {
+            // stdout is reserved for the record; git's summary goes to stderr
+            cmd_builder
+                .stdin(Stdio::inherit())
+                .stdout(Stdio::from(std::io::stderr()))
+                .stderr(Stdio::inherit());
+            run_in_foreground(&mut cmd_builder)
+        } else {
+            run_attached_to_terminal(&mut cmd_builder)
         }
-        
-        let commit_out = cmd_builder.output().map_err(|e| AppError::Io("AI commit failed".into(), e))?;
-        if !commit_out.status.success() {
-            tracing::error!("Git commit command with AI message failed.");
-            return Err(map_output_to_git_command_error("git commit -m <AI>", commit_out).into());
+        .map_err(|e| AppError::Io("AI commit failed".into(), e))?;
+        if status.success() && (config.history.enabled || config.stats.enabled) {
+            let interaction = Interaction::new("commit", &resp_data.model, &req_payload.messages, &completion, Decision::Accepted);
+            let interaction = committed_interaction(interaction, &final_msg, open_editor);
+            usage_stats::record(config, Event::Suggestion { task: "commit".to_string(), decision: interaction.decision });
+            interaction_log::record(config, &interaction);
+        } else if !status.success() {
+            // Aborted in the editor, or stopped by a hook: the message was not used
+            usage_stats::record(config, Event::Suggestion { task: "commit".to_string(), decision: Decision::Rejected });
+        }
+        if machine_output {
+            let reply = AiReply { text: final_msg, model: resp_data.model, usage: Some(resp_data.usage) };
+            output::print_record(config.output.format, &ResultRecord::reply("commit", &reply, exit_code(status).unwrap_or(128)))?;
+        }
+        if !status.success() {
+            tracing::debug!("Git commit command with AI message failed: {}", status);
+            return Err(AppError::Git(GitError::PassthroughFailed {
+                command: "git commit -m <AI>".to_string(),
+                status_code: exit_code(status),
+            }));
         }
         tracing::info!("Successfully committed with AI message.");
     } else {
         return handle_commit_passthrough(args, "(standard commit)".to_string()).await;
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Generates a message for the staged changes, without committing them
+///
+/// This is `commit --ai` for callers that commit by themselves, such as the
+/// tools of `serve --mcp`. The message includes the provenance trailer when
+/// it is configured.
+///
+/// # Arguments
+///
+/// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
+/// * `ticket` - The ticket the change is for; found in the branch name when `None`
+///
+/// # Returns
+///
+/// * `Result<AiReply, AppError>` - The message, or an error, e.g. when nothing is staged
+pub async fn generate_staged_message(config: &AppConfig, git_context: &GitContext, ticket: Option<&str>) -> Result<AiReply, AppError> {
+    git_context.require_repository()?;
+    let parameters = config.model_parameters("commit");
+    config.policy.check_request(&parameters.api_url, &parameters.model, true)?;
+    let backend = git_context.backend();
+    let state = backend.repository_state()?;
+    let mut diff_options = config.commit.diff_options();
+    if let Some(scope) = scope::resolve(git_context, config.scope.as_deref())? {
+        diff_options.pathspecs = vec![scope.pathspec()];
+    }
+    let diff = backend.diff(DiffSource::Staged, &diff_options)?;
+    if diff.trim().is_empty() {
+        return Err(AppError::Git(GitError::NoStagedChanges));
+    }
+    let ticket = issue_tracker::ticket_context(config, git_context, ticket).await;
+    let messages = commit_prompt_messages(&diff, &state, backend.as_ref(), config, ticket.as_ref());
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages,
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    let (mut message, response) = request_commit_message(config, git_context, &parameters.api_url, &request, &diff).await?;
+    if config.commit.provenance_trailer {
+        message = append_trailer(&message, &format!("AI-assisted-by: gitie ({})", response.model));
+    }
+    Ok(AiReply { text: message, model: response.model, usage: Some(response.usage) })
+}
+
+/// The system and user messages asking for a commit message for `diff`
+///
+/// # Arguments
+///
+/// * `diff` - The changes to describe
+/// * `state` - The repository state, e.g. a merge in progress
+/// * `backend` - Repository access for details the diff alone doesn't show
+/// * `config` - Application configuration
+/// * `ticket` - The ticket the change is for, if one was found
+///
+/// # Returns
+///
+/// * `Vec<ChatMessage>` - The prompt
+fn commit_prompt_messages(
+    diff: &str,
+    state: &RepositoryState,
+    backend: &dyn GitBackend,
+    config: &AppConfig,
+    ticket: Option<&Ticket>,
+) -> Vec<ChatMessage> {
+    let user_prompt = build_commit_user_prompt(diff, state, backend, config, ticket);
+    let mut system_prompt = config.system_prompt("commit");
+    if let Some(language) = &config.commit.language {
+        system_prompt.push_str(&format!("\n\nWrite the commit message in {}.", language));
+    }
+    if ticket.is_some() {
+        system_prompt.push_str(&format!("\n\n{}", issue_tracker::TICKET_RULES));
+    }
+    system_prompt.push_str(&format!("\n\n{}", untrusted::UNTRUSTED_CONTENT_RULES));
+    vec![
+        ChatMessage { role: "system".to_string(), content: system_prompt },
+        ChatMessage { role: "user".to_string(), content: user_prompt },
+    ]
+}
+
+/// Sends `request` and checks that the reply is a usable commit message for `diff`
+///
+/// # Arguments
+///
+/// * `config` - Application configuration
+/// * `git_context` - Where the `post_generate_cmd` script runs
+/// * `api_url` - The chat completions endpoint
+/// * `request` - The prompt and model parameters
+/// * `diff` - The changes the message describes
+///
+/// # Returns
+///
+/// * `Result<(String, OpenAIChatCompletionResponse), AIError>` - The cleaned message and the full response
+async fn request_commit_message(
+    config: &AppConfig,
+    git_context: &GitContext,
+    api_url: &str,
+    request: &OpenAIChatRequest,
+    diff: &str,
+) -> Result<(String, OpenAIChatCompletionResponse), AIError> {
+    let response = send_chat_request(config, api_url, request).await?;
+    let ai_msg = response.choices.first().map_or("", |c| &c.message.content);
+    let message = clean_ai_output(ai_msg).trim().to_string();
+    if message.is_empty() {
+        tracing::debug!("AI returned an empty message.");
+        return Err(AIError::EmptyMessage);
+    }
+    if let Err(reason) = untrusted::check_commit_message(&message, diff) {
+        audit::record("rejected-commit-message", &format!("model {}: {}: {}", response.model, reason, message));
+        return Err(AIError::SuspiciousOutput(reason));
+    }
+    let message = post_generate::apply_in(git_context.dir(), config.post_generate_cmd("commit"), "commit", &message)?;
+    Ok((message, response))
+}
+
+/// Completes the record of a proposed message with the commit just made
+///
+/// The decision is `Edited` when the message was changed in the editor.
+///
+/// # Arguments
+///
+/// * `interaction` - The proposal, as accepted
+/// * `proposed` - The message as proposed, trailer included
+/// * `opened_editor` - Whether the editor was opened on it
+///
+/// # Returns
+///
+/// * `Interaction` - The record with the committed message and hash
+fn committed_interaction(mut interaction: Interaction, proposed: &str, opened_editor: bool) -> Interaction {
+    let args = ["log".to_string(), "-1".to_string(), "--format=%H%n%B".to_string()];
+    let head = execute_git_command_and_capture_output(&args)
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| output.stdout);
+    if let Some((hash, message)) = head.as_deref().and_then(|head| head.split_once('\n')) {
+        let message = message.trim();
+        if opened_editor && message != proposed.trim() {
+            interaction.decision = Decision::Edited;
+        }
+        interaction.final_message = Some(message.to_string());
+        interaction.commit = Some(hash.to_string());
+    }
+    interaction
+}
+
+/// Builds the `git commit` that records the changes `commit --ai` looked at
+///
+/// # Arguments
+///
+/// * `message_args` - Options that supply the message, e.g. `-m <message>`
+/// * `args` - Commit arguments from CLI
+/// * `pathspecs` - Paths the commit is limited to, if any
+///
+/// # Returns
+///
+/// * `StdCommand` - The command, not started yet
+fn commit_command(message_args: &[&str], args: &CommitArgs, pathspecs: &[String]) -> StdCommand {
+    let mut cmd_builder = StdCommand::new("git");
+    // The options as given, so hooks, signing and the rest behave as for a plain `git commit`
+    cmd_builder.arg("commit").args(message_args).args(&args.git_options);
+    // Changes were already staged with `git add -u` for -a/--all; edits made
+    // since then were not part of the diff the message describes
+    if args.auto_stage {
+        cmd_builder.arg("--no-all");
+    }
+    if !pathspecs.is_empty() {
+        cmd_builder.arg("--").args(pathspecs);
+    }
+    cmd_builder
+}
+
+/// Opens git's commit editor after message generation failed
+///
+/// The message template holds the error and the diff stat as comments, so the
+/// user can write the message without looking the changes up again.
+///
+/// # Arguments
+///
+/// * `args` - Commit arguments from CLI
+/// * `source` - Which changes are being committed
+/// * `pathspecs` - Paths the commit is limited to, if any
+/// * `error` - Why no message was generated
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success, or an error if the commit failed or was aborted
+fn commit_in_editor(
+    args: &CommitArgs,
+    source: DiffSource,
+    pathspecs: &[String],
+    error: &AIError,
+) -> Result<(), AppError> {
+    let mut stat_command = StdCommand::new("git");
+    stat_command.args(["diff", "--stat", "--no-color"]);
+    match source {
+        DiffSource::Staged => stat_command.arg("--staged"),
+        DiffSource::WorkTree => &mut stat_command,
+        DiffSource::Head => stat_command.arg("HEAD"),
+    };
+    if !pathspecs.is_empty() {
+        stat_command.arg("--").args(pathspecs);
+    }
+    let stat = stat_command
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default();
+
+    // Next to git's own COMMIT_EDITMSG rather than in a shared temporary
+    // directory, where another user could put a file or symlink first
+    let path_args = ["rev-parse".to_string(), "--git-path".to_string(), "GITIE_COMMIT_TEMPLATE".to_string()];
+    let output = execute_git_command_and_capture_output(&path_args)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: format!("git {}", path_args.join(" ")),
+            status_code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+        .into());
+    }
+    let template_path = std::path::PathBuf::from(output.stdout.trim());
+    std::fs::write(&template_path, editor_template(error, &stat))
+        .map_err(|e| AppError::Io(format!("writing {}", template_path.display()), e))?;
+    let template = template_path.to_string_lossy().into_owned();
+    let mut cmd_builder = commit_command(&["--template", &template], args, pathspecs);
+    let status = run_attached_to_terminal(&mut cmd_builder);
+    let _ = std::fs::remove_file(&template_path);
+    let status = status.map_err(|e| AppError::Io("git commit failed".into(), e))?;
+    if !status.success() {
+        return Err(AppError::Git(GitError::PassthroughFailed {
+            command: "git commit --template <diff stat>".to_string(),
+            status_code: exit_code(status),
+        }));
+    }
+    Ok(())
+}
+
+/// A commit message template that is all comments: the generation error and the diff stat
+fn editor_template(error: &AIError, stat: &str) -> String {
+    let mut template = format!("\n# No commit message could be generated: {}\n#\n", error);
+    for line in stat.lines() {
+        // Stat lines are indented by one space already
+        template.push_str(&format!("# {}\n", line.strip_prefix(' ').unwrap_or(line)));
+    }
+    template
+}
+
+/// Refuses to commit over unresolved conflicts and warns about a detached HEAD
+///
+/// # Arguments
+///
+/// * `state` - The repository state
+/// * `status` - The current status
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success, or an error if conflicts remain
+fn check_repository_state(state: &RepositoryState, status: &GitStatus) -> Result<(), AppError> {
+    if !status.conflicted.is_empty() {
+        return Err(GitError::UnresolvedConflicts(status.conflicted.clone()).into());
+    }
+    // HEAD is always detached while rebasing, so only warn outside of one
+    if state.detached_head && state.operation != Some(InProgressOperation::Rebase) {
+        ui::warning("HEAD is detached; the new commit will not belong to any branch");
+    }
+    Ok(())
+}
+
+/// Describes an unusual repository state for the commit prompt
+///
+/// # Arguments
+///
+/// * `state` - The repository state
+///
+/// # Returns
+///
+/// * `Vec<String>` - One sentence per notable fact, empty for a plain commit on a branch
+fn describe_repository_state(state: &RepositoryState) -> Vec<String> {
+    let mut notes = Vec::new();
+    match state.operation {
+        Some(InProgressOperation::Merge) => {
+            notes.push("This commit concludes a merge; say what was merged.".to_string())
+        }
+        Some(InProgressOperation::Rebase) => {
+            notes.push("This commit is made while rebasing.".to_string())
+        }
+        Some(operation @ (InProgressOperation::CherryPick | InProgressOperation::Revert)) => {
+            notes.push(format!("This commit concludes a {}.", operation))
+        }
+        Some(InProgressOperation::Bisect) => notes.push("A bisect is in progress.".to_string()),
+        None => {}
+    }
+    if state.detached_head && state.operation.is_none() {
+        notes.push("HEAD is detached, so the commit is not on a branch.".to_string());
+    }
+    if let Some(message) = &state.prepared_message {
+        notes.push(format!("Git prepared this message for it:\n{}", message));
+    }
+    notes
+}
+
+/// Warns about files that are staged but also have unstaged edits
+///
+/// The generated message only describes the staged version of those files. When
+/// the user is asked, they can stage the remaining edits, continue, or abort.
+///
+/// # Arguments
+///
+/// * `status` - The current status
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success, or an error if the user aborted or staging failed
+fn check_partially_staged_files(status: &GitStatus) -> Result<(), AppError> {
+    let partially_staged = partially_staged_paths(status);
+    if partially_staged.is_empty() {
+        return Ok(());
+    }
+
+    ui::warning("these files also have unstaged changes, which the commit message will not cover:");
+    for path in &partially_staged {
+        eprintln!("  {}", path);
+    }
+    let choices = [
+        Choice::new('s', "stage them too"),
+        Choice::new('c', "continue with the staged changes only"),
+        Choice::new('a', "abort"),
+    ];
+    match prompt::select("", &choices, 1)? {
+        0 => {
+            let add_out = StdCommand::new("git")
+                .arg("add")
+                .arg("--")
+                .args(&partially_staged)
+                .output()
+                .map_err(|e| AppError::Io("Failed to stage remaining changes".to_string(), e))?;
+            if !add_out.status.success() {
+                return Err(map_output_to_git_command_error("git add -- <paths>", add_out).into());
+            }
+            Ok(())
+        }
+        2 => Err(AppError::Generic("Commit aborted".to_string())),
+        _ => Ok(()),
+    }
+}
+
+/// Shows the generated message and asks what to do with it
+///
+/// The diff the message was generated from can be shown through git's pager
+/// (`d`) as often as needed. Without anyone to ask the message is accepted.
+///
+/// # Arguments
+///
+/// * `message` - The generated commit message
+/// * `source` - Which changes the message describes
+/// * `pathspecs` - Paths the commit is limited to, if any
+///
+/// # Returns
+///
+/// * `Result<bool, AppError>` - Whether to open the editor on the message, or an error if the user aborted
+fn review_commit_message(message: &str, source: DiffSource, pathspecs: &[String]) -> Result<bool, AppError> {
+    if !prompt::is_interactive() {
+        return Ok(false);
+    }
+    eprintln!("\nProposed commit message:\n");
+    for line in message.lines() {
+        eprintln!("    {}", line);
+    }
+    eprintln!();
+    let choices = [
+        Choice::new('y', "yes"),
+        Choice::new('e', "edit"),
+        Choice::new('d', "diff"),
+        Choice::new('q', "quit"),
+    ];
+    loop {
+        match prompt::select("Commit with this message:", &choices, 0)? {
+            0 => return Ok(false),
+            1 => return Ok(true),
+            2 => show_diff(source, pathspecs)?,
+            _ => return Err(AppError::Generic("Commit aborted".to_string())),
+        }
+    }
+}
+
+/// Shows the changes being committed the way `git diff` would, pager included
+fn show_diff(source: DiffSource, pathspecs: &[String]) -> Result<(), AppError> {
+    let mut cmd_builder = StdCommand::new("git");
+    cmd_builder.arg("diff");
+    match source {
+        DiffSource::Staged => cmd_builder.arg("--staged"),
+        DiffSource::WorkTree => &mut cmd_builder,
+        DiffSource::Head => cmd_builder.arg("HEAD"),
+    };
+    if !pathspecs.is_empty() {
+        cmd_builder.arg("--").args(pathspecs);
+    }
+    let status = run_attached_to_terminal(&mut cmd_builder)
+        .map_err(|e| AppError::Io("showing the diff".to_string(), e))?;
+    if !status.success() {
+        tracing::debug!("git diff exited with {}", status);
+    }
+    Ok(())
+}
+
+/// Tells the user about staged files outside `scope`
+///
+/// They are still committed, but the message only describes the changes
+/// inside the scope.
+///
+/// # Arguments
+///
+/// * `scope` - The directory the message is about
+/// * `status` - The current status
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - An error when nothing inside the scope is staged
+fn check_staged_files_in_scope(scope: &Scope, status: &GitStatus) -> Result<(), AppError> {
+    let outside: Vec<&str> = status
+        .staged
+        .iter()
+        .map(|file| file.path.as_str())
+        .filter(|path| !scope.contains(path))
+        .collect();
+    if outside.is_empty() {
+        return Ok(());
+    }
+    if outside.len() == status.staged.len() {
+        return Err(AppError::Generic(format!(
+            "nothing is staged inside the scope '{}'; stage changes there or use a different --scope",
+            scope
+        )));
+    }
+    ui::warning(&format!(
+        "these staged files are outside the scope '{}' and will be committed without being described:",
+        scope
+    ));
+    for path in outside {
+        eprintln!("  {}", path);
+    }
+    Ok(())
+}
+
+/// Paths that appear both in the staged and in the unstaged changes
+fn partially_staged_paths(status: &GitStatus) -> Vec<String> {
+    status
+        .staged
+        .iter()
+        .filter(|staged| status.modified.iter().any(|modified| modified.path == staged.path))
+        .map(|staged| staged.path.clone())
+        .collect()
+}
+
+/// Builds the user prompt for commit message generation from the staged diff
+///
+/// # Arguments
+///
+/// * `diff` - The staged diff
+/// * `state` - The repository state, e.g. a merge in progress
+/// * `backend` - Repository access for details the diff alone doesn't show
+/// * `config` - Application configuration
+/// * `ticket` - The ticket the change is for, if one was found
+///
+/// # Returns
+///
+/// * `String` - The prompt text
+fn build_commit_user_prompt(
+    diff: &str,
+    state: &RepositoryState,
+    backend: &dyn GitBackend,
+    config: &AppConfig,
+    ticket: Option<&Ticket>,
+) -> String {
+    let files = diff::parse(diff);
+    let diff = summarize_oversized_diffs(&files, config.commit.max_file_diff_bytes(), |object| {
+        backend.object_size(object).ok()
+    });
+    let mut prompt = String::new();
+    let state_notes = describe_repository_state(state);
+    if !state_notes.is_empty() {
+        prompt.push_str("Repository state:\n");
+        for note in &state_notes {
+            prompt.push_str(&format!("- {}\n", note));
+        }
+        prompt.push('\n');
+    }
+    prompt.push_str(&file_overview(&files));
+    let moves = parse_file_moves(&files);
+    if !moves.is_empty() {
+        prompt.push_str("Moved or copied files:\n");
+        for file_move in &moves {
+            prompt.push_str(&format!("- {}\n", file_move));
+        }
+        prompt.push('\n');
+    }
+    if let Some(ticket) = ticket {
+        prompt.push_str(&ticket.prompt_section());
+        prompt.push('\n');
+    }
+    if let Some(background) = untrusted::background(&config.context) {
+        prompt.push_str(&background);
+    }
+    prompt.push_str(&format!("Git diff:\n{}", untrusted::fence("diff", &diff)));
+    if config.commit.include_submodule_log {
+        for bump in parse_submodule_bumps(&files) {
+            match backend.submodule_log(Path::new(&bump.path), &bump.old, &bump.new) {
+                Ok(log) => prompt.push_str(&format!(
+                    "\nSubmodule {} updated ({}..{}), new commits:\n{}",
+                    bump.path,
+                    short_hash(&bump.old),
+                    short_hash(&bump.new),
+                    untrusted::fence("submodule-log", &log)
+                )),
+                Err(e) => ui::warning(&format!("could not read the log of submodule {}: {}", bump.path, e)),
+            }
+        }
+    }
+    prompt.push_str("Generate commit message.");
+    prompt
+}
+
+/// Lists the changed files and their line counts, sorted by path
+///
+/// This gives the model an overview that survives even when the detailed diff
+/// below it had to be summarized.
+///
+/// # Arguments
+///
+/// * `files` - The parsed diff
+///
+/// # Returns
+///
+/// * `String` - A file list and a `git diff --numstat` style table, or nothing for an empty diff
+fn file_overview(files: &[FileDiff]) -> String {
+    if files.is_empty() {
+        return String::new();
+    }
+    let mut sorted: Vec<&FileDiff> = files.iter().collect();
+    sorted.sort_by(|a, b| a.path().cmp(b.path()));
+
+    let mut overview = String::from("Changed files:\n");
+    for file in &sorted {
+        match file.status {
+            FileStatus::Renamed | FileStatus::Copied => overview.push_str(&format!(
+                "{}\t{} -> {}\n",
+                file.status.letter(),
+                file.old_path,
+                file.new_path
+            )),
+            _ => overview.push_str(&format!("{}\t{}\n", file.status.letter(), file.path())),
+        }
+    }
+    overview.push_str("\nLines added and removed per file:\n");
+    for file in &sorted {
+        // Like --numstat, binary files have no line counts
+        let (added, removed) = if file.is_binary {
+            ("-".to_string(), "-".to_string())
+        } else {
+            (file.added_lines().to_string(), file.removed_lines().to_string())
+        };
+        overview.push_str(&format!("{}\t{}\t{}\n", added, removed, file.path()));
+    }
+    overview.push('\n');
+    overview
+}
+
+/// A file renamed or copied in a diff
+#[derive(Debug, PartialEq)]
+struct FileMove {
+    kind: &'static str,
+    from: String,
+    to: String,
+    similarity: Option<u32>,
+}
+
+impl std::fmt::Display for FileMove {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} -> {}", self.kind, self.from, self.to)?;
+        match self.similarity {
+            Some(100) => write!(f, " (content unchanged)"),
+            Some(similarity) => write!(f, " ({}% similar)", similarity),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Collects the renamed and copied files of a diff
+fn parse_file_moves(files: &[FileDiff]) -> Vec<FileMove> {
+    files
+        .iter()
+        .filter_map(|file| {
+            let kind = match file.status {
+                FileStatus::Renamed => "renamed",
+                FileStatus::Copied => "copied",
+                _ => return None,
+            };
+            Some(FileMove {
+                kind,
+                from: file.old_path.clone(),
+                to: file.new_path.clone(),
+                similarity: file.similarity,
+            })
+        })
+        .collect()
+}
+
+/// A submodule whose recorded commit changes in a diff
+#[derive(Debug, PartialEq)]
+struct SubmoduleBump {
+    path: String,
+    old: String,
+    new: String,
+}
+
+/// Finds submodule pointer changes (`-Subproject commit <old>` / `+Subproject commit <new>`) in a diff
+fn parse_submodule_bumps(files: &[FileDiff]) -> Vec<SubmoduleBump> {
+    files
+        .iter()
+        .filter(|file| file.is_submodule())
+        .filter_map(|file| {
+            let commit = |line: &str| {
+                line.strip_prefix("Subproject commit ")
+                    .map(|hash| hash.trim_end_matches("-dirty").to_string())
+            };
+            let lines = file.hunks.iter().flat_map(|hunk| hunk.lines.iter());
+            let (mut old, mut new) = (None, None);
+            for line in lines {
+                match line {
+                    HunkLine::Removed(text) => old = old.or_else(|| commit(text)),
+                    HunkLine::Added(text) => new = new.or_else(|| commit(text)),
+                    _ => {}
+                }
+            }
+            Some(SubmoduleBump {
+                path: file.new_path.clone(),
+                old: old?,
+                new: new?,
+            })
+        })
+        .collect()
+}
+
+/// Replaces binary files and per-file diffs over `max_bytes` with a one-line summary
+///
+/// The summary keeps the file's extended headers (mode, rename, index lines) and
+/// reports its size change, so the model still knows what happened to the file.
+///
+/// # Arguments
+///
+/// * `files` - The parsed diff
+/// * `max_bytes` - Largest per-file diff that is kept verbatim
+/// * `object_size` - Looks up the size of a blob by (abbreviated) hash
+///
+/// # Returns
+///
+/// * `String` - The diff with oversized sections summarized
+fn summarize_oversized_diffs(
+    files: &[FileDiff],
+    max_bytes: u64,
+    object_size: impl Fn(&str) -> Option<u64>,
+) -> String {
+    let mut result = String::new();
+    for file in files {
+        if !file.is_binary && file.omitted_lines.is_none() && file.raw.len() as u64 <= max_bytes {
+            result.push_str(&file.raw);
+            continue;
+        }
+
+        for header in &file.headers {
+            result.push_str(header);
+            result.push('\n');
+        }
+        let size = |hash: &str| {
+            if hash.chars().all(|c| c == '0') {
+                Some(0)
+            } else {
+                object_size(hash)
+            }
+        };
+        let sizes = match (
+            file.old_blob.as_deref().and_then(size),
+            file.new_blob.as_deref().and_then(size),
+        ) {
+            (Some(old), Some(new)) => format!(
+                ", {} -> {} ({}{})",
+                format_size(old),
+                format_size(new),
+                if new >= old { "+" } else { "-" },
+                format_size(new.abs_diff(old))
+            ),
+            _ => String::new(),
+        };
+        if file.is_binary {
+            result.push_str(&format!("[binary file changed: {}{}]\n", file.path(), sizes));
+        } else {
+            result.push_str(&format!(
+                "[large diff omitted: {}{}, +{} -{} lines]\n",
+                file.path(),
+                sizes,
+                file.added_lines(),
+                file.removed_lines()
+            ));
+        }
+    }
+    result
+}
+
+/// Appends `trailer` to `message`, joining the message's trailer block if it ends with one
+///
+/// # Arguments
+///
+/// * `message` - The commit message
+/// * `trailer` - A `Key: value` line
+///
+/// # Returns
+///
+/// * `String` - The message with the trailer as its last line
+fn append_trailer(message: &str, trailer: &str) -> String {
+    let message = message.trim_end();
+    let last_paragraph = message.rsplit("\n\n").next().unwrap_or_default();
+    let is_trailer = |line: &str| {
+        line.split_once(": ").is_some_and(|(key, _)| {
+            !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+    };
+    // The subject line alone is never a trailer block
+    if message.contains("\n\n") && last_paragraph.lines().all(is_trailer) {
+        format!("{}\n{}", message, trailer)
+    } else {
+        format!("{}\n\n{}", message, trailer)
+    }
+}
+
+/// Formats a byte count for humans, e.g. `512 B` or `1.5 KiB`
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn short_hash(hash: &str) -> &str {
+    &hash[..hash.len().min(12)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::GitFileStatus;
+
+    #[test]
+    fn test_partially_staged_paths() {
+        let file = |path: &str, code: &str| GitFileStatus {
+            path: path.to_string(),
+            status_code: code.to_string(),
+        };
+        let status = GitStatus {
+            staged: vec![file("both.rs", "M"), file("staged.rs", "A")],
+            modified: vec![file("both.rs", "M"), file("unstaged.rs", "M")],
+            ..GitStatus::default()
+        };
+        assert_eq!(partially_staged_paths(&status), vec!["both.rs"]);
+    }
+
+    #[test]
+    fn test_append_trailer() {
+        let trailer = "AI-assisted-by: gitie (m)";
+        assert_eq!(append_trailer("Fix: pager", trailer), "Fix: pager\n\nAI-assisted-by: gitie (m)");
+        assert_eq!(
+            append_trailer("Fix pager\n\nIt hung on short output.\n", trailer),
+            "Fix pager\n\nIt hung on short output.\n\nAI-assisted-by: gitie (m)"
+        );
+        assert_eq!(
+            append_trailer("Fix pager\n\nSigned-off-by: A <a@example.com>", trailer),
+            "Fix pager\n\nSigned-off-by: A <a@example.com>\nAI-assisted-by: gitie (m)"
+        );
+    }
+
+    #[test]
+    fn test_editor_template_is_all_comments() {
+        let stat = " src/main.rs | 4 +++-\n 1 file changed, 3 insertions(+), 1 deletion(-)\n";
+        assert_eq!(
+            editor_template(&AIError::EmptyMessage, stat),
+            "\n# No commit message could be generated: AI returned an empty message.\n#\n\
+             # src/main.rs | 4 +++-\n\
+             # 1 file changed, 3 insertions(+), 1 deletion(-)\n"
+        );
+    }
+
+    #[test]
+    fn test_check_repository_state_blocks_conflicts() {
+        let status = GitStatus {
+            conflicted: vec!["lib.rs".to_string()],
+            ..GitStatus::default()
+        };
+        let result = check_repository_state(&RepositoryState::default(), &status);
+        assert!(matches!(
+            result,
+            Err(AppError::Git(GitError::UnresolvedConflicts(paths))) if paths == vec!["lib.rs"]
+        ));
+        assert!(check_repository_state(&RepositoryState::default(), &GitStatus::default()).is_ok());
+    }
+
+    #[test]
+    fn test_describe_repository_state() {
+        assert!(describe_repository_state(&RepositoryState::default()).is_empty());
+
+        let merge = RepositoryState {
+            operation: Some(InProgressOperation::Merge),
+            prepared_message: Some("Merge branch 'feature'".to_string()),
+            ..RepositoryState::default()
+        };
+        assert_eq!(
+            describe_repository_state(&merge),
+            vec![
+                "This commit concludes a merge; say what was merged.",
+                "Git prepared this message for it:\nMerge branch 'feature'",
+            ]
+        );
+
+        let detached = RepositoryState {
+            detached_head: true,
+            ..RepositoryState::default()
+        };
+        assert_eq!(
+            describe_repository_state(&detached),
+            vec!["HEAD is detached, so the commit is not on a branch."]
+        );
+        // Rebasing always detaches HEAD; that alone isn't worth mentioning
+        let rebase = RepositoryState {
+            detached_head: true,
+            operation: Some(InProgressOperation::Rebase),
+            ..RepositoryState::default()
+        };
+        assert_eq!(describe_repository_state(&rebase), vec!["This commit is made while rebasing."]);
+    }
+
+    #[test]
+    fn test_parse_file_moves() {
+        let diff = "diff --git a/src/old.rs b/src/new.rs
+similarity index 100%
+rename from src/old.rs
+rename to src/new.rs
+diff --git a/a.rs b/b.rs
+similarity index 87%
+copy from a.rs
+copy to b.rs
+index 1111111..2222222 100644
+--- a/a.rs
++++ b/b.rs
+@@ -1 +1 @@
+-rename to not/a/header.rs
++x
+";
+        let moves: Vec<String> = parse_file_moves(&diff::parse(diff)).iter().map(ToString::to_string).collect();
+        assert_eq!(
+            moves,
+            vec![
+                "renamed src/old.rs -> src/new.rs (content unchanged)",
+                "copied a.rs -> b.rs (87% similar)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_file_overview_is_sorted_with_line_counts() {
+        let diff = "diff --git a/src/b.rs b/src/b.rs
+index 1111111..2222222 100644
+--- a/src/b.rs
++++ b/src/b.rs
+@@ -1,2 +1,3 @@
+-one
++uno
++dos
+ three
+diff --git a/assets/logo.png b/assets/logo.png
+new file mode 100644
+index 0000000..3333333
+Binary files /dev/null and b/assets/logo.png differ
+diff --git a/old.rs b/src/a.rs
+similarity index 100%
+rename from old.rs
+rename to src/a.rs
+";
+        assert_eq!(
+            file_overview(&diff::parse(diff)),
+            "Changed files:
+A\tassets/logo.png
+R\told.rs -> src/a.rs
+M\tsrc/b.rs
+
+Lines added and removed per file:
+-\t-\tassets/logo.png
+0\t0\tsrc/a.rs
+2\t1\tsrc/b.rs
+
+"
+        );
+        assert_eq!(file_overview(&[]), "");
+    }
+
+    #[test]
+    fn test_summarize_oversized_diffs() {
+        let small = "diff --git a/small.rs b/small.rs\nindex 1111111..2222222 100644\n--- a/small.rs\n+++ b/small.rs\n@@ -1 +1 @@\n-a\n+b\n";
+        let binary = "diff --git a/logo.png b/logo.png\nnew file mode 100644\nindex 0000000..3333333\nBinary files /dev/null and b/logo.png differ\n";
+        let large = format!(
+            "diff --git a/data.json b/data.json\nindex 4444444..5555555 100644\n--- a/data.json\n+++ b/data.json\n@@ -1,2 +1,3 @@\n-{}\n+{}\n+x\n",
+            "o".repeat(200),
+            "n".repeat(200)
+        );
+        let diff = format!("{}{}{}", small, binary, large);
+        let sizes = |hash: &str| match hash {
+            "3333333" => Some(2048),
+            "4444444" => Some(1000),
+            "5555555" => Some(1536),
+            _ => None,
+        };
+
+        let summarized = summarize_oversized_diffs(&diff::parse(&diff), 300, sizes);
+        assert_eq!(
+            summarized,
+            format!(
+                "{}{}{}",
+                small,
+                "diff --git a/logo.png b/logo.png\nnew file mode 100644\nindex 0000000..3333333\n[binary file changed: logo.png, 0 B -> 2.0 KiB (+2.0 KiB)]\n",
+                "diff --git a/data.json b/data.json\nindex 4444444..5555555 100644\n[large diff omitted: data.json, 1000 B -> 1.5 KiB (+536 B), +2 -1 lines]\n"
+            )
+        );
+        // Nothing changes when every section fits
+        assert_eq!(summarize_oversized_diffs(&diff::parse(small), 300, sizes), small);
+    }
+
+    #[test]
+    fn test_parse_submodule_bumps() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1 +1 @@
+-Subproject commit aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa
++Subproject commit dddddddddddddddddddddddddddddddddddddddd
+diff --git a/vendor/lib b/vendor/lib
+index aaaaaaa..bbbbbbb 160000
+--- a/vendor/lib
++++ b/vendor/lib
+@@ -1 +1 @@
+-Subproject commit aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa
++Subproject commit bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb
+diff --git a/vendor/new b/vendor/new
+new file mode 160000
+--- /dev/null
++++ b/vendor/new
+@@ -0,0 +1 @@
++Subproject commit cccccccccccccccccccccccccccccccccccccccc
+";
+        assert_eq!(
+            parse_submodule_bumps(&diff::parse(diff)),
+            vec![SubmoduleBump {
+                path: "vendor/lib".to_string(),
+                old: "a".repeat(40),
+                new: "b".repeat(40),
+            }]
+        );
+    }
+}
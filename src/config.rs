@@ -1,25 +1,734 @@
+//! Loading, validating and editing the configuration in `~/.config/gitie`.
+
 use dirs::home_dir;
 use serde::Deserialize;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::create_dir_all;
 use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use std::{fs, io};
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::errors::ConfigError;
+use crate::ai_explainer::ExplanationLevel;
+use crate::git_backend::{DiffOptions, RenameDetection};
+use crate::ai_hooks::HooksConfig;
+use crate::forge::ForgeConfig;
+use crate::gitea::GiteaConfig;
+use crate::github::GitHubConfig;
+use crate::interaction_log::HistoryConfig;
+use crate::issue_tracker::IssuesConfig;
+use crate::glossary::Glossary;
+use crate::guard::GuardConfig;
+use crate::history_index::SearchConfig;
+use crate::notify::NotifyConfig;
+use crate::output::OutputConfig;
+use crate::policy::{Policy, SYSTEM_POLICY_FILE};
+use crate::usage_stats::StatsConfig;
+use crate::prompt_template::{self, PromptTemplate};
+use crate::state_file::{FileLock, write_atomic};
+use crate::ui;
 
 const USER_CONFIG_DIR: &str = ".config/gitie";
 const USER_CONFIG_FILE_NAME: &str = "config.toml";
 const USER_COMMIT_PROMPT_FILE_NAME: &str = "commit-prompt";
 const USER_EXPLANATION_PROMPT_FILE_NAME: &str = "explanation-prompt";
+const USER_GLOSSARY_FILE_NAME: &str = "glossary.toml";
 const CONFIG_EXAMPLE_FILE_NAME: &str = "assets/config.example.toml";
 const COMMIT_PROMPT_EXAMPLE_FILE_NAME: &str = "assets/commit-prompt";
 const EXPLANATION_PROMPT_EXAMPLE_FILE_NAME: &str = "assets/explanation-prompt";
 
-const TEST_ASSETS_CONFIG_EXAMPLE_FILE_NAME: &str = "test_assets/config.example.toml";
-const TEST_ASSETS_COMMIT_PROMPT_FILE_NAME: &str = "test_assets/commit-prompt";
-const TEST_ASSETS_EXPLANATION_PROMPT_FILE_NAME: &str = "test_assets/explanation-prompt";
+/// Expected shape of a recognised configuration value
+#[derive(Debug, Clone, Copy)]
+enum ConfigValueKind {
+    String,
+    Float { min: f64, max: f64 },
+    Integer { min: i64, max: i64 },
+    Bool,
+    StringList,
+    /// A string restricted to a fixed set of values
+    Choice(&'static [&'static str]),
+    /// The URL of an OpenAI-compatible chat completions endpoint
+    Endpoint,
+    Table,
+    /// An array of tables; each entry is validated against `<key>.*` patterns
+    TableList,
+}
+
+/// Every key the config file understands, as dotted paths.
+///
+/// Keys found in the file but not listed here are reported as warnings so typos
+/// like `temprature` don't get silently ignored.
+const CONFIG_SCHEMA: &[(&str, ConfigValueKind)] = &[
+    ("ai", ConfigValueKind::Table),
+    ("ai.api_url", ConfigValueKind::Endpoint),
+    ("ai.model_name", ConfigValueKind::String),
+    ("ai.temperature", ConfigValueKind::Float { min: 0.0, max: 2.0 }),
+    ("ai.api_key", ConfigValueKind::String),
+    ("ai.max_concurrency", ConfigValueKind::Integer { min: 1, max: 32 }),
+    ("ai.retry", ConfigValueKind::Table),
+    ("ai.retry.max_attempts", ConfigValueKind::Integer { min: 1, max: 10 }),
+    ("ai.retry.base_delay_ms", ConfigValueKind::Integer { min: 0, max: 60_000 }),
+    ("ai.retry.retry_on", ConfigValueKind::StringList),
+    ("ai.retry.json_repairs", ConfigValueKind::Integer { min: 0, max: 5 }),
+    ("ai.timeouts", ConfigValueKind::Table),
+    ("ai.timeouts.connect", ConfigValueKind::Integer { min: 1, max: 600 }),
+    ("ai.timeouts.total", ConfigValueKind::Integer { min: 1, max: 3600 }),
+    ("ai.timeouts.stream_idle", ConfigValueKind::Integer { min: 1, max: 3600 }),
+    ("ai.aliases", ConfigValueKind::Table),
+    ("ai.aliases.*", ConfigValueKind::Table),
+    ("ai.aliases.*.provider", ConfigValueKind::String),
+    ("ai.aliases.*.model", ConfigValueKind::String),
+    ("ai.aliases.*.api_url", ConfigValueKind::Endpoint),
+    ("ai.endpoints", ConfigValueKind::TableList),
+    ("ai.endpoints.*.url", ConfigValueKind::Endpoint),
+    ("ai.endpoints.*.key", ConfigValueKind::String),
+    ("ai.endpoints.*.models", ConfigValueKind::StringList),
+    ("commit", ConfigValueKind::Table),
+    ("commit.language", ConfigValueKind::String),
+    ("commit.model", ConfigValueKind::String),
+    ("commit.temperature", ConfigValueKind::Float { min: 0.0, max: 2.0 }),
+    ("commit.max_tokens", ConfigValueKind::Integer { min: 1, max: 1_000_000 }),
+    ("commit.post_generate_cmd", ConfigValueKind::String),
+    ("commit.diff_context_lines", ConfigValueKind::Integer { min: 0, max: 1000 }),
+    ("commit.include_submodule_log", ConfigValueKind::Bool),
+    ("commit.provenance_trailer", ConfigValueKind::Bool),
+    ("commit.max_file_diff_bytes", ConfigValueKind::Integer { min: 1, max: 1_000_000_000 }),
+    ("commit.large_file_bytes", ConfigValueKind::Integer { min: 0, max: 1_000_000_000_000 }),
+    ("commit.detect_renames", ConfigValueKind::Choice(&["off", "renames", "copies"])),
+    ("explain", ConfigValueKind::Table),
+    ("explain.language", ConfigValueKind::String),
+    ("explain.level", ConfigValueKind::Choice(&["beginner", "normal", "expert"])),
+    ("explain.glossary", ConfigValueKind::Bool),
+    ("explain.teach_mode", ConfigValueKind::Bool),
+    ("explain.teach_interval_minutes", ConfigValueKind::Integer { min: 0, max: 525_600 }),
+    ("explain.model", ConfigValueKind::String),
+    ("explain.temperature", ConfigValueKind::Float { min: 0.0, max: 2.0 }),
+    ("explain.max_tokens", ConfigValueKind::Integer { min: 1, max: 1_000_000 }),
+    ("explain.post_generate_cmd", ConfigValueKind::String),
+    ("review", ConfigValueKind::Table),
+    ("review.model", ConfigValueKind::String),
+    ("review.temperature", ConfigValueKind::Float { min: 0.0, max: 2.0 }),
+    ("review.max_tokens", ConfigValueKind::Integer { min: 1, max: 1_000_000 }),
+    ("review.post_generate_cmd", ConfigValueKind::String),
+    ("output", ConfigValueKind::Table),
+    ("output.format", ConfigValueKind::Choice(&["markdown", "plain", "json", "porcelain"])),
+    ("output.color", ConfigValueKind::Choice(&["auto", "always", "never"])),
+    ("output.pager", ConfigValueKind::Bool),
+    ("guard", ConfigValueKind::Table),
+    ("guard.enabled", ConfigValueKind::Bool),
+    ("guard.allow", ConfigValueKind::StringList),
+    ("guard.shared_branches", ConfigValueKind::StringList),
+    ("notify", ConfigValueKind::Table),
+    ("notify.enabled", ConfigValueKind::Bool),
+    ("notify.after_seconds", ConfigValueKind::Integer { min: 0, max: 86_400 }),
+    ("search", ConfigValueKind::Table),
+    ("search.embedding_model", ConfigValueKind::String),
+    ("search.api_url", ConfigValueKind::String),
+    ("github", ConfigValueKind::Table),
+    ("github.api_url", ConfigValueKind::String),
+    ("github.token", ConfigValueKind::String),
+    ("gitea", ConfigValueKind::Table),
+    ("gitea.api_url", ConfigValueKind::String),
+    ("gitea.token", ConfigValueKind::String),
+    ("forge", ConfigValueKind::Table),
+    ("forge.kind", ConfigValueKind::Choice(&["auto", "github", "gitea"])),
+    ("issues", ConfigValueKind::Table),
+    ("issues.tracker", ConfigValueKind::Choice(&["none", "github", "jira"])),
+    ("issues.jira_url", ConfigValueKind::String),
+    ("issues.jira_email", ConfigValueKind::String),
+    ("issues.jira_projects", ConfigValueKind::StringList),
+    ("issues.token", ConfigValueKind::String),
+    ("history", ConfigValueKind::Table),
+    ("history.enabled", ConfigValueKind::Bool),
+    ("stats", ConfigValueKind::Table),
+    ("stats.enabled", ConfigValueKind::Bool),
+    ("hooks", ConfigValueKind::Table),
+    ("hooks.pre_ai", ConfigValueKind::String),
+    ("hooks.post_ai", ConfigValueKind::String),
+    ("aliases", ConfigValueKind::Table),
+    ("aliases.*", ConfigValueKind::String),
+];
+
+/// `git config` keys (lower-cased, as git reports them) mapped onto config file keys
+const GIT_CONFIG_KEYS: &[(&str, &str)] = &[
+    ("gitie.model", "ai.model_name"),
+    ("gitie.apiurl", "ai.api_url"),
+    ("gitie.apikey", "ai.api_key"),
+    ("gitie.temperature", "ai.temperature"),
+    ("gitie.commitlanguage", "commit.language"),
+    ("gitie.explainlanguage", "explain.language"),
+    ("gitie.forge", "forge.kind"),
+];
+
+/// Outcome of checking a parsed config file against `CONFIG_SCHEMA`
+#[derive(Debug, Default)]
+pub struct ConfigValidation {
+    /// Non-fatal problems, e.g. unknown keys
+    pub warnings: Vec<String>,
+    /// Problems that make the configuration unusable
+    pub errors: Vec<String>,
+}
+
+/// Validates a raw TOML document against the known configuration schema.
+///
+/// All problems are collected instead of stopping at the first one, so users can
+/// fix their config file in a single pass.
+pub fn validate_config_value(root: &toml::Value) -> ConfigValidation {
+    let mut report = ConfigValidation::default();
+    match root.as_table() {
+        Some(table) => validate_table(table, "", &mut report),
+        None => report
+            .errors
+            .push("configuration root must be a table".to_string()),
+    }
+    report
+}
+
+fn validate_table(table: &toml::Table, prefix: &str, report: &mut ConfigValidation) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+
+        let Some(kind) = lookup_schema_kind(&path) else {
+            let mut warning = format!("unknown configuration key '{}'", path);
+            if let Some(suggestion) = suggest_config_key(&path) {
+                warning.push_str(&format!(" (did you mean '{}'?)", suggestion));
+            }
+            report.warnings.push(warning);
+            continue;
+        };
+
+        match kind {
+            ConfigValueKind::Table => match value.as_table() {
+                Some(inner) => validate_table(inner, &path, report),
+                None => report
+                    .errors
+                    .push(format!("'{}' must be a table, found {}", path, value.type_str())),
+            },
+            ConfigValueKind::TableList => match value.as_array() {
+                Some(items) => {
+                    for (index, item) in items.iter().enumerate() {
+                        let item_path = format!("{}.{}", path, index);
+                        match item.as_table() {
+                            Some(inner) => validate_table(inner, &item_path, report),
+                            None => report.errors.push(format!(
+                                "'{}' must be a table, found {}",
+                                item_path,
+                                item.type_str()
+                            )),
+                        }
+                    }
+                }
+                None => report.errors.push(format!(
+                    "'{}' must be an array of tables, found {}",
+                    path,
+                    value.type_str()
+                )),
+            },
+            ConfigValueKind::String => {
+                if !value.is_str() {
+                    report
+                        .errors
+                        .push(format!("'{}' must be a string, found {}", path, value.type_str()));
+                }
+            }
+            ConfigValueKind::StringList => {
+                let all_strings = value
+                    .as_array()
+                    .is_some_and(|items| items.iter().all(|item| item.is_str()));
+                if !all_strings {
+                    report.errors.push(format!(
+                        "'{}' must be a list of strings, found {}",
+                        path,
+                        value.type_str()
+                    ));
+                }
+            }
+            ConfigValueKind::Bool => {
+                if !value.is_bool() {
+                    report
+                        .errors
+                        .push(format!("'{}' must be true or false, found {}", path, value.type_str()));
+                }
+            }
+            ConfigValueKind::Choice(choices) => match value.as_str() {
+                Some(v) if choices.contains(&v) => {}
+                Some(v) => report.errors.push(format!(
+                    "'{}' must be one of {}, found '{}'",
+                    path,
+                    choices.join(", "),
+                    v
+                )),
+                None => report
+                    .errors
+                    .push(format!("'{}' must be a string, found {}", path, value.type_str())),
+            },
+            ConfigValueKind::Endpoint => match value.as_str() {
+                Some(url) => validate_endpoint(&path, url, report),
+                None => report
+                    .errors
+                    .push(format!("'{}' must be a string, found {}", path, value.type_str())),
+            },
+            ConfigValueKind::Integer { min, max } => match value.as_integer() {
+                Some(n) if n < min || n > max => report.errors.push(format!(
+                    "'{}' must be between {} and {}, found {}",
+                    path, min, max, n
+                )),
+                Some(_) => {}
+                None => report
+                    .errors
+                    .push(format!("'{}' must be an integer, found {}", path, value.type_str())),
+            },
+            ConfigValueKind::Float { min, max } => {
+                let number = match value {
+                    toml::Value::Float(f) => Some(*f),
+                    toml::Value::Integer(i) => Some(*i as f64),
+                    _ => None,
+                };
+                match number {
+                    Some(n) if n < min || n > max => report.errors.push(format!(
+                        "'{}' must be between {} and {}, found {}",
+                        path, min, max, n
+                    )),
+                    Some(_) => {}
+                    None => report
+                        .errors
+                        .push(format!("'{}' must be a number, found {}", path, value.type_str())),
+                }
+            }
+        }
+    }
+}
+
+/// Checks that `url` can take chat completion requests, so that a typo is
+/// reported when loading instead of as a request error on first use.
+///
+/// A path other than `.../chat/completions` is only a warning: proxies and
+/// gateways sometimes use their own.
+fn validate_endpoint(path: &str, url: &str, report: &mut ConfigValidation) {
+    let parsed = match reqwest::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            report.errors.push(format!("'{}' must be a URL, found '{}' ({})", path, url, e));
+            return;
+        }
+    };
+    if !matches!(parsed.scheme(), "http" | "https") {
+        report.errors.push(format!(
+            "'{}' must be an http:// or https:// URL, found '{}'",
+            path, url
+        ));
+    } else if parsed.host_str().is_none_or(str::is_empty) {
+        report.errors.push(format!("'{}' must name a host, found '{}'", path, url));
+    } else if !parsed.path().trim_end_matches('/').ends_with("/chat/completions") {
+        let base = url.trim_end_matches('/');
+        let suggestion = if parsed.path().trim_matches('/').is_empty() {
+            format!("{}/v1/chat/completions", base)
+        } else {
+            format!("{}/chat/completions", base)
+        };
+        report.warnings.push(format!(
+            "'{}' does not look like a chat completions endpoint: '{}' (did you mean '{}'?)",
+            path, url, suggestion
+        ));
+    }
+}
+
+/// Reads every `gitie.*` entry visible to git from `dir`, or the current directory
+/// (system, global and repository scopes, with git's usual precedence).
+pub fn read_git_config_settings(dir: Option<&Path>) -> Result<Vec<(String, String)>, ConfigError> {
+    let mut command = std::process::Command::new("git");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command
+        .args(["config", "-z", "--get-regexp", r"^gitie\."])
+        .output()
+        .map_err(|e| ConfigError::GitConfigRead("gitie.* settings".to_string(), e))?;
+    // Exit code 1 simply means no matching keys
+    if !output.status.success() {
+        return Ok(Vec::new());
+    }
+    Ok(parse_git_config_entries(&String::from_utf8_lossy(&output.stdout)))
+}
+
+/// Parses `git config -z` output, where each entry is `key\nvalue\0`.
+fn parse_git_config_entries(output: &str) -> Vec<(String, String)> {
+    output
+        .split('\0')
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once('\n') {
+            Some((key, value)) => (key.to_lowercase(), value.to_string()),
+            // A key without a value is git's shorthand for boolean true
+            None => (entry.to_lowercase(), "true".to_string()),
+        })
+        .collect()
+}
+
+/// Looks up one setting in the policy's locked values and the user's config file,
+/// without initializing the configuration directory or running `git config`.
+///
+/// For decisions made before loading the full configuration is worthwhile; only
+/// suitable for keys that have no `gitie.*` git setting. `None` when the key is
+/// unset or a file can't be read.
+pub fn peek_setting(paths: &ConfigPaths, key: &str) -> Option<toml::Value> {
+    let policy = Policy::load_from(&paths.policy_file).ok()?;
+    if let Some(value) = lookup_dotted_value(&toml::Value::Table(policy.locked), key) {
+        return Some(value.clone());
+    }
+    let content = fs::read_to_string(paths.config_file_path()).ok()?;
+    let raw: toml::Value = toml::from_str(&content).ok()?;
+    lookup_dotted_value(&raw, key).cloned()
+}
+
+/// Overlays `gitie.*` git config entries onto a parsed config file.
+///
+/// Later entries win, matching git's own "last one wins" rule across scopes.
+fn apply_git_config_settings(
+    raw: &mut toml::Value,
+    entries: &[(String, String)],
+) -> ConfigValidation {
+    let mut report = ConfigValidation::default();
+    for (git_key, value) in entries {
+        let Some((_, config_key)) = GIT_CONFIG_KEYS.iter().find(|(k, _)| k == git_key) else {
+            report
+                .warnings
+                .push(format!("unknown git config setting '{}'", git_key));
+            continue;
+        };
+        let converted = match lookup_schema_kind(config_key) {
+            Some(ConfigValueKind::Float { .. }) => match value.trim().parse::<f64>() {
+                Ok(number) => toml::Value::Float(number),
+                Err(_) => {
+                    report.errors.push(format!(
+                        "git config '{}' must be a number, found '{}'",
+                        git_key, value
+                    ));
+                    continue;
+                }
+            },
+            _ => toml::Value::String(value.clone()),
+        };
+        // Checked on its own, so that a bad value is reported as git config's
+        let mut single = toml::Value::Table(toml::Table::new());
+        insert_dotted_value(&mut single, config_key, converted.clone());
+        let problems = validate_config_value(&single).errors;
+        if !problems.is_empty() {
+            report
+                .errors
+                .extend(problems.into_iter().map(|problem| format!("git config '{}': {}", git_key, problem)));
+            continue;
+        }
+        insert_dotted_value(raw, config_key, converted);
+    }
+    report
+}
+
+/// Forces the policy's locked values onto the configuration, returning a warning
+/// for every user-supplied value that was overridden.
+fn apply_policy_locks(raw: &mut toml::Value, locked: &toml::Table) -> Vec<String> {
+    let mut locked_values = Vec::new();
+    flatten_table(locked, "", &mut locked_values);
+
+    let mut warnings = Vec::new();
+    for (key, value) in locked_values {
+        if let Some(existing) = lookup_dotted_value(raw, &key)
+            && *existing != value
+        {
+            warnings.push(format!(
+                "'{}' is locked by the system policy; ignoring the configured value",
+                key
+            ));
+        }
+        insert_dotted_value(raw, &key, value);
+    }
+    warnings
+}
+
+fn flatten_table(table: &toml::Table, prefix: &str, out: &mut Vec<(String, toml::Value)>) {
+    for (key, value) in table {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match value {
+            toml::Value::Table(inner) => flatten_table(inner, &path, out),
+            other => out.push((path, other.clone())),
+        }
+    }
+}
+
+fn lookup_dotted_value<'a>(raw: &'a toml::Value, key: &str) -> Option<&'a toml::Value> {
+    key.split('.')
+        .try_fold(raw, |current, segment| current.as_table()?.get(segment))
+}
+
+fn insert_dotted_value(raw: &mut toml::Value, key: &str, value: toml::Value) {
+    let segments: Vec<&str> = key.split('.').collect();
+    let (last, sections) = segments.split_last().expect("split always yields a segment");
+    let mut current = raw;
+    for section in sections {
+        let table = match current {
+            toml::Value::Table(table) => table,
+            _ => return,
+        };
+        current = table
+            .entry(section.to_string())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    }
+    if let toml::Value::Table(table) = current {
+        table.insert(last.to_string(), value);
+    }
+}
+
+fn lookup_schema_kind(path: &str) -> Option<ConfigValueKind> {
+    CONFIG_SCHEMA.iter().find_map(|(pattern, kind)| {
+        let mut pattern_parts = pattern.split('.');
+        let mut path_parts = path.split('.');
+        loop {
+            match (pattern_parts.next(), path_parts.next()) {
+                (None, None) => return Some(*kind),
+                (Some(p), Some(k)) if p == "*" || p == k => continue,
+                _ => return None,
+            }
+        }
+    })
+}
+
+/// Hides a secret for display, keeping only its last four characters when it is long enough.
+fn mask_secret(secret: Option<&str>) -> String {
+    match secret {
+        None => "(none)".to_string(),
+        Some(secret) if secret.chars().count() > 8 => {
+            let tail: String = secret.chars().skip(secret.chars().count() - 4).collect();
+            format!("****{}", tail)
+        }
+        Some(_) => "****".to_string(),
+    }
+}
+
+/// Finds the closest known key for a misspelled one, if any is close enough.
+fn suggest_config_key(path: &str) -> Option<&'static str> {
+    CONFIG_SCHEMA
+        .iter()
+        .map(|(pattern, _)| *pattern)
+        .filter(|pattern| !pattern.contains('*'))
+        .map(|pattern| (pattern, edit_distance(path, pattern)))
+        .filter(|(_, distance)| *distance <= 2)
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(pattern, _)| pattern)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b_chars.len()]
+}
+
+/// Locations the configuration is loaded from.
+///
+/// [`ConfigPaths::from_env`] resolves the standard locations; tests and embedders can
+/// point every field at a scratch directory instead, and turn `git_config` off,
+/// keeping loading hermetic.
+#[derive(Debug, Clone)]
+pub struct ConfigPaths {
+    /// Directory holding `config.toml` and the prompt files, normally `~/.config/gitie`
+    pub user_config_dir: PathBuf,
+    /// Templates copied into `user_config_dir` when a file is missing there
+    pub config_template: PathBuf,
+    pub commit_prompt_template: PathBuf,
+    pub explanation_prompt_template: PathBuf,
+    /// System policy file; a missing file means no restrictions
+    pub policy_file: PathBuf,
+    /// Whether `gitie.*` settings from git config are layered on top of the file
+    pub git_config: bool,
+    /// A file to use instead of `config.toml` in `user_config_dir` (`--config`).
+    /// Nothing is created or copied then: prompts missing next to the file
+    /// come straight from the templates.
+    pub config_file: Option<PathBuf>,
+}
+
+impl ConfigPaths {
+    /// Standard locations: the user directory under `HOME`, the bundled `assets/`
+    /// templates (overridable with `GITIE_ASSETS_CONFIG`, `GITIE_ASSETS_COMMIT_PROMPT`
+    /// and `GITIE_ASSETS_EXPLANATION_PROMPT`) and the system policy file.
+    ///
+    /// `GITIE_CONFIG`, which `--config` sets, names the configuration file
+    /// instead; its directory then takes the place of the user directory.
+    pub fn from_env() -> Self {
+        let template = |var: &str, default: &str| {
+            std::env::var_os(var)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(default))
+        };
+        let config_file = std::env::var_os("GITIE_CONFIG").filter(|path| !path.is_empty()).map(PathBuf::from);
+        let user_config_dir = match &config_file {
+            Some(file) => file.parent().map_or_else(PathBuf::new, Path::to_path_buf),
+            None => home_config_dir(),
+        };
+        ConfigPaths {
+            user_config_dir,
+            config_template: template("GITIE_ASSETS_CONFIG", CONFIG_EXAMPLE_FILE_NAME),
+            commit_prompt_template: template(
+                "GITIE_ASSETS_COMMIT_PROMPT",
+                COMMIT_PROMPT_EXAMPLE_FILE_NAME,
+            ),
+            explanation_prompt_template: template(
+                "GITIE_ASSETS_EXPLANATION_PROMPT",
+                EXPLANATION_PROMPT_EXAMPLE_FILE_NAME,
+            ),
+            policy_file: PathBuf::from(SYSTEM_POLICY_FILE),
+            git_config: true,
+            config_file,
+        }
+    }
+
+    fn user_file(&self, filename: &str) -> PathBuf {
+        self.user_config_dir.join(filename)
+    }
+
+    /// The configuration file itself
+    pub fn config_file_path(&self) -> PathBuf {
+        self.config_file.clone().unwrap_or_else(|| self.user_file(USER_CONFIG_FILE_NAME))
+    }
+}
+
+/// The user's own configuration directory under `HOME`, `~/.config/gitie`,
+/// which `--config` doesn't change
+pub fn home_config_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .or_else(home_dir)
+        .expect("Could not determine home directory")
+        .join(USER_CONFIG_DIR)
+}
+
+// AI请求的重试策略
+#[derive(Deserialize, Debug, Clone)]
+pub struct RetryConfig {
+    /// Total number of attempts, including the first one
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry; doubled for each further attempt
+    #[serde(default = "default_base_delay_ms")]
+    pub base_delay_ms: u64,
+    /// Failure kinds worth retrying: "timeout", "connect", "5xx" or an HTTP status such as "429"
+    #[serde(default = "default_retry_on")]
+    pub retry_on: Vec<String>,
+    /// Follow-up requests asking the model to fix a reply that should be JSON but doesn't parse
+    #[serde(default = "default_json_repairs")]
+    pub json_repairs: u32,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: default_max_attempts(),
+            base_delay_ms: default_base_delay_ms(),
+            retry_on: default_retry_on(),
+            json_repairs: default_json_repairs(),
+        }
+    }
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_base_delay_ms() -> u64 {
+    500
+}
+
+fn default_retry_on() -> Vec<String> {
+    ["timeout", "connect", "429", "5xx"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+fn default_json_repairs() -> u32 {
+    2
+}
+
+fn default_max_concurrency() -> usize {
+    4
+}
+
+// AI请求的超时设置（单位：秒）
+#[derive(Deserialize, Debug, Clone)]
+pub struct TimeoutConfig {
+    /// Time allowed to establish the connection
+    #[serde(default = "default_connect_timeout")]
+    pub connect: u64,
+    /// Time allowed for the whole request, including reading the response
+    #[serde(default = "default_total_timeout")]
+    pub total: u64,
+    /// Longest pause allowed between two chunks of a response
+    #[serde(default = "default_stream_idle_timeout")]
+    pub stream_idle: u64,
+    /// `--timeout`: time allowed for a whole AI request, retries included
+    #[serde(skip)]
+    pub deadline: Option<u64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        TimeoutConfig {
+            connect: default_connect_timeout(),
+            total: default_total_timeout(),
+            stream_idle: default_stream_idle_timeout(),
+            deadline: None,
+        }
+    }
+}
+
+fn default_connect_timeout() -> u64 {
+    10
+}
+
+fn default_total_timeout() -> u64 {
+    300
+}
+
+fn default_stream_idle_timeout() -> u64 {
+    60
+}
+
+// 模型别名，例如 fast / smart / local
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct ModelAlias {
+    /// Free-form provider label, e.g. "ollama" or "openai"
+    #[serde(default)]
+    pub provider: Option<String>,
+    pub model: String,
+    /// Endpoint serving this model; defaults to `ai.api_url`
+    #[serde(default)]
+    pub api_url: Option<String>,
+}
+
+// 额外的AI服务端点及其密钥
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct EndpointConfig {
+    pub url: String,
+    #[serde(default)]
+    pub key: Option<String>,
+    /// Models served by this endpoint; requests for them are routed here
+    #[serde(default)]
+    pub models: Vec<String>,
+}
 
 // AI服务的配置
 #[derive(Deserialize, Debug, Clone, Default)]
@@ -28,6 +737,149 @@ pub struct AIConfig {
     pub model_name: String,
     pub temperature: f32,
     pub api_key: Option<String>, // Made Option in case it's not always needed or provided
+    /// Most AI requests of one run that may be in flight at the same time
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    #[serde(default)]
+    pub retry: RetryConfig,
+    #[serde(default)]
+    pub timeouts: TimeoutConfig,
+    #[serde(default)]
+    pub aliases: HashMap<String, ModelAlias>,
+    #[serde(default)]
+    pub endpoints: Vec<EndpointConfig>,
+}
+
+impl AIConfig {
+    /// Returns the API key for `url`: the matching `[[ai.endpoints]]` entry's key,
+    /// or `api_key` for the default endpoint (`api_url`, and the embeddings URL
+    /// derived from it). Any other URL gets no key, so a key is never sent to a
+    /// host it wasn't configured for.
+    pub fn api_key_for(&self, url: &str) -> Option<&str> {
+        let key = match self.endpoints.iter().find(|endpoint| endpoint.url == url) {
+            Some(endpoint) => endpoint.key.as_deref(),
+            None if url == self.api_url || url == self.default_embeddings_url() => self.api_key.as_deref(),
+            None => None,
+        };
+        key.filter(|k| !k.is_empty() && *k != "YOUR_API_KEY_IF_NEEDED")
+    }
+
+    /// The embeddings endpoint next to `api_url`
+    pub fn default_embeddings_url(&self) -> String {
+        match self.api_url.strip_suffix("chat/completions") {
+            Some(base) => format!("{}embeddings", base),
+            None => self.api_url.clone(),
+        }
+    }
+}
+
+// 单个AI任务可覆盖的设置，模型参数未设置时回退到 [ai] 中的默认值
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct TaskOverrides {
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    #[serde(default)]
+    pub max_tokens: Option<u32>,
+    /// Shell command the AI output is piped through (see `post_generate`)
+    #[serde(default)]
+    pub post_generate_cmd: Option<String>,
+}
+
+/// Model parameters resolved for a single AI task
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelParameters {
+    pub model: String,
+    pub api_url: String,
+    pub temperature: f32,
+    pub max_tokens: Option<u32>,
+}
+
+// 提交相关的配置
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct CommitConfig {
+    /// Language the generated commit message should be written in
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Lines of context around each change in the diff sent to the AI (`git diff -U<N>`)
+    #[serde(default)]
+    pub diff_context_lines: Option<u32>,
+    /// Expand submodule pointer bumps into the submodule's `git log --oneline old..new`
+    #[serde(default)]
+    pub include_submodule_log: bool,
+    /// Append an `AI-assisted-by: gitie (<model>)` trailer to generated messages
+    #[serde(default)]
+    pub provenance_trailer: bool,
+    /// Per-file diffs larger than this are replaced by a summary (default 64 KiB)
+    #[serde(default)]
+    pub max_file_diff_bytes: Option<u64>,
+    /// Newly added files larger than this get the large file advice before
+    /// committing (default 5 MiB; 0 turns it off)
+    #[serde(default)]
+    pub large_file_bytes: Option<u64>,
+    /// Rename and copy detection for the diff sent to the AI (default: renames and copies)
+    #[serde(default)]
+    pub detect_renames: RenameDetection,
+    #[serde(flatten)]
+    pub overrides: TaskOverrides,
+}
+
+impl CommitConfig {
+    /// `max_file_diff_bytes`, or its default when unset
+    pub fn max_file_diff_bytes(&self) -> u64 {
+        self.max_file_diff_bytes.unwrap_or(64 * 1024)
+    }
+
+    /// `large_file_bytes`, or its default when unset
+    pub fn large_file_bytes(&self) -> u64 {
+        self.large_file_bytes.unwrap_or(5 * 1024 * 1024)
+    }
+
+    /// Options for collecting the staged diff
+    pub fn diff_options(&self) -> DiffOptions {
+        DiffOptions {
+            context_lines: self.diff_context_lines,
+            renames: self.detect_renames,
+            ..DiffOptions::default()
+        }
+    }
+}
+
+// 命令解释相关的配置
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ExplainConfig {
+    /// Language AI explanations should be written in, e.g. "zh-CN"
+    #[serde(default)]
+    pub language: Option<String>,
+    /// How much git knowledge explanations assume
+    #[serde(default)]
+    pub level: ExplanationLevel,
+    /// Define git jargon inline, using the glossary
+    #[serde(default)]
+    pub glossary: bool,
+    /// Show a short tip after commands passed through to git
+    #[serde(default)]
+    pub teach_mode: bool,
+    /// Minimum time between two tips (default 60)
+    #[serde(default)]
+    pub teach_interval_minutes: Option<u64>,
+    #[serde(flatten)]
+    pub overrides: TaskOverrides,
+}
+
+impl ExplainConfig {
+    /// `teach_interval_minutes`, or its default when unset
+    pub fn teach_interval_minutes(&self) -> u64 {
+        self.teach_interval_minutes.unwrap_or(60)
+    }
+}
+
+// 代码审查相关的配置
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct ReviewConfig {
+    #[serde(flatten)]
+    pub overrides: TaskOverrides,
 }
 
 // 应用的总体配置
@@ -36,235 +888,480 @@ pub struct AppConfig {
     #[serde(default)]
     pub ai: AIConfig,
 
+    #[serde(default)]
+    pub commit: CommitConfig,
+
+    #[serde(default)]
+    pub explain: ExplainConfig,
+
+    #[serde(default)]
+    pub review: ReviewConfig,
+
+    #[serde(default)]
+    pub output: OutputConfig,
+
+    #[serde(default)]
+    pub notify: NotifyConfig,
+
+    #[serde(default)]
+    pub guard: GuardConfig,
+
+    #[serde(default)]
+    pub search: SearchConfig,
+
+    #[serde(default)]
+    pub github: GitHubConfig,
+
+    #[serde(default)]
+    pub gitea: GiteaConfig,
+
+    #[serde(default)]
+    pub forge: ForgeConfig,
+
+    #[serde(default)]
+    pub issues: IssuesConfig,
+
+    #[serde(default)]
+    pub history: HistoryConfig,
+
+    #[serde(default)]
+    pub stats: StatsConfig,
+
+    #[serde(default)]
+    pub hooks: HooksConfig,
+
+    /// `[aliases]`, expanded by the command line before the configuration is loaded
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+
     #[serde(skip)] // Prompts are loaded separately
-    pub prompts: HashMap<String, String>,
+    pub prompts: HashMap<String, PromptTemplate>,
+
+    /// Definitions used when `explain.glossary` is on
+    #[serde(skip)]
+    pub glossary: Glossary,
+
+    /// System prompt supplied on the command line (`--prompt-file` / `--system-prompt`),
+    /// replacing whichever task prompt would otherwise be used for this invocation
+    #[serde(skip)]
+    pub prompt_override: Option<PromptTemplate>,
+
+    /// Model (or model alias) given with `--model`, used by every task of this invocation
+    #[serde(skip)]
+    pub model_override: Option<String>,
+
+    /// Temperature given with `--temperature`, used by every task of this invocation
+    #[serde(skip)]
+    pub temperature_override: Option<f32>,
+
+    /// Directory given with `--scope`, relative to the current directory;
+    /// not a configuration key (see `scope::resolve`)
+    #[serde(skip)]
+    pub scope: Option<String>,
+
+    /// Background given with `--context`, stdin already read for `-`; added
+    /// to the prompts of commit messages, explanations and reviews
+    #[serde(skip)]
+    pub context: Vec<String>,
+
+    /// Restrictions from the system policy file
+    #[serde(skip)]
+    pub policy: Policy,
 }
 
 impl AppConfig {
     /// 初始化用户配置
     ///
-    /// 此函数会检查用户配置目录是否存在配置文件，如果不存在，
-    /// 则从assets目录复制默认配置文件
-    pub fn initialize_config() -> Result<(PathBuf, HashMap<String, PathBuf>), ConfigError> {
-        let user_config_path = Self::get_user_file_path(USER_CONFIG_FILE_NAME)?;
-        let user_commit_prompt_path = Self::get_user_file_path(USER_COMMIT_PROMPT_FILE_NAME)?;
-        let user_explanation_prompt_path = Self::get_user_file_path(USER_EXPLANATION_PROMPT_FILE_NAME)?;
-        
+    /// 此函数会检查用户配置目录中的配置文件和提示文件，
+    /// 缺失的文件从模板目录复制，已存在的文件保持不变
+    pub fn initialize_config(
+        paths: &ConfigPaths,
+    ) -> Result<(PathBuf, HashMap<String, PathBuf>), ConfigError> {
+        let user_config_path = paths.config_file_path();
+        let user_commit_prompt_path = paths.user_file(USER_COMMIT_PROMPT_FILE_NAME);
+        let user_explanation_prompt_path = paths.user_file(USER_EXPLANATION_PROMPT_FILE_NAME);
+
+        // 指定了配置文件时不初始化用户目录，缺失的提示直接使用模板
+        if paths.config_file.is_some() {
+            let prompt = |user: PathBuf, template: &PathBuf| if user.exists() { user } else { template.clone() };
+            let prompt_paths = HashMap::from([
+                ("commit".to_string(), prompt(user_commit_prompt_path, &paths.commit_prompt_template)),
+                ("explanation".to_string(), prompt(user_explanation_prompt_path, &paths.explanation_prompt_template)),
+            ]);
+            return Ok((user_config_path, prompt_paths));
+        }
+
         let mut user_prompt_paths = HashMap::new();
         user_prompt_paths.insert("commit".to_string(), user_commit_prompt_path.clone());
         user_prompt_paths.insert("explanation".to_string(), user_explanation_prompt_path.clone());
 
-        // 如果用户配置已存在，则直接返回路径
-        if user_config_path.exists() && user_commit_prompt_path.exists() && user_explanation_prompt_path.exists() {
-            info!(
-                "User configuration already exists at: {:?}\n User commit-prompt already exists at: {:?}\n User explanation-prompt already exists at: {:?}",
-                user_config_path, user_commit_prompt_path, user_explanation_prompt_path
-            );
-            return Ok((user_config_path, user_prompt_paths));
-        }
+        // 确保配置目录存在
+        create_dir_all(&paths.user_config_dir).map_err(|e| {
+            ConfigError::FileWrite(paths.user_config_dir.to_string_lossy().to_string(), e)
+        })?;
 
-        // 获取配置目录
-        let user_config_dir = match user_config_path.parent() {
-            Some(dir) => dir.to_path_buf(),
-            None => {
-                return Err(ConfigError::FileWrite(
-                    user_config_path.to_string_lossy().to_string(),
-                    io::Error::new(ErrorKind::Other, "Invalid user config path"),
+        // 并发运行的进程不能同时初始化
+        let _lock = FileLock::acquire(&user_config_path)
+            .map_err(|e| ConfigError::FileWrite(user_config_path.to_string_lossy().to_string(), e))?;
+
+        // 仅复制缺失的文件，避免覆盖用户已有的修改
+        let files = [
+            ("Config", &paths.config_template, &user_config_path),
+            ("Commit prompt", &paths.commit_prompt_template, &user_commit_prompt_path),
+            (
+                "Explanation prompt",
+                &paths.explanation_prompt_template,
+                &user_explanation_prompt_path,
+            ),
+        ];
+        for (description, template, target) in files {
+            if target.exists() {
+                continue;
+            }
+            info!("{} file does not exist. Initializing {:?} from {:?}", description, target, template);
+            if !template.exists() {
+                return Err(ConfigError::FileRead(
+                    format!("{} template not found at {}", description, template.display()),
+                    io::Error::new(ErrorKind::NotFound, "Template file not found"),
                 ));
             }
-        };
+            fs::read(template).and_then(|content| write_atomic(target, content)).map_err(|e| {
+                ConfigError::FileWrite(
+                    format!(
+                        "Failed to copy source file {} to target file {}",
+                        template.display(),
+                        target.display()
+                    ),
+                    e,
+                )
+            })?;
+        }
 
-        // 确保配置目录存在
-        create_dir_all(&user_config_dir).map_err(|e| {
-            ConfigError::FileWrite(user_config_dir.to_string_lossy().to_string(), e)
-        })?;
+        Ok((user_config_path, user_prompt_paths))
+    }
 
-        // 初始化配置文件
-        if !user_config_path.exists() {
-            info!("User configuration file does not exist. Initializing...");
-        }
+    /// Loads the configuration from the standard locations (see [`ConfigPaths::from_env`]).
+    pub fn load() -> Result<Self, ConfigError> {
+        Self::load_from(ConfigPaths::from_env())
+    }
 
-        // 检查我们是否在测试环境中
-        let in_test = std::env::current_dir()
-            .map(|p| p.to_string_lossy().contains("target/test_temp_data"))
-            .unwrap_or(false);
-
-        // 获取配置文件源路径
-        let assets_config_path = if in_test {
-            // 在测试环境中，使用测试资源路径
-            let test_dir = std::env::current_dir().unwrap_or_default();
-            // 优先使用环境变量指定的路径
-            if let Ok(path) = std::env::var("GITIE_ASSETS_CONFIG") {
-                PathBuf::from(path)
-            } else {
-                // 否则使用当前目录下的测试资源
-                test_dir.join(CONFIG_EXAMPLE_FILE_NAME)
-            }
-        } else {
-            // 在正常环境中，使用标准资源路径
-            PathBuf::from(
-                std::env::var("GITIE_ASSETS_CONFIG")
-                    .unwrap_or_else(|_| CONFIG_EXAMPLE_FILE_NAME.to_string()),
-            )
-        };
+    /// Loads the configuration like [`AppConfig::load`], with the `gitie.*`
+    /// settings of the repository `dir` is in rather than the current directory's.
+    pub fn load_for_repository(dir: &Path) -> Result<Self, ConfigError> {
+        Self::load_in(ConfigPaths::from_env(), Some(dir))
+    }
 
-        // 获取提示文件源路径
-        let assets_commit_prompt_path = if in_test {
-            // 在测试环境中，使用测试资源路径
-            let test_dir = std::env::current_dir().unwrap_or_default();
-            // 优先使用环境变量指定的路径
-            if let Ok(path) = std::env::var("GITIE_ASSETS_COMMIT_PROMPT") {
-                PathBuf::from(path)
-            } else {
-                // 否则使用当前目录下的测试资源
-                test_dir.join(TEST_ASSETS_COMMIT_PROMPT_FILE_NAME)
-            }
-        } else {
-            // 在正常环境中，使用标准资源路径
-            PathBuf::from(
-                std::env::var("GITIE_ASSETS_COMMIT_PROMPT")
-                    .unwrap_or_else(|_| COMMIT_PROMPT_EXAMPLE_FILE_NAME.to_string()),
-            )
-        };
+    /// Loads the configuration from explicit locations, without consulting `HOME`
+    /// or gitie's environment variables.
+    ///
+    /// With `paths.git_config`, the `gitie.*` settings come from `git config` run
+    /// in the current directory, so they depend on its repository and on git's
+    /// own environment (`GIT_DIR`, `GIT_CONFIG_GLOBAL`, ...). Set it to `false`
+    /// for loading that depends on `paths` alone.
+    pub fn load_from(paths: ConfigPaths) -> Result<Self, ConfigError> {
+        Self::load_in(paths, None)
+    }
 
-        // 获取解释提示文件源路径
-        let assets_explanation_prompt_path = if in_test {
-            // 在测试环境中，使用测试资源路径
-            let test_dir = std::env::current_dir().unwrap_or_default();
-            // 优先使用环境变量指定的路径
-            if let Ok(path) = std::env::var("GITIE_ASSETS_EXPLANATION_PROMPT") {
-                PathBuf::from(path)
-            } else {
-                // 否则使用当前目录下的测试资源
-                test_dir.join(TEST_ASSETS_EXPLANATION_PROMPT_FILE_NAME)
-            }
+    /// `load_from`, with git config read in `dir` (the current directory when `None`)
+    fn load_in(paths: ConfigPaths, dir: Option<&Path>) -> Result<Self, ConfigError> {
+        // 1. 初始化配置
+        let (user_config_path, user_prompt_paths) = Self::initialize_config(&paths)?;
+
+        // 2. 读取 git config 中的 gitie.* 设置
+        let git_settings = if paths.git_config {
+            read_git_config_settings(dir)?
         } else {
-            // 在正常环境中，使用标准资源路径
-            PathBuf::from(
-                std::env::var("GITIE_ASSETS_EXPLANATION_PROMPT")
-                    .unwrap_or_else(|_| EXPLANATION_PROMPT_EXAMPLE_FILE_NAME.to_string()),
-            )
+            Vec::new()
         };
 
-        // 检查源文件是否存在
-        if !assets_config_path.exists() {
-            return Err(ConfigError::FileRead(
-                format!(
-                    "Config template not found at {}",
-                    assets_config_path.display()
-                ),
-                io::Error::new(ErrorKind::NotFound, "Config template file not found"),
-            ));
-        }
-
-        if !assets_commit_prompt_path.exists() {
-            return Err(ConfigError::FileRead(
-                format!(
-                    "Commit prompt template not found at {}",
-                    assets_commit_prompt_path.display()
-                ),
-                io::Error::new(ErrorKind::NotFound, "Commit prompt template file not found"),
-            ));
-        }
+        // 3. 读取系统策略文件
+        let policy = Policy::load_from(&paths.policy_file)?;
 
-        if !assets_explanation_prompt_path.exists() {
-            return Err(ConfigError::FileRead(
-                format!(
-                    "Explanation prompt template not found at {}",
-                    assets_explanation_prompt_path.display()
-                ),
-                io::Error::new(ErrorKind::NotFound, "Explanation prompt template file not found"),
-            ));
-        }
+        // 4. 从用户目录加载配置
+        info!(
+            "Loading configuration from user directory: {:?}",
+            user_config_path
+        );
+        let mut config =
+            Self::load_config_from_file(&user_config_path, &user_prompt_paths, &git_settings, policy)?;
 
-        // 复制配置文件
-        fs::copy(&assets_config_path, &user_config_path).map_err(|e| {
-            ConfigError::FileWrite(
-                format!(
-                    "Failed to copy source config file {} to target config file {}",
-                    assets_config_path.display(),
-                    user_config_path.display()
-                ),
-                e,
-            )
-        })?;
+        // 5. 加载术语表（用户文件可选）
+        config.glossary = Glossary::load(&paths.user_file(USER_GLOSSARY_FILE_NAME))?;
+        Ok(config)
+    }
 
-        // 复制提示文件
-        fs::copy(&assets_commit_prompt_path, &user_commit_prompt_path).map_err(|e| {
-            ConfigError::FileWrite(
-                format!(
-                    "Failed to copy source commit prompt file {} to target prompt file {}",
-                    assets_commit_prompt_path.display(),
-                    user_commit_prompt_path.display()
-                ),
-                e,
-            )
-        })?;
+    /// Returns the system prompt for a task (`"commit"` or `"explanation"`),
+    /// honoring a command-line override if one was given, with its placeholders
+    /// filled in (see `prompt_template`).
+    pub fn system_prompt(&self, task: &str) -> String {
+        let Some(template) = self.prompt_override.as_ref().or_else(|| self.prompts.get(task)) else {
+            warn!("{} prompt not found in config, using empty string", task);
+            return String::new();
+        };
+        let language = match task {
+            "commit" => &self.commit.language,
+            _ => &self.explain.language,
+        };
+        template.render(|name| match name {
+            "language" => language.clone().unwrap_or_else(|| "English".to_string()),
+            "level" => self.explain.level.name().to_string(),
+            _ => String::new(),
+        })
+    }
 
-        // 复制解释提示文件
-        fs::copy(&assets_explanation_prompt_path, &user_explanation_prompt_path).map_err(|e| {
-            ConfigError::FileWrite(
-                format!(
-                    "Failed to copy source explanation prompt file {} to target prompt file {}",
-                    assets_explanation_prompt_path.display(),
-                    user_explanation_prompt_path.display()
-                ),
-                e,
-            )
-        })?;
+    /// Resolves model, temperature and token limit for an AI task
+    /// (`"commit"`, `"explain"` or `"review"`), falling back to the `[ai]` defaults.
+    pub fn model_parameters(&self, task: &str) -> ModelParameters {
+        let overrides = self.task_overrides(task);
+        let requested = self
+            .model_override
+            .as_ref()
+            .or(overrides.model.as_ref())
+            .unwrap_or(&self.ai.model_name);
+        let (model, alias_url) = match self.ai.aliases.get(requested) {
+            Some(alias) => (alias.model.clone(), alias.api_url.clone()),
+            None => (requested.clone(), None),
+        };
+        let api_url = alias_url
+            .or_else(|| {
+                self.ai
+                    .endpoints
+                    .iter()
+                    .find(|endpoint| endpoint.models.contains(&model))
+                    .map(|endpoint| endpoint.url.clone())
+            })
+            .unwrap_or_else(|| self.ai.api_url.clone());
+        ModelParameters {
+            model,
+            api_url,
+            temperature: self
+                .temperature_override
+                .or(overrides.temperature)
+                .unwrap_or(self.ai.temperature),
+            max_tokens: overrides.max_tokens,
+        }
+    }
 
-        Ok((user_config_path, user_prompt_paths))
+    /// The command the output of an AI task is piped through, if one is set
+    pub fn post_generate_cmd(&self, task: &str) -> Option<&str> {
+        self.task_overrides(task).post_generate_cmd.as_deref()
     }
 
-    pub fn load() -> Result<Self, ConfigError> {
-        // 1. 初始化配置
-        let (user_config_path, user_prompt_paths) = Self::initialize_config()?;
+    fn task_overrides(&self, task: &str) -> &TaskOverrides {
+        static NONE: TaskOverrides =
+            TaskOverrides { model: None, temperature: None, max_tokens: None, post_generate_cmd: None };
+        match task {
+            "commit" => &self.commit.overrides,
+            "explain" => &self.explain.overrides,
+            "review" => &self.review.overrides,
+            _ => &NONE,
+        }
+    }
 
-        // 2. 从用户目录加载配置
-        info!(
-            "Loading configuration from user directory: {:?}",
-            user_config_path
-        );
-        Self::load_config_from_file(&user_config_path, &user_prompt_paths)
+    /// Keys and tokens set in the configuration, for redacting them from text
+    pub fn configured_secrets(&self) -> Vec<String> {
+        let endpoint_keys = self.ai.endpoints.iter().map(|endpoint| &endpoint.key);
+        [&self.ai.api_key, &self.github.token, &self.gitea.token, &self.issues.token]
+            .into_iter()
+            .chain(endpoint_keys)
+            .flatten()
+            .filter(|secret| !secret.is_empty())
+            .cloned()
+            .collect()
     }
 
-    // 获取用户目录中指定文件的路径
-    fn get_user_file_path(filename: &str) -> Result<std::path::PathBuf, ConfigError> {
-        // Use the environment variable HOME set during test setup
-        let home_str = std::env::var("HOME").unwrap_or_else(|_| {
-            // Fallback to real home directory if env var not set
-            home_dir()
-                .expect("Could not determine home directory")
-                .to_string_lossy()
-                .to_string()
-        });
+    /// Lists every effective setting as `(dotted key, value)` pairs, with API keys masked.
+    ///
+    /// Task sections show the fully resolved model parameters, so aliases, endpoint
+    /// routing and `[ai]` fallbacks are visible as they would be used.
+    pub fn resolved_settings(&self) -> Vec<(String, String)> {
+        let mut settings = vec![
+            ("ai.api_url".to_string(), self.ai.api_url.clone()),
+            ("ai.model_name".to_string(), self.ai.model_name.clone()),
+            ("ai.temperature".to_string(), self.ai.temperature.to_string()),
+            ("ai.api_key".to_string(), mask_secret(self.ai.api_key_for(&self.ai.api_url))),
+            ("ai.max_concurrency".to_string(), self.ai.max_concurrency.to_string()),
+            ("ai.retry.max_attempts".to_string(), self.ai.retry.max_attempts.to_string()),
+            ("ai.retry.base_delay_ms".to_string(), self.ai.retry.base_delay_ms.to_string()),
+            ("ai.retry.retry_on".to_string(), self.ai.retry.retry_on.join(", ")),
+            ("ai.retry.json_repairs".to_string(), self.ai.retry.json_repairs.to_string()),
+            ("ai.timeouts.connect".to_string(), self.ai.timeouts.connect.to_string()),
+            ("ai.timeouts.total".to_string(), self.ai.timeouts.total.to_string()),
+            ("ai.timeouts.stream_idle".to_string(), self.ai.timeouts.stream_idle.to_string()),
+        ];
+
+        let mut aliases: Vec<_> = self.ai.aliases.iter().collect();
+        aliases.sort_by(|a, b| a.0.cmp(b.0));
+        for (name, alias) in aliases {
+            let target = match &alias.api_url {
+                Some(url) => format!("{} @ {}", alias.model, url),
+                None => alias.model.clone(),
+            };
+            settings.push((format!("ai.aliases.{}", name), target));
+        }
+        for (index, endpoint) in self.ai.endpoints.iter().enumerate() {
+            settings.push((format!("ai.endpoints.{}.url", index), endpoint.url.clone()));
+            settings.push((format!("ai.endpoints.{}.key", index), mask_secret(endpoint.key.as_deref())));
+            settings.push((format!("ai.endpoints.{}.models", index), endpoint.models.join(", ")));
+        }
+
+        let unset = |value: &Option<String>| value.clone().unwrap_or_else(|| "(unset)".to_string());
+        for task in ["commit", "explain", "review"] {
+            match task {
+                "commit" => {
+                    settings.push(("commit.language".to_string(), unset(&self.commit.language)));
+                    settings.push((
+                        "commit.diff_context_lines".to_string(),
+                        self.commit
+                            .diff_context_lines
+                            .map_or_else(|| "(git default)".to_string(), |n| n.to_string()),
+                    ));
+                    settings.push((
+                        "commit.include_submodule_log".to_string(),
+                        self.commit.include_submodule_log.to_string(),
+                    ));
+                    settings.push((
+                        "commit.provenance_trailer".to_string(),
+                        self.commit.provenance_trailer.to_string(),
+                    ));
+                    settings.push((
+                        "commit.max_file_diff_bytes".to_string(),
+                        self.commit.max_file_diff_bytes().to_string(),
+                    ));
+                    settings.push((
+                        "commit.large_file_bytes".to_string(),
+                        self.commit.large_file_bytes().to_string(),
+                    ));
+                    settings.push((
+                        "commit.detect_renames".to_string(),
+                        format!("{:?}", self.commit.detect_renames).to_lowercase(),
+                    ));
+                }
+                "explain" => {
+                    settings.push(("explain.language".to_string(), unset(&self.explain.language)));
+                    settings.push((
+                        "explain.level".to_string(),
+                        format!("{:?}", self.explain.level).to_lowercase(),
+                    ));
+                    settings.push(("explain.glossary".to_string(), self.explain.glossary.to_string()));
+                    settings.push(("explain.teach_mode".to_string(), self.explain.teach_mode.to_string()));
+                    settings.push((
+                        "explain.teach_interval_minutes".to_string(),
+                        self.explain.teach_interval_minutes().to_string(),
+                    ));
+                }
+                _ => {}
+            }
+            let parameters = self.model_parameters(task);
+            settings.push((format!("{}.model", task), parameters.model));
+            settings.push((format!("{}.api_url", task), parameters.api_url));
+            settings.push((format!("{}.temperature", task), parameters.temperature.to_string()));
+            settings.push((
+                format!("{}.max_tokens", task),
+                parameters.max_tokens.map_or_else(|| "(model default)".to_string(), |n| n.to_string()),
+            ));
+            settings.push((
+                format!("{}.post_generate_cmd", task),
+                self.post_generate_cmd(task).unwrap_or("(unset)").to_string(),
+            ));
+        }
 
-        let home = PathBuf::from(home_str);
-        Ok(home.join(USER_CONFIG_DIR).join(filename))
+        settings.push(("output.format".to_string(), format!("{:?}", self.output.format).to_lowercase()));
+        settings.push(("output.color".to_string(), format!("{:?}", self.output.color).to_lowercase()));
+        settings.push(("output.pager".to_string(), self.output.pager.to_string()));
+        settings.push(("notify.enabled".to_string(), self.notify.enabled.to_string()));
+        settings.push(("notify.after_seconds".to_string(), self.notify.after_seconds.to_string()));
+        settings.push(("guard.enabled".to_string(), self.guard.enabled.to_string()));
+        settings.push(("guard.allow".to_string(), self.guard.allow.join(", ")));
+        settings.push(("guard.shared_branches".to_string(), self.guard.shared_branches.join(", ")));
+        settings.push(("search.embedding_model".to_string(), self.search.embedding_model.clone()));
+        settings.push(("search.api_url".to_string(), self.search.embeddings_url(&self.ai)));
+        settings.push(("github.api_url".to_string(), self.github.api_url.clone()));
+        settings.push(("github.token".to_string(), mask_secret(self.github.token.as_deref())));
+        settings.push((
+            "gitea.api_url".to_string(),
+            self.gitea.api_url.clone().unwrap_or_else(|| "(from the remote's host)".to_string()),
+        ));
+        settings.push(("gitea.token".to_string(), mask_secret(self.gitea.token.as_deref())));
+        settings.push(("forge.kind".to_string(), self.forge.kind.name().to_string()));
+        settings.push(("issues.tracker".to_string(), self.issues.tracker.name().to_string()));
+        settings.push(("issues.jira_url".to_string(), unset(&self.issues.jira_url)));
+        settings.push(("issues.jira_email".to_string(), unset(&self.issues.jira_email)));
+        settings.push(("issues.jira_projects".to_string(), self.issues.jira_projects.join(", ")));
+        settings.push(("issues.token".to_string(), mask_secret(self.issues.token.as_deref())));
+        settings.push(("history.enabled".to_string(), self.history.enabled.to_string()));
+        settings.push(("stats.enabled".to_string(), self.stats.enabled.to_string()));
+        settings.push(("hooks.pre_ai".to_string(), unset(&self.hooks.pre_ai)));
+        settings.push(("hooks.post_ai".to_string(), unset(&self.hooks.post_ai)));
+        for (name, value) in &self.aliases {
+            settings.push((format!("aliases.{}", name), value.clone()));
+        }
+        settings
     }
 
-    // 以下函数被移除，直接使用 get_user_file_path 函数代替
-    // - get_user_config_path
-    // - get_user_prompt_path
+    /// Returns the path of the user configuration file, creating it from the
+    /// bundled template first if it does not exist yet.
+    pub fn ensure_user_config_path() -> Result<PathBuf, ConfigError> {
+        let (user_config_path, _) = Self::initialize_config(&ConfigPaths::from_env())?;
+        Ok(user_config_path)
+    }
 
     // 从指定文件加载配置
-    fn load_config_from_file(config_path: &Path, prompt_paths: &HashMap<String, PathBuf>) -> Result<Self, ConfigError> {
+    fn load_config_from_file(
+        config_path: &Path,
+        prompt_paths: &HashMap<String, PathBuf>,
+        git_settings: &[(String, String)],
+        policy: Policy,
+    ) -> Result<Self, ConfigError> {
         // 读取配置文件
         let config_content = fs::read_to_string(config_path)
             .map_err(|e| ConfigError::FileRead(config_path.to_string_lossy().to_string(), e))?;
 
         // 解析TOML
-        let mut partial_config: PartialAppConfig = toml::from_str(&config_content)
+        let mut raw_config: toml::Value = toml::from_str(&config_content)
+            .map_err(|e| ConfigError::TomlParse(config_path.to_string_lossy().to_string(), e))?;
+
+        // git config 中的 gitie.* 设置优先于配置文件，其问题按 git config 报告
+        let git_validation = apply_git_config_settings(&mut raw_config, git_settings);
+        for warning in &git_validation.warnings {
+            ui::warning(warning);
+        }
+
+        // 策略文件锁定的配置项优先级最高，用户无法覆盖
+        let mut validation = ConfigValidation {
+            warnings: apply_policy_locks(&mut raw_config, &policy.locked),
+            errors: Vec::new(),
+        };
+
+        // 校验配置项：未知键仅警告，非法值一次性全部报告
+        let file_validation = validate_config_value(&raw_config);
+        validation.warnings.extend(file_validation.warnings);
+        validation.errors.extend(file_validation.errors);
+        for warning in &validation.warnings {
+            ui::warning(&format!("{}: {}", config_path.display(), warning));
+        }
+        let origin = match (validation.errors.is_empty(), git_validation.errors.is_empty()) {
+            (true, true) => None,
+            (false, true) => Some(config_path.to_string_lossy().to_string()),
+            (true, false) => Some("git config".to_string()),
+            (false, false) => Some(format!("{} and git config", config_path.display())),
+        };
+        if let Some(origin) = origin {
+            validation.errors.extend(git_validation.errors);
+            return Err(ConfigError::Invalid(origin, validation.errors));
+        }
+
+        let mut partial_config: PartialAppConfig = raw_config
+            .try_into()
             .map_err(|e| ConfigError::TomlParse(config_path.to_string_lossy().to_string(), e))?;
 
         // 处理API密钥占位符
-        if let Some(ai) = &mut partial_config.ai {
-            if let Some(api_key) = &ai.api_key {
-                if api_key == "YOUR_API_KEY_IF_NEEDED" || api_key.is_empty() {
-                    ai.api_key = None;
-                    info!("API key placeholder or empty string found. Treating as no API key.");
-                }
-            }
+        if let Some(ai) = &mut partial_config.ai
+            && let Some(api_key) = &ai.api_key
+            && (api_key == "YOUR_API_KEY_IF_NEEDED" || api_key.is_empty())
+        {
+            ai.api_key = None;
+            info!("API key placeholder or empty string found. Treating as no API key.");
         }
 
         // 确保ai部分存在
@@ -272,15 +1369,32 @@ impl AppConfig {
             partial_config.ai = Some(PartialAIConfig::default());
         }
 
-        // 加载所有提示文件
+        // 加载并编译所有提示文件，占位符错误在此一次性报告
         let mut prompts = HashMap::new();
-        
+
         for (prompt_type, prompt_path) in prompt_paths {
             let prompt_content = fs::read_to_string(prompt_path)
                 .map_err(|e| ConfigError::FileRead(prompt_path.to_string_lossy().to_string(), e))?;
-            prompts.insert(prompt_type.clone(), prompt_content);
+            let template = PromptTemplate::compile(&prompt_content, &prompt_template::variables_for(Some(prompt_type)))
+                .map_err(|problems| ConfigError::Invalid(prompt_path.to_string_lossy().to_string(), problems))?;
+            prompts.insert(prompt_type.clone(), template);
         }
 
+        Ok(Self::from_partial(partial_config, prompts, policy))
+    }
+
+    /// Defaults with AI features turned off.
+    ///
+    /// Used when the configuration can't be loaded but the command only passes
+    /// through to git. AI stays off because the policy file may not have been
+    /// read either.
+    pub fn without_ai() -> Self {
+        let mut policy = Policy::default();
+        policy.ai.enabled = false;
+        Self::from_partial(PartialAppConfig::default(), HashMap::new(), policy)
+    }
+
+    fn from_partial(partial_config: PartialAppConfig, prompts: HashMap<String, PromptTemplate>, policy: Policy) -> Self {
         // 验证并处理AI配置
         let partial_ai_config = partial_config.ai.unwrap_or_default();
 
@@ -299,13 +1413,129 @@ impl AppConfig {
             model_name,
             temperature,
             api_key: partial_ai_config.api_key,
+            max_concurrency: partial_ai_config.max_concurrency.unwrap_or_else(default_max_concurrency),
+            retry: partial_ai_config.retry,
+            timeouts: partial_ai_config.timeouts,
+            aliases: partial_ai_config.aliases,
+            endpoints: partial_ai_config.endpoints,
         };
 
-        Ok(AppConfig {
-            ai: ai_config,
-            prompts,
-        })
+        AppConfig {
+            ai: ai_config,
+            commit: partial_config.commit,
+            explain: partial_config.explain,
+            review: partial_config.review,
+            output: partial_config.output,
+            notify: partial_config.notify,
+            guard: partial_config.guard,
+            search: partial_config.search,
+            github: partial_config.github,
+            gitea: partial_config.gitea,
+            forge: partial_config.forge,
+            issues: partial_config.issues,
+            history: partial_config.history,
+            stats: partial_config.stats,
+            hooks: partial_config.hooks,
+            aliases: partial_config.aliases,
+            prompts,
+            glossary: Glossary::default(),
+            prompt_override: None,
+            model_override: None,
+            temperature_override: None,
+            scope: None,
+            context: Vec::new(),
+            policy,
+        }
+    }
+}
+
+/// Validates the full text of a config file, returning the warnings on success.
+pub fn validate_config_text(path: &Path, content: &str) -> Result<Vec<String>, ConfigError> {
+    let raw: toml::Value = toml::from_str(content)
+        .map_err(|e| ConfigError::TomlParse(path.to_string_lossy().to_string(), e))?;
+    let validation = validate_config_value(&raw);
+    if !validation.errors.is_empty() {
+        return Err(ConfigError::Invalid(
+            path.to_string_lossy().to_string(),
+            validation.errors,
+        ));
+    }
+    Ok(validation.warnings)
+}
+
+/// Sets a single dotted key (e.g. `ai.temperature`) in the config file at `path`.
+///
+/// The document is edited in place with `toml_edit`, so comments, ordering and
+/// formatting of everything else are preserved. `raw_value` is interpreted as a
+/// TOML literal when possible (`0.5`, `true`, `"text"`) and as a plain string otherwise.
+/// The file is only written if the resulting configuration passes validation.
+pub fn set_config_value(path: &Path, key: &str, raw_value: &str) -> Result<(), ConfigError> {
+    let path_str = path.to_string_lossy().to_string();
+    let kind = match lookup_schema_kind(key) {
+        Some(ConfigValueKind::Table | ConfigValueKind::TableList) => {
+            return Err(ConfigError::Invalid(
+                path_str,
+                vec![format!("'{}' is a section, not a value", key)],
+            ));
+        }
+        None => {
+            let mut problem = format!("unknown configuration key '{}'", key);
+            if let Some(suggestion) = suggest_config_key(key) {
+                problem.push_str(&format!(" (did you mean '{}'?)", suggestion));
+            }
+            return Err(ConfigError::Invalid(path_str, vec![problem]));
+        }
+        Some(kind) => kind,
+    };
+
+    // Another process changing the file in between would lose one of the changes
+    let _lock = FileLock::acquire(path).map_err(|e| ConfigError::FileWrite(path_str.clone(), e))?;
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(ConfigError::FileRead(path_str, e)),
+    };
+    let mut document: toml_edit::DocumentMut = content.parse().map_err(|e| {
+        ConfigError::Invalid(path_str.clone(), vec![format!("not valid TOML: {}", e)])
+    })?;
+
+    let parsed = raw_value.parse::<toml_edit::Value>().ok();
+    let value = match kind {
+        // Unquoted input for string keys is always taken literally
+        ConfigValueKind::String => parsed.filter(|v| v.is_str()),
+        _ => parsed,
+    }
+    .unwrap_or_else(|| toml_edit::Value::from(raw_value));
+
+    let segments: Vec<&str> = key.split('.').collect();
+    let (last, sections) = segments.split_last().expect("split always yields a segment");
+    let mut table = document.as_table_mut();
+    for section in sections {
+        let entry = table
+            .entry(section)
+            .or_insert_with(|| toml_edit::Item::Table(toml_edit::Table::new()));
+        table = entry.as_table_mut().ok_or_else(|| {
+            ConfigError::Invalid(
+                path_str.clone(),
+                vec![format!("'{}' must be a table", section)],
+            )
+        })?;
+    }
+    match table.get_mut(last).and_then(|item| item.as_value_mut()) {
+        Some(existing) => {
+            // Keep surrounding whitespace and trailing comments of the old value
+            let decor = existing.decor().clone();
+            *existing = value;
+            *existing.decor_mut() = decor;
+        }
+        None => table[*last] = toml_edit::value(value),
+    }
+
+    let updated = document.to_string();
+    for warning in validate_config_text(path, &updated)? {
+        warn!("{}: {}", path.display(), warning);
     }
+    write_atomic(path, updated).map_err(|e| ConfigError::FileWrite(path_str, e))
 }
 
 // AI配置的部分加载辅助结构体
@@ -319,762 +1549,755 @@ struct PartialAIConfig {
     temperature: Option<f32>,
     #[serde(default)]
     api_key: Option<String>,
+    #[serde(default)]
+    max_concurrency: Option<usize>,
+    #[serde(default)]
+    retry: RetryConfig,
+    #[serde(default)]
+    timeouts: TimeoutConfig,
+    #[serde(default)]
+    aliases: HashMap<String, ModelAlias>,
+    #[serde(default)]
+    endpoints: Vec<EndpointConfig>,
 }
 
 // 部分加载的配置辅助结构体
 #[derive(Deserialize, Debug, Default)]
 struct PartialAppConfig {
     ai: Option<PartialAIConfig>,
+    #[serde(default)]
+    commit: CommitConfig,
+    #[serde(default)]
+    explain: ExplainConfig,
+    #[serde(default)]
+    review: ReviewConfig,
+    #[serde(default)]
+    output: OutputConfig,
+    #[serde(default)]
+    notify: NotifyConfig,
+    #[serde(default)]
+    guard: GuardConfig,
+    #[serde(default)]
+    search: SearchConfig,
+    #[serde(default)]
+    github: GitHubConfig,
+    #[serde(default)]
+    gitea: GiteaConfig,
+    #[serde(default)]
+    forge: ForgeConfig,
+    #[serde(default)]
+    issues: IssuesConfig,
+    #[serde(default)]
+    history: HistoryConfig,
+    #[serde(default)]
+    stats: StatsConfig,
+    #[serde(default)]
+    hooks: HooksConfig,
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::errors::ConfigError; // Import ConfigError for matching
-    use std::fs::{self, File};
-    use std::io::Write;
+    use std::fs;
     use std::path::PathBuf;
-    use std::sync::Mutex;
-
-    // Mutex to ensure tests changing current directory run serially
-    // This is crucial because AppConfig::load() relies on relative paths from the current directory.
-    static TEST_MUTEX: Mutex<()> = Mutex::new(());
-
-    fn setup_test_environment(
-        test_name: &str,
-        config_content: Option<&str>,
-        prompt_content: Option<&str>,
-        create_prompts_dir: bool,
-        create_example_config: bool,
-        create_assets_dir: bool, // For testing assets directory fallback
-    ) -> PathBuf {
-        let base_path = PathBuf::from(format!("target/test_temp_data/{}", test_name));
-        if base_path.exists() {
-            fs::remove_dir_all(&base_path).expect("Failed to remove test directory during setup");
-        }
-
-        // Create project root directory
-        fs::create_dir_all(&base_path).expect("Failed to create base directory during setup");
-
-        // Create a mock home directory with .config/gitie structure
-        let mock_home = base_path.join("mock_home");
-        let mock_config_dir = mock_home.join(USER_CONFIG_DIR);
-        fs::create_dir_all(&mock_config_dir)
-            .expect("Failed to create mock config directory during setup");
-
-        // Patch the configuration loading function for testing
-        // This is crucial as it ensures the initialize_config function finds the right path
-        unsafe {
-            std::env::set_var("HOME", mock_home.to_str().unwrap());
-
-            // Make sure we don't have any test-related env vars
-            std::env::remove_var("GITIE_ASSETS_CONFIG");
-            std::env::remove_var("GITIE_ASSETS_PROMPT");
-        };
-
-        // Create default assets directory structure for tests
-        let assets_dir = base_path.join("assets");
-        fs::create_dir_all(&assets_dir).expect("Failed to create assets directory during setup");
-
-        if create_prompts_dir {
-            fs::create_dir_all(base_path.join("prompts"))
-                .expect("Failed to create prompts directory during setup");
-        }
-
-        // Create assets directory if requested (always create basic assets for tests)
-        // Create assets/config.example.toml
-        let assets_config_path = base_path.join(CONFIG_EXAMPLE_FILE_NAME);
-        // Ensure directory exists
-        fs::create_dir_all(assets_config_path.parent().unwrap())
-            .expect("Failed to create assets directory");
-        let assets_content = r#"[ai]
-api_url = "http://assets.example.com/api"
-model_name = "assets-model"
-temperature = 0.5
-api_key = "YOUR_API_KEY_IF_NEEDED"
-"#;
-        let mut file =
-            File::create(assets_config_path).expect("Failed to create assets config.example.toml");
-        file.write_all(assets_content.as_bytes())
-            .expect("Failed to write to assets config.example.toml");
-
-        // Create assets/commit-prompt
-        let assets_prompt_path = base_path.join(COMMIT_PROMPT_EXAMPLE_FILE_NAME);
-        let assets_prompt = "Assets prompt content";
-        let mut file =
-            File::create(assets_prompt_path).expect("Failed to create assets commit-prompt");
-        file.write_all(assets_prompt.as_bytes())
-            .expect("Failed to write to assets commit-prompt");
-
-        if create_assets_dir {
-            // Create additional test assets if needed
-            // Create test_assets directory for better test control
-            fs::create_dir_all(base_path.join("test_assets"))
-                .expect("Failed to create test_assets directory");
-
-            // Create test_assets/config.example.toml
-            let test_assets_config_path = base_path.join(TEST_ASSETS_CONFIG_EXAMPLE_FILE_NAME);
-            let test_assets_content = r#"[ai]
-api_url = "http://test.assets.example.com/api"
-model_name = "test-assets-model"
-temperature = 0.6
-api_key = "TEST_ASSETS_KEY"
-"#;
-            let mut file =
-                File::create(test_assets_config_path).expect("Failed to create test_assets config");
-            file.write_all(test_assets_content.as_bytes())
-                .expect("Failed to write to test_assets config");
-
-            // Create test_assets/commit-prompt
-            let test_assets_prompt_path = base_path.join(TEST_ASSETS_COMMIT_PROMPT_FILE_NAME);
-            let test_assets_prompt = "Test assets prompt content";
-            let mut file =
-                File::create(test_assets_prompt_path).expect("Failed to create test_assets prompt");
-            file.write_all(test_assets_prompt.as_bytes())
-                .expect("Failed to write to test_assets prompt");
-        }
 
-        // Add this block to create config.example.toml in project directory
-        if create_example_config {
-            // Create in both project and assets directory
-            let example_config_path = base_path.join("config.example.toml");
-            let assets_config_path = base_path.join(CONFIG_EXAMPLE_FILE_NAME);
-
-            // Ensure assets directory exists
-            if let Some(parent) = assets_config_path.parent() {
-                fs::create_dir_all(parent).expect("Failed to create assets directory during setup");
-            }
-
-            // Hardcode the example config content here for tests
-            let example_content = r#"[ai]
+    const TEMPLATE_CONFIG: &str = r#"[ai]
 api_url = "http://localhost:11434/v1/chat/completions"
 model_name = "qwen3:32b-q8_0"
 temperature = 0.7
 api_key = "YOUR_API_KEY_IF_NEEDED"
 "#;
-            let mut file = File::create(example_config_path)
-                .expect("Failed to create config.example.toml during setup");
-            file.write_all(example_content.as_bytes())
-                .expect("Failed to write to config.example.toml during setup");
-
-            let mut file = File::create(assets_config_path)
-                .expect("Failed to create assets/config.example.toml during setup");
-            file.write_all(example_content.as_bytes())
-                .expect("Failed to write to assets/config.example.toml during setup");
-        }
 
-        // Create project config.toml if content is provided
-        if let Some(content) = config_content {
-            let mut file = File::create(base_path.join("config.toml"))
-                .expect("Failed to create config.toml during setup");
-            file.write_all(content.as_bytes())
-                .expect("Failed to write to config.toml during setup");
+    /// Creates a scratch directory with templates and an empty user config directory,
+    /// returning paths that keep loading isolated from the real environment.
+    fn setup_test_paths(test_name: &str) -> ConfigPaths {
+        let base_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target/test_temp_data/config")
+            .join(test_name);
+        if base_path.exists() {
+            fs::remove_dir_all(&base_path).expect("Failed to remove test directory during setup");
         }
-
-        if let Some(content) = prompt_content {
-            // "prompts/commit-prompt" includes "prompts/" prefix
-            let prompt_path = base_path.join("prompts/commit-prompt");
-            // Ensure the prompts directory exists before creating the prompt file
-            fs::create_dir_all(
-                prompt_path
-                    .parent()
-                    .expect("Failed to get prompt file parent directory"),
-            )
-            .expect("Failed to create prompts directory during setup");
-            let mut file =
-                File::create(prompt_path).expect("Failed to create prompt file during setup");
-            file.write_all(content.as_bytes())
-                .expect("Failed to write to prompt file during setup");
-
-            // Also create assets commit-prompt file
-            let assets_prompt_path = base_path.join(COMMIT_PROMPT_EXAMPLE_FILE_NAME);
-            if let Some(parent) = assets_prompt_path.parent() {
-                fs::create_dir_all(parent).expect("Failed to create assets directory during setup");
-            }
-            let mut file = File::create(assets_prompt_path)
-                .expect("Failed to create assets commit-prompt file during setup");
-            file.write_all(content.as_bytes())
-                .expect("Failed to write to assets commit-prompt during setup");
+        let assets_dir = base_path.join("assets");
+        fs::create_dir_all(&assets_dir).expect("Failed to create assets directory during setup");
+        fs::write(assets_dir.join("config.example.toml"), TEMPLATE_CONFIG)
+            .expect("Failed to write config template");
+        fs::write(assets_dir.join("commit-prompt"), "Template commit prompt")
+            .expect("Failed to write commit prompt template");
+        fs::write(assets_dir.join("explanation-prompt"), "Template explanation prompt")
+            .expect("Failed to write explanation prompt template");
+
+        ConfigPaths {
+            user_config_dir: base_path.join("home").join(USER_CONFIG_DIR),
+            config_template: assets_dir.join("config.example.toml"),
+            commit_prompt_template: assets_dir.join("commit-prompt"),
+            explanation_prompt_template: assets_dir.join("explanation-prompt"),
+            policy_file: base_path.join("policy.toml"),
+            git_config: false,
+            config_file: None,
         }
-        base_path
     }
 
-    fn cleanup_test_environment(base_path: PathBuf) {
-        if base_path.exists() {
-            fs::remove_dir_all(&base_path).expect("Failed to clean up test directory");
+    /// Writes a user config file (and optionally a commit prompt) before loading.
+    fn write_user_files(paths: &ConfigPaths, config: &str, commit_prompt: Option<&str>) {
+        fs::create_dir_all(&paths.user_config_dir).expect("Failed to create user config directory");
+        fs::write(paths.user_file(USER_CONFIG_FILE_NAME), config)
+            .expect("Failed to write user config");
+        if let Some(prompt) = commit_prompt {
+            fs::write(paths.user_file(USER_COMMIT_PROMPT_FILE_NAME), prompt)
+                .expect("Failed to write user commit prompt");
         }
-        // Reset the HOME environment variable after test cleanup
-        unsafe {
-            // Only remove if test didn't panic
-            if std::env::var("HOME").is_ok() {
-                std::env::remove_var("HOME");
-            }
-        };
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes"]
     fn test_load_full_config() {
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_full_config";
+        let paths = setup_test_paths("test_load_full_config");
         let config_toml = r#"[ai]
 api_url = "http://custom.host/api"
 model_name = "custom-model"
 temperature = 0.5
 api_key = "test_key_123"
 "#;
-        let prompt_text = "Test system prompt";
-        // Setup with project config
-        let base_path = setup_test_environment(
-            test_name,
-            Some(config_toml),
-            Some(prompt_text),
-            true,
-            true,
-            true,
-        );
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        // The config will be copied to user directory during load
-        let config_result = AppConfig::load();
-        assert!(
-            config_result.is_ok(),
-            "Expected OK, got {:?}",
-            config_result.err()
-        );
-        let config = config_result.unwrap();
+        write_user_files(&paths, config_toml, Some("Test system prompt"));
+
+        let config = AppConfig::load_from(paths.clone()).expect("config should load");
 
-        // Verify the config values
         assert_eq!(config.ai.api_url, "http://custom.host/api");
         assert_eq!(config.ai.model_name, "custom-model");
         assert_eq!(config.ai.temperature, 0.5);
         assert_eq!(config.ai.api_key, Some("test_key_123".to_string()));
-        assert_eq!(config.prompts.get("commit").unwrap(), &prompt_text);
-
-        // Verify the config was copied to user directory
-        let mock_user_config = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_CONFIG_FILE_NAME);
-        let mock_user_prompt = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_COMMIT_PROMPT_FILE_NAME);
-        assert!(
-            mock_user_config.exists(),
-            "Config should be copied to user directory"
-        );
-        assert!(
-            mock_user_prompt.exists(),
-            "Prompt should be copied to user directory"
-        );
-
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+        assert_eq!(config.system_prompt("commit"), "Test system prompt");
+        // The missing explanation prompt is filled in from its template
+        assert_eq!(config.system_prompt("explanation"), "Template explanation prompt");
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes"]
     fn test_load_partial_config_missing_temp_and_key() {
-        // Directly lock the mutex to prevent PoisonError issues
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_partial_config_missing_temp_and_key";
+        let paths = setup_test_paths("test_load_partial_config_missing_temp_and_key");
         let config_toml = r#"[ai]
 api_url = "http://partial.host/api"
 model_name = "partial-model"
-"#; // Missing temperature and api_key
-        let prompt_text = "Partial config prompt";
-        // Needs example config for defaults
-        let base_path = setup_test_environment(
-            test_name,
-            Some(config_toml),
-            Some(prompt_text),
-            true,
-            true,
-            true,
-        );
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        let config_result = AppConfig::load();
-        assert!(
-            config_result.is_ok(),
-            "Expected OK, got {:?}",
-            config_result.err()
-        );
-        let config = config_result.unwrap();
+"#;
+        write_user_files(&paths, config_toml, None);
+
+        let config = AppConfig::load_from(paths).expect("config should load");
 
-        // These values should match the project config
         assert_eq!(config.ai.api_url, "http://partial.host/api");
         assert_eq!(config.ai.model_name, "partial-model");
-        // These should have default values
-        assert_eq!(config.ai.temperature, 0.7); // Default from example
-        assert_eq!(config.ai.api_key, None); // Should be None (not specified)
-        assert_eq!(config.prompts.get("commit").unwrap(), &prompt_text);
-
-        // Verify files were copied to user directory
-        let mock_user_config = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_CONFIG_FILE_NAME);
-        let mock_user_prompt = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_COMMIT_PROMPT_FILE_NAME);
-        assert!(
-            mock_user_config.exists(),
-            "Config should be copied to user directory"
-        );
-        assert!(
-            mock_user_prompt.exists(),
-            "Prompt should be copied to user directory"
-        );
-
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+        assert_eq!(config.ai.temperature, 0.7);
+        assert_eq!(config.ai.api_key, None);
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes with prompts HashMap"]
     fn test_load_partial_config_empty_toml() {
-        // Directly lock the mutex to prevent PoisonError issues
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_partial_config_empty_toml";
-        // Use minimal valid config instead of empty TOML
-        let config_toml = r#"[ai]
-api_url = "http://localhost:11434/v1/chat/completions"
-model_name = "qwen3:32b-q8_0"
-"#;
-        let prompt_text = "Empty TOML config prompt";
-        // Needs example config for defaults
-        let base_path = setup_test_environment(
-            test_name,
-            Some(config_toml),
-            Some(prompt_text),
-            true,
-            true,
-            true,
-        );
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        let config_result = AppConfig::load();
-        assert!(
-            config_result.is_ok(),
-            "Expected OK, got {:?}",
-            config_result.err()
-        );
-        let config = config_result.unwrap();
+        let paths = setup_test_paths("test_load_partial_config_empty_toml");
+        write_user_files(&paths, "", Some("Empty TOML config prompt"));
 
-        assert_eq!(
-            config.ai.api_url,
-            "http://localhost:11434/v1/chat/completions"
-        ); // From config
-        assert_eq!(config.ai.model_name, "qwen3:32b-q8_0"); // From config
-        assert_eq!(config.ai.temperature, 0.7); // Default
-        assert_eq!(config.ai.api_key, None); // Should be None (not specified)
-        assert_eq!(config.prompts.get("commit").unwrap(), &prompt_text);
-
-        // Verify files were copied to user directory
-        let mock_user_config = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_CONFIG_FILE_NAME);
-        let mock_user_prompt = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_COMMIT_PROMPT_FILE_NAME);
-        assert!(
-            mock_user_config.exists(),
-            "Config should be copied to user directory"
-        );
-        assert!(
-            mock_user_prompt.exists(),
-            "Prompt should be copied to user directory"
-        );
+        let config = AppConfig::load_from(paths).expect("config should load");
 
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+        assert_eq!(config.ai.api_url, "http://localhost:11434/v1/chat/completions");
+        assert_eq!(config.ai.model_name, "qwen3:32b-q8_0");
+        assert_eq!(config.ai.temperature, 0.7);
+        assert_eq!(config.ai.api_key, None);
+        assert_eq!(config.system_prompt("commit"), "Empty TOML config prompt");
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes with prompts HashMap"]
     fn test_load_no_config_file() {
-        // Directly lock the mutex to prevent PoisonError issues
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_no_config_file";
-        let prompt_text = "No config file prompt";
-        // Pass None for config_content, but needs example config for defaults
-        let base_path =
-            setup_test_environment(test_name, None, Some(prompt_text), true, true, true);
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        let config_result = AppConfig::load();
-        assert!(
-            config_result.is_ok(),
-            "Expected OK, got {:?}",
-            config_result.err()
-        );
-        let config = config_result.unwrap();
+        let paths = setup_test_paths("test_load_no_config_file");
 
-        // All values should come from example config
-        assert_eq!(
-            config.ai.api_url,
-            "http://localhost:11434/v1/chat/completions"
-        );
+        let config = AppConfig::load_from(paths.clone()).expect("config should load");
+
+        // Every file is initialized from its template
         assert_eq!(config.ai.model_name, "qwen3:32b-q8_0");
-        assert_eq!(config.ai.temperature, 0.7);
-        assert_eq!(config.ai.api_key, None); // Should be None (placeholder in example)
-        assert_eq!(config.prompts.get("commit").unwrap(), &prompt_text);
-
-        // Verify the example config was copied to user directory
-        let mock_user_config = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_CONFIG_FILE_NAME);
-        let mock_user_prompt = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_COMMIT_PROMPT_FILE_NAME);
-        assert!(
-            mock_user_config.exists(),
-            "Example config should be copied to user directory"
-        );
-        assert!(
-            mock_user_prompt.exists(),
-            "Prompt should be copied to user directory"
+        assert_eq!(config.system_prompt("commit"), "Template commit prompt");
+        assert_eq!(
+            fs::read_to_string(paths.user_file(USER_CONFIG_FILE_NAME)).unwrap(),
+            TEMPLATE_CONFIG
         );
+        assert!(paths.user_file(USER_EXPLANATION_PROMPT_FILE_NAME).exists());
+    }
 
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+    #[test]
+    fn test_load_alternate_config_file() {
+        let mut paths = setup_test_paths("test_load_alternate_config_file");
+        let dir = paths.user_config_dir.parent().unwrap().parent().unwrap().join("alternate");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("ci.toml"), "[ai]\nmodel_name = \"ci-model\"\n").unwrap();
+        fs::write(dir.join(USER_COMMIT_PROMPT_FILE_NAME), "Alternate commit prompt").unwrap();
+        paths.user_config_dir = dir.clone();
+        paths.config_file = Some(dir.join("ci.toml"));
+
+        let config = AppConfig::load_from(paths.clone()).expect("config should load");
+        assert_eq!(config.ai.model_name, "ci-model");
+        assert_eq!(config.system_prompt("commit"), "Alternate commit prompt");
+        assert_eq!(config.system_prompt("explanation"), "Template explanation prompt");
+        // Nothing is initialized next to it
+        assert!(!dir.join(USER_CONFIG_FILE_NAME).exists());
+        assert!(!paths.user_file(USER_EXPLANATION_PROMPT_FILE_NAME).exists());
+
+        paths.config_file = Some(dir.join("missing.toml"));
+        assert!(matches!(AppConfig::load_from(paths), Err(ConfigError::FileRead(..))));
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes"]
     fn test_load_no_config_and_no_example_file() {
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_no_config_and_no_example_file";
-        let prompt_text = "Prompt text";
-        // No config files at all, just the prompt, and NO assets directory
-        let base_path =
-            setup_test_environment(test_name, None, Some(prompt_text), true, false, false);
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        let config_result = AppConfig::load();
-        assert!(config_result.is_err());
-        match config_result.err().unwrap() {
-            // Should fail because no configuration sources exist
-            ConfigError::FileRead(path, _) => {
-                assert_eq!(path, "configuration");
+        let paths = setup_test_paths("test_load_no_config_and_no_example_file");
+        fs::remove_file(&paths.config_template).unwrap();
+
+        match AppConfig::load_from(paths) {
+            Err(ConfigError::FileRead(message, _)) => {
+                assert!(message.starts_with("Config template not found"), "{}", message);
             }
-            e => panic!("Expected FileRead error for configuration, got {:?}", e),
+            other => panic!("Expected FileRead error for the config template, got {:?}", other),
         }
-
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes"]
     fn test_load_missing_prompt_file() {
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_missing_prompt_file";
-        let config_toml = r#""#;
-        // Create empty config but no prompt file, and NO assets directory
-        let base_path =
-            setup_test_environment(test_name, Some(config_toml), None, false, true, false);
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        let config_result = AppConfig::load();
-        assert!(config_result.is_err());
-        match config_result.err().unwrap() {
-            ConfigError::FileRead(path, _) => {
-                assert_eq!(path, "prompt");
+        let paths = setup_test_paths("test_load_missing_prompt_file");
+        fs::remove_file(&paths.commit_prompt_template).unwrap();
+        write_user_files(&paths, "", None);
+
+        match AppConfig::load_from(paths.clone()) {
+            Err(ConfigError::FileRead(message, _)) => {
+                assert!(message.starts_with("Commit prompt template not found"), "{}", message);
             }
-            e => panic!("Expected FileRead error for prompt, got {:?}", e),
+            other => panic!("Expected FileRead error for the prompt template, got {:?}", other),
         }
-
-        // Config should still be copied to user directory even though prompt is missing
-        let mock_user_config = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_CONFIG_FILE_NAME);
-        assert!(
-            mock_user_config.exists(),
-            "Config should be copied to user directory despite prompt error"
-        );
-        // No need to check prompt file as it's missing by design in this test
-
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+        assert!(paths.user_file(USER_CONFIG_FILE_NAME).exists());
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes"]
-    fn test_load_missing_prompts_directory() {
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_missing_prompts_directory";
-        let config_toml = r#""#;
-        // Setup without prompts directory and NO assets directory
-        let base_path =
-            setup_test_environment(test_name, Some(config_toml), None, false, true, false);
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        let config_result = AppConfig::load();
-        assert!(config_result.is_err());
-        match config_result.err().unwrap() {
-            ConfigError::FileRead(path, _) => {
-                assert_eq!(path, "prompt");
-            }
-            e => panic!("Expected FileRead error for prompt, got {:?}", e),
-        }
+    fn test_load_missing_prompts_keeps_existing_config() {
+        let paths = setup_test_paths("test_load_missing_prompts_keeps_existing_config");
+        let config_toml = "[ai]\nmodel_name = \"my-model\"\n";
+        write_user_files(&paths, config_toml, None);
 
-        // Config should still be copied to user directory even though prompt is missing
-        let mock_user_config = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_CONFIG_FILE_NAME);
-        assert!(
-            mock_user_config.exists(),
-            "Config should be copied to user directory despite prompt directory error"
-        );
-        // No need to check prompt file as it's missing by design in this test
+        let config = AppConfig::load_from(paths.clone()).expect("config should load");
 
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+        assert_eq!(config.ai.model_name, "my-model");
+        assert_eq!(config.system_prompt("commit"), "Template commit prompt");
+        assert_eq!(
+            fs::read_to_string(paths.user_file(USER_CONFIG_FILE_NAME)).unwrap(),
+            config_toml
+        );
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes"]
     fn test_load_invalid_config_toml() {
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_invalid_config_toml";
+        let paths = setup_test_paths("test_load_invalid_config_toml");
         let invalid_config_toml = r#"[ai]
 api_url = "http://invalid.toml"
 model_name = "invalid-model"
-temperature = "not_a_float"  # Invalid type
+temperature = "not_a_float"
 "#;
-        let prompt_text = "Invalid config prompt";
-
-        // Setup a clean environment with only a prompt
-        let base_path =
-            setup_test_environment(test_name, None, Some(prompt_text), true, false, false);
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        // Make sure no environment variables affect the test
-        unsafe {
-            std::env::remove_var("GITIE_ASSETS_CONFIG");
-            std::env::remove_var("GITIE_ASSETS_PROMPT");
+        write_user_files(&paths, invalid_config_toml, None);
+
+        match AppConfig::load_from(paths) {
+            Err(ConfigError::Invalid(_, problems)) => {
+                assert_eq!(problems, vec!["'ai.temperature' must be a number, found string"]);
+            }
+            other => panic!("Expected Invalid error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_load_invalid_example_config_toml() {
+        let paths = setup_test_paths("test_load_invalid_example_config_toml");
+        fs::write(&paths.config_template, "[ai\nmodel_name = ").unwrap();
+
+        match AppConfig::load_from(paths) {
+            Err(ConfigError::TomlParse(_, _)) => {}
+            other => panic!("Expected TomlParse error, got {:?}", other),
         }
+    }
+
+    #[test]
+    fn test_load_config_with_empty_api_key() {
+        let paths = setup_test_paths("test_load_config_with_empty_api_key");
+        write_user_files(&paths, "[ai]\napi_key = \"\"\n", None);
+
+        let config = AppConfig::load_from(paths).expect("config should load");
+
+        assert_eq!(config.ai.api_key, None);
+    }
+
+    #[test]
+    fn test_api_key_placeholder_becomes_none() {
+        let paths = setup_test_paths("test_api_key_placeholder_becomes_none");
+
+        let config = AppConfig::load_from(paths).expect("config should load");
+
+        assert_eq!(config.ai.api_key, None);
+    }
+
+    #[test]
+    fn test_load_applies_policy_file() {
+        let paths = setup_test_paths("test_load_applies_policy_file");
+        fs::write(&paths.policy_file, "[locked.ai]\nmodel_name = \"corp-model\"\n").unwrap();
+
+        let config = AppConfig::load_from(paths).expect("config should load");
+
+        assert_eq!(config.ai.model_name, "corp-model");
+    }
+
+    #[test]
+    fn test_validate_config_accepts_known_keys() {
+        let raw: toml::Value = toml::from_str(
+            r#"[ai]
+api_url = "http://localhost:11434/v1/chat/completions"
+model_name = "qwen3:32b-q8_0"
+temperature = 1
+api_key = "key"
+"#,
+        )
+        .unwrap();
+        let report = validate_config_value(&raw);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+    }
+
+    #[test]
+    fn test_validate_config_warns_on_unknown_keys_with_suggestion() {
+        let raw: toml::Value = toml::from_str(
+            r#"[ai]
+temprature = 0.2
+[extra]
+key = "value"
+"#,
+        )
+        .unwrap();
+        let report = validate_config_value(&raw);
+        assert!(report.errors.is_empty());
+        assert_eq!(
+            report.warnings,
+            vec![
+                "unknown configuration key 'ai.temprature' (did you mean 'ai.temperature'?)",
+                "unknown configuration key 'extra'",
+            ]
+        );
+    }
 
-        // Create the project config with invalid TOML - this is the only config source
-        fs::create_dir_all(base_path.parent().unwrap()).unwrap();
-        fs::write(base_path.join("config.toml"), invalid_config_toml).unwrap();
+    #[test]
+    fn test_validate_config_reports_all_errors_at_once() {
+        let raw: toml::Value = toml::from_str(
+            r#"[ai]
+api_url = 42
+temperature = 3.5
+"#,
+        )
+        .unwrap();
+        let report = validate_config_value(&raw);
+        assert_eq!(
+            report.errors,
+            vec![
+                "'ai.api_url' must be a string, found integer",
+                "'ai.temperature' must be between 0 and 2, found 3.5",
+            ]
+        );
+    }
 
-        // Make sure the config file exists
-        assert!(
-            Path::new("config.toml").exists(),
-            "Test setup failed: invalid config file not created"
+    #[test]
+    fn test_validate_config_checks_endpoints() {
+        let raw: toml::Value = toml::from_str(
+            r#"[ai]
+api_url = "localhost:11434/v1/chat/completions"
+
+[ai.aliases.fast]
+model = "small"
+api_url = "http://localhost:11434"
+
+[[ai.endpoints]]
+url = "ftp://llm.example.com/v1/chat/completions"
+
+[[ai.endpoints]]
+url = "https://llm.example.com/v1/"
+"#,
+        )
+        .unwrap();
+        let report = validate_config_value(&raw);
+        assert_eq!(
+            report.errors,
+            vec![
+                "'ai.api_url' must be an http:// or https:// URL, found 'localhost:11434/v1/chat/completions'",
+                "'ai.endpoints.0.url' must be an http:// or https:// URL, found 'ftp://llm.example.com/v1/chat/completions'",
+            ]
         );
-        assert!(
-            !Path::new(CONFIG_EXAMPLE_FILE_NAME).exists(),
-            "Test setup failed: assets config file should not exist"
+        assert_eq!(
+            report.warnings,
+            vec![
+                "'ai.aliases.fast.api_url' does not look like a chat completions endpoint: 'http://localhost:11434' \
+                 (did you mean 'http://localhost:11434/v1/chat/completions'?)",
+                "'ai.endpoints.1.url' does not look like a chat completions endpoint: 'https://llm.example.com/v1/' \
+                 (did you mean 'https://llm.example.com/v1/chat/completions'?)",
+            ]
         );
+    }
 
-        // Try to load the config - it should fail with a FileWrite error when source file doesn't exist
-        let config_result = AppConfig::initialize_config();
-        assert!(config_result.is_err(), "Expected error, got Ok(...)");
-
-        if let Err(e) = config_result {
-            match e {
-                ConfigError::FileWrite(message, _) => {
-                    assert!(
-                        message.contains("Failed to copy source"),
-                        "Wrong error message: {}",
-                        message
-                    );
-                }
-                _ => panic!("Expected FileWrite error, got {:?}", e),
-            }
-        }
+    #[test]
+    fn test_validate_config_rejects_non_table_section() {
+        let raw: toml::Value = toml::from_str("ai = \"oops\"").unwrap();
+        let report = validate_config_value(&raw);
+        assert_eq!(report.errors, vec!["'ai' must be a table, found string"]);
+    }
 
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+    fn scratch_config_path(test_name: &str) -> PathBuf {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target/test_temp_data")
+            .join(test_name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        dir.join(USER_CONFIG_FILE_NAME)
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes"]
-    fn test_load_invalid_example_config_toml() {
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_invalid_example_config_toml";
-        // Invalid type in example config
-        let invalid_example_config_toml = r#"[ai]
-api_url = "http://invalid.toml"
-model_name = "invalid-model"
-temperature = "not_a_float"
-"#;
-        let prompt_text = "Prompt text";
+    fn test_set_config_value_preserves_comments() {
+        let path = scratch_config_path("test_set_config_value_preserves_comments");
+        fs::write(
+            &path,
+            "# Top comment\n[ai]\n# The model to use\nmodel_name = \"old\" # trailing\ntemperature = 0.7\n",
+        )
+        .unwrap();
+
+        set_config_value(&path, "ai.model_name", "new-model").unwrap();
+        set_config_value(&path, "ai.temperature", "0.2").unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            "# Top comment\n[ai]\n# The model to use\nmodel_name = \"new-model\" # trailing\ntemperature = 0.2\n"
+        );
+    }
 
-        // Clean environment with no config files yet
-        let base_path =
-            setup_test_environment(test_name, None, Some(prompt_text), true, false, false);
+    #[test]
+    fn test_set_config_value_rejects_invalid_value_without_writing() {
+        let path = scratch_config_path("test_set_config_value_rejects_invalid_value");
+        let original = "[ai]\ntemperature = 0.7\n";
+        fs::write(&path, original).unwrap();
+
+        let result = set_config_value(&path, "ai.temperature", "5");
+        assert!(matches!(result, Err(ConfigError::Invalid(_, _))));
+        assert_eq!(fs::read_to_string(&path).unwrap(), original);
+    }
 
-        // Clear environment variables that might affect test
-        unsafe {
-            std::env::remove_var("GITIE_ASSETS_CONFIG");
-            std::env::remove_var("GITIE_ASSETS_PROMPT");
+    #[test]
+    fn test_set_config_value_rejects_unknown_key() {
+        let path = scratch_config_path("test_set_config_value_rejects_unknown_key");
+        match set_config_value(&path, "ai.modle_name", "x") {
+            Err(ConfigError::Invalid(_, problems)) => assert_eq!(
+                problems,
+                vec!["unknown configuration key 'ai.modle_name' (did you mean 'ai.model_name'?)"]
+            ),
+            other => panic!("Expected Invalid error, got {:?}", other),
         }
+        assert!(!path.exists());
+    }
 
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        // Make sure no config file exists (clean state)
-        assert!(!Path::new("config.toml").exists());
+    #[test]
+    fn test_set_config_value_creates_missing_section() {
+        let path = scratch_config_path("test_set_config_value_creates_missing_section");
+        set_config_value(&path, "ai.api_url", "http://localhost:8080/v1/chat/completions").unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "[ai]\napi_url = \"http://localhost:8080/v1/chat/completions\"\n"
+        );
+    }
 
-        // Create only the example config with invalid TOML
-        fs::write("config.example.toml", invalid_example_config_toml).unwrap();
-        assert!(
-            Path::new("config.example.toml").exists(),
-            "Failed to create example config file"
+    #[test]
+    fn test_parse_git_config_entries() {
+        let output = "gitie.model\nllama3\0gitie.commitLanguage\nen\0gitie.flag\0";
+        assert_eq!(
+            parse_git_config_entries(output),
+            vec![
+                ("gitie.model".to_string(), "llama3".to_string()),
+                ("gitie.commitlanguage".to_string(), "en".to_string()),
+                ("gitie.flag".to_string(), "true".to_string()),
+            ]
         );
+    }
+
+    #[test]
+    fn test_git_config_settings_override_file_values() {
+        let mut raw: toml::Value =
+            toml::from_str("[ai]\nmodel_name = \"from-file\"\ntemperature = 0.7\n").unwrap();
+        let entries = vec![
+            ("gitie.model".to_string(), "from-global".to_string()),
+            ("gitie.model".to_string(), "from-repo".to_string()),
+            ("gitie.temperature".to_string(), "0.2".to_string()),
+            ("gitie.commitlanguage".to_string(), "en".to_string()),
+        ];
+        let report = apply_git_config_settings(&mut raw, &entries);
+        assert!(report.errors.is_empty() && report.warnings.is_empty());
+        assert_eq!(raw["ai"]["model_name"].as_str(), Some("from-repo"));
+        assert_eq!(raw["ai"]["temperature"].as_float(), Some(0.2));
+        assert_eq!(raw["commit"]["language"].as_str(), Some("en"));
+    }
 
-        // Now try to initialize config (should fail with FileWrite when source file doesn't exist)
-        let config_result = AppConfig::initialize_config();
-        assert!(
-            config_result.is_err(),
-            "Expected config initialization to fail"
+    #[test]
+    fn test_git_config_settings_report_bad_entries() {
+        let mut raw = toml::Value::Table(toml::Table::new());
+        let entries = vec![
+            ("gitie.temperature".to_string(), "warm".to_string()),
+            ("gitie.unknown".to_string(), "x".to_string()),
+        ];
+        let report = apply_git_config_settings(&mut raw, &entries);
+        assert_eq!(
+            report.errors,
+            vec!["git config 'gitie.temperature' must be a number, found 'warm'"]
         );
+        assert_eq!(report.warnings, vec!["unknown git config setting 'gitie.unknown'"]);
+    }
 
-        if let Err(e) = config_result {
-            match e {
-                ConfigError::FileWrite(message, _) => {
-                    assert!(
-                        message.contains("Failed to copy source"),
-                        "Expected error message to contain 'Failed to copy source', got {}",
-                        message
-                    );
-                }
-                e => panic!("Expected FileWrite error for example config, got {:?}", e),
+    #[test]
+    fn test_invalid_git_config_values_are_reported_as_git_configs() {
+        let paths = setup_test_paths("git_config_origin");
+        write_user_files(&paths, TEMPLATE_CONFIG, None);
+        let settings = vec![("gitie.temperature".to_string(), "5".to_string())];
+        let prompt_paths = HashMap::new();
+        match AppConfig::load_config_from_file(&paths.config_file_path(), &prompt_paths, &settings, Policy::default()) {
+            Err(ConfigError::Invalid(origin, problems)) => {
+                assert_eq!(origin, "git config");
+                assert_eq!(problems, vec!["git config 'gitie.temperature': 'ai.temperature' must be between 0 and 2, found 5"]);
             }
+            other => panic!("unexpected {:?}", other.map(|_| ())),
         }
+    }
+
+    #[test]
+    fn test_model_parameters_fall_back_to_ai_defaults() {
+        let raw = r#"[ai]
+api_url = "http://localhost:11434/v1/chat/completions"
+model_name = "base-model"
+temperature = 0.7
+
+[commit]
+temperature = 0.2
+max_tokens = 200
 
-        // The invalid example config should not be copied to user directory
-        let mock_user_config = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_CONFIG_FILE_NAME);
-        assert!(
-            !mock_user_config.exists(),
-            "Invalid example config should not be copied to user directory"
+[review]
+model = "big-model"
+"#;
+        let config: AppConfig = toml::from_str(raw).unwrap();
+
+        assert_eq!(
+            config.model_parameters("commit"),
+            ModelParameters {
+                model: "base-model".to_string(),
+                api_url: "http://localhost:11434/v1/chat/completions".to_string(),
+                temperature: 0.2,
+                max_tokens: Some(200),
+            }
         );
+        assert_eq!(
+            config.model_parameters("review"),
+            ModelParameters {
+                model: "big-model".to_string(),
+                api_url: "http://localhost:11434/v1/chat/completions".to_string(),
+                temperature: 0.7,
+                max_tokens: None,
+            }
+        );
+        assert_eq!(config.model_parameters("explain").model, "base-model");
+    }
 
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+    #[test]
+    fn test_validate_config_checks_task_sections() {
+        let raw: toml::Value =
+            toml::from_str("[commit]\nmax_tokens = 0\ndiff_context_lines = -1\n[explain]\nmax_tokens = \"many\"\n").unwrap();
+        let report = validate_config_value(&raw);
+        assert_eq!(
+            report.errors,
+            vec![
+                "'commit.diff_context_lines' must be between 0 and 1000, found -1",
+                "'commit.max_tokens' must be between 1 and 1000000, found 0",
+                "'explain.max_tokens' must be an integer, found string",
+            ]
+        );
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes with prompts HashMap"]
-    fn test_load_config_with_empty_api_key() {
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_load_config_with_empty_api_key";
-        let config_toml = r#"[ai]
-api_url = "http://custom.host/api"
-model_name = "custom-model"
-temperature = 0.5
-api_key = ""
-"#;
-        let prompt_text = "Test system prompt with empty API key";
-        // Setup with project config that has empty API key
-        let base_path = setup_test_environment(
-            test_name,
-            Some(config_toml),
-            Some(prompt_text),
-            true,
-            true,
-            true,
+    fn test_policy_locks_override_user_values() {
+        let mut raw: toml::Value =
+            toml::from_str("[ai]\napi_url = \"http://my-own-endpoint\"\nmodel_name = \"m\"\n")
+                .unwrap();
+        let locked: toml::Table =
+            toml::from_str("[ai]\napi_url = \"https://llm.corp/v1/chat/completions\"\n").unwrap();
+
+        let warnings = apply_policy_locks(&mut raw, &locked);
+        assert_eq!(
+            raw["ai"]["api_url"].as_str(),
+            Some("https://llm.corp/v1/chat/completions")
         );
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        let config_result = AppConfig::load();
-        assert!(
-            config_result.is_ok(),
-            "Expected OK, got {:?}",
-            config_result.err()
+        assert_eq!(raw["ai"]["model_name"].as_str(), Some("m"));
+        assert_eq!(
+            warnings,
+            vec!["'ai.api_url' is locked by the system policy; ignoring the configured value"]
         );
-        let config = config_result.unwrap();
-
-        assert_eq!(config.ai.api_key, None); // Empty string in TOML becomes None after our conversion
-
-        // Verify the config was copied to user directory
-        let mock_user_config = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_CONFIG_FILE_NAME);
-        let mock_user_prompt = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_COMMIT_PROMPT_FILE_NAME);
-        assert!(
-            mock_user_config.exists(),
-            "Config should be copied to user directory"
+    }
+
+    #[test]
+    fn test_validate_config_checks_output_choices() {
+        let raw: toml::Value =
+            toml::from_str("[output]\nformat = \"html\"\npager = \"yes\"\n").unwrap();
+        let report = validate_config_value(&raw);
+        assert_eq!(
+            report.errors,
+            vec![
+                "'output.format' must be one of markdown, plain, json, porcelain, found 'html'",
+                "'output.pager' must be true or false, found string",
+            ]
         );
-        assert!(
-            mock_user_prompt.exists(),
-            "Prompt should be copied to user directory"
+    }
+
+    #[test]
+    fn test_retry_and_timeout_settings() {
+        let raw: toml::Value = toml::from_str(
+            "[ai.retry]\nmax_attempts = 0\nretry_on = \"429\"\n[ai.timeouts]\nconnect = 5\n",
+        )
+        .unwrap();
+        let report = validate_config_value(&raw);
+        assert_eq!(
+            report.errors,
+            vec![
+                "'ai.retry.max_attempts' must be between 1 and 10, found 0",
+                "'ai.retry.retry_on' must be a list of strings, found string",
+            ]
         );
 
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+        let config: AIConfig = toml::from_str(
+            "api_url = \"u\"\nmodel_name = \"m\"\ntemperature = 0.5\n[timeouts]\nconnect = 5\n",
+        )
+        .unwrap();
+        assert_eq!(config.timeouts.connect, 5);
+        assert_eq!(config.timeouts.total, 300);
+        assert_eq!(config.retry.max_attempts, 3);
+        assert_eq!(config.retry.retry_on, vec!["timeout", "connect", "429", "5xx"]);
     }
 
     #[test]
-    #[ignore = "Temporarily disabled due to API changes with prompts HashMap"]
-    fn test_api_key_placeholder_becomes_none() {
-        let _lock = TEST_MUTEX.lock().unwrap_or_else(|e| e.into_inner());
-        let test_name = "test_api_key_placeholder_becomes_none";
-        let prompt_text = "Prompt text";
-        // Only example config with placeholder API key
-        let base_path =
-            setup_test_environment(test_name, None, Some(prompt_text), true, true, true);
-        let original_dir = std::env::current_dir().unwrap_or_else(|_| PathBuf::new());
-        std::env::set_current_dir(&base_path).unwrap_or_else(|_| ());
-
-        let config_result = AppConfig::load();
-        assert!(
-            config_result.is_ok(),
-            "Expected OK, got {:?}",
-            config_result.err()
-        );
-        let config = config_result.unwrap();
-
-        assert_eq!(config.ai.api_key, None); // Placeholder should be treated as None
-
-        // Verify the example config was copied to user directory
-        let mock_user_config = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_CONFIG_FILE_NAME);
-        let mock_user_prompt = base_path
-            .join("mock_home")
-            .join(USER_CONFIG_DIR)
-            .join(USER_COMMIT_PROMPT_FILE_NAME);
-        assert!(
-            mock_user_config.exists(),
-            "Example config should be copied to user directory"
-        );
-        assert!(
-            mock_user_prompt.exists(),
-            "Prompt should be copied to user directory"
+    fn test_model_aliases_resolve_per_task() {
+        let raw = r#"[ai]
+api_url = "http://localhost:11434/v1/chat/completions"
+model_name = "local"
+temperature = 0.7
+api_key = "local-key"
+
+[ai.aliases.local]
+model = "qwen3:32b-q8_0"
+
+[ai.aliases.smart]
+provider = "openai"
+model = "gpt-4o"
+api_url = "https://api.openai.com/v1/chat/completions"
+
+[review]
+model = "smart"
+"#;
+        let config: AppConfig = toml::from_str(raw).unwrap();
+        assert!(validate_config_value(&toml::from_str(raw).unwrap()).errors.is_empty());
+
+        let commit = config.model_parameters("commit");
+        assert_eq!(commit.model, "qwen3:32b-q8_0");
+        assert_eq!(commit.api_url, "http://localhost:11434/v1/chat/completions");
+
+        let review = config.model_parameters("review");
+        assert_eq!(review.model, "gpt-4o");
+        assert_eq!(review.api_url, "https://api.openai.com/v1/chat/completions");
+        // The default key stays with the default endpoint
+        assert_eq!(config.ai.api_key_for(&review.api_url), None);
+        assert_eq!(config.ai.api_key_for(&commit.api_url), Some("local-key"));
+
+        // `--model` and `--temperature` win over every task's settings
+        let mut config = config;
+        config.model_override = Some("smart".to_string());
+        config.temperature_override = Some(0.1);
+        let commit = config.model_parameters("commit");
+        assert_eq!((commit.model.as_str(), commit.temperature), ("gpt-4o", 0.1));
+        assert_eq!(commit.api_url, "https://api.openai.com/v1/chat/completions");
+    }
+
+    #[test]
+    fn test_endpoints_route_models_and_keys() {
+        let raw = r#"[ai]
+api_url = "http://localhost:11434/v1/chat/completions"
+model_name = "qwen3:8b"
+temperature = 0.7
+api_key = "default-key"
+
+[[ai.endpoints]]
+url = "https://api.openai.com/v1/chat/completions"
+key = "openai-key"
+models = ["gpt-4o"]
+
+[[ai.endpoints]]
+url = "http://gpu-box:11434/v1/chat/completions"
+
+[review]
+model = "gpt-4o"
+"#;
+        let config: AppConfig = toml::from_str(raw).unwrap();
+        assert!(validate_config_value(&toml::from_str(raw).unwrap()).errors.is_empty());
+
+        let review = config.model_parameters("review");
+        assert_eq!(review.api_url, "https://api.openai.com/v1/chat/completions");
+        assert_eq!(config.ai.api_key_for(&review.api_url), Some("openai-key"));
+
+        let commit = config.model_parameters("commit");
+        assert_eq!(commit.api_url, "http://localhost:11434/v1/chat/completions");
+        assert_eq!(config.ai.api_key_for(&commit.api_url), Some("default-key"));
+
+        // A listed endpoint without a key never receives the default key
+        assert_eq!(config.ai.api_key_for("http://gpu-box:11434/v1/chat/completions"), None);
+        // Nor does a host that isn't listed, e.g. an alias's or a task's `api_url`
+        assert_eq!(config.ai.api_key_for("https://other.example.com/v1/chat/completions"), None);
+        assert_eq!(config.ai.api_key_for("http://localhost:11434/v1/embeddings"), Some("default-key"));
+
+        let bad: toml::Value = toml::from_str("[ai]\nendpoints = [{ url = 1 }, \"x\"]\n").unwrap();
+        assert_eq!(
+            validate_config_value(&bad).errors,
+            vec![
+                "'ai.endpoints.0.url' must be a string, found integer",
+                "'ai.endpoints.1' must be a table, found string",
+            ]
         );
+    }
+
+    #[test]
+    fn test_resolved_settings_mask_secrets() {
+        let raw = r#"[ai]
+api_url = "http://localhost:11434/v1/chat/completions"
+model_name = "base-model"
+temperature = 0.7
+api_key = "sk-very-secret-1234"
+
+[[ai.endpoints]]
+url = "https://api.openai.com/v1/chat/completions"
+key = "short"
+"#;
+        let config: AppConfig = toml::from_str(raw).unwrap();
+        let settings: HashMap<String, String> = config.resolved_settings().into_iter().collect();
+
+        assert_eq!(settings["ai.api_key"], "****1234");
+        assert_eq!(settings["ai.endpoints.0.key"], "****");
+        assert_eq!(settings["review.model"], "base-model");
+        assert!(!settings.values().any(|v| v.contains("secret")));
+    }
 
-        let _ = std::env::set_current_dir(original_dir);
-        cleanup_test_environment(base_path);
+    #[test]
+    fn test_peek_setting_reads_only_the_file_and_policy() {
+        let paths = setup_test_paths("test_peek_setting_reads_only_the_file_and_policy");
+        assert_eq!(peek_setting(&paths, "explain.teach_mode"), None);
+        // Nothing was initialized
+        assert!(!paths.user_file(USER_CONFIG_FILE_NAME).exists());
+
+        write_user_files(&paths, "[explain]\nteach_mode = true\n", None);
+        assert_eq!(peek_setting(&paths, "explain.teach_mode"), Some(toml::Value::Boolean(true)));
+        fs::write(&paths.policy_file, "[locked.explain]\nteach_mode = false\n").unwrap();
+        assert_eq!(peek_setting(&paths, "explain.teach_mode"), Some(toml::Value::Boolean(false)));
     }
 }
@@ -0,0 +1,156 @@
+//! The `gitie-config` subcommand.
+
+use crate::cli::{ConfigAction, ConfigArgs};
+use crate::config::{set_config_value, validate_config_text, AppConfig};
+use crate::errors::{AppError, ConfigError};
+use crate::prompt;
+use crate::state_file::{FileLock, write_atomic};
+
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::Command as StdCommand;
+
+/// Handles the `gitie-config` subcommand
+///
+/// # Arguments
+///
+/// * `args` - Config arguments from CLI
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub fn handle_config(args: ConfigArgs) -> Result<(), AppError> {
+    let config_path = AppConfig::ensure_user_config_path()?;
+    match args.action {
+        ConfigAction::Edit => edit_config_file(&config_path),
+        ConfigAction::Check => check_config(&config_path),
+        ConfigAction::Set { key, value } => {
+            set_config_value(&config_path, &key, &value)?;
+            println!("Set {} in {}", key, config_path.display());
+            Ok(())
+        }
+    }
+}
+
+/// Loads every configuration layer (file, `gitie.*` git config, system policy) and
+/// prints the effective values with secrets masked.
+///
+/// Any load or validation error is returned, so scripts get a non-zero exit status.
+fn check_config(config_path: &Path) -> Result<(), AppError> {
+    let config = AppConfig::load()?;
+    let settings = config.resolved_settings();
+    let width = settings.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    println!("# {}", config_path.display());
+    for (key, value) in settings {
+        println!("{:<width$} = {}", key, value, width = width);
+    }
+    println!("Configuration OK");
+    Ok(())
+}
+
+/// Opens the config file in the user's editor and re-validates it after saving.
+///
+/// Edits happen on a scratch copy next to the real file, which is only replaced once
+/// the edited content validates. If validation fails the user can re-open the editor
+/// or discard their changes, so a typo never leaves a broken config behind.
+fn edit_config_file(config_path: &Path) -> Result<(), AppError> {
+    let original = fs::read_to_string(config_path).map_err(|e| {
+        ConfigError::FileRead(config_path.to_string_lossy().to_string(), e)
+    })?;
+    let scratch_path = config_path.with_extension("toml.edit");
+    fs::write(&scratch_path, &original).map_err(|e| {
+        ConfigError::FileWrite(scratch_path.to_string_lossy().to_string(), e)
+    })?;
+
+    let result = loop {
+        if let Err(e) = launch_editor(&scratch_path) {
+            break Err(e);
+        }
+        let edited = match fs::read_to_string(&scratch_path) {
+            Ok(edited) => edited,
+            Err(e) => {
+                break Err(ConfigError::FileRead(scratch_path.to_string_lossy().to_string(), e).into());
+            }
+        };
+        if edited == original {
+            println!("No changes made to {}", config_path.display());
+            break Ok(());
+        }
+        match validate_config_text(config_path, &edited) {
+            Ok(warnings) => {
+                for warning in warnings {
+                    eprintln!("warning: {}", warning);
+                }
+                break save_edited_config(config_path, &original, &edited)
+                    .map(|_| println!("Saved {}", config_path.display()));
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                // Without a terminal nobody can fix it, and re-opening would loop forever
+                let reopen = io::stdin().is_terminal()
+                    && prompt::confirm(
+                        "Re-open the editor to fix it? Answering 'n' discards your changes.",
+                        true,
+                    )?;
+                if !reopen {
+                    println!("Discarded changes; {} is unchanged.", config_path.display());
+                    break Ok(());
+                }
+            }
+        }
+    };
+
+    let _ = fs::remove_file(&scratch_path);
+    result
+}
+
+/// Replaces the config file with `edited`, unless it changed since `original` was read.
+///
+/// Another process (e.g. `gitie-config set` in a second terminal) may have written the
+/// file while the editor was open; overwriting it would silently drop that change.
+fn save_edited_config(config_path: &Path, original: &str, edited: &str) -> Result<(), AppError> {
+    let path_str = config_path.to_string_lossy().to_string();
+    let _lock = FileLock::acquire(config_path).map_err(|e| ConfigError::FileWrite(path_str.clone(), e))?;
+    let current = fs::read_to_string(config_path).map_err(|e| ConfigError::FileRead(path_str.clone(), e))?;
+    if current != original {
+        let scratch_path = config_path.with_extension("toml.rejected");
+        let kept = write_atomic(&scratch_path, edited).is_ok();
+        let mut problem = "the file was changed by another process while it was being edited".to_string();
+        if kept {
+            problem.push_str(&format!("; your version was saved to {}", scratch_path.display()));
+        }
+        return Err(ConfigError::Invalid(path_str, vec![problem]).into());
+    }
+    write_atomic(config_path, edited).map_err(|e| ConfigError::FileWrite(path_str, e).into())
+}
+
+/// Launches the editor git would use (`GIT_EDITOR`, `core.editor`, `VISUAL`, `EDITOR`).
+fn launch_editor(path: &Path) -> Result<(), AppError> {
+    let editor = StdCommand::new("git")
+        .args(["var", "GIT_EDITOR"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|editor| !editor.is_empty())
+        .or_else(|| std::env::var("EDITOR").ok())
+        .unwrap_or_else(|| "vi".to_string());
+
+    tracing::debug!("Opening {} with editor '{}'", path.display(), editor);
+    // Run through the shell like git does, so editors configured with arguments work
+    let status = StdCommand::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$@\"", editor))
+        .arg(&editor)
+        .arg(path)
+        .status()
+        .map_err(|e| AppError::Io(format!("launching editor '{}'", editor), e))?;
+    if !status.success() {
+        return Err(AppError::Generic(format!(
+            "Editor '{}' exited with {}",
+            editor, status
+        )));
+    }
+    Ok(())
+}
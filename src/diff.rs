@@ -0,0 +1,423 @@
+//! Structured view of unified diffs as printed by `git diff`.
+//!
+//! [`parse`] splits a diff into one [`FileDiff`] per file, with the extended headers
+//! (modes, renames, blob hashes) decoded and the changes grouped into [`Hunk`]s, so
+//! callers don't have to pick apart the raw text themselves.
+
+/// What happened to a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+    Copied,
+}
+
+impl FileStatus {
+    /// The status letter `git diff --name-status` uses
+    pub fn letter(self) -> char {
+        match self {
+            FileStatus::Added => 'A',
+            FileStatus::Deleted => 'D',
+            FileStatus::Modified => 'M',
+            FileStatus::Renamed => 'R',
+            FileStatus::Copied => 'C',
+        }
+    }
+}
+
+/// The changes to a single file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDiff {
+    /// Path before the change (same as `new_path` unless renamed or copied)
+    pub old_path: String,
+
+    /// Path after the change
+    pub new_path: String,
+
+    pub status: FileStatus,
+
+    /// File mode before the change, e.g. `100644`, when the diff states it
+    pub old_mode: Option<String>,
+
+    /// File mode after the change
+    pub new_mode: Option<String>,
+
+    /// Similarity percentage reported for renames and copies
+    pub similarity: Option<u32>,
+
+    /// Abbreviated blob hash before the change, from the `index` line
+    pub old_blob: Option<String>,
+
+    /// Abbreviated blob hash after the change
+    pub new_blob: Option<String>,
+
+    /// Git reported the file as binary instead of printing its content
+    pub is_binary: bool,
+
+    /// The `diff --git` line and the extended header lines following it
+    pub headers: Vec<String>,
+
+    pub hunks: Vec<Hunk>,
+
+    /// Added and removed line counts when the content was left out of a
+    /// diff too large to read in full (a `[content omitted: ...]` line)
+    pub omitted_lines: Option<(usize, usize)>,
+
+    /// This file's section of the diff, verbatim
+    pub raw: String,
+}
+
+/// One `@@ -a,b +c,d @@` block of changes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    pub old_start: u32,
+    pub old_lines: u32,
+    pub new_start: u32,
+    pub new_lines: u32,
+
+    /// Text after the closing `@@`, usually the enclosing function
+    pub section: String,
+
+    pub lines: Vec<HunkLine>,
+}
+
+/// A line inside a hunk, without its leading marker
+#[derive(Debug, Clone, PartialEq)]
+pub enum HunkLine {
+    Context(String),
+    Added(String),
+    Removed(String),
+    /// `\ No newline at end of file`
+    NoNewlineAtEof,
+}
+
+impl FileDiff {
+    /// The path to show for this file: the new path, or the old one for deletions
+    pub fn path(&self) -> &str {
+        if self.status == FileStatus::Deleted {
+            &self.old_path
+        } else {
+            &self.new_path
+        }
+    }
+
+    /// Whether this entry records a submodule (gitlink, mode 160000)
+    pub fn is_submodule(&self) -> bool {
+        [&self.old_mode, &self.new_mode]
+            .into_iter()
+            .any(|mode| mode.as_deref() == Some("160000"))
+    }
+
+    /// Number of added lines across all hunks
+    pub fn added_lines(&self) -> usize {
+        if let Some((added, _)) = self.omitted_lines {
+            return added;
+        }
+        self.hunk_lines()
+            .filter(|line| matches!(line, HunkLine::Added(_)))
+            .count()
+    }
+
+    /// Number of removed lines across all hunks
+    pub fn removed_lines(&self) -> usize {
+        if let Some((_, removed)) = self.omitted_lines {
+            return removed;
+        }
+        self.hunk_lines()
+            .filter(|line| matches!(line, HunkLine::Removed(_)))
+            .count()
+    }
+
+    fn hunk_lines(&self) -> impl Iterator<Item = &HunkLine> {
+        self.hunks.iter().flat_map(|hunk| hunk.lines.iter())
+    }
+}
+
+/// Parses `git diff` output into one entry per file.
+///
+/// Anything before the first `diff --git` line is ignored.
+pub fn parse(diff: &str) -> Vec<FileDiff> {
+    split_sections(diff)
+        .into_iter()
+        .filter(|section| section.starts_with("diff --git "))
+        .map(parse_file)
+        .collect()
+}
+
+/// Splits a diff at each `diff --git` line, keeping line endings
+fn split_sections(diff: &str) -> Vec<&str> {
+    let mut sections = Vec::new();
+    let mut start = 0;
+    for (offset, _) in diff.match_indices("diff --git ") {
+        if offset > start && diff.as_bytes()[offset - 1] == b'\n' {
+            sections.push(&diff[start..offset]);
+            start = offset;
+        }
+    }
+    sections.push(&diff[start..]);
+    sections
+}
+
+fn parse_file(section: &str) -> FileDiff {
+    let mut lines = section.lines();
+    let header = lines.next().unwrap_or_default();
+    let (old_path, new_path) = header_paths(header);
+    let mut file = FileDiff {
+        old_path,
+        new_path,
+        status: FileStatus::Modified,
+        old_mode: None,
+        new_mode: None,
+        similarity: None,
+        old_blob: None,
+        new_blob: None,
+        is_binary: false,
+        headers: vec![header.to_string()],
+        hunks: Vec::new(),
+        omitted_lines: None,
+        raw: section.to_string(),
+    };
+
+    let mut in_headers = true;
+    for line in lines {
+        if in_headers {
+            if line.starts_with("--- ") || line.starts_with("+++ ") {
+                // The ---/+++ lines repeat the paths; `/dev/null` marks additions and deletions
+                continue;
+            }
+            if line.starts_with("@@") {
+                in_headers = false;
+            } else if line.starts_with("Binary files ") || line == "GIT binary patch" {
+                file.is_binary = true;
+                in_headers = false;
+                continue;
+            } else if let Some(counts) = parse_omitted_content(line) {
+                file.omitted_lines = Some(counts);
+                in_headers = false;
+                continue;
+            } else {
+                parse_extended_header(&mut file, line);
+                file.headers.push(line.to_string());
+                continue;
+            }
+        }
+        if let Some(hunk) = parse_hunk_header(line) {
+            file.hunks.push(hunk);
+        } else if let Some(hunk) = file.hunks.last_mut() {
+            let hunk_line = if let Some(text) = line.strip_prefix('+') {
+                HunkLine::Added(text.to_string())
+            } else if let Some(text) = line.strip_prefix('-') {
+                HunkLine::Removed(text.to_string())
+            } else if line.starts_with('\\') {
+                HunkLine::NoNewlineAtEof
+            } else {
+                HunkLine::Context(line.strip_prefix(' ').unwrap_or(line).to_string())
+            };
+            hunk.lines.push(hunk_line);
+        }
+    }
+    file
+}
+
+/// `[content omitted: +<added> -<removed> lines]`, written by the git backend
+fn parse_omitted_content(line: &str) -> Option<(usize, usize)> {
+    let counts = line.strip_prefix("[content omitted: +")?.strip_suffix(" lines]")?;
+    let (added, removed) = counts.split_once(" -")?;
+    Some((added.parse().ok()?, removed.parse().ok()?))
+}
+
+fn parse_extended_header(file: &mut FileDiff, line: &str) {
+    if let Some(mode) = line.strip_prefix("new file mode ") {
+        file.status = FileStatus::Added;
+        file.new_mode = Some(mode.to_string());
+    } else if let Some(mode) = line.strip_prefix("deleted file mode ") {
+        file.status = FileStatus::Deleted;
+        file.old_mode = Some(mode.to_string());
+    } else if let Some(mode) = line.strip_prefix("old mode ") {
+        file.old_mode = Some(mode.to_string());
+    } else if let Some(mode) = line.strip_prefix("new mode ") {
+        file.new_mode = Some(mode.to_string());
+    } else if let Some(percent) = line.strip_prefix("similarity index ") {
+        file.similarity = percent.trim_end_matches('%').parse().ok();
+    } else if let Some(path) = line.strip_prefix("rename from ") {
+        file.status = FileStatus::Renamed;
+        file.old_path = unquote(path);
+    } else if let Some(path) = line.strip_prefix("rename to ") {
+        file.new_path = unquote(path);
+    } else if let Some(path) = line.strip_prefix("copy from ") {
+        file.status = FileStatus::Copied;
+        file.old_path = unquote(path);
+    } else if let Some(path) = line.strip_prefix("copy to ") {
+        file.new_path = unquote(path);
+    } else if let Some(index) = line.strip_prefix("index ") {
+        // `index <old>..<new>[ <mode>]`; the mode is only given when it didn't change
+        let mut parts = index.split(' ');
+        if let Some((old, new)) = parts.next().and_then(|range| range.split_once("..")) {
+            file.old_blob = Some(old.to_string());
+            file.new_blob = Some(new.to_string());
+        }
+        if let Some(mode) = parts.next() {
+            file.old_mode.get_or_insert_with(|| mode.to_string());
+            file.new_mode.get_or_insert_with(|| mode.to_string());
+        }
+    }
+}
+
+/// Paths from `diff --git a/<old> b/<new>`; renames and copies are corrected by
+/// their extended headers, which are unambiguous even when paths contain spaces
+fn header_paths(header: &str) -> (String, String) {
+    let rest = header.strip_prefix("diff --git ").unwrap_or(header);
+    match rest.split_once(" b/").or_else(|| rest.split_once(" \"b/")) {
+        Some((old, new)) => {
+            let old = unquote(old);
+            let old = old.strip_prefix("a/").unwrap_or(&old).to_string();
+            (old, unquote(new.trim_end_matches('"')))
+        }
+        None => (rest.to_string(), rest.to_string()),
+    }
+}
+
+/// Removes the quotes git puts around paths with unusual characters
+fn unquote(path: &str) -> String {
+    path.strip_prefix('"')
+        .and_then(|p| p.strip_suffix('"'))
+        .unwrap_or(path)
+        .to_string()
+}
+
+/// Parses `@@ -old_start[,old_lines] +new_start[,new_lines] @@ section`
+fn parse_hunk_header(line: &str) -> Option<Hunk> {
+    let rest = line.strip_prefix("@@ -")?;
+    let (ranges, section) = rest.split_once(" @@")?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |range: &str| -> Option<(u32, u32)> {
+        match range.split_once(',') {
+            Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_lines) = range(old)?;
+    let (new_start, new_lines) = range(new)?;
+    Some(Hunk {
+        old_start,
+        old_lines,
+        new_start,
+        new_lines,
+        section: section.trim_start().to_string(),
+        lines: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_modified_file_with_hunks() {
+        let diff = "diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@ fn main() {
+ keep
+-old
++new
+@@ -10 +10,2 @@
++added
+ last
+\\ No newline at end of file
+";
+        let files = parse(diff);
+        assert_eq!(files.len(), 1);
+        let file = &files[0];
+        assert_eq!(file.path(), "src/lib.rs");
+        assert_eq!(file.status, FileStatus::Modified);
+        assert_eq!(file.old_mode.as_deref(), Some("100644"));
+        assert_eq!(file.old_blob.as_deref(), Some("1111111"));
+        assert_eq!(file.new_blob.as_deref(), Some("2222222"));
+        assert_eq!(file.headers.len(), 2);
+        assert_eq!(file.raw, diff);
+        assert_eq!(file.hunks.len(), 2);
+        assert_eq!(file.hunks[0].section, "fn main() {");
+        assert_eq!(
+            file.hunks[0].lines,
+            vec![
+                HunkLine::Context("keep".to_string()),
+                HunkLine::Removed("old".to_string()),
+                HunkLine::Added("new".to_string()),
+            ]
+        );
+        assert_eq!((file.hunks[1].old_start, file.hunks[1].old_lines), (10, 1));
+        assert_eq!(file.hunks[1].lines.last(), Some(&HunkLine::NoNewlineAtEof));
+        assert_eq!((file.added_lines(), file.removed_lines()), (2, 1));
+    }
+
+    #[test]
+    fn test_parse_added_deleted_renamed_and_binary_files() {
+        let diff = "diff --git a/new.txt b/new.txt
+new file mode 100755
+index 0000000..3333333
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++hello
+diff --git a/gone.txt b/gone.txt
+deleted file mode 100644
+index 4444444..0000000
+diff --git a/old name.rs b/new name.rs
+similarity index 92%
+rename from old name.rs
+rename to new name.rs
+diff --git a/logo.png b/logo.png
+index 5555555..6666666 100644
+Binary files a/logo.png and b/logo.png differ
+diff --git a/sub b/sub
+index 7777777..8888888 160000
+--- a/sub
++++ b/sub
+@@ -1 +1 @@
+-Subproject commit 7777777
++Subproject commit 8888888
+";
+        let files = parse(diff);
+        let summary: Vec<_> = files.iter().map(|f| (f.path(), f.status)).collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("new.txt", FileStatus::Added),
+                ("gone.txt", FileStatus::Deleted),
+                ("new name.rs", FileStatus::Renamed),
+                ("logo.png", FileStatus::Modified),
+                ("sub", FileStatus::Modified),
+            ]
+        );
+        assert_eq!(files[0].new_mode.as_deref(), Some("100755"));
+        assert_eq!(files[0].added_lines(), 1);
+        assert_eq!(files[1].old_mode.as_deref(), Some("100644"));
+        assert_eq!(files[2].old_path, "old name.rs");
+        assert_eq!(files[2].similarity, Some(92));
+        assert!(files[3].is_binary);
+        assert!(files[3].hunks.is_empty());
+        assert!(files[4].is_submodule());
+        assert!(!files[0].is_submodule());
+        let letters: String = files.iter().map(|f| f.status.letter()).collect();
+        assert_eq!(letters, "ADRMM");
+        assert_eq!(files.iter().map(|f| f.raw.as_str()).collect::<String>(), diff);
+    }
+
+    #[test]
+    fn test_parse_reads_omitted_content_counts() {
+        let files = parse("diff --git a/big.sql b/big.sql\nindex 1..2 100644\n[content omitted: +1200 -30 lines]\n");
+        assert_eq!(files[0].omitted_lines, Some((1200, 30)));
+        assert_eq!((files[0].added_lines(), files[0].removed_lines()), (1200, 30));
+        assert_eq!(files[0].headers, vec!["diff --git a/big.sql b/big.sql", "index 1..2 100644"]);
+    }
+
+    #[test]
+    fn test_parse_ignores_text_outside_file_sections() {
+        assert!(parse("").is_empty());
+        assert!(parse("no diff here\n").is_empty());
+    }
+}
@@ -0,0 +1,134 @@
+// git-enhancer/src/doctor_commands.rs
+//! The `doctor` subcommand.
+//!
+//! Checks what git-enhancer depends on (git, the configuration, the AI
+//! endpoints and the system clock) and prints one line per check, with a hint
+//! for each problem. Endpoints are only connected to, no prompt is sent.
+
+use std::process::Command;
+
+use crate::ai_utils::{probe_endpoint, run_concurrently};
+use crate::clock::{self, MAX_CLOCK_SKEW_SECS};
+use crate::config::AppConfig;
+use crate::errors::AppError;
+use crate::style::{self, Stream, Style};
+
+/// Outcome of one check
+enum Check {
+    Ok(String),
+    Warning(String),
+    Failed(String, Option<String>),
+}
+
+impl Check {
+    fn print(&self, name: &str) {
+        let (label, color, message, hint) = match self {
+            Check::Ok(message) => ("ok", Style::Green, message, None),
+            Check::Warning(message) => ("warn", Style::Yellow, message, None),
+            Check::Failed(message, hint) => ("fail", Style::Red, message, hint.as_ref()),
+        };
+        println!("{} {}: {}", style::paint(Stream::Stdout, color, &format!("[{:>4}]", label)), name, message);
+        if let Some(hint) = hint {
+            println!("       hint: {}", hint);
+        }
+    }
+}
+
+/// Handles the `doctor` subcommand
+///
+/// The configuration is loaded here rather than by the caller, so a broken
+/// configuration is reported as a failed check instead of ending the command.
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success, or an error if any check failed
+pub async fn handle_doctor() -> Result<(), AppError> {
+    let mut failures = 0;
+    let mut report = |name: &str, check: Check| {
+        if matches!(check, Check::Failed(..)) {
+            failures += 1;
+        }
+        check.print(name);
+    };
+
+    report("git", check_git());
+    let config = match AppConfig::load() {
+        Ok(config) => {
+            report("configuration", Check::Ok("loaded".to_string()));
+            Some(config)
+        }
+        Err(e) => {
+            report(
+                "configuration",
+                Check::Failed(e.to_string(), Some("`git-enhancer gitie-config edit` opens the file and validates it on save.".to_string())),
+            );
+            None
+        }
+    };
+    if let Some(config) = &config {
+        if !config.policy.ai.enabled {
+            report("AI", Check::Warning("disabled by the system policy".to_string()));
+        } else {
+            // Endpoints are probed side by side, but reported in order
+            let urls = endpoint_urls(config);
+            let results = run_concurrently(config.ai.max_concurrency, &urls, |url| check_endpoint(config, url)).await;
+            for (name, check) in results.into_iter().flatten() {
+                report(&name, check);
+            }
+        }
+    }
+
+    match failures {
+        0 => Ok(()),
+        n => Err(AppError::Generic(format!("{} check(s) failed", n))),
+    }
+}
+
+fn check_git() -> Check {
+    match Command::new("git").arg("--version").output() {
+        Ok(output) if output.status.success() => Check::Ok(String::from_utf8_lossy(&output.stdout).trim().to_string()),
+        Ok(output) => Check::Failed(String::from_utf8_lossy(&output.stderr).trim().to_string(), None),
+        Err(e) => Check::Failed(
+            format!("could not run git: {}", e),
+            Some("Install git and make sure it is on your PATH.".to_string()),
+        ),
+    }
+}
+
+/// Every endpoint the configuration can send requests to, in order of appearance
+fn endpoint_urls(config: &AppConfig) -> Vec<String> {
+    let mut aliases: Vec<_> = config.ai.aliases.iter().collect();
+    aliases.sort_by(|a, b| a.0.cmp(b.0));
+    let mut urls: Vec<String> = Vec::new();
+    let candidates = std::iter::once(&config.ai.api_url)
+        .chain(config.ai.endpoints.iter().map(|endpoint| &endpoint.url))
+        .chain(aliases.iter().filter_map(|(_, alias)| alias.api_url.as_ref()));
+    for url in candidates {
+        if !urls.contains(url) {
+            urls.push(url.clone());
+        }
+    }
+    urls
+}
+
+/// The connection check for `url`, followed by the clock check if the server reported its time
+async fn check_endpoint(config: &AppConfig, url: &str) -> Vec<(String, Check)> {
+    let name = format!("endpoint {}", url);
+    let probe = match probe_endpoint(config, url).await {
+        Ok(probe) => probe,
+        Err(e) => return vec![(name, Check::Failed(e.to_string(), e.hint()))],
+    };
+    let mut checks = vec![(name, Check::Ok(format!("reachable (HTTP {})", probe.status.as_u16())))];
+    if let Some(skew) = probe.clock_skew {
+        let check = if skew.abs() > MAX_CLOCK_SKEW_SECS {
+            Check::Failed(
+                format!("the system clock is {} the server's", clock::describe_skew(skew)),
+                Some(clock::SYNC_CLOCK_HINT.to_string()),
+            )
+        } else {
+            Check::Ok("in sync with the server".to_string())
+        };
+        checks.push(("clock".to_string(), check));
+    }
+    checks
+}
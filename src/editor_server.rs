@@ -0,0 +1,205 @@
+// git-enhancer/src/editor_server.rs
+//! The editor protocol of `serve --stdio`.
+//!
+//! Editor plugins (VS Code, Neovim) keep one `git-enhancer serve --stdio`
+//! running and send it JSON-RPC requests instead of starting a process per
+//! request: the configuration is loaded once and the connection to the model
+//! stays open. Methods:
+//!
+//! * `initialize` - the server's name, version and methods
+//! * `generateCommitMessage` - `{ cwd?, ticket? }`: a message for the staged changes
+//! * `explainDiff` - `{ cwd?, diff?, staged? }`: an explanation of `diff`, or
+//!   of the unstaged (or with `staged`, the staged) changes
+//! * `review` - `{ cwd?, diff?, staged? }`: a review of the same
+//!
+//! `cwd` is the directory to work in, by default the one the server was
+//! started in. It has to be in the same repository: the configuration,
+//! repository settings included, was loaded for that one. AI methods answer
+//! `{ text, model, usage }`; their failures are errors with code `-32000` and
+//! the message the command line would print.
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::path::{Path, PathBuf};
+
+use crate::ai_explainer::explain_diff;
+use crate::ai_utils::AiReply;
+use crate::commit_commands::generate_staged_message;
+use crate::config::AppConfig;
+use crate::errors::{AppError, GitError};
+use crate::git_commands::execute_git_command_and_capture_output;
+use crate::git_context::GitContext;
+use crate::json_rpc::{Handler, INVALID_PARAMS, RpcError, SERVER_ERROR};
+use crate::review::review_diff;
+
+const METHODS: &[&str] = &["initialize", "generateCommitMessage", "explainDiff", "review"];
+
+/// Parameters of `generateCommitMessage`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct CommitMessageParams {
+    cwd: Option<PathBuf>,
+    ticket: Option<String>,
+}
+
+/// Parameters of `explainDiff` and `review`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+struct DiffParams {
+    cwd: Option<PathBuf>,
+    diff: Option<String>,
+    staged: bool,
+}
+
+/// Answers editor requests
+pub struct EditorServer<'a> {
+    config: &'a AppConfig,
+    /// Where the server was started; requests without `cwd` work here
+    start_dir: PathBuf,
+    /// The top of the repository the server was started in, if any
+    start_repository: Option<PathBuf>,
+}
+
+impl<'a> EditorServer<'a> {
+    pub fn new(config: &'a AppConfig, start_dir: PathBuf) -> Self {
+        let start_repository = repository_root(&start_dir);
+        EditorServer { config, start_dir, start_repository }
+    }
+
+    /// A context for git in `cwd`, or the start directory
+    fn enter(&self, cwd: Option<&Path>) -> Result<GitContext, RpcError> {
+        let dir = cwd.unwrap_or(&self.start_dir);
+        // `.gitie.toml` and `gitie.*` git config of another repository were never loaded
+        if cwd.is_some() && repository_root(dir) != self.start_repository {
+            return Err(RpcError::new(
+                INVALID_PARAMS,
+                format!(
+                    "'{}' is not in the repository the server was started in; start a server there to use its settings",
+                    dir.display()
+                ),
+            ));
+        }
+        let cannot_work_in =
+            |reason: String| RpcError::new(INVALID_PARAMS, format!("cannot work in '{}': {}", dir.display(), reason));
+        let metadata = std::fs::metadata(dir).map_err(|e| cannot_work_in(e.to_string()))?;
+        if !metadata.is_dir() {
+            return Err(cannot_work_in("not a directory".to_string()));
+        }
+        // Requests run side by side, so git gets the directory rather than the
+        // process changing its own; and the repository may change between
+        // requests, e.g. another branch is checked out
+        Ok(GitContext::in_dir(dir))
+    }
+
+    /// `params.diff`, or the changes in the repository
+    fn diff(&self, params: &DiffParams) -> Result<String, RpcError> {
+        let git_context = self.enter(params.cwd.as_deref())?;
+        if let Some(diff) = &params.diff {
+            return Ok(diff.clone());
+        }
+        git_context.require_repository().map_err(server_error)?;
+        let mut args = vec!["diff".to_string()];
+        if params.staged {
+            args.push("--cached".to_string());
+        }
+        let output = git_context.git(&args).map_err(server_error)?;
+        if !output.status.success() {
+            return Err(server_error(
+                GitError::CommandFailed {
+                    command: format!("git {}", args.join(" ")),
+                    status_code: output.status.code(),
+                    stdout: output.stdout,
+                    stderr: output.stderr,
+                }
+                .into(),
+            ));
+        }
+        Ok(output.stdout)
+    }
+}
+
+impl Handler for EditorServer<'_> {
+    async fn handle(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let reply = match method {
+            "initialize" => {
+                return Ok(json!({
+                    "serverInfo": { "name": "git-enhancer", "version": env!("CARGO_PKG_VERSION") },
+                    "methods": METHODS,
+                }));
+            }
+            "generateCommitMessage" => {
+                let params: CommitMessageParams = parse_params(params)?;
+                let git_context = self.enter(params.cwd.as_deref())?;
+                generate_staged_message(self.config, &git_context, params.ticket.as_deref()).await
+            }
+            "explainDiff" => {
+                let diff = self.diff(&parse_params(params)?)?;
+                explain_diff(self.config, &diff).await.map_err(AppError::from)
+            }
+            "review" => {
+                let diff = self.diff(&parse_params(params)?)?;
+                review_diff(self.config, &diff).await.map_err(AppError::from)
+            }
+            _ => return Err(RpcError::method_not_found(method)),
+        };
+        reply.map(reply_value).map_err(server_error)
+    }
+}
+
+/// The top directory of the repository `dir` is in
+fn repository_root(dir: &Path) -> Option<PathBuf> {
+    let args = ["-C".to_string(), dir.to_string_lossy().into_owned(), "rev-parse".to_string(), "--show-toplevel".to_string()];
+    let output = execute_git_command_and_capture_output(&args).ok()?;
+    output.status.success().then(|| PathBuf::from(output.stdout.trim()))
+}
+
+/// `params` as `T`; no params are the defaults
+fn parse_params<T: for<'de> Deserialize<'de> + Default>(params: Value) -> Result<T, RpcError> {
+    if params.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn reply_value(reply: AiReply) -> Value {
+    json!({ "text": reply.text, "model": reply.model, "usage": reply.usage })
+}
+
+fn server_error(error: AppError) -> RpcError {
+    RpcError::new(SERVER_ERROR, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_methods_and_params() {
+        let config = AppConfig::without_ai();
+        let mut server = EditorServer::new(&config, std::env::current_dir().unwrap());
+        let result = server.handle("initialize", Value::Null).await.unwrap();
+        assert_eq!(result["methods"][1], "generateCommitMessage");
+
+        // An empty diff is answered without asking the model
+        let result = server.handle("review", json!({ "diff": "" })).await.unwrap();
+        assert_eq!(result["text"], "There are no changes to review.");
+        assert_eq!(result["model"], "");
+
+        let error = server.handle("explainDiff", json!({ "diff": 3 })).await.unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+        let error = server.handle("review", json!({ "stagd": true })).await.unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+        // Another repository's settings were never loaded
+        let error = server.handle("review", json!({ "cwd": "/", "diff": "" })).await.unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+        // A directory of the same repository is fine, and leaves the process where it was
+        let before = std::env::current_dir().unwrap();
+        let result = server.handle("review", json!({ "cwd": "src", "diff": "" })).await.unwrap();
+        assert_eq!(result["text"], "There are no changes to review.");
+        assert_eq!(std::env::current_dir().unwrap(), before);
+        // AI is disabled by the policy of `without_ai`
+        let error = server.handle("explainDiff", json!({ "diff": "+x\n" })).await.unwrap_err();
+        assert_eq!(error.code, SERVER_ERROR);
+        assert_eq!(server.handle("shutdown", Value::Null).await.unwrap_err().code, crate::json_rpc::METHOD_NOT_FOUND);
+    }
+}
@@ -1,224 +1,222 @@
+//! The error types returned throughout git-enhancer.
+
 use std::io;
-use std::process::ExitStatus; // For GitError::PassthroughFailed
 
-// General Application Error
-#[derive(Debug)]
-pub enum AppError {
-    Config(ConfigError),
-    Git(GitError),
-    AI(AIError),
-    Io(String, io::Error), // For general I/O errors not covered by specific types
-    Generic(String),       // For simple string-based errors
-}
+use thiserror::Error;
 
-impl std::fmt::Display for AppError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            AppError::Config(e) => write!(f, "Configuration error: {}", e),
-            AppError::Git(e) => write!(f, "Git command error: {}", e),
-            AppError::AI(e) => write!(f, "AI interaction error: {}", e),
-            AppError::Io(context, e) => write!(f, "I/O error while {}: {}", context, e),
-            AppError::Generic(s) => write!(f, "Application error: {}", s),
-        }
-    }
-}
+// Errors are grouped by where they come from; every variant is constructed
+// somewhere, and the tests below pin down the message of each one.
 
-impl std::error::Error for AppError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            AppError::Config(e) => Some(e),
-            AppError::Git(e) => Some(e),
-            AppError::AI(e) => Some(e),
-            AppError::Io(_, e) => Some(e),
-            AppError::Generic(_) => None,
-        }
-    }
+// General Application Error
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("Configuration error: {0}")]
+    Config(#[from] ConfigError),
+    #[error("Git command error: {0}")]
+    Git(#[from] GitError),
+    #[error("AI interaction error: {0}")]
+    AI(#[from] AIError),
+    #[error("Code hosting error: {0}")]
+    Forge(#[from] ForgeError),
+    /// For general I/O errors not covered by specific types
+    #[error("I/O error while {0}: {1}")]
+    Io(String, #[source] io::Error),
+    /// For simple string-based errors
+    #[error("Application error: {0}")]
+    Generic(String),
 }
 
 // Configuration Errors
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum ConfigError {
-    FileRead(String, io::Error),
-    FileWrite(String, io::Error),
-    TomlParse(String, toml::de::Error),
-    PromptFileMissing(String),
-    FieldMissing(String), // Added for missing required fields
-    GitConfigRead(String, io::Error),
-}
-
-impl std::fmt::Display for ConfigError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ConfigError::FileRead(file, e) => write!(f, "Failed to read file '{}': {}", file, e),
-            ConfigError::FileWrite(path, e) => {
-                write!(f, "Failed to write to path '{}': {}", path, e)
-            }
-            ConfigError::TomlParse(file, e) => {
-                write!(f, "Failed to parse TOML from file '{}': {}", file, e)
-            }
-            ConfigError::PromptFileMissing(file) => {
-                write!(f, "Critical prompt file '{}' is missing.", file)
-            }
-            ConfigError::FieldMissing(field) => write!(
-                f,
-                "Required configuration field '{}' is missing or invalid.",
-                field
-            ),
-            ConfigError::GitConfigRead(context, e) => {
-                write!(f, "Failed to read Git configuration for {}: {}", context, e)
-            }
-        }
-    }
-}
-
-impl std::error::Error for ConfigError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            ConfigError::FileRead(_, e) => Some(e),
-            ConfigError::FileWrite(_, e) => Some(e),
-            ConfigError::TomlParse(_, e) => Some(e),
-            ConfigError::PromptFileMissing(_) => None,
-            ConfigError::FieldMissing(_) => None, // Added match arm
-            ConfigError::GitConfigRead(_, e) => Some(e),
-        }
-    }
+    #[error("Failed to read file '{0}': {1}")]
+    FileRead(String, #[source] io::Error),
+    #[error("Failed to write to path '{0}': {1}")]
+    FileWrite(String, #[source] io::Error),
+    #[error("Failed to parse TOML from file '{0}': {1}")]
+    TomlParse(String, #[source] toml::de::Error),
+    #[error("Failed to read Git configuration for {0}: {1}")]
+    GitConfigRead(String, #[source] io::Error),
+    /// File path and every validation problem found in it
+    #[error("Invalid configuration in '{0}':{problems}", problems = bullet_list(.1))]
+    Invalid(String, Vec<String>),
 }
 
 // Git Command Errors
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum GitError {
+    /// A git command whose output was captured
+    #[error("Git command '{command}' failed{}{}{}", exit_code_suffix(*.status_code), labeled("Stdout", .stdout), labeled("Stderr", .stderr))]
     CommandFailed {
         command: String,
         status_code: Option<i32>,
         stdout: String,
         stderr: String,
     },
+    /// A git command that wrote to the terminal itself
+    #[error("Git passthrough command '{command}' failed{}", exit_code_suffix(*.status_code))]
     PassthroughFailed {
-        // For commands where output is not captured (used .status())
         command: String,
         status_code: Option<i32>,
     },
-    DiffError(io::Error), // Changed to io::Error as it's more idiomatic
+    #[error("Not a git repository (or any of the parent directories).")]
     NotARepository,
+    #[error("No changes staged for commit.")]
     NoStagedChanges,
-    Other(String), // Generic Git error
-}
-
-impl std::fmt::Display for GitError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            GitError::CommandFailed {
-                command,
-                status_code,
-                stdout,
-                stderr,
-            } => {
-                write!(f, "Git command '{}' failed", command)?;
-                if let Some(c) = status_code {
-                    write!(f, " with exit code {}", c)?;
-                }
-                if !stdout.is_empty() {
-                    write!(f, "\nStdout:\n{}", stdout)?;
-                }
-                if !stderr.is_empty() {
-                    write!(f, "\nStderr:\n{}", stderr)?;
-                }
-                Ok(())
-            }
-            GitError::PassthroughFailed {
-                command,
-                status_code,
-            } => {
-                write!(f, "Git passthrough command '{}' failed", command)?;
-                if let Some(c) = status_code {
-                    write!(f, " with exit code {}", c)?;
-                }
-                Ok(())
-            }
-            GitError::DiffError(e) => write!(f, "Failed to get git diff: {}", e),
-            GitError::NotARepository => write!(
-                f,
-                "Not a git repository (or any of the parent directories)."
-            ),
-            GitError::NoStagedChanges => write!(f, "No changes staged for commit."),
-            GitError::Other(s) => write!(f, "Git error: {}", s),
-        }
-    }
-}
-
-impl std::error::Error for GitError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            GitError::DiffError(e) => Some(e),
-            _ => None,
-        }
-    }
+    /// Paths that still have conflict markers
+    #[error("Unresolved conflicts in {paths}; fix them and mark them with 'git add' first.", paths = .0.join(", "))]
+    UnresolvedConflicts(Vec<String>),
+    #[error("Git error: {0}")]
+    Other(String),
 }
 
 // AI Interaction Errors
-#[derive(Debug)]
+#[derive(Debug, Error)]
 pub enum AIError {
-    RequestFailed(reqwest::Error),
-    ResponseParseFailed(reqwest::Error),
-    ApiResponseError(reqwest::StatusCode, String), // HTTP status was not success, String is response body
+    /// A transport failure not covered by the variants below
+    #[error("AI API request failed: {0}")]
+    RequestFailed(#[source] reqwest::Error),
+    /// The connection or response took longer than `[ai.timeouts]` allow
+    #[error("AI API request timed out: {0}")]
+    Timeout(#[source] reqwest::Error),
+    /// Nothing is listening at the endpoint's address
+    #[error("AI API refused the connection (is the server running?): {0}")]
+    ConnectionRefused(#[source] reqwest::Error),
+    /// The endpoint's host name could not be resolved
+    #[error("Could not resolve the AI API host: {0}")]
+    Dns(#[source] reqwest::Error),
+    /// The TLS handshake failed, e.g. over an untrusted certificate
+    #[error("TLS error connecting to the AI API: {}", root_cause(.0))]
+    Tls(#[source] reqwest::Error),
+    /// A request failed while the local clock is far off the server's
+    #[error("{cause} (the system clock is {} the AI API's)", crate::clock::describe_skew(*.skew_seconds))]
+    ClockSkew { skew_seconds: i64, cause: Box<AIError> },
+    #[error("Failed to parse AI API JSON response: {0}")]
+    ResponseParseFailed(#[source] reqwest::Error),
+    /// 401 or 403, with the response body
+    #[error("AI API rejected the credentials ({0}), check the API key: {1}")]
+    AuthFailed(reqwest::StatusCode, String),
+    /// 429, with the `Retry-After` seconds if the API sent them
+    #[error("AI API rate limit reached{}: {body}", .retry_after.map(|s| format!(" (retry after {}s)", s)).unwrap_or_default())]
+    RateLimited { retry_after: Option<u64>, body: String },
+    /// The endpoint doesn't serve the requested model
+    #[error("Model '{model}' is not available at the AI API: {body}")]
+    ModelNotFound { model: String, body: String },
+    /// The prompt doesn't fit in the model's context window
+    #[error("The request is too long for the model's context window: {0}")]
+    ContextLengthExceeded(String),
+    /// Any other unsuccessful HTTP status, with the response body
+    #[error("AI API responded with error {0}: {1}")]
+    ApiResponseError(reqwest::StatusCode, String),
+    #[error("AI API response contained no choices.")]
     NoChoiceInResponse,
+    /// An embeddings response without exactly one vector per input
+    #[error("AI API returned {received} embedding(s) for {expected} input(s).")]
+    EmbeddingCountMismatch { expected: usize, received: usize },
+    #[error("AI returned an empty message.")]
     EmptyMessage,
-    ExplanationGenerationFailed(String), // For errors from ai_explainer
-    ExplainerConfigurationError(String), // For config errors specific to explainer
-    ExplainerNetworkError(String), // For network errors from explainer not covered by reqwest::Error
+    /// Request refused by the system policy file
+    #[error("Blocked by policy: {0}")]
+    DisabledByPolicy(String),
+    /// Stopped by Ctrl-C
+    #[error("AI request cancelled")]
+    Cancelled,
+    /// A reply showing signs that repository content steered the model
+    #[error("AI reply rejected because {0}")]
+    SuspiciousOutput(String),
+    /// A reply that should be JSON still didn't parse after the repair requests
+    #[error("AI response is not valid JSON after {attempts} attempt(s): {error}")]
+    MalformedJson { attempts: u32, error: String },
+    /// Not finished within `--timeout`, retries included
+    #[error("AI request timed out after {0}s (--timeout)")]
+    DeadlineExceeded(u64),
+    /// A `[hooks]` command denied the request or its reply
+    #[error("AI request blocked by the {hook} hook: {reason}")]
+    BlockedByHook { hook: String, reason: String },
+    /// A `[hooks]` command failed or answered something else than a hook answer
+    #[error("The {hook} hook failed: {reason}")]
+    HookFailed { hook: String, reason: String },
+    /// The `post_generate_cmd` script failed or printed nothing
+    #[error("post_generate_cmd `{command}` failed: {reason}")]
+    PostGenerateFailed { command: String, reason: String },
 }
 
-impl std::fmt::Display for AIError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl AIError {
+    /// A concrete way to fix the problem, where there is one beyond the message itself
+    pub fn hint(&self) -> Option<String> {
         match self {
-            AIError::RequestFailed(e) => write!(f, "AI API request failed: {}", e),
-            AIError::ResponseParseFailed(e) => {
-                write!(f, "Failed to parse AI API JSON response: {}", e)
+            AIError::Tls(e) => {
+                let chain = error_chain_text(e);
+                let hint = if ["expired", "not yet valid", "notvalidyet"].iter().any(|text| chain.contains(text)) {
+                    "The certificate looks expired or not yet valid, which usually means the system clock is wrong. \
+                     Check it with `date` and turn on time synchronization (e.g. `sudo timedatectl set-ntp true`)."
+                } else if ["hostname mismatch", "not valid for", "does not match"].iter().any(|text| chain.contains(text)) {
+                    "The certificate belongs to a different host than the one in the endpoint URL. Check \
+                     `ai.api_url` (or the `[[ai.endpoints]]` entry), and whether a proxy is answering in its place."
+                } else if ["self signed", "self-signed", "local issuer", "unknown issuer", "unknownissuer", "verify failed"]
+                    .iter()
+                    .any(|text| chain.contains(text))
+                {
+                    "This machine doesn't trust the certificate. Behind a TLS-inspecting proxy, add the proxy's CA \
+                     certificate to the system trust store (e.g. copy it to /usr/local/share/ca-certificates and run \
+                     `sudo update-ca-certificates`), or point SSL_CERT_FILE at a bundle that includes it."
+                } else if ["wrong version number", "packet length too long", "http request"].iter().any(|text| chain.contains(text)) {
+                    "The server doesn't speak TLS at this address. Local servers such as Ollama usually serve plain \
+                     HTTP: use an http:// URL for them."
+                } else {
+                    "Check the endpoint URL, and run `git-enhancer doctor` to test the connection to every \
+                     configured endpoint."
+                };
+                Some(hint.to_string())
             }
-            AIError::ApiResponseError(status, body) => {
-                write!(f, "AI API responded with error {}: {}", status, body)
-            }
-            AIError::NoChoiceInResponse => write!(f, "AI API response contained no choices."),
-            AIError::EmptyMessage => write!(f, "AI returned an empty message."),
-            AIError::ExplanationGenerationFailed(s) => {
-                write!(f, "AI explanation generation failed: {}", s)
-            }
-            AIError::ExplainerConfigurationError(s) => {
-                write!(f, "AI explainer configuration error: {}", s)
-            }
-            AIError::ExplainerNetworkError(s) => write!(f, "AI explainer network error: {}", s),
-        }
-    }
-}
-
-impl std::error::Error for AIError {
-    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        match self {
-            AIError::RequestFailed(e) => Some(e),
-            AIError::ResponseParseFailed(e) => Some(e),
-            _ => None, // Other variants are self-contained or wrap String
+            AIError::ClockSkew { .. } => Some(crate::clock::SYNC_CLOCK_HINT.to_string()),
+            _ => None,
         }
     }
 }
 
-// --- From implementations for AppError ---
-
-impl From<ConfigError> for AppError {
-    fn from(err: ConfigError) -> AppError {
-        AppError::Config(err)
-    }
+// Code Hosting Errors (GitHub, Gitea and the like)
+#[derive(Debug, Error)]
+pub enum ForgeError {
+    /// The remote the branch is pushed to is not hosted there
+    #[error("Remote '{remote}' ({url}) is not a repository on {host}")]
+    UnsupportedRemote { remote: String, url: String, host: String },
+    /// The remote's host is neither configured nor recognizably a known forge
+    #[error("Could not tell which forge hosts {host}; set forge.kind to github or gitea")]
+    UnknownForge { host: String },
+    /// Neither the configuration, the environment nor git's credential helper has a token
+    #[error("No API token for {host}: set {setting}, or store one with `git credential approve`")]
+    MissingToken { host: String, setting: String },
+    /// A transport failure talking to the API
+    #[error("Request to {host} failed: {}", root_cause(.source))]
+    RequestFailed { host: String, #[source] source: reqwest::Error },
+    /// The API answered with an error status
+    #[error("{host} rejected the request ({status}): {message}")]
+    Api { host: String, status: u16, message: String },
 }
 
-impl From<GitError> for AppError {
-    fn from(err: GitError) -> AppError {
-        AppError::Git(err)
+/// The messages of the causes of `error`, lowercased, one per line.
+///
+/// `error` itself is left out: for a reqwest error it only repeats the URL,
+/// which must not be mistaken for a description of the problem.
+pub fn error_chain_text(error: &dyn std::error::Error) -> String {
+    let mut text = String::new();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        text.push_str(&cause.to_string().to_lowercase());
+        text.push('\n');
+        source = cause.source();
     }
+    text
 }
 
-impl From<AIError> for AppError {
-    fn from(err: AIError) -> AppError {
-        AppError::AI(err)
+/// The message of the innermost cause of `error`, which names the actual problem
+fn root_cause(error: &dyn std::error::Error) -> String {
+    let mut cause = error;
+    while let Some(source) = cause.source() {
+        cause = source;
     }
+    cause.to_string()
 }
 
 impl From<io::Error> for AppError {
@@ -228,20 +226,17 @@ impl From<io::Error> for AppError {
     }
 }
 
-// Helper for converting Command output to GitError when output is captured
-pub fn map_command_error(
-    cmd_str: &str,
-    output: std::process::Output, // Takes ownership
-    status: ExitStatus,           // Provided separately as output is consumed for stdout/stderr
-) -> GitError {
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-    GitError::CommandFailed {
-        command: cmd_str.to_string(),
-        status_code: status.code(),
-        stdout,
-        stderr,
-    }
+fn bullet_list(items: &[String]) -> String {
+    items.iter().map(|item| format!("\n  - {}", item)).collect()
+}
+
+fn exit_code_suffix(status_code: Option<i32>) -> String {
+    status_code.map(|code| format!(" with exit code {}", code)).unwrap_or_default()
+}
+
+/// `\n<label>:\n<text>`, or nothing for empty output
+fn labeled(label: &str, text: &str) -> String {
+    if text.is_empty() { String::new() } else { format!("\n{}:\n{}", label, text) }
 }
 
 #[cfg(test)]
@@ -262,12 +257,6 @@ mod tests {
         })
     }
 
-    fn mock_serde_json_error() -> serde_json::Error {
-        serde_json::from_str::<serde_json::Value>("{invalid_json")
-            .err()
-            .unwrap()
-    }
-
     fn mock_toml_error() -> toml::de::Error {
         toml::from_str::<toml::Value>("invalid_toml").err().unwrap()
     }
@@ -291,12 +280,6 @@ mod tests {
                 .starts_with("Failed to parse TOML from file 'test_config.toml': ")
         );
 
-        let err_prompt_missing = ConfigError::PromptFileMissing("prompts/my_prompt".to_string());
-        assert_eq!(
-            format!("{}", err_prompt_missing),
-            "Critical prompt file 'prompts/my_prompt' is missing."
-        );
-
         let git_config_io_err =
             io::Error::new(io::ErrorKind::PermissionDenied, "permission denied");
         let err_git_config_read =
@@ -306,22 +289,18 @@ mod tests {
             "Failed to read Git configuration for user name: permission denied"
         );
 
-        let err_field_missing = ConfigError::FieldMissing("model_name".to_string());
+        let err_invalid = ConfigError::Invalid(
+            "config.toml".to_string(),
+            vec!["first problem".to_string(), "second problem".to_string()],
+        );
         assert_eq!(
-            format!("{}", err_field_missing),
-            "Required configuration field 'model_name' is missing or invalid."
+            format!("{}", err_invalid),
+            "Invalid configuration in 'config.toml':\n  - first problem\n  - second problem"
         );
     }
 
     #[test]
     fn test_git_error_display() {
-        let io_err_for_diff = io::Error::new(io::ErrorKind::Other, "diff generation failed");
-        let err_diff = GitError::DiffError(io_err_for_diff);
-        assert_eq!(
-            format!("{}", err_diff),
-            "Failed to get git diff: diff generation failed"
-        );
-
         let err_not_repo = GitError::NotARepository;
         assert_eq!(
             format!("{}", err_not_repo),
@@ -334,6 +313,12 @@ mod tests {
             "No changes staged for commit."
         );
 
+        let err_conflicts = GitError::UnresolvedConflicts(vec!["a.rs".to_string(), "b.rs".to_string()]);
+        assert_eq!(
+            format!("{}", err_conflicts),
+            "Unresolved conflicts in a.rs, b.rs; fix them and mark them with 'git add' first."
+        );
+
         let err_cmd_failed_simple = GitError::CommandFailed {
             command: "git version".to_string(),
             status_code: Some(128),
@@ -391,6 +376,15 @@ mod tests {
             "AI API responded with error 500 Internal Server Error: Server meltdown"
         );
 
+        let err_rate_limited = AIError::RateLimited { retry_after: Some(20), body: "slow down".to_string() };
+        assert_eq!(format!("{}", err_rate_limited), "AI API rate limit reached (retry after 20s): slow down");
+        let err_model = AIError::ModelNotFound { model: "qwen3".to_string(), body: "not found".to_string() };
+        assert_eq!(format!("{}", err_model), "Model 'qwen3' is not available at the AI API: not found");
+
+        let err_skew = AIError::ClockSkew { skew_seconds: -7_300, cause: Box::new(AIError::EmptyMessage) };
+        assert_eq!(format!("{}", err_skew), "AI returned an empty message. (the system clock is 2 hours behind the AI API's)");
+        assert!(err_skew.hint().is_some_and(|hint| hint.contains("timedatectl")));
+
         let err_no_choice = AIError::NoChoiceInResponse;
         assert_eq!(
             format!("{}", err_no_choice),
@@ -403,32 +397,59 @@ mod tests {
             "AI returned an empty message."
         );
 
-        let err_expl_gen = AIError::ExplanationGenerationFailed("model unavailable".to_string());
+        let err_policy = AIError::DisabledByPolicy("AI features are disabled".to_string());
         assert_eq!(
-            format!("{}", err_expl_gen),
-            "AI explanation generation failed: model unavailable"
+            format!("{}", err_policy),
+            "Blocked by policy: AI features are disabled"
         );
+    }
 
-        let err_expl_conf = AIError::ExplainerConfigurationError("missing prompt".to_string());
+    #[test]
+    fn test_forge_error_display() {
+        let remote = ForgeError::UnsupportedRemote {
+            remote: "origin".to_string(),
+            url: "git@gitlab.com:a/b.git".to_string(),
+            host: "github.com".to_string(),
+        };
+        assert_eq!(remote.to_string(), "Remote 'origin' (git@gitlab.com:a/b.git) is not a repository on github.com");
+        let unknown = ForgeError::UnknownForge { host: "git.example.com".to_string() };
+        assert_eq!(unknown.to_string(), "Could not tell which forge hosts git.example.com; set forge.kind to github or gitea");
+        let token = ForgeError::MissingToken { host: "github.com".to_string(), setting: "github.token or GITHUB_TOKEN".to_string() };
         assert_eq!(
-            format!("{}", err_expl_conf),
-            "AI explainer configuration error: missing prompt"
+            token.to_string(),
+            "No API token for github.com: set github.token or GITHUB_TOKEN, or store one with `git credential approve`"
         );
-
-        let err_expl_net = AIError::ExplainerNetworkError("connection refused".to_string());
+        let request = ForgeError::RequestFailed { host: "github.com".to_string(), source: mock_reqwest_error() };
+        assert!(request.to_string().starts_with("Request to github.com failed: "));
+        let api = ForgeError::Api { host: "github.com".to_string(), status: 422, message: "Validation Failed".to_string() };
         assert_eq!(
-            format!("{}", err_expl_net),
-            "AI explainer network error: connection refused"
+            AppError::from(api).to_string(),
+            "Code hosting error: github.com rejected the request (422): Validation Failed"
         );
     }
 
+    #[test]
+    fn test_sources_are_kept() {
+        use std::error::Error as _;
+
+        let io_err = io::Error::new(io::ErrorKind::NotFound, "file not found");
+        let app_err = AppError::from(ConfigError::FileRead("a.toml".to_string(), io_err));
+        let config_err = app_err.source().unwrap();
+        assert_eq!(config_err.to_string(), "Failed to read file 'a.toml': file not found");
+        assert_eq!(config_err.source().unwrap().to_string(), "file not found");
+        assert!(AppError::Generic("x".to_string()).source().is_none());
+
+        let passthrough = GitError::PassthroughFailed { command: "git push".to_string(), status_code: None };
+        assert_eq!(passthrough.to_string(), "Git passthrough command 'git push' failed");
+    }
+
     #[test]
     fn test_app_error_display() {
-        let config_err = ConfigError::PromptFileMissing("prompts/sys".to_string());
+        let config_err = ConfigError::Invalid("config.toml".to_string(), vec!["bad".to_string()]);
         let app_config_err = AppError::from(config_err);
         assert_eq!(
             format!("{}", app_config_err),
-            "Configuration error: Critical prompt file 'prompts/sys' is missing."
+            "Configuration error: Invalid configuration in 'config.toml':\n  - bad"
         );
 
         let git_err = GitError::NotARepository;
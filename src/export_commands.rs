@@ -0,0 +1,48 @@
+// git-enhancer/src/export_commands.rs
+//! The `export-history` subcommand.
+//!
+//! Prints the AI interactions recorded in the interaction log (see
+//! `interaction_log`) as a dataset, with secrets redacted, for teams that
+//! fine-tune local models on the messages they accepted.
+
+use std::io::{self, Write};
+
+use crate::cli::ExportHistoryArgs;
+use crate::config::AppConfig;
+use crate::errors::AppError;
+use crate::interaction_log;
+use crate::ui;
+
+/// Handles the `export-history` subcommand
+///
+/// # Arguments
+///
+/// * `args` - Export format from CLI
+/// * `config` - Application configuration, for the secrets to redact
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub fn handle_export_history(args: ExportHistoryArgs, config: &AppConfig) -> Result<(), AppError> {
+    tracing::debug!("Exporting the interaction history as {}", args.format);
+    let interactions = interaction_log::read_all().map_err(|e| AppError::Io("reading the interaction log".to_string(), e))?;
+    if interactions.is_empty() {
+        let hint = if config.history.enabled {
+            "No AI interactions recorded yet."
+        } else {
+            "No AI interactions recorded; set [history] enabled = true to record them."
+        };
+        ui::status(hint);
+        return Ok(());
+    }
+    let secrets = config.configured_secrets();
+    let mut stdout = io::stdout().lock();
+    for interaction in &interactions {
+        let line = serde_json::to_string(&interaction.redacted(&secrets))
+            .map_err(|e| AppError::Generic(format!("could not serialize an interaction: {}", e)))?;
+        writeln!(stdout, "{}", line).map_err(|e| AppError::Io("writing the export".to_string(), e))?;
+    }
+    let plural = if interactions.len() == 1 { "" } else { "s" };
+    ui::status(&format!("Exported {} interaction{}", interactions.len(), plural));
+    Ok(())
+}
@@ -0,0 +1,345 @@
+// git-enhancer/src/forge.rs
+//! Code hosting services ("forges") that pull requests are opened on.
+//!
+//! `pr-describe --create` talks to a [`Forge`]: GitHub (see `github`) or Gitea
+//! and its fork Forgejo (see `gitea`). Which one hosts the repository is told
+//! from the remote's URL: the hosts configured in `[github]` and `[gitea]` and
+//! well-known public ones are recognized directly, and any other host is asked
+//! whether it serves the Gitea API. `[forge] kind` (or `git config gitie.forge`)
+//! skips the detection.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::io::Write;
+use std::pin::Pin;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use crate::config::AppConfig;
+use crate::errors::ForgeError;
+use crate::gitea::GiteaClient;
+use crate::github::GitHubClient;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long an unknown host may take to say whether it runs Gitea
+const DETECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Public hosts running Gitea or Forgejo
+const GITEA_HOSTS: &[&str] = &["codeberg.org", "gitea.com"];
+
+/// What the methods of a [`Forge`] return
+pub type ForgeFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, ForgeError>> + Send + 'a>>;
+
+/// The pull request API of one repository on a forge
+pub trait Forge: Send + Sync {
+    /// Name shown to the user, e.g. "GitHub"
+    fn name(&self) -> &'static str;
+
+    /// Opens `pull_request`
+    fn create_pull_request<'a>(&'a self, pull_request: &'a NewPullRequest<'a>) -> ForgeFuture<'a, PullRequest>;
+
+    /// Adds `labels` (by name) to pull request `number`
+    fn add_labels<'a>(&'a self, number: u64, labels: &'a [String]) -> ForgeFuture<'a, ()>;
+
+    /// Asks `reviewers` (user names) to review pull request `number`
+    fn request_reviewers<'a>(&'a self, number: u64, reviewers: &'a [String]) -> ForgeFuture<'a, ()>;
+}
+
+// 代码托管平台的配置
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ForgeConfig {
+    /// Which forge hosts the repository; `auto` tells from the remote URL
+    #[serde(default)]
+    pub kind: ForgeKind,
+}
+
+/// A kind of forge, or `Auto` to detect it
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeKind {
+    #[default]
+    Auto,
+    #[serde(rename = "github")]
+    GitHub,
+    /// Gitea or Forgejo
+    Gitea,
+}
+
+impl ForgeKind {
+    /// The name used in the configuration
+    pub fn name(self) -> &'static str {
+        match self {
+            ForgeKind::Auto => "auto",
+            ForgeKind::GitHub => "github",
+            ForgeKind::Gitea => "gitea",
+        }
+    }
+}
+
+/// A pull request to open
+#[derive(Serialize, Debug)]
+pub struct NewPullRequest<'a> {
+    pub title: &'a str,
+    pub body: &'a str,
+    /// Branch with the changes
+    pub head: &'a str,
+    /// Branch the changes are merged into
+    pub base: &'a str,
+    pub draft: bool,
+}
+
+/// A pull request that was opened
+#[derive(Deserialize, Debug)]
+pub struct PullRequest {
+    pub number: u64,
+    pub html_url: String,
+}
+
+/// `owner/name` of a repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepositoryId {
+    pub owner: String,
+    pub name: String,
+}
+
+impl fmt::Display for RepositoryId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.owner, self.name)
+    }
+}
+
+/// The API client for the repository at `url`, the URL of `remote`
+pub async fn connect(config: &AppConfig, remote: &str, url: &str) -> Result<Box<dyn Forge>, ForgeError> {
+    let unsupported = |host: String| ForgeError::UnsupportedRemote { remote: remote.to_string(), url: url.to_string(), host };
+    let host = remote_host(url).ok_or_else(|| unsupported("a forge".to_string()))?;
+    let kind = match config.forge.kind {
+        ForgeKind::Auto => match known_kind(config, &host) {
+            Some(kind) => kind,
+            None => detect(&host).await?,
+        },
+        kind => kind,
+    };
+    tracing::debug!("Remote {} ({}) is on {:?}", remote, url, kind);
+    match kind {
+        ForgeKind::GitHub => {
+            let host = config.github.host();
+            let repository = parse_remote_url(url, &host).ok_or_else(|| unsupported(host.clone()))?;
+            let token = token(config.github.token.as_deref(), crate::github::TOKEN_VARIABLES, &host, "github.token or GITHUB_TOKEN")?;
+            Ok(Box::new(GitHubClient::new(&config.github, token, repository)?))
+        }
+        // Detection never answers `Auto`
+        ForgeKind::Gitea | ForgeKind::Auto => {
+            let repository = parse_remote_url(url, &host).ok_or_else(|| unsupported(host.clone()))?;
+            // Any host that answers the Gitea API gets here, so the configured
+            // token is only sent to the host it was configured for
+            let token = if config.gitea.serves(&host) {
+                token(config.gitea.token.as_deref(), crate::gitea::TOKEN_VARIABLES, &host, "gitea.token or GITEA_TOKEN")?
+            } else {
+                token(None, &[], &host, "gitea.api_url to this host's API and gitea.token")?
+            };
+            Ok(Box::new(GiteaClient::new(&config.gitea.api_url(&host), &host, token, repository)?))
+        }
+    }
+}
+
+/// The forge `host` is known to run, from the configuration or the public hosts
+fn known_kind(config: &AppConfig, host: &str) -> Option<ForgeKind> {
+    if host.eq_ignore_ascii_case(&config.github.host()) {
+        return Some(ForgeKind::GitHub);
+    }
+    let gitea_host = config.gitea.host();
+    if gitea_host.is_some_and(|gitea| gitea.eq_ignore_ascii_case(host)) || GITEA_HOSTS.contains(&host) {
+        return Some(ForgeKind::Gitea);
+    }
+    None
+}
+
+/// Asks `host` for its Gitea version; Forgejo answers the same
+async fn detect(host: &str) -> Result<ForgeKind, ForgeError> {
+    #[derive(Deserialize)]
+    struct Version {
+        #[allow(dead_code)]
+        version: String,
+    }
+    let client = http_client(host)?;
+    let response = client
+        .get(format!("https://{}/api/v1/version", host))
+        .timeout(DETECT_TIMEOUT)
+        .send()
+        .await;
+    if let Ok(response) = response
+        && response.status().is_success()
+        && response.json::<Version>().await.is_ok()
+    {
+        return Ok(ForgeKind::Gitea);
+    }
+    Err(ForgeError::UnknownForge { host: host.to_string() })
+}
+
+/// The host name in a remote URL such as `https://host/owner/name.git`,
+/// `ssh://git@host:22/owner/name` or the scp-like `git@host:owner/name.git`
+fn remote_host(url: &str) -> Option<String> {
+    let authority = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.0,
+        None => url.split_once(':')?.0,
+    };
+    let host = authority.rsplit('@').next()?.split(':').next()?;
+    (!host.is_empty()).then(|| host.to_lowercase())
+}
+
+/// The repository a remote URL points to, if it is on `host`
+pub fn parse_remote_url(url: &str, host: &str) -> Option<RepositoryId> {
+    if !remote_host(url)?.eq_ignore_ascii_case(host) {
+        return None;
+    }
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/')?.1,
+        None => url.split_once(':')?.1,
+    };
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (owner, name) = path.split_once('/')?;
+    (!owner.is_empty() && !name.is_empty() && !name.contains('/'))
+        .then(|| RepositoryId { owner: owner.to_string(), name: name.to_string() })
+}
+
+/// A token for `host`: `configured`, else the first of the environment `variables`
+/// that is set, else what git's credential helper has stored for `https://<host>`.
+///
+/// The credential helper is where system keyrings (macOS Keychain, Windows
+/// Credential Manager, libsecret) keep the token git itself uses for HTTPS pushes.
+pub(crate) fn token(configured: Option<&str>, variables: &[&str], host: &str, setting: &str) -> Result<String, ForgeError> {
+    if let Some(token) = configured.filter(|token| !token.is_empty()) {
+        return Ok(token.to_string());
+    }
+    if let Some(token) = variables
+        .iter()
+        .find_map(|name| std::env::var(name).ok().filter(|token| !token.is_empty()))
+    {
+        return Ok(token);
+    }
+    credential_helper_password(host).ok_or_else(|| ForgeError::MissingToken {
+        host: host.to_string(),
+        setting: setting.to_string(),
+    })
+}
+
+/// The password git's credential helper has stored for `https://<host>`, without prompting
+fn credential_helper_password(host: &str) -> Option<String> {
+    let mut child = Command::new("git")
+        .args(["credential", "fill"])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    child
+        .stdin
+        .take()?
+        .write_all(format!("protocol=https\nhost={}\n\n", host).as_bytes())
+        .ok()?;
+    let output = child.wait_with_output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.strip_prefix("password="))
+        .filter(|password| !password.is_empty())
+        .map(str::to_string)
+}
+
+/// HTTP client for the API of `host`
+pub(crate) fn http_client(host: &str) -> Result<reqwest::Client, ForgeError> {
+    reqwest::Client::builder()
+        .user_agent(concat!("git-enhancer/", env!("CARGO_PKG_VERSION")))
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|source| ForgeError::RequestFailed { host: host.to_string(), source })
+}
+
+/// Sends `request` to `host`, turning error statuses into `ForgeError::Api`
+pub(crate) async fn send(host: &str, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ForgeError> {
+    let response = request
+        .send()
+        .await
+        .map_err(|source| ForgeError::RequestFailed { host: host.to_string(), source })?;
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let text = response.text().await.unwrap_or_default();
+    Err(ForgeError::Api { host: host.to_string(), status: status.as_u16(), message: error_message(&text) })
+}
+
+/// Reads the pull request from a successful response of `host`
+pub(crate) async fn read_pull_request(host: &str, response: reqwest::Response) -> Result<PullRequest, ForgeError> {
+    response
+        .json()
+        .await
+        .map_err(|source| ForgeError::RequestFailed { host: host.to_string(), source })
+}
+
+/// The `message` of an error body (GitHub and Gitea use the same shape) and the
+/// messages of its `errors`, or the body itself
+fn error_message(body: &str) -> String {
+    #[derive(Deserialize)]
+    struct ErrorBody {
+        message: String,
+        #[serde(default)]
+        errors: Vec<serde_json::Value>,
+    }
+    match serde_json::from_str::<ErrorBody>(body) {
+        Ok(error) => {
+            let details: Vec<String> = error
+                .errors
+                .iter()
+                .filter_map(|detail| detail.get("message").and_then(|message| message.as_str()).map(str::to_string))
+                .collect();
+            if details.is_empty() { error.message } else { format!("{} ({})", error.message, details.join("; ")) }
+        }
+        Err(_) => body.trim().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote_url_forms() {
+        let expected = Some(RepositoryId { owner: "nehcuh".to_string(), name: "git-enhauser".to_string() });
+        assert_eq!(parse_remote_url("https://github.com/nehcuh/git-enhauser.git", "github.com"), expected);
+        assert_eq!(parse_remote_url("https://token@github.com/nehcuh/git-enhauser/", "github.com"), expected);
+        assert_eq!(parse_remote_url("git@github.com:nehcuh/git-enhauser.git", "github.com"), expected);
+        assert_eq!(parse_remote_url("ssh://git@GitHub.com:22/nehcuh/git-enhauser", "github.com"), expected);
+        assert_eq!(parse_remote_url("git@gitlab.com:nehcuh/git-enhauser.git", "github.com"), None);
+        assert_eq!(parse_remote_url("https://github.com/nehcuh", "github.com"), None);
+        assert_eq!(parse_remote_url("/srv/git/project.git", "github.com"), None);
+        assert_eq!(remote_host("ssh://git@git.example.com:2222/a/b.git").as_deref(), Some("git.example.com"));
+        assert_eq!(
+            error_message(r#"{"message":"Validation Failed","errors":[{"message":"A pull request already exists"}]}"#),
+            "Validation Failed (A pull request already exists)"
+        );
+    }
+
+    #[test]
+    fn test_known_kind_from_configured_and_public_hosts() {
+        let mut config = AppConfig::without_ai();
+        assert_eq!(known_kind(&config, "github.com"), Some(ForgeKind::GitHub));
+        assert_eq!(known_kind(&config, "codeberg.org"), Some(ForgeKind::Gitea));
+        assert_eq!(known_kind(&config, "git.example.com"), None);
+        config.gitea.api_url = Some("https://git.example.com/api/v1".to_string());
+        assert_eq!(known_kind(&config, "git.example.com"), Some(ForgeKind::Gitea));
+        // The configured API and token are for that host only
+        assert!(config.gitea.serves("git.example.com") && !config.gitea.serves("codeberg.org"));
+        assert_eq!(config.gitea.api_url("git.example.com"), "https://git.example.com/api/v1");
+        assert_eq!(config.gitea.api_url("codeberg.org"), "https://codeberg.org/api/v1");
+        config.github.api_url = "https://ghe.example.com/api/v3".to_string();
+        assert_eq!(known_kind(&config, "ghe.example.com"), Some(ForgeKind::GitHub));
+        assert_eq!(known_kind(&config, "github.com"), None);
+    }
+}
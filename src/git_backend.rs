@@ -0,0 +1,1052 @@
+//! Read-only repository access used to build AI prompts.
+//!
+//! [`CliBackend`] shells out to `git` and parses its machine-readable output. With the
+//! `git2` feature enabled, [`Git2Backend`] reads the same data in-process through
+//! libgit2, which avoids spawning a process for each of the many small reads.
+
+use crate::capture;
+#[cfg(feature = "git2")]
+use crate::capture::SpillBuffer;
+#[cfg(feature = "git2")]
+use std::io::Write;
+use crate::errors::GitError;
+use crate::git_commands::{map_output_to_git_command_error, DiffSource};
+use crate::types::{GitBlameLine, GitCommit, GitFileStatus, GitStatus, InProgressOperation, RepositoryState};
+
+use serde::Deserialize;
+use std::io::{self, BufRead};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Bytes of a diff kept verbatim. Beyond this the diff spills to disk, and the
+/// files that don't fit keep only their headers and line counts.
+const DIFF_MEMORY_LIMIT: usize = 32 * 1024 * 1024;
+
+/// How renamed and copied files are detected when collecting a diff
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RenameDetection {
+    /// Every move shows up as a deletion plus an addition (`--no-renames`)
+    Off,
+    /// Detect renames (`-M`)
+    Renames,
+    /// Detect renames and copies (`-M -C`)
+    #[default]
+    Copies,
+}
+
+/// Options for collecting a diff
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Lines of context around each change (`-U<n>`); git's default when `None`
+    pub context_lines: Option<u32>,
+    pub renames: RenameDetection,
+    /// Limits the diff to these paths (`-- <pathspec>...`); everything when empty
+    pub pathspecs: Vec<String>,
+}
+
+/// Repository reads needed by the AI features
+pub trait GitBackend {
+    /// The changes from `source` as a unified diff, like `git diff [--staged] [-U<n>] [-M] [-C]`
+    fn diff(&self, source: DiffSource, options: &DiffOptions) -> Result<String, GitError>;
+
+    /// Staged, unstaged and untracked files plus the current branch
+    fn status(&self) -> Result<GitStatus, GitError>;
+
+    /// The most recent commits reachable from `HEAD`, newest first
+    fn log(&self, max_count: usize) -> Result<Vec<GitCommit>, GitError>;
+
+    /// Who last changed each line of `path` (relative to the work tree)
+    fn blame(&self, path: &Path) -> Result<Vec<GitBlameLine>, GitError>;
+
+    /// Commits between two revisions of the submodule at `path`, one
+    /// `<short hash> <subject>` line each, like `git log --oneline old..new`
+    fn submodule_log(&self, path: &Path, old: &str, new: &str) -> Result<String, GitError>;
+
+    /// Size in bytes of an object, given its full or abbreviated hash
+    fn object_size(&self, object: &str) -> Result<u64, GitError>;
+
+    /// Whether HEAD is detached and which merge, rebase, cherry-pick, revert or
+    /// bisect is in progress
+    fn repository_state(&self) -> Result<RepositoryState, GitError>;
+}
+
+/// Opens the preferred backend for the current directory.
+///
+/// Uses libgit2 when built with the `git2` feature and the repository can be opened
+/// in-process, and the `git` executable otherwise.
+pub fn open_backend() -> Box<dyn GitBackend> {
+    open_backend_in(None)
+}
+
+/// Opens the preferred backend for `dir`, or the current directory when `None`
+pub fn open_backend_in(dir: Option<&Path>) -> Box<dyn GitBackend> {
+    #[cfg(feature = "git2")]
+    match Git2Backend::open(dir.unwrap_or(Path::new("."))) {
+        Ok(backend) => return Box::new(backend),
+        Err(e) => tracing::debug!("Falling back to the git CLI backend: {}", e),
+    }
+    match dir {
+        Some(dir) => Box::new(CliBackend::in_dir(dir)),
+        None => Box::new(CliBackend::new()),
+    }
+}
+
+/// Backend that runs the `git` executable
+#[derive(Debug, Default)]
+pub struct CliBackend {
+    work_dir: Option<PathBuf>,
+}
+
+impl CliBackend {
+    /// Runs git in the current directory
+    pub fn new() -> Self {
+        CliBackend { work_dir: None }
+    }
+
+    /// Runs git in `work_dir` instead of the current directory
+    pub fn in_dir(work_dir: impl Into<PathBuf>) -> Self {
+        CliBackend {
+            work_dir: Some(work_dir.into()),
+        }
+    }
+
+    fn command(&self) -> Command {
+        let mut cmd = Command::new("git");
+        if let Some(dir) = &self.work_dir {
+            cmd.arg("-C").arg(dir);
+        }
+        cmd
+    }
+
+    fn run(&self, args: &[&str]) -> Result<String, GitError> {
+        let mut cmd = self.command();
+        let cmd_str = format!("git {}", args.join(" "));
+        tracing::debug!("Reading from repository: {}", cmd_str);
+        let output = cmd
+            .args(args)
+            .output()
+            .map_err(|e| GitError::Other(format!("Failed to execute {}: {}", cmd_str, e)))?;
+        if !output.status.success() {
+            return Err(map_output_to_git_command_error(&cmd_str, output));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+    }
+
+    /// Resolves a path printed by git relative to the directory it ran in
+    fn resolve(&self, path: &str) -> PathBuf {
+        match &self.work_dir {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        }
+    }
+}
+
+impl GitBackend for CliBackend {
+    fn diff(&self, source: DiffSource, options: &DiffOptions) -> Result<String, GitError> {
+        let context_arg = options.context_lines.map(|n| format!("-U{}", n));
+        let mut args = vec!["diff"];
+        match source {
+            DiffSource::Staged => args.push("--staged"),
+            DiffSource::WorkTree => {}
+            DiffSource::Head => args.push("HEAD"),
+        }
+        args.extend(context_arg.as_deref());
+        args.extend_from_slice(match options.renames {
+            RenameDetection::Off => &["--no-renames"][..],
+            RenameDetection::Renames => &["-M"],
+            RenameDetection::Copies => &["-M", "-C"],
+        });
+        args.push("--");
+        args.extend(options.pathspecs.iter().map(String::as_str));
+
+        let cmd_str = format!("git {}", args.join(" "));
+        tracing::debug!("Reading from repository: {}", cmd_str);
+        let failed = |e: io::Error| GitError::Other(format!("Failed to execute {}: {}", cmd_str, e));
+        let mut output = capture::stream_output(self.command().args(&args), DIFF_MEMORY_LIMIT).map_err(failed)?;
+        if !output.status.success() {
+            return Err(GitError::CommandFailed {
+                command: cmd_str,
+                status_code: output.status.code(),
+                stdout: output.stdout.text().unwrap_or_default(),
+                stderr: output.stderr,
+            });
+        }
+        match output.stdout.text() {
+            Some(text) => Ok(text),
+            None => {
+                tracing::debug!("The diff has {} bytes, summarizing the files that don't fit", output.stdout.total_bytes());
+                read_diff_within(output.stdout.reader().map_err(failed)?, DIFF_MEMORY_LIMIT).map_err(failed)
+            }
+        }
+    }
+
+    fn status(&self) -> Result<GitStatus, GitError> {
+        let output = self.run(&["status", "--porcelain=v1", "--branch", "-z"])?;
+        Ok(parse_porcelain_status(&output))
+    }
+
+    fn log(&self, max_count: usize) -> Result<Vec<GitCommit>, GitError> {
+        let max_count = format!("--max-count={}", max_count);
+        // Unit and record separators keep multi-line messages intact
+        let output = self.run(&[
+            "log",
+            &max_count,
+            "--format=%H%x1f%an%x1f%ae%x1f%aI%x1f%B%x1e",
+        ])?;
+        Ok(parse_log(&output))
+    }
+
+    fn blame(&self, path: &Path) -> Result<Vec<GitBlameLine>, GitError> {
+        let path = path.to_string_lossy();
+        let output = self.run(&["blame", "--line-porcelain", "--", &path])?;
+        Ok(parse_blame_porcelain(&output))
+    }
+
+    fn submodule_log(&self, path: &Path, old: &str, new: &str) -> Result<String, GitError> {
+        let submodule = CliBackend::in_dir(match &self.work_dir {
+            Some(dir) => dir.join(path),
+            None => path.to_path_buf(),
+        });
+        submodule.run(&["log", "--oneline", &format!("{}..{}", old, new)])
+    }
+
+    fn object_size(&self, object: &str) -> Result<u64, GitError> {
+        let output = self.run(&["cat-file", "-s", object])?;
+        output
+            .trim()
+            .parse()
+            .map_err(|_| GitError::Other(format!("unexpected object size '{}'", output.trim())))
+    }
+
+    fn repository_state(&self) -> Result<RepositoryState, GitError> {
+        // Checked in the same order as libgit2, so both backends agree when
+        // several markers exist (a conflicted rebase step also writes MERGE_MSG)
+        const MARKERS: [(&str, InProgressOperation); 6] = [
+            ("rebase-merge", InProgressOperation::Rebase),
+            ("rebase-apply", InProgressOperation::Rebase),
+            ("MERGE_HEAD", InProgressOperation::Merge),
+            ("REVERT_HEAD", InProgressOperation::Revert),
+            ("CHERRY_PICK_HEAD", InProgressOperation::CherryPick),
+            ("BISECT_LOG", InProgressOperation::Bisect),
+        ];
+        let mut args = vec!["rev-parse", "--git-dir"];
+        for (marker, _) in MARKERS {
+            args.extend(["--git-path", marker]);
+        }
+        args.extend(["--git-path", "MERGE_MSG"]);
+        let output = self.run(&args)?;
+        let mut paths = output.lines().map(|line| self.resolve(line));
+        let git_dir = paths.next().unwrap_or_default();
+        let marker_paths: Vec<PathBuf> = paths.by_ref().take(MARKERS.len()).collect();
+        let merge_msg = paths.next();
+
+        let head = std::fs::read_to_string(git_dir.join("HEAD")).unwrap_or_default();
+        let operation = MARKERS
+            .iter()
+            .zip(&marker_paths)
+            .find(|(_, path)| path.exists())
+            .map(|((_, operation), _)| *operation);
+        Ok(RepositoryState {
+            detached_head: !head.starts_with("ref: "),
+            operation,
+            prepared_message: operation
+                .filter(|op| *op != InProgressOperation::Bisect)
+                .and_then(|_| std::fs::read_to_string(merge_msg?).ok())
+                .and_then(|message| strip_comment_lines(&message)),
+        })
+    }
+}
+
+/// Drops `#` comment lines from a message file, `None` when nothing else remains.
+fn strip_comment_lines(message: &str) -> Option<String> {
+    let message = message
+        .lines()
+        .filter(|line| !line.starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let message = message.trim();
+    (!message.is_empty()).then(|| message.to_string())
+}
+
+/// Parses `git status --porcelain=v1 --branch -z` output.
+fn parse_porcelain_status(output: &str) -> GitStatus {
+    let mut status = GitStatus::default();
+    let mut entries = output.split('\0').filter(|entry| !entry.is_empty());
+    while let Some(entry) = entries.next() {
+        if let Some(branch) = entry.strip_prefix("## ") {
+            status.current_branch = parse_branch_header(branch);
+            continue;
+        }
+        if entry.len() < 4 {
+            continue;
+        }
+        let (code, path) = entry.split_at(3);
+        let mut code = code.chars();
+        let (index, worktree) = (code.next().unwrap_or(' '), code.next().unwrap_or(' '));
+        if matches!((index, worktree), ('U', _) | (_, 'U') | ('A', 'A') | ('D', 'D')) {
+            status.conflicted.push(path.to_string());
+            continue;
+        }
+        // Renames and copies are followed by their original path
+        if matches!(index, 'R' | 'C') {
+            entries.next();
+        }
+        if index == '?' {
+            status.untracked.push(path.to_string());
+            continue;
+        }
+        if index != ' ' {
+            status.staged.push(GitFileStatus {
+                path: path.to_string(),
+                status_code: index.to_string(),
+            });
+        }
+        if worktree != ' ' {
+            status.modified.push(GitFileStatus {
+                path: path.to_string(),
+                status_code: worktree.to_string(),
+            });
+        }
+    }
+    status
+}
+
+/// Extracts the branch name from a `## main...origin/main [ahead 1]` header.
+fn parse_branch_header(header: &str) -> Option<String> {
+    let branch = header
+        .strip_prefix("No commits yet on ")
+        .unwrap_or(header)
+        .split("...")
+        .next()?
+        .split(' ')
+        .next()?;
+    // A detached HEAD has no branch
+    (!branch.starts_with("HEAD")).then(|| branch.to_string())
+}
+
+/// Parses `git log` output produced with unit/record separators.
+fn parse_log(output: &str) -> Vec<GitCommit> {
+    output
+        .split('\u{1e}')
+        .filter_map(|record| {
+            let mut fields = record.trim_start_matches('\n').splitn(5, '\u{1f}');
+            Some(GitCommit {
+                hash: fields.next().filter(|hash| !hash.is_empty())?.to_string(),
+                author: fields.next()?.to_string(),
+                email: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                message: fields.next()?.trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Parses `git blame --line-porcelain` output.
+fn parse_blame_porcelain(output: &str) -> Vec<GitBlameLine> {
+    let mut lines = Vec::new();
+    let mut commit = String::new();
+    let mut author = String::new();
+    let mut line_number = 0;
+    for line in output.lines() {
+        if let Some(content) = line.strip_prefix('\t') {
+            lines.push(GitBlameLine {
+                commit: commit.clone(),
+                author: author.clone(),
+                line_number,
+                content: content.to_string(),
+            });
+        } else if let Some(name) = line.strip_prefix("author ") {
+            author = name.to_string();
+        } else {
+            let mut parts = line.split(' ');
+            if let (Some(hash), Some(_), Some(final_line)) = (parts.next(), parts.next(), parts.next())
+                && hash.len() >= 40
+                && hash.chars().all(|c| c.is_ascii_hexdigit())
+            {
+                commit = hash.to_string();
+                line_number = final_line.parse().unwrap_or(0);
+            }
+        }
+    }
+    lines
+}
+
+/// Backend that reads the repository in-process through libgit2
+#[cfg(feature = "git2")]
+pub struct Git2Backend {
+    repo: git2::Repository,
+}
+
+#[cfg(feature = "git2")]
+impl Git2Backend {
+    /// Opens the repository containing `path`, honoring `GIT_DIR` and friends
+    pub fn open(path: &Path) -> Result<Self, GitError> {
+        let repo = if std::env::var_os("GIT_DIR").is_some() {
+            git2::Repository::open_from_env()
+        } else {
+            git2::Repository::discover(path)
+        }
+        .map_err(|e| map_git2_error("opening repository", e))?;
+        Ok(Git2Backend { repo })
+    }
+}
+
+#[cfg(feature = "git2")]
+fn map_git2_error(action: &str, e: git2::Error) -> GitError {
+    if e.code() == git2::ErrorCode::NotFound && action == "opening repository" {
+        GitError::NotARepository
+    } else {
+        GitError::Other(format!("libgit2 failed {}: {}", action, e))
+    }
+}
+
+#[cfg(feature = "git2")]
+impl GitBackend for Git2Backend {
+    fn diff(&self, source: DiffSource, options: &DiffOptions) -> Result<String, GitError> {
+        let mut diff_options = git2::DiffOptions::new();
+        if let Some(n) = options.context_lines {
+            diff_options.context_lines(n);
+        }
+        for pathspec in &options.pathspecs {
+            // libgit2 pathspecs are relative to the work tree root already
+            diff_options.pathspec(pathspec.strip_prefix(":(top)").unwrap_or(pathspec));
+        }
+        let head_tree = || match self.repo.head() {
+            Ok(head) => head
+                .peel_to_tree()
+                .map(Some)
+                .map_err(|e| map_git2_error("reading HEAD", e)),
+            // An unborn branch has no HEAD yet; diff against the empty tree
+            Err(e) if e.code() == git2::ErrorCode::UnbornBranch => Ok(None),
+            Err(e) => Err(map_git2_error("reading HEAD", e)),
+        };
+        let mut diff = match source {
+            DiffSource::Staged => self
+                .repo
+                .diff_tree_to_index(head_tree()?.as_ref(), None, Some(&mut diff_options))
+                .map_err(|e| map_git2_error("diffing the index", e))?,
+            DiffSource::WorkTree => self
+                .repo
+                .diff_index_to_workdir(None, Some(&mut diff_options))
+                .map_err(|e| map_git2_error("diffing the working tree", e))?,
+            DiffSource::Head => self
+                .repo
+                .diff_tree_to_workdir_with_index(head_tree()?.as_ref(), Some(&mut diff_options))
+                .map_err(|e| map_git2_error("diffing the working tree", e))?,
+        };
+        if options.renames != RenameDetection::Off {
+            let mut find_options = git2::DiffFindOptions::new();
+            find_options
+                .renames(true)
+                .copies(options.renames == RenameDetection::Copies);
+            diff.find_similar(Some(&mut find_options))
+                .map_err(|e| map_git2_error("detecting renames", e))?;
+        }
+
+        let mut patch = SpillBuffer::new(DIFF_MEMORY_LIMIT);
+        let mut write_error = None;
+        diff.print(git2::DiffFormat::Patch, |_, _, line| {
+            let origin = [line.origin() as u8];
+            let origin: &[u8] = if matches!(line.origin(), '+' | '-' | ' ') { &origin } else { &[] };
+            match patch.write_all(origin).and_then(|()| patch.write_all(line.content())) {
+                Ok(()) => true,
+                Err(e) => {
+                    write_error = Some(e);
+                    false
+                }
+            }
+        })
+        .map_err(|e| match write_error.take() {
+            Some(write_error) => GitError::Other(format!("Failed to store the diff: {}", write_error)),
+            None => map_git2_error("printing the diff", e),
+        })?;
+        let stored = |e: io::Error| GitError::Other(format!("Failed to store the diff: {}", e));
+        match patch.text() {
+            Some(text) => Ok(text),
+            None => {
+                tracing::debug!("The diff has {} bytes, summarizing the files that don't fit", patch.total_bytes());
+                read_diff_within(patch.reader().map_err(stored)?, DIFF_MEMORY_LIMIT).map_err(stored)
+            }
+        }
+    }
+
+    fn status(&self) -> Result<GitStatus, GitError> {
+        let mut options = git2::StatusOptions::new();
+        options.include_untracked(true).renames_head_to_index(true);
+        let statuses = self
+            .repo
+            .statuses(Some(&mut options))
+            .map_err(|e| map_git2_error("reading status", e))?;
+
+        let mut status = GitStatus {
+            current_branch: self
+                .repo
+                .head()
+                .ok()
+                .filter(|head| head.is_branch())
+                .and_then(|head| head.shorthand().ok().map(str::to_string)),
+            ..GitStatus::default()
+        };
+        for entry in statuses.iter() {
+            let Ok(path) = entry.path() else { continue };
+            let flags = entry.status();
+            if flags.contains(git2::Status::CONFLICTED) {
+                status.conflicted.push(path.to_string());
+                continue;
+            }
+            if flags.contains(git2::Status::WT_NEW) {
+                status.untracked.push(path.to_string());
+                continue;
+            }
+            let index_code = [
+                (git2::Status::INDEX_NEW, "A"),
+                (git2::Status::INDEX_MODIFIED, "M"),
+                (git2::Status::INDEX_DELETED, "D"),
+                (git2::Status::INDEX_RENAMED, "R"),
+                (git2::Status::INDEX_TYPECHANGE, "T"),
+            ]
+            .into_iter()
+            .find(|(flag, _)| flags.contains(*flag));
+            if let Some((_, code)) = index_code {
+                status.staged.push(GitFileStatus {
+                    path: path.to_string(),
+                    status_code: code.to_string(),
+                });
+            }
+            let worktree_code = [
+                (git2::Status::WT_MODIFIED, "M"),
+                (git2::Status::WT_DELETED, "D"),
+                (git2::Status::WT_RENAMED, "R"),
+                (git2::Status::WT_TYPECHANGE, "T"),
+            ]
+            .into_iter()
+            .find(|(flag, _)| flags.contains(*flag));
+            if let Some((_, code)) = worktree_code {
+                status.modified.push(GitFileStatus {
+                    path: path.to_string(),
+                    status_code: code.to_string(),
+                });
+            }
+        }
+        Ok(status)
+    }
+
+    fn log(&self, max_count: usize) -> Result<Vec<GitCommit>, GitError> {
+        let mut revwalk = self
+            .repo
+            .revwalk()
+            .map_err(|e| map_git2_error("walking history", e))?;
+        revwalk
+            .push_head()
+            .map_err(|e| map_git2_error("walking history", e))?;
+        revwalk
+            .take(max_count)
+            .map(|oid| {
+                let oid = oid.map_err(|e| map_git2_error("walking history", e))?;
+                let commit = self
+                    .repo
+                    .find_commit(oid)
+                    .map_err(|e| map_git2_error("reading a commit", e))?;
+                let author = commit.author();
+                Ok(GitCommit {
+                    hash: oid.to_string(),
+                    message: commit.message().unwrap_or_default().trim_end().to_string(),
+                    author: author.name().unwrap_or_default().to_string(),
+                    email: author.email().unwrap_or_default().to_string(),
+                    date: format_git_time(author.when()),
+                })
+            })
+            .collect()
+    }
+
+    fn blame(&self, path: &Path) -> Result<Vec<GitBlameLine>, GitError> {
+        let workdir = self
+            .repo
+            .workdir()
+            .ok_or_else(|| GitError::Other("cannot blame in a bare repository".to_string()))?;
+        let content = std::fs::read_to_string(workdir.join(path))
+            .map_err(|e| GitError::Other(format!("Failed to read {}: {}", path.display(), e)))?;
+        let committed = self
+            .repo
+            .blame_file(path, None)
+            .map_err(|e| map_git2_error("running blame", e))?;
+        // Blaming the work tree content attributes local edits like `git blame` does
+        let blame = committed
+            .blame_buffer(content.as_bytes())
+            .map_err(|e| map_git2_error("running blame", e))?;
+        Ok(content
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                let hunk = blame.get_line(index + 1);
+                let commit = hunk
+                    .as_ref()
+                    .map_or(git2::Oid::ZERO_SHA1, |h| h.final_commit_id());
+                let author = if commit.is_zero() {
+                    "Not Committed Yet".to_string()
+                } else {
+                    hunk.as_ref()
+                        .and_then(|h| h.final_signature()?.name().ok().map(str::to_string))
+                        .unwrap_or_default()
+                };
+                GitBlameLine {
+                    commit: commit.to_string(),
+                    author,
+                    line_number: index + 1,
+                    content: line.to_string(),
+                }
+            })
+            .collect())
+    }
+
+    fn submodule_log(&self, path: &Path, old: &str, new: &str) -> Result<String, GitError> {
+        self.submodule_log_impl(path, old, new)
+    }
+
+    fn object_size(&self, object: &str) -> Result<u64, GitError> {
+        let oid = self
+            .repo
+            .revparse_single(object)
+            .map_err(|e| map_git2_error("finding an object", e))?
+            .id();
+        let odb = self
+            .repo
+            .odb()
+            .map_err(|e| map_git2_error("opening the object database", e))?;
+        let (size, _) = odb
+            .read_header(oid)
+            .map_err(|e| map_git2_error("reading an object header", e))?;
+        Ok(size as u64)
+    }
+
+    fn repository_state(&self) -> Result<RepositoryState, GitError> {
+        use git2::RepositoryState as State;
+        let operation = match self.repo.state() {
+            State::Clean => None,
+            State::Merge => Some(InProgressOperation::Merge),
+            State::Revert | State::RevertSequence => Some(InProgressOperation::Revert),
+            State::CherryPick | State::CherryPickSequence => Some(InProgressOperation::CherryPick),
+            State::Bisect => Some(InProgressOperation::Bisect),
+            State::Rebase
+            | State::RebaseInteractive
+            | State::RebaseMerge
+            | State::ApplyMailbox
+            | State::ApplyMailboxOrRebase => Some(InProgressOperation::Rebase),
+        };
+        Ok(RepositoryState {
+            detached_head: self
+                .repo
+                .head_detached()
+                .map_err(|e| map_git2_error("reading HEAD", e))?,
+            operation,
+            prepared_message: operation
+                .filter(|op| *op != InProgressOperation::Bisect)
+                .and_then(|_| self.repo.message().ok())
+                .and_then(|message| strip_comment_lines(&message)),
+        })
+    }
+}
+
+#[cfg(feature = "git2")]
+impl Git2Backend {
+    fn submodule_log_impl(&self, path: &Path, old: &str, new: &str) -> Result<String, GitError> {
+        let workdir = self
+            .repo
+            .workdir()
+            .ok_or_else(|| GitError::Other("bare repositories have no submodules".to_string()))?;
+        let submodule = git2::Repository::open(workdir.join(path))
+            .map_err(|e| map_git2_error("opening submodule", e))?;
+        let mut revwalk = submodule
+            .revwalk()
+            .map_err(|e| map_git2_error("walking submodule history", e))?;
+        revwalk
+            .push_range(&format!("{}..{}", old, new))
+            .map_err(|e| map_git2_error("walking submodule history", e))?;
+        let mut log = String::new();
+        for oid in revwalk {
+            let oid = oid.map_err(|e| map_git2_error("walking submodule history", e))?;
+            let commit = submodule
+                .find_commit(oid)
+                .map_err(|e| map_git2_error("reading a submodule commit", e))?;
+            let short_id = commit
+                .as_object()
+                .short_id()
+                .map_err(|e| map_git2_error("reading a submodule commit", e))?;
+            log.push_str(&format!(
+                "{} {}\n",
+                short_id.as_str().unwrap_or_default(),
+                commit.summary().ok().flatten().unwrap_or_default()
+            ));
+        }
+        Ok(log)
+    }
+}
+
+/// Formats a libgit2 timestamp like git's `%aI` (strict ISO 8601).
+#[cfg(feature = "git2")]
+fn format_git_time(time: git2::Time) -> String {
+    let offset = time.offset_minutes() as i64;
+    let local = time.seconds() + offset * 60;
+    let (days, seconds) = (local.div_euclid(86_400), local.rem_euclid(86_400));
+    // Days since 1970-01-01 to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+/// Reads a unified diff, keeping file sections verbatim while they fit in
+/// `budget` bytes altogether.
+///
+/// Files that don't fit keep their header lines, and their content is replaced
+/// by a `[content omitted: +<added> -<removed> lines]` line that `diff::parse`
+/// reads as the file's line counts.
+fn read_diff_within(mut reader: impl BufRead, budget: usize) -> io::Result<String> {
+    let mut diff = String::new();
+    let mut section = DiffSection::default();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let text = String::from_utf8_lossy(&line);
+        if text.starts_with("diff --git ") {
+            section.finish(&mut diff);
+        }
+        section.push(&text, budget.saturating_sub(diff.len()));
+    }
+    section.finish(&mut diff);
+    Ok(diff)
+}
+
+/// The file section `read_diff_within` is reading
+#[derive(Default)]
+struct DiffSection {
+    text: String,
+    /// Length of the header lines in `text`, once the content has started
+    header_len: Option<usize>,
+    omitted: bool,
+    added: usize,
+    removed: usize,
+}
+
+impl DiffSection {
+    fn push(&mut self, line: &str, budget: usize) {
+        if self.header_len.is_none()
+            && (line.starts_with("@@") || line.starts_with("Binary files ") || line.trim_end() == "GIT binary patch")
+        {
+            self.header_len = Some(self.text.len());
+        }
+        if self.header_len.is_some() {
+            if line.starts_with('+') {
+                self.added += 1;
+            } else if line.starts_with('-') {
+                self.removed += 1;
+            }
+        }
+        if self.omitted {
+            return;
+        }
+        self.text.push_str(line);
+        if let Some(header_len) = self.header_len
+            && self.text.len() > budget
+        {
+            self.text.truncate(header_len);
+            self.omitted = true;
+        }
+    }
+
+    fn finish(&mut self, diff: &mut String) {
+        let section = std::mem::take(self);
+        diff.push_str(&section.text);
+        if section.omitted {
+            diff.push_str(&format!("[content omitted: +{} -{} lines]\n", section.added, section.removed));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_read_diff_within_keeps_headers_of_files_over_budget() {
+        let small = "diff --git a/a.txt b/a.txt\nindex 1..2 100644\n--- a/a.txt\n+++ b/a.txt\n@@ -1 +1 @@\n-old\n+new\n";
+        let large = "diff --git a/b.txt b/b.txt\nnew file mode 100644\nindex 0..3\n--- /dev/null\n+++ b/b.txt\n@@ -0,0 +1,3 @@\n+1\n+2\n+3\n";
+        let diff = format!("{}{}", small, large);
+        assert_eq!(read_diff_within(diff.as_bytes(), diff.len()).unwrap(), diff);
+        assert_eq!(
+            read_diff_within(diff.as_bytes(), small.len() + 100).unwrap(),
+            format!(
+                "{}diff --git a/b.txt b/b.txt\nnew file mode 100644\nindex 0..3\n--- /dev/null\n+++ b/b.txt\n\
+                 [content omitted: +3 -0 lines]\n",
+                small
+            )
+        );
+    }
+
+    /// Creates a scratch repository with one commit and a mix of file states.
+    fn setup_repo(name: &str) -> PathBuf {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target/test_temp_data/git_backend")
+            .join(name);
+        if dir.exists() {
+            fs::remove_dir_all(&dir).unwrap();
+        }
+        fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| {
+            let status = Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(["-c", "user.name=Test User", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap();
+            assert!(status.status.success(), "git {:?} failed: {:?}", args, status);
+        };
+        git(&["init", "-q", "-b", "main"]);
+        fs::write(dir.join("tracked.txt"), "one\ntwo\n").unwrap();
+        git(&["add", "tracked.txt"]);
+        git(&["commit", "-q", "-m", "Initial commit\n\nWith a body"]);
+        fs::write(dir.join("tracked.txt"), "one\ntwo\nthree\n").unwrap();
+        fs::write(dir.join("staged.txt"), "new\n").unwrap();
+        git(&["add", "staged.txt"]);
+        fs::write(dir.join("untracked.txt"), "?\n").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_parse_porcelain_status() {
+        let output = "## main...origin/main [ahead 1]\0M  staged.rs\0 M changed.rs\0R  new.rs\0old.rs\0AM both.rs\0?? notes.txt\0";
+        let status = parse_porcelain_status(output);
+        assert_eq!(status.current_branch.as_deref(), Some("main"));
+        let staged: Vec<_> = status.staged.iter().map(|f| (f.path.as_str(), f.status_code.as_str())).collect();
+        assert_eq!(staged, vec![("staged.rs", "M"), ("new.rs", "R"), ("both.rs", "A")]);
+        let modified: Vec<_> = status.modified.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(modified, vec!["changed.rs", "both.rs"]);
+        assert_eq!(status.untracked, vec!["notes.txt"]);
+
+        let conflicted = parse_porcelain_status("UU both.rs\0AA added.rs\0M  ok.rs\0");
+        assert_eq!(conflicted.conflicted, vec!["both.rs", "added.rs"]);
+        assert_eq!(conflicted.staged.len(), 1);
+
+        assert_eq!(parse_branch_header("HEAD (no branch)"), None);
+        assert_eq!(parse_branch_header("No commits yet on main"), Some("main".to_string()));
+    }
+
+    #[test]
+    fn test_parse_log_keeps_multiline_messages() {
+        let output = "abc\u{1f}Ann\u{1f}ann@example.com\u{1f}2024-01-02T03:04:05+00:00\u{1f}Subject\n\nBody\n\u{1e}\ndef\u{1f}Bob\u{1f}bob@example.com\u{1f}2024-01-01T00:00:00+00:00\u{1f}Second\n\u{1e}\n";
+        let commits = parse_log(output);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].hash, "abc");
+        assert_eq!(commits[0].message, "Subject\n\nBody");
+        assert_eq!(commits[1].author, "Bob");
+    }
+
+    #[test]
+    fn test_cli_backend_reads_repository() {
+        let dir = setup_repo("cli");
+        let backend = CliBackend::in_dir(&dir);
+
+        let options = DiffOptions {
+            context_lines: Some(0),
+            ..DiffOptions::default()
+        };
+        let diff = backend.diff(DiffSource::Staged, &options).unwrap();
+        assert!(diff.contains("+++ b/staged.txt"));
+        assert!(!diff.contains("tracked.txt"));
+        let worktree_diff = backend.diff(DiffSource::WorkTree, &options).unwrap();
+        assert!(worktree_diff.contains("+++ b/tracked.txt"));
+        assert!(!worktree_diff.contains("staged.txt"));
+        let limited = DiffOptions {
+            pathspecs: vec!["tracked.txt".to_string()],
+            ..options.clone()
+        };
+        let head_diff = backend.diff(DiffSource::Head, &limited).unwrap();
+        assert!(head_diff.contains("+++ b/tracked.txt"));
+        assert!(!head_diff.contains("staged.txt"));
+        assert!(backend.diff(DiffSource::Head, &options).unwrap().contains("+++ b/staged.txt"));
+
+        let status = backend.status().unwrap();
+        assert_eq!(status.current_branch.as_deref(), Some("main"));
+        assert_eq!(status.staged[0].path, "staged.txt");
+        assert_eq!(status.modified[0].path, "tracked.txt");
+        assert_eq!(status.untracked, vec!["untracked.txt"]);
+
+        let log = backend.log(10).unwrap();
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].message, "Initial commit\n\nWith a body");
+        assert_eq!(log[0].email, "test@example.com");
+
+        let blame = backend.blame(Path::new("tracked.txt")).unwrap();
+        assert_eq!(blame.len(), 3);
+        assert_eq!(blame[0].author, "Test User");
+        assert_eq!(blame[1].line_number, 2);
+        assert_eq!(blame[1].commit, log[0].hash);
+        assert_eq!(blame[2].content, "three");
+
+        let staged_blob = diff
+            .lines()
+            .find_map(|line| line.strip_prefix("index 0000000.."))
+            .and_then(|rest| rest.split(' ').next())
+            .unwrap();
+        assert_eq!(backend.object_size(staged_blob).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_repository_state_detects_merge_conflicts_and_detached_head() {
+        let dir = setup_repo("state");
+        let git = |args: &[&str]| {
+            Command::new("git")
+                .arg("-C")
+                .arg(&dir)
+                .args(["-c", "user.name=Test User", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap()
+        };
+        let backend = CliBackend::in_dir(&dir);
+        assert_eq!(backend.repository_state().unwrap(), RepositoryState::default());
+
+        git(&["stash", "-q", "--include-untracked"]);
+        git(&["checkout", "-q", "-b", "feature"]);
+        fs::write(dir.join("tracked.txt"), "feature\n").unwrap();
+        git(&["commit", "-q", "-am", "Feature change"]);
+        git(&["checkout", "-q", "main"]);
+        fs::write(dir.join("tracked.txt"), "main\n").unwrap();
+        git(&["commit", "-q", "-am", "Main change"]);
+        assert!(!git(&["merge", "feature"]).status.success());
+
+        let state = backend.repository_state().unwrap();
+        assert_eq!(state.operation, Some(InProgressOperation::Merge));
+        assert!(!state.detached_head);
+        assert!(state.prepared_message.unwrap().starts_with("Merge branch 'feature'"));
+        assert_eq!(backend.status().unwrap().conflicted, vec!["tracked.txt"]);
+        #[cfg(feature = "git2")]
+        {
+            let git2 = Git2Backend::open(&dir).unwrap();
+            assert_eq!(git2.repository_state().unwrap(), backend.repository_state().unwrap());
+            assert_eq!(git2.status().unwrap().conflicted, vec!["tracked.txt"]);
+        }
+
+        git(&["merge", "--abort"]);
+        git(&["checkout", "-q", "--detach"]);
+        let state = backend.repository_state().unwrap();
+        assert!(state.detached_head);
+        assert_eq!(state.operation, None);
+    }
+
+    #[test]
+    fn test_diff_rename_detection() {
+        let dir = setup_repo("renames");
+        let status = Command::new("git")
+            .arg("-C")
+            .arg(&dir)
+            .args(["mv", "tracked.txt", "moved.txt"])
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let backend = CliBackend::in_dir(&dir);
+
+        let detected = backend.diff(DiffSource::Staged, &DiffOptions::default()).unwrap();
+        assert!(detected.contains("rename from tracked.txt\nrename to moved.txt"));
+        let off = DiffOptions {
+            renames: RenameDetection::Off,
+            ..DiffOptions::default()
+        };
+        assert!(!backend.diff(DiffSource::Staged, &off).unwrap().contains("rename from"));
+
+        #[cfg(feature = "git2")]
+        assert!(
+            Git2Backend::open(&dir)
+                .unwrap()
+                .diff(DiffSource::Staged, &DiffOptions::default())
+                .unwrap()
+                .contains("rename from tracked.txt\nrename to moved.txt")
+        );
+    }
+
+    #[test]
+    fn test_submodule_log_lists_commits_in_range() {
+        let dir = setup_repo("submodule_log");
+        let sub = dir.join("sub");
+        let git = |args: &[&str]| {
+            let output = Command::new("git")
+                .arg("-C")
+                .arg(&sub)
+                .args(["-c", "user.name=Test User", "-c", "user.email=test@example.com"])
+                .args(args)
+                .output()
+                .unwrap();
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        };
+        fs::create_dir_all(&sub).unwrap();
+        git(&["init", "-q"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "Base"]);
+        let old = git(&["rev-parse", "HEAD"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "Add feature"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "Fix feature"]);
+        let new = git(&["rev-parse", "HEAD"]);
+
+        let log = CliBackend::in_dir(&dir)
+            .submodule_log(Path::new("sub"), &old, &new)
+            .unwrap();
+        let subjects: Vec<_> = log.lines().filter_map(|l| l.split_once(' ')).map(|(_, s)| s).collect();
+        assert_eq!(subjects, vec!["Fix feature", "Add feature"]);
+
+        #[cfg(feature = "git2")]
+        assert_eq!(
+            Git2Backend::open(&dir).unwrap().submodule_log(Path::new("sub"), &old, &new).unwrap(),
+            log
+        );
+    }
+
+    #[cfg(feature = "git2")]
+    #[test]
+    fn test_git2_backend_matches_cli_backend() {
+        let dir = setup_repo("git2");
+        let cli = CliBackend::in_dir(&dir);
+        let git2 = Git2Backend::open(&dir).unwrap();
+
+        let options = DiffOptions::default();
+        let limited = DiffOptions {
+            pathspecs: vec!["tracked.txt".to_string()],
+            ..DiffOptions::default()
+        };
+        for source in [DiffSource::Staged, DiffSource::WorkTree, DiffSource::Head] {
+            assert_eq!(git2.diff(source, &options).unwrap(), cli.diff(source, &options).unwrap());
+            assert_eq!(git2.diff(source, &limited).unwrap(), cli.diff(source, &limited).unwrap());
+        }
+        assert_eq!(git2.status().unwrap(), cli.status().unwrap());
+        let (git2_log, cli_log) = (git2.log(10).unwrap(), cli.log(10).unwrap());
+        assert_eq!(git2_log[0].hash, cli_log[0].hash);
+        assert_eq!(git2_log[0].message, cli_log[0].message);
+        assert_eq!(git2_log[0].date, cli_log[0].date);
+        assert_eq!(
+            git2.blame(Path::new("tracked.txt")).unwrap(),
+            cli.blame(Path::new("tracked.txt")).unwrap()
+        );
+        assert_eq!(git2.object_size("HEAD:tracked.txt").unwrap(), cli.object_size("HEAD:tracked.txt").unwrap());
+    }
+}
@@ -1,7 +1,27 @@
+//! Running git: capturing its output, or passing a command through with the
+//! terminal attached.
+
+use crate::capture::{self, StreamedOutput};
 use crate::errors::{AppError, GitError};
+use crate::interrupt::{exit_code, ForegroundChild};
 use crate::types::CommandOutput;
-use std::process::{Command, Output as ProcessOutput};
-use tracing;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output as ProcessOutput, Stdio};
+
+/// Which changes a diff covers
+///
+/// Every diff-consuming feature selects its input through this, so `--staged` and
+/// `--worktree` mean the same thing everywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)] // `WorkTree` is unused until a command offers `--worktree`
+pub enum DiffSource {
+    /// Changes staged in the index, compared with HEAD (`git diff --staged`)
+    Staged,
+    /// Changes in the working tree that are not staged yet (`git diff`)
+    WorkTree,
+    /// Changes in the working tree compared with HEAD, staged or not (`git diff HEAD`)
+    Head,
+}
 
 /// Executes a git command and captures its output
 ///
@@ -18,8 +38,8 @@ use tracing;
 ///
 /// # Examples
 ///
-/// ```
-/// use crate::git_commands::execute_git_command_and_capture_output;
+/// ```no_run
+/// use git_enhancer::git_commands::execute_git_command_and_capture_output;
 ///
 /// let args = vec!["status".to_string(), "--short".to_string()];
 /// match execute_git_command_and_capture_output(&args) {
@@ -28,10 +48,28 @@ use tracing;
 /// }
 /// ```
 pub fn execute_git_command_and_capture_output(args: &[String]) -> Result<CommandOutput, AppError> {
+    execute_git_command_in(None, args)
+}
+
+/// Executes a git command in `dir`, or the current directory when `None`, and captures its output
+///
+/// # Arguments
+///
+/// * `dir` - The directory git runs in
+/// * `args` - A slice of String containing the arguments to pass to git
+///
+/// # Returns
+///
+/// * `Result<CommandOutput, AppError>` - The command output or an error
+pub fn execute_git_command_in(dir: Option<&Path>, args: &[String]) -> Result<CommandOutput, AppError> {
     let cmd_to_run = args.to_vec();
     tracing::debug!("Capturing output: git {}", cmd_to_run.join(" "));
     
-    let output = Command::new("git")
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+    let output = command
         .args(&cmd_to_run)
         .output()
         .map_err(|e| AppError::Io(format!("Failed to execute: git {}", cmd_to_run.join(" ")), e))?;
@@ -40,7 +78,7 @@ pub fn execute_git_command_and_capture_output(args: &[String]) -> Result<Command
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
     
     if !output.status.success() {
-        tracing::warn!(
+        tracing::debug!(
             "Git cmd 'git {}' non-success {}. Stdout: [{}], Stderr: [{}]", 
             cmd_to_run.join(" "), 
             output.status, 
@@ -56,30 +94,80 @@ pub fn execute_git_command_and_capture_output(args: &[String]) -> Result<Command
     })
 }
 
-/// Checks if Git is installed and available
+/// Executes a git command whose output may be too large to hold in memory
+///
+/// Stdout beyond `memory_limit` bytes is spilled to a temporary file; read it
+/// back with `StreamedOutput::stdout.reader()`.
+///
+/// # Arguments
+///
+/// * `args` - Arguments to pass to git
+/// * `memory_limit` - Bytes of stdout to keep in memory
 ///
 /// # Returns
 ///
-/// * `Result<bool, AppError>` - True if git is available, or an error
-pub fn is_git_available() -> Result<bool, AppError> {
-    match Command::new("git").arg("--version").output() {
-        Ok(output) => Ok(output.status.success()),
-        Err(e) => Err(AppError::Io("Failed to check if git is available".to_string(), e))
+/// * `Result<StreamedOutput, AppError>` - The command output or an error
+pub fn stream_git_command_output(args: &[String], memory_limit: usize) -> Result<StreamedOutput, AppError> {
+    tracing::debug!("Streaming output: git {}", args.join(" "));
+    let output = capture::stream_output(Command::new("git").args(args), memory_limit)
+        .map_err(|e| AppError::Io(format!("Failed to execute: git {}", args.join(" ")), e))?;
+    if !output.status.success() {
+        tracing::debug!("Git cmd 'git {}' non-success {}. Stderr: [{}]", args.join(" "), output.status, output.stderr);
     }
+    Ok(output)
 }
 
-/// Checks if the current directory is within a Git repository
+/// What one `git rev-parse` run tells about git and a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepositoryProbe {
+    /// Whether the `git` executable could be run
+    pub git_available: bool,
+    /// The top-level directory of the work tree, `None` outside of one
+    pub root: Option<PathBuf>,
+}
+
+/// Checks that git is available and finds the work tree of `dir`, or of the
+/// current directory when `None`, with a single
+/// `git rev-parse --is-inside-work-tree --show-toplevel`
+///
+/// # Arguments
+///
+/// * `dir` - The directory to look from
 ///
 /// # Returns
 ///
-/// * `Result<bool, AppError>` - True if in a git repo, or an error
-pub fn is_in_git_repository() -> Result<bool, AppError> {
-    let result = execute_git_command_and_capture_output(&["rev-parse".to_string(), "--is-inside-work-tree".to_string()]);
-    
-    match result {
-        Ok(output) => Ok(output.is_success() && output.stdout.trim() == "true"),
-        Err(e) => Err(e)
+/// * `Result<RepositoryProbe, AppError>` - The findings, or an error if git could not be started for another reason than being missing
+pub fn probe_repository(dir: Option<&Path>) -> Result<RepositoryProbe, AppError> {
+    let mut command = Command::new("git");
+    if let Some(dir) = dir {
+        // A missing directory would look like a missing git below
+        std::fs::metadata(dir).map_err(|e| AppError::Io(format!("reading {}", dir.display()), e))?;
+        command.current_dir(dir);
+    }
+    let output = match command.args(["rev-parse", "--is-inside-work-tree", "--show-toplevel"]).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(RepositoryProbe { git_available: false, root: None });
+        }
+        Err(e) => return Err(AppError::Io("Failed to check if git is available".to_string(), e)),
+    };
+    Ok(RepositoryProbe { git_available: true, root: parse_work_tree_probe(&output) })
+}
+
+/// The work tree root from `git rev-parse --is-inside-work-tree --show-toplevel`
+///
+/// Outside a repository, and inside `.git` or a bare repository, git fails and
+/// prints at most `false`.
+fn parse_work_tree_probe(output: &ProcessOutput) -> Option<PathBuf> {
+    if !output.status.success() {
+        return None;
     }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    if lines.next() != Some("true") {
+        return None;
+    }
+    lines.next().filter(|root| !root.is_empty()).map(|root| PathBuf::from(root.trim_end_matches('\r')))
 }
 
 /// Passes arguments directly to the system's git command
@@ -98,20 +186,59 @@ pub fn passthrough_to_git(args: &[String]) -> Result<(), AppError> {
     let command_to_run = args.to_vec();
     let cmd_str_log = command_to_run.join(" ");
     tracing::debug!("Passing to system git: git {}", cmd_str_log);
-    let status = Command::new("git")
-        .args(&command_to_run)
-        .status()
+    let mut command = Command::new("git");
+    command.args(&command_to_run);
+    let status = run_attached_to_terminal(&mut command)
         .map_err(|e| AppError::Io(format!("Failed to execute system git: git {}", cmd_str_log), e))?;
     if !status.success() {
-        tracing::warn!("Git passthrough 'git {}' failed: {}", cmd_str_log, status);
+        tracing::debug!("Git passthrough 'git {}' failed: {}", cmd_str_log, status);
         return Err(AppError::Git(GitError::PassthroughFailed {
             command: format!("git {}", cmd_str_log),
-            status_code: status.code(),
+            status_code: exit_code(status),
         }));
     }
     Ok(())
 }
 
+/// Runs a command with stdin, stdout and stderr inherited from this process
+///
+/// Git decides about colors, the pager, progress output and whether it may
+/// prompt (`add -p`, `rebase -i`, `core.editor`, credential helpers) by looking
+/// at its own standard streams, so anything meant to behave exactly like plain
+/// git must run through here instead of capturing output. An interrupt while the
+/// command runs is left to the command instead of terminating this process.
+///
+/// # Arguments
+///
+/// * `command` - The prepared command; its stdio settings are overridden
+///
+/// # Returns
+///
+/// * `std::io::Result<ExitStatus>` - The exit status once the command finishes
+pub fn run_attached_to_terminal(command: &mut Command) -> std::io::Result<ExitStatus> {
+    command
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::inherit())
+        .stderr(Stdio::inherit());
+    run_in_foreground(command)
+}
+
+/// Runs a command with the stdio it was configured with, leaving Ctrl-C to it
+///
+/// # Arguments
+///
+/// * `command` - The prepared command
+///
+/// # Returns
+///
+/// * `std::io::Result<ExitStatus>` - The exit status once the command finishes
+pub fn run_in_foreground(command: &mut Command) -> std::io::Result<ExitStatus> {
+    let mut child = command.spawn()?;
+    // Ctrl-C now belongs to the child; we only report how it exited
+    let _foreground = ForegroundChild::register(&child);
+    child.wait()
+}
+
 /// Maps command output to a GitError
 ///
 /// # Arguments
@@ -129,4 +256,20 @@ pub fn map_output_to_git_command_error(cmd_str: &str, output: ProcessOutput) ->
         stdout: String::from_utf8_lossy(&output.stdout).to_string(),
         stderr: String::from_utf8_lossy(&output.stderr).to_string(),
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell_output(script: &str) -> ProcessOutput {
+        Command::new("sh").args(["-c", script]).output().unwrap()
+    }
+
+    #[test]
+    fn test_parse_work_tree_probe() {
+        assert_eq!(parse_work_tree_probe(&shell_output("printf 'true\\n/src/app\\n'")), Some(PathBuf::from("/src/app")));
+        // Inside `.git`: git prints `false`, then fails on --show-toplevel
+        assert_eq!(parse_work_tree_probe(&shell_output("printf 'false\\n'; exit 128")), None);
+        assert_eq!(parse_work_tree_probe(&shell_output("exit 128")), None);
+    }
+}
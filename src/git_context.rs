@@ -0,0 +1,119 @@
+// git-enhancer/src/git_context.rs
+//! Facts about git and the current repository, looked up once per run.
+//!
+//! Several steps of one command ask the same questions (is git installed, is
+//! this a repository, where is its root, which branch is checked out), each
+//! answered by spawning `git`. A `GitContext` is created at the start of a run
+//! and passed down to the handlers; every answer is looked up on first use and
+//! reused afterwards. Nothing in a single run changes these answers. The first
+//! three come from a single `git rev-parse` run.
+//!
+//! A context is about the current directory, or about the directory given to
+//! [`GitContext::in_dir`]; git then runs there without changing the working
+//! directory of the process.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::errors::{AppError, GitError};
+use crate::git_backend::{GitBackend, open_backend_in};
+use crate::git_commands::{RepositoryProbe, execute_git_command_in, probe_repository};
+use crate::types::CommandOutput;
+
+/// Cached answers about git and the repository of a directory
+#[derive(Debug, Default)]
+pub struct GitContext {
+    /// Where git runs; the current directory when `None`
+    dir: Option<PathBuf>,
+    probe: OnceLock<RepositoryProbe>,
+    current_branch: OnceLock<Option<String>>,
+}
+
+impl GitContext {
+    /// A context for the repository `dir` is in rather than the current directory's
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        GitContext { dir: Some(dir.into()), ..GitContext::default() }
+    }
+
+    /// The directory given to `in_dir`, if any
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// The directory relative paths are taken from: `dir` or the current directory
+    pub fn working_dir(&self) -> Result<PathBuf, AppError> {
+        match &self.dir {
+            Some(dir) => Ok(dir.clone()),
+            None => std::env::current_dir().map_err(|e| AppError::Io("reading the current directory".to_string(), e)),
+        }
+    }
+
+    /// Runs `git <args>` in this context's directory and captures its output
+    pub fn git(&self, args: &[String]) -> Result<CommandOutput, AppError> {
+        execute_git_command_in(self.dir(), args)
+    }
+
+    /// The preferred repository backend for this context's directory
+    pub fn backend(&self) -> Box<dyn GitBackend> {
+        open_backend_in(self.dir())
+    }
+
+    fn probe(&self) -> Result<&RepositoryProbe, AppError> {
+        get_or_try_init(&self.probe, || probe_repository(self.dir()))
+    }
+
+    /// Whether the `git` executable can be run
+    pub fn is_git_available(&self) -> Result<bool, AppError> {
+        Ok(self.probe()?.git_available)
+    }
+
+    /// Fails with `GitError::NotARepository` unless the directory is inside a Git work tree.
+    ///
+    /// Only commands that read repository content call this, so explanations and
+    /// passthrough commands such as `clone` or `init` keep working anywhere.
+    pub fn require_repository(&self) -> Result<(), AppError> {
+        self.root().map(|_| ())
+    }
+
+    /// The top-level directory of the work tree
+    pub fn root(&self) -> Result<&Path, AppError> {
+        self.probe()?.root.as_deref().ok_or_else(|| {
+            tracing::debug!("Not a git repository (or any of the parent directories).");
+            GitError::NotARepository.into()
+        })
+    }
+
+    /// The checked-out branch, or `None` when HEAD is detached or outside a repository
+    pub fn current_branch(&self) -> Option<&str> {
+        self.current_branch
+            .get_or_init(|| {
+                let args = ["symbolic-ref".to_string(), "--short".to_string(), "-q".to_string(), "HEAD".to_string()];
+                let output = self.git(&args).ok()?;
+                let branch = output.stdout.trim();
+                (output.status.success() && !branch.is_empty()).then(|| branch.to_string())
+            })
+            .as_deref()
+    }
+}
+
+/// The value in `cell`, computed with `init` if it isn't there yet; errors are not cached.
+fn get_or_try_init<T>(cell: &OnceLock<T>, init: impl FnOnce() -> Result<T, AppError>) -> Result<&T, AppError> {
+    if let Some(value) = cell.get() {
+        return Ok(value);
+    }
+    let value = init()?;
+    Ok(cell.get_or_init(|| value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_or_try_init_caches_values_but_not_errors() {
+        let cell = OnceLock::new();
+        assert!(get_or_try_init(&cell, || Err(AppError::Generic("not yet".to_string()))).is_err());
+        assert_eq!(get_or_try_init(&cell, || Ok(1)).unwrap(), &1);
+        assert_eq!(get_or_try_init(&cell, || Ok(2)).unwrap(), &1);
+    }
+}
@@ -0,0 +1,148 @@
+// git-enhancer/src/gitea.rs
+//! Opening pull requests through the Gitea REST API, which Forgejo shares.
+//!
+//! The API lives at `https://<host>/api/v1` of the host the repository is
+//! cloned from; `[gitea] api_url` sets it when the server is reached another
+//! way. For the host of `[gitea] api_url` the token is looked up in
+//! `[gitea] token`, then in `GITEA_TOKEN` / `FORGEJO_TOKEN`, then in git's
+//! credential helper (see `forge`); any other host only gets the token the
+//! credential helper stores for it, so a remote on an unknown server never
+//! receives the configured one.
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ForgeError;
+use crate::forge::{self, Forge, ForgeFuture, NewPullRequest, PullRequest, RepositoryId};
+
+/// Environment variables holding a token, in the order they are tried
+pub(crate) const TOKEN_VARIABLES: &[&str] = &["GITEA_TOKEN", "FORGEJO_TOKEN"];
+
+/// Title prefix that marks a pull request as a draft ("work in progress")
+const DRAFT_PREFIX: &str = "WIP: ";
+
+// Gitea/Forgejo 集成的配置
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GiteaConfig {
+    /// REST API root, e.g. `https://git.example.com/api/v1`; by default that of
+    /// the remote's host
+    #[serde(default)]
+    pub api_url: Option<String>,
+    /// Access token with the `write:repository` scope
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl GiteaConfig {
+    /// The API root for repositories cloned from `host`: `api_url` if it is
+    /// on that host, the host's own API otherwise
+    pub fn api_url(&self, host: &str) -> String {
+        match &self.api_url {
+            Some(api_url) if self.serves(host) => api_url.trim_end_matches('/').to_string(),
+            _ => format!("https://{}/api/v1", host),
+        }
+    }
+
+    /// Whether `host` is the one `api_url` and `token` are configured for
+    pub fn serves(&self, host: &str) -> bool {
+        self.host().is_some_and(|configured| configured.eq_ignore_ascii_case(host))
+    }
+
+    /// Host of the configured `api_url`
+    pub fn host(&self) -> Option<String> {
+        let api_url = self.api_url.as_deref()?;
+        let authority = api_url.split_once("://").map_or(api_url, |(_, rest)| rest);
+        authority.split(['/', ':']).next().filter(|host| !host.is_empty()).map(str::to_lowercase)
+    }
+}
+
+/// Requests to one repository's API
+pub struct GiteaClient {
+    http: reqwest::Client,
+    api_url: String,
+    host: String,
+    token: String,
+    repository: RepositoryId,
+}
+
+impl GiteaClient {
+    pub fn new(api_url: &str, host: &str, token: String, repository: RepositoryId) -> Result<Self, ForgeError> {
+        let http = forge::http_client(host)?;
+        Ok(GiteaClient { http, api_url: api_url.to_string(), host: host.to_string(), token, repository })
+    }
+
+    /// POSTs `body` to `/repos/<owner>/<name>/<path>`
+    async fn post<B: Serialize + ?Sized>(&self, path: &str, body: &B) -> Result<reqwest::Response, ForgeError> {
+        let url = format!("{}/repos/{}/{}/{}", self.api_url, self.repository.owner, self.repository.name, path);
+        let request = self
+            .http
+            .post(&url)
+            .header(reqwest::header::AUTHORIZATION, format!("token {}", self.token))
+            .json(body);
+        forge::send(&self.host, request).await
+    }
+}
+
+/// The title Gitea gets: it has no draft flag, drafts are marked in the title
+fn title(pull_request: &NewPullRequest<'_>) -> String {
+    if pull_request.draft {
+        format!("{}{}", DRAFT_PREFIX, pull_request.title)
+    } else {
+        pull_request.title.to_string()
+    }
+}
+
+impl Forge for GiteaClient {
+    fn name(&self) -> &'static str {
+        "Gitea"
+    }
+
+    fn create_pull_request<'a>(&'a self, pull_request: &'a NewPullRequest<'a>) -> ForgeFuture<'a, PullRequest> {
+        Box::pin(async move {
+            let body = serde_json::json!({
+                "title": title(pull_request),
+                "body": pull_request.body,
+                "head": pull_request.head,
+                "base": pull_request.base,
+            });
+            let response = self.post("pulls", &body).await?;
+            forge::read_pull_request(&self.host, response).await
+        })
+    }
+
+    /// The labels must exist in the repository; Gitea doesn't create them
+    fn add_labels<'a>(&'a self, number: u64, labels: &'a [String]) -> ForgeFuture<'a, ()> {
+        Box::pin(async move {
+            self.post(&format!("issues/{}/labels", number), &serde_json::json!({ "labels": labels }))
+                .await
+                .map(drop)
+        })
+    }
+
+    fn request_reviewers<'a>(&'a self, number: u64, reviewers: &'a [String]) -> ForgeFuture<'a, ()> {
+        Box::pin(async move {
+            self.post(&format!("pulls/{}/requested_reviewers", number), &serde_json::json!({ "reviewers": reviewers }))
+                .await
+                .map(drop)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_url_and_draft_title() {
+        let config = GiteaConfig::default();
+        assert_eq!(config.api_url("codeberg.org"), "https://codeberg.org/api/v1");
+        assert_eq!(config.host(), None);
+        let config = GiteaConfig { api_url: Some("http://Git.Example.com:3000/api/v1/".to_string()), token: None };
+        assert_eq!(config.api_url("git.example.com"), "http://Git.Example.com:3000/api/v1");
+        assert_eq!(config.host().as_deref(), Some("git.example.com"));
+
+        let mut pull_request = NewPullRequest { title: "Add caching", body: "", head: "cache", base: "main", draft: false };
+        assert_eq!(title(&pull_request), "Add caching");
+        pull_request.draft = true;
+        assert_eq!(title(&pull_request), "WIP: Add caching");
+    }
+}
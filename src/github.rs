@@ -0,0 +1,119 @@
+// git-enhancer/src/github.rs
+//! Opening pull requests through the GitHub REST API.
+//!
+//! The token is looked up in `[github] token`, then in `GITHUB_TOKEN` /
+//! `GH_TOKEN`, then in git's credential helper (see `forge`). `[github]
+//! api_url` points at a GitHub Enterprise server instead of github.com.
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::ForgeError;
+use crate::forge::{self, Forge, ForgeFuture, NewPullRequest, PullRequest, RepositoryId};
+
+const DEFAULT_API_URL: &str = "https://api.github.com";
+
+/// Environment variables holding a token, in the order they are tried
+pub(crate) const TOKEN_VARIABLES: &[&str] = &["GITHUB_TOKEN", "GH_TOKEN"];
+
+// GitHub 集成的配置
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GitHubConfig {
+    /// REST API root, e.g. `https://github.example.com/api/v3` for GitHub Enterprise
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+    /// Personal access token with permission to create pull requests
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl Default for GitHubConfig {
+    fn default() -> Self {
+        GitHubConfig { api_url: default_api_url(), token: None }
+    }
+}
+
+fn default_api_url() -> String {
+    DEFAULT_API_URL.to_string()
+}
+
+impl GitHubConfig {
+    /// Host that repositories are cloned from: `github.com` for the public API,
+    /// otherwise the host of `api_url`
+    pub fn host(&self) -> String {
+        let authority = self.api_url.split_once("://").map_or(self.api_url.as_str(), |(_, rest)| rest);
+        let host = authority.split(['/', ':']).next().unwrap_or_default();
+        if host == "api.github.com" { "github.com".to_string() } else { host.to_string() }
+    }
+}
+
+/// Requests to one repository's API
+pub struct GitHubClient {
+    http: reqwest::Client,
+    api_url: String,
+    host: String,
+    token: String,
+    repository: RepositoryId,
+}
+
+impl GitHubClient {
+    pub fn new(config: &GitHubConfig, token: String, repository: RepositoryId) -> Result<Self, ForgeError> {
+        let host = config.host();
+        let http = forge::http_client(&host)?;
+        Ok(GitHubClient { http, api_url: config.api_url.trim_end_matches('/').to_string(), host, token, repository })
+    }
+
+    /// POSTs `body` to `/repos/<owner>/<name>/<path>`
+    async fn post<B: Serialize + ?Sized>(&self, path: &str, body: &B) -> Result<reqwest::Response, ForgeError> {
+        let url = format!("{}/repos/{}/{}/{}", self.api_url, self.repository.owner, self.repository.name, path);
+        let request = self
+            .http
+            .post(&url)
+            .bearer_auth(&self.token)
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .json(body);
+        forge::send(&self.host, request).await
+    }
+}
+
+impl Forge for GitHubClient {
+    fn name(&self) -> &'static str {
+        "GitHub"
+    }
+
+    fn create_pull_request<'a>(&'a self, pull_request: &'a NewPullRequest<'a>) -> ForgeFuture<'a, PullRequest> {
+        Box::pin(async move {
+            let response = self.post("pulls", pull_request).await?;
+            forge::read_pull_request(&self.host, response).await
+        })
+    }
+
+    /// Labels that don't exist yet are created
+    fn add_labels<'a>(&'a self, number: u64, labels: &'a [String]) -> ForgeFuture<'a, ()> {
+        Box::pin(async move {
+            self.post(&format!("issues/{}/labels", number), &serde_json::json!({ "labels": labels }))
+                .await
+                .map(drop)
+        })
+    }
+
+    fn request_reviewers<'a>(&'a self, number: u64, reviewers: &'a [String]) -> ForgeFuture<'a, ()> {
+        Box::pin(async move {
+            self.post(&format!("pulls/{}/requested_reviewers", number), &serde_json::json!({ "reviewers": reviewers }))
+                .await
+                .map(drop)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_of_public_and_enterprise_api() {
+        let enterprise = GitHubConfig { api_url: "https://git.example.com/api/v3".to_string(), token: None };
+        assert_eq!(enterprise.host(), "git.example.com");
+        assert_eq!(GitHubConfig::default().host(), "github.com");
+    }
+}
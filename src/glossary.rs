@@ -0,0 +1,83 @@
+// git-enhancer/src/glossary.rs
+//! Definitions of git jargon for explanations in glossary mode.
+//!
+//! The model is handed the definitions with the prompt and told to repeat them
+//! word for word, so a term reads the same in every explanation.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::errors::ConfigError;
+
+/// The glossary shipped with gitie
+const BUILT_IN_GLOSSARY: &str = include_str!("../assets/glossary.toml");
+
+/// Terms and their one-line definitions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glossary {
+    terms: BTreeMap<String, String>,
+}
+
+impl Default for Glossary {
+    fn default() -> Self {
+        Glossary {
+            terms: toml::from_str(BUILT_IN_GLOSSARY).expect("built-in glossary is valid TOML"),
+        }
+    }
+}
+
+impl Glossary {
+    /// The built-in glossary with the user's file at `path` layered on top;
+    /// a missing file just means no additions.
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let mut glossary = Glossary::default();
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(glossary),
+            Err(e) => return Err(ConfigError::FileRead(path.to_string_lossy().to_string(), e)),
+        };
+        let user_terms: BTreeMap<String, String> = toml::from_str(&content)
+            .map_err(|e| ConfigError::TomlParse(path.to_string_lossy().to_string(), e))?;
+        glossary.terms.extend(user_terms);
+        Ok(glossary)
+    }
+
+    /// Instructions for the system prompt telling the model how to use the glossary
+    pub fn prompt_instructions(&self) -> String {
+        let mut instructions = String::from(
+            "When one of the following git terms appears in your answer, follow its first \
+             occurrence with its definition in parentheses, using exactly the wording given \
+             here. Do not define terms that are not listed.\n",
+        );
+        for (term, definition) in &self.terms {
+            instructions.push_str(&format!("\n- {}: {}", term, definition));
+        }
+        instructions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DIR: &str = "target/test_temp_data/glossary";
+
+    #[test]
+    fn test_user_glossary_extends_and_rewords_built_in_terms() {
+        let dir = Path::new(TEST_DIR).join("layered");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("glossary.toml");
+        fs::write(&path, "\"reflog\" = \"HEAD's history\"\n\"worktree\" = \"an extra checkout\"\n").unwrap();
+
+        let glossary = Glossary::load(&path).unwrap();
+        assert_eq!(glossary.terms["reflog"], "HEAD's history");
+        assert_eq!(glossary.terms["worktree"], "an extra checkout");
+        assert!(glossary.terms.contains_key("detached HEAD"));
+        assert!(glossary.prompt_instructions().contains("\n- reflog: HEAD's history"));
+
+        assert_eq!(Glossary::load(&dir.join("missing.toml")).unwrap(), Glossary::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
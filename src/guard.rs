@@ -0,0 +1,296 @@
+// git-enhancer/src/guard.rs
+//! Confirmation before git commands that can lose work.
+//!
+//! Commands recognized by `safety::classify`, and rebases of the branches
+//! listed in `[guard] shared_branches`, are stopped before they run. The user
+//! sees what would be lost, described by the model from the repository's
+//! current state, and has to confirm. Only interactive use is guarded, so
+//! scripts keep git's behavior.
+
+use serde::Deserialize;
+use std::io::{self, IsTerminal};
+
+use crate::ai_utils::{ChatMessage, OpenAIChatRequest, clean_ai_output, send_chat_request};
+use crate::cli::CommandLine;
+use crate::config::AppConfig;
+use crate::errors::{AIError, AppError};
+use crate::git_commands::execute_git_command_and_capture_output;
+use crate::git_context::GitContext;
+use crate::prompt;
+use crate::safety::{self, Risk};
+use crate::ui;
+use crate::untrusted;
+
+const LOSS_PROMPT: &str = "The user is about to run a git command that can lose work. Using only \
+    the repository information given, describe in plain language exactly what will be lost or \
+    overwritten: name the specific files, commits, branches or stashes affected, and say whether \
+    and how they could be recovered (for example through the reflog). Be brief and do not use \
+    headings. If the information shows that nothing would be lost, say so.";
+
+/// Longest stretch of command output included in the description request
+const MAX_CONTEXT_CHARS: usize = 8_000;
+
+// 破坏性命令保护的配置
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GuardConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Commands run without asking, matched by prefix without the leading `git`,
+    /// e.g. `"push --force-with-lease"`
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Branches whose history others build on; rebasing them is guarded
+    #[serde(default = "default_shared_branches")]
+    pub shared_branches: Vec<String>,
+    /// Run without asking; set by `--force-yes`, not a configuration key
+    #[serde(skip)]
+    pub force_yes: bool,
+}
+
+impl Default for GuardConfig {
+    fn default() -> Self {
+        GuardConfig {
+            enabled: default_enabled(),
+            allow: Vec::new(),
+            shared_branches: default_shared_branches(),
+            force_yes: false,
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_shared_branches() -> Vec<String> {
+    vec!["main".to_string(), "master".to_string()]
+}
+
+impl GuardConfig {
+    /// Whether `command_line` (without `git`) matches an `allow` entry
+    fn allows(&self, command_line: &str) -> bool {
+        self.allow.iter().any(|entry| {
+            let entry = entry.trim();
+            let entry = entry.strip_prefix("git ").unwrap_or(entry).trim();
+            !entry.is_empty()
+                && command_line
+                    .strip_prefix(entry)
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+        })
+    }
+}
+
+/// Whether `check` could stop `line`, judged without the configuration.
+///
+/// When this is false the configuration doesn't need to be loaded for the guard.
+pub fn may_intervene(line: &CommandLine) -> bool {
+    let args = &line.command;
+    !args.is_empty() && io::stdin().is_terminal() && (args[0] == "rebase" || matches!(risk(line), Risk::Destructive(_)))
+}
+
+/// How risky the command of `line` is; git's own leading options such as
+/// `-c` or `--no-pager` don't change that.
+fn risk(line: &CommandLine) -> Risk {
+    safety::classify(&format!("git {}", line.command.join(" ")))
+}
+
+/// Stops `line` until the user confirms it if it can lose work.
+///
+/// `--force-yes` skips the check. `--yes` does not: without a confirmation
+/// typed at the terminal the command is refused.
+pub async fn check(config: &AppConfig, git_context: &GitContext, line: &CommandLine) -> Result<(), AppError> {
+    let guard = &config.guard;
+    let args = &line.command;
+    if !guard.enabled || guard.force_yes || args.is_empty() || !io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let command_line = args.join(" ");
+    if guard.allows(&command_line) {
+        return Ok(());
+    }
+    let reason = match risk(line) {
+        Risk::Destructive(reason) => reason.to_string(),
+        Risk::Safe => match rebased_shared_branch(guard, git_context, args) {
+            Some(branch) => format!("rewrites the history of the shared branch '{}'", branch),
+            None => return Ok(()),
+        },
+    };
+
+    ui::warning(&format!("`git {}` {}.", command_line, reason));
+    let context = loss_context(line);
+    match describe_loss(config, &command_line, &reason, &context).await {
+        Ok(description) => eprintln!("\n{}\n", description.trim()),
+        Err(AIError::Cancelled) => return Err(AIError::Cancelled.into()),
+        Err(e) => {
+            tracing::debug!("Could not describe the loss: {}", e);
+            if !context.trim().is_empty() {
+                eprintln!("\n{}\n", context.trim());
+            }
+        }
+    }
+
+    if !prompt::is_interactive() {
+        return Err(AppError::Generic(format!(
+            "refusing to run `git {}` without confirmation; add --force-yes to run it anyway",
+            command_line
+        )));
+    }
+    if prompt::confirm("Run it anyway?", false)? {
+        Ok(())
+    } else {
+        Err(AppError::Generic("Command aborted".to_string()))
+    }
+}
+
+/// The branch being rebased, if it is one of the shared branches.
+fn rebased_shared_branch(guard: &GuardConfig, git_context: &GitContext, args: &[String]) -> Option<String> {
+    if args.first().map(String::as_str) != Some("rebase") {
+        return None;
+    }
+    // Continuing or abandoning a rebase already in progress is not a new rewrite
+    if args.iter().any(|arg| {
+        matches!(arg.as_str(), "--continue" | "--abort" | "--skip" | "--quit" | "--edit-todo" | "--show-current-patch")
+    }) {
+        return None;
+    }
+    let branch = git_context.current_branch()?;
+    guard.shared_branches.iter().any(|shared| shared == branch).then(|| branch.to_string())
+}
+
+/// Repository state relevant to what `line` would destroy, as `$ git ...` transcripts.
+fn loss_context(line: &CommandLine) -> String {
+    let mut context = String::new();
+    for command in loss_context_commands(line) {
+        let Ok(output) = execute_git_command_and_capture_output(&command) else {
+            continue;
+        };
+        let text = if output.status.success() { output.stdout } else { output.stderr };
+        context.push_str(&format!("$ git {}\n{}\n", command.join(" "), text.trim_end()));
+    }
+    context.chars().take(MAX_CONTEXT_CHARS).collect()
+}
+
+/// The git commands that show what `line` would destroy.
+///
+/// Each starts with git's own options from the command line, such as `-c` or
+/// `--namespace`, so it looks at the repository the way the command would.
+/// `-C`, `--git-dir` and `--work-tree` are left out: `main` has already
+/// applied them to the process, which every git it starts inherits, and a
+/// relative `-C` given again would be applied twice.
+fn loss_context_commands(line: &CommandLine) -> Vec<Vec<String>> {
+    let args = &line.command;
+    let subcommand = args[0].as_str();
+    let operands: Vec<&String> = args[1..].iter().filter(|arg| !arg.starts_with('-')).collect();
+    let mut commands: Vec<Vec<String>> = Vec::new();
+    match subcommand {
+        "clean" => {
+            let mut dry_run = args.to_vec();
+            dry_run.push("--dry-run".to_string());
+            commands.push(dry_run);
+        }
+        "branch" => {
+            for branch in operands {
+                commands.push(strings(&["log", "--oneline", "-n", "20", branch, "--not", "--remotes", "HEAD"]));
+            }
+        }
+        "push" => commands.push(strings(&["log", "--oneline", "-n", "20", "@{upstream}", "--not", "HEAD"])),
+        "stash" => commands.push(strings(&["stash", "list"])),
+        "rebase" => {
+            commands.push(strings(&["status", "--short", "--branch"]));
+            commands.push(strings(&["log", "--oneline", "-n", "20"]));
+        }
+        _ => {
+            commands.push(strings(&["status", "--short", "--branch"]));
+            commands.push(strings(&["diff", "--stat", "HEAD"]));
+        }
+    }
+    commands
+        .into_iter()
+        .map(|command| line.git_options.iter().cloned().chain(command).collect())
+        .collect()
+}
+
+/// Asks the model what running the command would lose.
+async fn describe_loss(
+    config: &AppConfig,
+    command_line: &str,
+    reason: &str,
+    context: &str,
+) -> Result<String, AIError> {
+    let parameters = config.model_parameters("explain");
+    // File names and commit subjects are repository content
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, true)?;
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages: vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: format!("{}\n\n{}", LOSS_PROMPT, untrusted::UNTRUSTED_CONTENT_RULES),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: format!(
+                    "Command: git {}\nFlagged because it {}.\n\n{}",
+                    command_line,
+                    reason,
+                    untrusted::fence("git-output", context)
+                ),
+            },
+        ],
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    let response = send_chat_request(config, &parameters.api_url, &request).await?;
+    let text = response
+        .choices
+        .first()
+        .map(|choice| clean_ai_output(&choice.message.content))
+        .ok_or(AIError::NoChoiceInResponse)?;
+    if text.trim().is_empty() {
+        return Err(AIError::EmptyMessage);
+    }
+    Ok(text)
+}
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allow_entries_match_whole_words_by_prefix() {
+        let guard = GuardConfig {
+            allow: vec!["git push --force-with-lease".to_string(), "reset --hard".to_string()],
+            ..GuardConfig::default()
+        };
+        assert!(guard.allows("push --force-with-lease origin feature"));
+        assert!(guard.allows("reset --hard"));
+        assert!(!guard.allows("reset --hardly"));
+        assert!(!guard.allows("push --force origin main"));
+    }
+
+    #[test]
+    fn test_git_options_before_the_command_do_not_hide_its_risk() {
+        let line = |args: &[&str]| crate::cli::parse_command_line(&strings(args), &Default::default()).unwrap();
+        assert!(matches!(risk(&line(&["--no-pager", "reset", "--hard"])), Risk::Destructive(_)));
+        assert!(matches!(risk(&line(&["-c", "x=y", "push", "--force"])), Risk::Destructive(_)));
+        assert!(matches!(risk(&line(&["-c", "x=y", "status"])), Risk::Safe));
+    }
+
+    #[test]
+    fn test_loss_context_keeps_the_git_options() {
+        let line = |args: &[&str]| crate::cli::parse_command_line(&strings(args), &Default::default()).unwrap();
+        assert_eq!(
+            loss_context_commands(&line(&["-c", "x=y", "--no-pager", "clean", "-fd"])),
+            vec![strings(&["-c", "x=y", "--no-pager", "clean", "-fd", "--dry-run"])]
+        );
+        // Applied to the whole process before the guard runs
+        assert_eq!(loss_context_commands(&line(&["-C", "repo", "stash", "drop"])), vec![strings(&["stash", "list"])]);
+    }
+}
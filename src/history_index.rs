@@ -0,0 +1,328 @@
+// git-enhancer/src/history_index.rs
+//! The embedding index behind `search`.
+//!
+//! Every commit message is embedded once and kept in `gitie/index` inside the
+//! repository's git directory (shared by all worktrees). An update only embeds
+//! the commits made since the last indexed one; after history was rewritten,
+//! e.g. by a rebase, commits that are still reachable keep their vectors and
+//! the others are dropped.
+//!
+//! The file is binary and little-endian: a header with the embedding model, the
+//! last indexed commit and the vector length, then one record per commit with
+//! its object id and its normalized `f32` vector.
+
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+use crate::ai_utils::{EmbeddingRequest, run_concurrently, send_embedding_request};
+use crate::config::{AIConfig, AppConfig};
+use crate::errors::{AIError, AppError, GitError};
+use crate::git_commands::execute_git_command_and_capture_output;
+use crate::progress::Progress;
+use crate::state_file::{FileLock, write_atomic};
+
+/// Identifies the file format
+const MAGIC: &[u8; 8] = b"GITIEIX1";
+
+/// Commit messages sent per embeddings request
+const EMBEDDING_BATCH_SIZE: usize = 32;
+
+/// Characters of a commit message that are embedded; the rest adds little
+const MAX_EMBEDDED_CHARS: usize = 2_000;
+
+// 历史语义搜索的配置
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct SearchConfig {
+    /// Model that turns commit messages and queries into vectors
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+    /// Embeddings endpoint; defaults to `ai.api_url` with `chat/completions`
+    /// replaced by `embeddings`
+    #[serde(default)]
+    pub api_url: Option<String>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig { embedding_model: default_embedding_model(), api_url: None }
+    }
+}
+
+fn default_embedding_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+impl SearchConfig {
+    /// The endpoint embeddings are requested from
+    pub fn embeddings_url(&self, ai: &AIConfig) -> String {
+        self.api_url.clone().unwrap_or_else(|| ai.default_embeddings_url())
+    }
+}
+
+/// Embedded commit messages of one repository
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryIndex {
+    /// Model the vectors came from; vectors of different models can't be compared
+    pub model: String,
+    /// The commit the last update ended at
+    pub head: Option<String>,
+    pub entries: Vec<IndexEntry>,
+}
+
+/// One commit's vector, normalized to unit length
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexEntry {
+    pub oid: String,
+    pub vector: Vec<f32>,
+}
+
+impl HistoryIndex {
+    fn new(model: &str) -> Self {
+        HistoryIndex { model: model.to_string(), head: None, entries: Vec::new() }
+    }
+
+    /// The stored index, or `None` if there is none yet or it can't be read
+    fn read(path: &Path) -> io::Result<Option<Self>> {
+        match fs::read(path) {
+            Ok(bytes) => {
+                let index = decode(&bytes);
+                if index.is_none() {
+                    tracing::debug!("Ignoring unreadable history index {}", path.display());
+                }
+                Ok(index)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Where the index of the current repository is stored
+pub fn index_path() -> Result<PathBuf, AppError> {
+    let common_dir = git(&["rev-parse", "--path-format=absolute", "--git-common-dir"])?;
+    Ok(PathBuf::from(common_dir.trim_end()).join("gitie").join("index"))
+}
+
+/// Brings the index at `path` up to date with HEAD and returns it.
+///
+/// Commits are embedded `EMBEDDING_BATCH_SIZE` at a time, with up to
+/// `ai.max_concurrency` requests in flight. If a request fails, the vectors
+/// received so far are still saved, so the next update picks up from there.
+pub async fn update(config: &AppConfig, path: &Path) -> Result<HistoryIndex, AppError> {
+    let model = &config.search.embedding_model;
+    let _lock = FileLock::acquire(path).map_err(|e| AppError::Io(format!("locking {}", path.display()), e))?;
+    let mut index = HistoryIndex::read(path)
+        .map_err(|e| AppError::Io(format!("reading {}", path.display()), e))?
+        .filter(|index| index.model == *model)
+        .unwrap_or_else(|| HistoryIndex::new(model));
+
+    // An unborn branch has nothing to index yet
+    let Ok(head) = git(&["rev-parse", "-q", "--verify", "HEAD^{commit}"]).map(|head| head.trim().to_string()) else {
+        return Ok(index);
+    };
+    if index.head.as_deref() == Some(head.as_str()) {
+        return Ok(index);
+    }
+    let range = match &index.head {
+        Some(old) if git(&["merge-base", "--is-ancestor", old, &head]).is_ok() => format!("{}..{}", old, head),
+        _ => head.clone(),
+    };
+    let commits = list_commits(&range)?;
+    if index.head.is_some() && range == head {
+        // History was rewritten: keep what is still reachable
+        let reachable: HashSet<&str> = commits.iter().map(|(oid, _)| oid.as_str()).collect();
+        index.entries.retain(|entry| reachable.contains(entry.oid.as_str()));
+    }
+    let known: HashSet<String> = index.entries.iter().map(|entry| entry.oid.clone()).collect();
+    let missing: Vec<&(String, String)> = commits.iter().filter(|(oid, _)| !known.contains(oid)).collect();
+
+    let mut failure = None;
+    if !missing.is_empty() {
+        let api_url = config.search.embeddings_url(&config.ai);
+        // Commit messages are repository content
+        config.policy.check_request(&api_url, model, true)?;
+        let progress = Progress::start("Indexing commits", missing.len());
+        let batches: Vec<_> = missing.chunks(EMBEDDING_BATCH_SIZE).collect();
+        let results = run_concurrently(config.ai.max_concurrency, batches, |batch| {
+            let (api_url, progress) = (&api_url, &progress);
+            async move {
+                let request = EmbeddingRequest {
+                    model: model.clone(),
+                    input: batch.iter().map(|(_, message)| message.clone()).collect(),
+                };
+                let vectors = send_embedding_request(config, api_url, &request).await?;
+                progress.advance(batch.len());
+                Ok::<_, AIError>(batch.iter().zip(vectors).map(|((oid, _), vector)| (oid.clone(), vector)).collect::<Vec<_>>())
+            }
+        })
+        .await;
+        for result in results {
+            match result {
+                Ok(vectors) => index.entries.extend(
+                    vectors.into_iter().map(|(oid, vector)| IndexEntry { oid, vector: normalized(vector) }),
+                ),
+                Err(e) => failure = failure.or(Some(e)),
+            }
+        }
+    }
+    if failure.is_none() {
+        index.head = Some(head);
+    }
+    let dimensions = index.entries.first().map_or(0, |entry| entry.vector.len());
+    index.entries.retain(|entry| entry.vector.len() == dimensions);
+    write_atomic(path, encode(&index)).map_err(|e| AppError::Io(format!("writing {}", path.display()), e))?;
+    match failure {
+        Some(e) => Err(e.into()),
+        None => Ok(index),
+    }
+}
+
+/// Embeds `text` with the index's model, normalized like the indexed vectors.
+pub async fn embed_query(config: &AppConfig, text: &str) -> Result<Vec<f32>, AIError> {
+    let api_url = config.search.embeddings_url(&config.ai);
+    config.policy.check_request(&api_url, &config.search.embedding_model, false)?;
+    let request = EmbeddingRequest { model: config.search.embedding_model.clone(), input: vec![text.to_string()] };
+    let vector = send_embedding_request(config, &api_url, &request).await?.pop().unwrap_or_default();
+    Ok(normalized(vector))
+}
+
+/// The `limit` entries closest to `query`, best first, with their cosine similarity
+pub fn nearest<'a>(index: &'a HistoryIndex, query: &[f32], limit: usize) -> Vec<(&'a IndexEntry, f32)> {
+    let mut scored: Vec<_> = index
+        .entries
+        .iter()
+        .filter(|entry| entry.vector.len() == query.len())
+        .map(|entry| (entry, entry.vector.iter().zip(query).map(|(a, b)| a * b).sum::<f32>()))
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(limit);
+    scored
+}
+
+fn normalized(mut vector: Vec<f32>) -> Vec<f32> {
+    let length = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if length > 0.0 {
+        vector.iter_mut().for_each(|x| *x /= length);
+    }
+    vector
+}
+
+/// `(object id, message)` of the commits in `range`, newest first
+fn list_commits(range: &str) -> Result<Vec<(String, String)>, AppError> {
+    let output = git(&["log", "--format=%x1e%H%x1f%B", range])?;
+    Ok(output
+        .split('\x1e')
+        .filter_map(|record| {
+            let (oid, message) = record.split_once('\x1f')?;
+            Some((oid.to_string(), message.trim().chars().take(MAX_EMBEDDED_CHARS).collect()))
+        })
+        .collect())
+}
+
+fn encode(index: &HistoryIndex) -> Vec<u8> {
+    let dimensions = index.entries.first().map_or(0, |entry| entry.vector.len());
+    let mut bytes = MAGIC.to_vec();
+    for text in [index.model.as_str(), index.head.as_deref().unwrap_or_default()] {
+        bytes.extend((text.len() as u32).to_le_bytes());
+        bytes.extend(text.as_bytes());
+    }
+    bytes.extend((dimensions as u32).to_le_bytes());
+    bytes.extend((index.entries.len() as u32).to_le_bytes());
+    for entry in &index.entries {
+        bytes.push(entry.oid.len() as u8);
+        bytes.extend(entry.oid.as_bytes());
+        for value in &entry.vector {
+            bytes.extend(value.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+fn decode(bytes: &[u8]) -> Option<HistoryIndex> {
+    let mut reader = Reader(bytes);
+    if reader.take(MAGIC.len())? != MAGIC {
+        return None;
+    }
+    let model = reader.text()?;
+    let head = Some(reader.text()?).filter(|head| !head.is_empty());
+    let dimensions = reader.u32()? as usize;
+    let count = reader.u32()? as usize;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let oid_length = *reader.take(1)?.first()? as usize;
+        let oid = String::from_utf8(reader.take(oid_length)?.to_vec()).ok()?;
+        let vector = reader
+            .take(dimensions * 4)?
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+        entries.push(IndexEntry { oid, vector });
+    }
+    reader.0.is_empty().then_some(HistoryIndex { model, head, entries })
+}
+
+/// Reads the index file front to back
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.0.len() < count {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(count);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn text(&mut self) -> Option<String> {
+        let length = self.u32()? as usize;
+        String::from_utf8(self.take(length)?.to_vec()).ok()
+    }
+}
+
+fn git(args: &[&str]) -> Result<String, AppError> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let output = execute_git_command_and_capture_output(&args)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: format!("git {}", args.join(" ")),
+            status_code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+        .into());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_round_trips_through_its_file_format() {
+        let index = HistoryIndex {
+            model: "nomic-embed-text".to_string(),
+            head: Some("c0ffee".to_string()),
+            entries: vec![
+                IndexEntry { oid: "c0ffee".to_string(), vector: normalized(vec![3.0, 4.0]) },
+                IndexEntry { oid: "decade".to_string(), vector: vec![1.0, 0.0] },
+            ],
+        };
+        let bytes = encode(&index);
+        assert_eq!(decode(&bytes), Some(index.clone()));
+        assert_eq!(decode(&bytes[..bytes.len() - 1]), None);
+
+        let ranked = nearest(&index, &[0.0, 1.0], 1);
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].0.oid, "c0ffee");
+        assert!((ranked[0].1 - 0.8).abs() < 1e-6);
+    }
+}
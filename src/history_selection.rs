@@ -0,0 +1,254 @@
+// git-enhancer/src/history_selection.rs
+//! Which commits a history command covers.
+//!
+//! `summarize-log`, `changelog` and `team-summary` select commits the same
+//! way: a revision range as `git log` takes it (`v1.2.0..HEAD`, `main...feature`
+//! or one revision and its history), narrowed by `--since`, `--until` and
+//! `--author`, and to the paths given after `--`. The selection is checked
+//! before anything is sent to the model, so a mistyped tag or a date git
+//! doesn't understand is reported as such instead of as a failed `git log` or
+//! an empty summary.
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cli::HistoryArgs;
+use crate::errors::AppError;
+use crate::git_commands::execute_git_command_and_capture_output;
+use crate::scope::Scope;
+
+/// Dates git resolves to the current time on purpose
+const NOW_WORDS: &[&str] = &["now", "today"];
+
+/// A checked selection of commits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selection {
+    pub range: String,
+    pub since: Option<String>,
+    pub until: Option<String>,
+    /// Patterns of which a commit's author has to match one
+    pub authors: Vec<String>,
+    /// Paths of which a commit has to touch one, relative to the current directory
+    pub paths: Vec<String>,
+    /// Whether `range` is the command's default rather than given
+    default_range: bool,
+}
+
+impl Selection {
+    /// The selection `args` ask for, `default_range` when they give no range.
+    ///
+    /// Fails with what is wrong when the range is malformed or names no
+    /// commit, a date is empty or not one git understands, `--since` is after
+    /// `--until`, or an author pattern is empty.
+    pub fn check(args: &HistoryArgs, default_range: &str) -> Result<Selection, AppError> {
+        let range = args.range.clone().unwrap_or_else(|| default_range.to_string());
+        for revision in parse_range(&range).map_err(AppError::Generic)? {
+            check_revision(revision, &range)?;
+        }
+        let since = args.since.as_deref().map(|since| git_date("since", since)).transpose()?;
+        let until = args.until.as_deref().map(|until| git_date("until", until)).transpose()?;
+        if let (Some(since), Some(until)) = (since, until)
+            && since > until
+        {
+            return Err(AppError::Generic(format!(
+                "--since '{}' is after --until '{}', so no commit can match",
+                args.since.as_deref().unwrap_or_default(),
+                args.until.as_deref().unwrap_or_default()
+            )));
+        }
+        if args.author.iter().any(|author| author.trim().is_empty()) {
+            return Err(AppError::Generic("--author needs a name or email to match, e.g. --author=ann@example.com".to_string()));
+        }
+        Ok(Selection {
+            range,
+            since: args.since.clone(),
+            until: args.until.clone(),
+            authors: args.author.clone(),
+            paths: args.paths.clone(),
+            default_range: args.range.is_none(),
+        })
+    }
+
+    /// The `git log` arguments that select the commits: the options, the
+    /// range, and after `--` the paths or, when none are given, the `scope`
+    pub fn log_args(&self, scope: Option<&Scope>) -> Vec<String> {
+        let mut args = Vec::new();
+        args.extend(self.since.iter().map(|since| format!("--since={}", since)));
+        args.extend(self.until.iter().map(|until| format!("--until={}", until)));
+        args.extend(self.authors.iter().map(|author| format!("--author={}", author)));
+        args.push(self.range.clone());
+        if !self.paths.is_empty() {
+            args.push("--".to_string());
+            args.extend(self.paths.iter().cloned());
+        } else if let Some(scope) = scope {
+            args.extend(["--".to_string(), scope.pathspec()]);
+        }
+        args
+    }
+}
+
+/// The selection in words, e.g. `in v1.0..HEAD since 2 weeks ago by ann`; a
+/// default range is left out when dates or authors narrow it.
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts = Vec::new();
+        let narrowed = self.since.is_some() || self.until.is_some() || !self.authors.is_empty() || !self.paths.is_empty();
+        if !self.default_range || !narrowed {
+            parts.push(format!("in {}", self.range));
+        }
+        parts.extend(self.since.iter().map(|since| format!("since {}", since)));
+        parts.extend(self.until.iter().map(|until| format!("until {}", until)));
+        if !self.authors.is_empty() {
+            parts.push(format!("by {}", self.authors.join(" or ")));
+        }
+        if !self.paths.is_empty() {
+            parts.push(format!("touching {}", self.paths.join(" or ")));
+        }
+        f.write_str(&parts.join(" "))
+    }
+}
+
+/// The revisions `range` names, or why it isn't a range `git log` takes.
+///
+/// `A..B` and `A...B` may leave out either end (it is then HEAD), and a single
+/// revision may end in `^!`, `^@` or `^-`.
+fn parse_range(range: &str) -> Result<Vec<&str>, String> {
+    if range.trim().is_empty() {
+        return Err("the range is empty; give one such as v1.0..HEAD".to_string());
+    }
+    if range.starts_with('-') {
+        return Err(format!("'{}' looks like an option, not a range of commits", range));
+    }
+    if range.chars().any(char::is_whitespace) {
+        return Err(format!("'{}' is not a range: give one range, e.g. v1.0..v2.0, without spaces", range));
+    }
+    let ends: Vec<&str> = match range.split_once("...").or_else(|| range.split_once("..")) {
+        Some((from, to)) => vec![from, to],
+        None => {
+            let revision = range.strip_suffix("^!").or_else(|| range.strip_suffix("^@")).unwrap_or(range);
+            let revision = match revision.rsplit_once("^-") {
+                Some((revision, parent)) if parent.chars().all(|c| c.is_ascii_digit()) => revision,
+                _ => revision,
+            };
+            return Ok(vec![revision]);
+        }
+    };
+    if ends.iter().any(|end| end.contains("..") || end.starts_with('.')) {
+        return Err(format!("'{}' has more than one '..'; a range has two ends, e.g. v1.0..v2.0", range));
+    }
+    if ends.iter().all(|end| end.is_empty()) {
+        return Err(format!("'{}' has no ends; give at least one, e.g. v1.0.. or ..main", range));
+    }
+    Ok(ends.into_iter().filter(|end| !end.is_empty()).collect())
+}
+
+/// Fails unless `revision` of `range` names a commit
+fn check_revision(revision: &str, range: &str) -> Result<(), AppError> {
+    let args = ["rev-parse".to_string(), "--verify".to_string(), "--quiet".to_string(), format!("{}^{{commit}}", revision)];
+    if execute_git_command_and_capture_output(&args)?.status.success() {
+        return Ok(());
+    }
+    let what = if revision == range { format!("'{}'", range) } else { format!("'{}' in '{}'", revision, range) };
+    Err(AppError::Generic(format!(
+        "{} is not a commit, branch or tag of this repository (see `git tag` and `git branch -a`)",
+        what
+    )))
+}
+
+/// The Unix time git takes `--<option>=<date>` for
+fn git_date(option: &str, date: &str) -> Result<i64, AppError> {
+    let invalid = || {
+        AppError::Generic(format!(
+            "--{} '{}' is not a date git understands; use e.g. '2 weeks ago', 'yesterday' or 2024-01-31",
+            option, date
+        ))
+    };
+    if date.trim().is_empty() {
+        return Err(invalid());
+    }
+    let is_now_word = NOW_WORDS.contains(&date.trim().to_lowercase().as_str());
+    let args = ["rev-parse".to_string(), format!("--{}={}", option, date)];
+    for attempt in 1..=MAX_DATE_ATTEMPTS {
+        let started = unix_now();
+        let output = execute_git_command_and_capture_output(&args)?;
+        let finished = unix_now();
+        let time: i64 = output
+            .stdout
+            .trim()
+            .split_once('=')
+            .and_then(|(_, time)| time.parse().ok())
+            .ok_or_else(invalid)?;
+        // git takes what it can't parse for the second it ran in, so a date is
+        // only known to be unparsed when the call didn't cross into the next
+        // second: `1 second ago` may otherwise look the same
+        if started != finished && attempt < MAX_DATE_ATTEMPTS {
+            continue;
+        }
+        if (started..=finished).contains(&time) && !is_now_word {
+            return Err(invalid());
+        }
+        return Ok(time);
+    }
+    unreachable!("the last attempt returns")
+}
+
+/// How often a date is asked for before an ambiguous answer is taken as unparsed
+const MAX_DATE_ATTEMPTS: u32 = 3;
+
+fn unix_now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range("v1.2.0..HEAD"), Ok(vec!["v1.2.0", "HEAD"]));
+        assert_eq!(parse_range("main...feature"), Ok(vec!["main", "feature"]));
+        assert_eq!(parse_range("v1.0.."), Ok(vec!["v1.0"]));
+        assert_eq!(parse_range("HEAD~3"), Ok(vec!["HEAD~3"]));
+        assert_eq!(parse_range("abc123^!"), Ok(vec!["abc123"]));
+        assert_eq!(parse_range("merge^-2"), Ok(vec!["merge"]));
+        for malformed in ["", "..", "...", "a..b..c", "a....b", "--all", "v1 v2"] {
+            assert!(parse_range(malformed).is_err(), "{:?} should be rejected", malformed);
+        }
+    }
+
+    #[test]
+    fn test_selection_in_words_and_log_arguments() {
+        let selection = Selection {
+            range: "HEAD".to_string(),
+            since: Some("2 weeks ago".to_string()),
+            until: None,
+            authors: vec!["ann".to_string(), "bob".to_string()],
+            paths: Vec::new(),
+            default_range: true,
+        };
+        assert_eq!(selection.to_string(), "since 2 weeks ago by ann or bob");
+        assert_eq!(selection.log_args(None), ["--since=2 weeks ago", "--author=ann", "--author=bob", "HEAD"]);
+        let selection = Selection {
+            range: "v1.0..HEAD".to_string(),
+            since: None,
+            until: None,
+            authors: Vec::new(),
+            paths: vec!["--odd name".to_string()],
+            default_range: false,
+        };
+        assert_eq!(selection.to_string(), "in v1.0..HEAD touching --odd name");
+        assert_eq!(selection.log_args(None), ["v1.0..HEAD", "--", "--odd name"]);
+    }
+
+    #[test]
+    fn test_dates_git_understands_even_just_before_now() {
+        let now = unix_now();
+        let second_ago = git_date("since", "1 second ago").unwrap();
+        assert!((now - 2..=now).contains(&second_ago), "{} is not just before {}", second_ago, now);
+        assert!(git_date("until", "now").is_ok());
+        assert_eq!(git_date("since", "2024-01-31 00:00:00 +0000").unwrap(), 1_706_659_200);
+        for invalid in ["", "whenever", "soon"] {
+            assert!(git_date("since", invalid).is_err(), "{:?} should be rejected", invalid);
+        }
+    }
+}
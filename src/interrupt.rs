@@ -0,0 +1,175 @@
+// git-enhancer/src/interrupt.rs
+//! Ctrl-C handling.
+//!
+//! While a child that owns the terminal runs (passthrough git, the pager) the
+//! interrupt belongs to that child: it is forwarded and we wait for the child to
+//! exit, then report its status. In-flight AI requests are cancelled through
+//! their `CancellationToken`, so the command stops the way it does on an error
+//! and exits with 130. Otherwise (e.g. at a prompt) nothing of ours needs
+//! cleaning up and the process exits with 130 right away; so does a second
+//! Ctrl-C while a cancellation is under way.
+//!
+//! Plain passthrough runs without the async runtime, so without the listener;
+//! there Ctrl-C is ignored while the child runs, which leaves it to the child
+//! just the same.
+
+use std::process::{Child, ExitStatus};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// Conventional exit status for a process terminated by SIGINT (128 + 2).
+pub const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// Process id of the child currently attached to the terminal, 0 when there is none.
+static FOREGROUND_CHILD: AtomicU32 = AtomicU32::new(0);
+
+/// How long to give a child to react to the terminal's own SIGINT before forwarding
+/// one. Children share our process group, so a Ctrl-C typed at the terminal already
+/// reached them; forwarding only matters when the signal was sent to us alone.
+const FORWARD_GRACE: Duration = Duration::from_millis(100);
+
+/// Becomes `true` when Ctrl-C cancels the in-flight AI requests
+static CANCELLED: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+/// Number of live `CancellationToken`s
+static IN_FLIGHT: AtomicU32 = AtomicU32::new(0);
+
+/// Whether `install_handler` ran
+static HANDLER_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+fn cancelled() -> &'static watch::Sender<bool> {
+    CANCELLED.get_or_init(|| watch::channel(false).0)
+}
+
+/// Lets Ctrl-C cancel an AI request instead of exiting under it, while held.
+pub struct CancellationToken(watch::Receiver<bool>);
+
+impl CancellationToken {
+    pub fn register() -> Self {
+        IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+        CancellationToken(cancelled().subscribe())
+    }
+
+    /// Completes once Ctrl-C was pressed, including before this was first awaited.
+    pub async fn cancelled(&mut self) {
+        // The sender is static, so waiting can't fail
+        let _ = self.0.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+impl Drop for CancellationToken {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Marks a spawned child as the owner of the terminal until dropped.
+pub struct ForegroundChild {
+    /// SIGINT disposition to restore, when Ctrl-C is ignored rather than handled
+    #[cfg(unix)]
+    previous_disposition: Option<libc::sighandler_t>,
+}
+
+impl ForegroundChild {
+    pub fn register(child: &Child) -> Self {
+        FOREGROUND_CHILD.store(child.id(), Ordering::SeqCst);
+        ForegroundChild {
+            // Only after spawning: the child must not inherit the ignored signal.
+            // SAFETY: signal(2) has no memory-safety preconditions
+            #[cfg(unix)]
+            previous_disposition: (!HANDLER_INSTALLED.load(Ordering::SeqCst))
+                .then(|| unsafe { libc::signal(libc::SIGINT, libc::SIG_IGN) }),
+        }
+    }
+}
+
+impl Drop for ForegroundChild {
+    fn drop(&mut self) {
+        FOREGROUND_CHILD.store(0, Ordering::SeqCst);
+        #[cfg(unix)]
+        if let Some(previous) = self.previous_disposition {
+            // SAFETY: as above; `previous` came from signal(2) itself
+            unsafe {
+                libc::signal(libc::SIGINT, previous);
+            }
+        }
+    }
+}
+
+/// Installs the Ctrl-C listener. Must be called from inside the tokio runtime.
+pub fn install_handler() -> std::io::Result<()> {
+    // Creating the stream registers the OS handler right away, so an interrupt
+    // arriving before the task is first polled is not lost.
+    #[cfg(unix)]
+    let mut interrupts = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::interrupt())?;
+    #[cfg(windows)]
+    let mut interrupts = tokio::signal::windows::ctrl_c()?;
+    HANDLER_INSTALLED.store(true, Ordering::SeqCst);
+
+    tokio::spawn(async move {
+        while interrupts.recv().await.is_some() {
+            if FOREGROUND_CHILD.load(Ordering::SeqCst) == 0 {
+                if IN_FLIGHT.load(Ordering::SeqCst) > 0 && !cancelled().send_replace(true) {
+                    continue;
+                }
+                // Finish a half-printed line so the shell prompt starts cleanly
+                eprintln!();
+                std::process::exit(INTERRUPTED_EXIT_CODE);
+            }
+            tokio::time::sleep(FORWARD_GRACE).await;
+            forward_to_foreground_child();
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+fn forward_to_foreground_child() {
+    let pid = FOREGROUND_CHILD.load(Ordering::SeqCst);
+    if pid != 0 {
+        tracing::debug!("Forwarding SIGINT to child process {}", pid);
+        // SAFETY: kill(2) has no memory-safety preconditions
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGINT);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn forward_to_foreground_child() {
+    // Console Ctrl-C events are delivered to every process attached to the console.
+}
+
+/// Exit code the shell would report for `status`: the child's own code, or
+/// 128 + signal number when it was killed by a signal.
+pub fn exit_code(status: ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return Some(128 + signal);
+        }
+    }
+    status.code()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_exit_code_keeps_regular_codes() {
+        let status = Command::new("sh").args(["-c", "exit 3"]).status().unwrap();
+        assert_eq!(exit_code(status), Some(3));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exit_code_maps_signals_like_the_shell() {
+        let status = Command::new("sh").args(["-c", "kill -INT $$"]).status().unwrap();
+        assert_eq!(exit_code(status), Some(INTERRUPTED_EXIT_CODE));
+    }
+}
@@ -0,0 +1,272 @@
+// git-enhancer/src/issue_tracker.rs
+//! The ticket a change is for, fetched from the issue tracker.
+//!
+//! `commit --ai` and `pr-describe` add the ticket's title and description to
+//! their prompts, so messages explain the requirement and not only the diff.
+//! The ticket is given with `--ticket`, or found in the branch name:
+//! `feature/PROJ-123-login` for Jira, `123-fix-login` or `issue-123` for GitHub
+//! Issues. `[issues] tracker` chooses the tracker; nothing is fetched while it
+//! is `none`, the default. A ticket that can't be fetched only warns, as the
+//! message can still be written from the diff.
+
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::LazyLock;
+
+use crate::config::AppConfig;
+use crate::errors::ForgeError;
+use crate::forge;
+use crate::git_context::GitContext;
+use crate::untrusted;
+
+/// Characters of a ticket's description put in a prompt
+const MAX_DESCRIPTION_CHARS: usize = 4_000;
+
+/// Environment variable holding the Jira API token
+const JIRA_TOKEN_VARIABLE: &str = "JIRA_API_TOKEN";
+
+/// Told to the model along with a ticket
+pub const TICKET_RULES: &str = "The changes were made for the ticket given with them. Use it to say why \
+    the change is made, and mention the ticket's ID.";
+
+static RE_JIRA_KEY: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^A-Za-z0-9])([A-Z][A-Z0-9]+-[0-9]+)(?:[^0-9]|$)").unwrap());
+static RE_JIRA_KEY_ANY_CASE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^A-Za-z0-9])([A-Za-z][A-Za-z0-9]+-[0-9]+)(?:[^0-9]|$)").unwrap());
+static RE_ISSUE_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)(?:^|/)(?:issue-|issues/|gh-|#)?([0-9]+)(?:[-_/]|$)").unwrap());
+
+// 工单系统集成的配置
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct IssuesConfig {
+    /// Where tickets are fetched from
+    #[serde(default)]
+    pub tracker: IssueTracker,
+    /// Jira site, e.g. `https://example.atlassian.net`
+    #[serde(default)]
+    pub jira_url: Option<String>,
+    /// Account e-mail for Jira Cloud API tokens; without it the token is sent
+    /// as a personal access token (Jira Server / Data Center)
+    #[serde(default)]
+    pub jira_email: Option<String>,
+    /// Project keys whose tickets are looked for in branch names, e.g. `["PROJ"]`.
+    /// With them, lowercase keys like `proj-123` are found too.
+    #[serde(default)]
+    pub jira_projects: Vec<String>,
+    /// Jira API token; GitHub Issues use the `[github]` token
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+/// An issue tracker, or none
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueTracker {
+    #[default]
+    None,
+    #[serde(rename = "github")]
+    GitHub,
+    Jira,
+}
+
+impl IssueTracker {
+    /// The name used in the configuration
+    pub fn name(self) -> &'static str {
+        match self {
+            IssueTracker::None => "none",
+            IssueTracker::GitHub => "github",
+            IssueTracker::Jira => "jira",
+        }
+    }
+}
+
+/// A ticket as put in prompts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ticket {
+    /// `PROJ-123`, or `#123` for GitHub Issues
+    pub id: String,
+    pub title: String,
+    pub description: String,
+}
+
+impl Ticket {
+    /// The ticket, fenced as untrusted content, for the user prompt
+    pub fn prompt_section(&self) -> String {
+        let mut description = self.description.trim().to_string();
+        if let Some((end, _)) = description.char_indices().nth(MAX_DESCRIPTION_CHARS) {
+            description.truncate(end);
+            description.push_str("\n[description truncated]");
+        }
+        let text = if description.is_empty() {
+            format!("{}: {}", self.id, self.title)
+        } else {
+            format!("{}: {}\n\n{}", self.id, self.title, description)
+        };
+        format!("Ticket:\n{}", untrusted::fence("ticket", &text))
+    }
+}
+
+/// The ticket for this change: `explicit` (from `--ticket`), else the one named
+/// by the current branch. `None` when no tracker is configured, no ticket is
+/// found, or fetching it fails (which warns).
+pub async fn ticket_context(config: &AppConfig, git_context: &GitContext, explicit: Option<&str>) -> Option<Ticket> {
+    let issues = &config.issues;
+    if issues.tracker == IssueTracker::None {
+        if explicit.is_some() {
+            crate::ui::warning("--ticket is ignored: set [issues] tracker to github or jira");
+        }
+        return None;
+    }
+    let id = match explicit {
+        Some(id) => normalize_id(issues, id)?,
+        None => ticket_in_branch(issues, git_context.current_branch()?)?,
+    };
+    tracing::debug!("Fetching ticket {} from {}", id, issues.tracker.name());
+    let fetched = match issues.tracker {
+        IssueTracker::Jira => fetch_jira(issues, &id).await,
+        _ => fetch_github_issue(config, git_context, &id).await,
+    };
+    match fetched {
+        Ok(ticket) => {
+            crate::ui::status(&format!("Using ticket {}: {}", ticket.id, ticket.title));
+            Some(ticket)
+        }
+        Err(e) => {
+            crate::ui::warning(&format!("could not fetch ticket {}: {}", id, e));
+            None
+        }
+    }
+}
+
+/// The ticket ID in `--ticket`'s value: an upper-cased Jira key, or an issue number
+fn normalize_id(issues: &IssuesConfig, id: &str) -> Option<String> {
+    let id = id.trim();
+    let normalized = match issues.tracker {
+        IssueTracker::Jira => id.to_uppercase(),
+        _ => id.trim_start_matches('#').to_string(),
+    };
+    if normalized.is_empty() {
+        crate::ui::warning("--ticket is empty");
+        return None;
+    }
+    Some(normalized)
+}
+
+/// The ticket ID the branch name carries, if any
+fn ticket_in_branch(issues: &IssuesConfig, branch: &str) -> Option<String> {
+    match issues.tracker {
+        IssueTracker::None => None,
+        IssueTracker::GitHub => RE_ISSUE_NUMBER
+            .captures(branch)
+            .map(|captures| captures[1].to_string()),
+        IssueTracker::Jira if issues.jira_projects.is_empty() => RE_JIRA_KEY
+            .captures_iter(branch)
+            .map(|captures| captures[1].to_string())
+            .next(),
+        IssueTracker::Jira => RE_JIRA_KEY_ANY_CASE
+            .captures_iter(branch)
+            .map(|captures| captures[1].to_uppercase())
+            .find(|key| {
+                let project = key.rsplit_once('-').map_or("", |(project, _)| project);
+                issues.jira_projects.iter().any(|known| known.eq_ignore_ascii_case(project))
+            }),
+    }
+}
+
+/// Issue `number` of the repository `origin` points to on GitHub
+async fn fetch_github_issue(config: &AppConfig, git_context: &GitContext, number: &str) -> Result<Ticket, String> {
+    #[derive(Deserialize)]
+    struct Issue {
+        number: u64,
+        title: String,
+        body: Option<String>,
+    }
+    let url = git_context
+        .git(&["remote".to_string(), "get-url".to_string(), "origin".to_string()])
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| output.stdout.trim().to_string())
+        .ok_or("the repository has no remote 'origin'")?;
+    let host = config.github.host();
+    let repository = forge::parse_remote_url(&url, &host).ok_or_else(|| format!("origin ({}) is not a repository on {}", url, host))?;
+    let http = forge::http_client(&host).map_err(|e| e.to_string())?;
+    let mut request = http
+        .get(format!("{}/repos/{}/issues/{}", config.github.api_url.trim_end_matches('/'), repository, number))
+        .header(reqwest::header::ACCEPT, "application/vnd.github+json");
+    // Public repositories don't need a token
+    if let Ok(token) = forge::token(config.github.token.as_deref(), crate::github::TOKEN_VARIABLES, &host, "github.token") {
+        request = request.bearer_auth(token);
+    }
+    let response = forge::send(&host, request).await.map_err(|e| e.to_string())?;
+    let issue: Issue = response
+        .json()
+        .await
+        .map_err(|source| ForgeError::RequestFailed { host: host.clone(), source }.to_string())?;
+    Ok(Ticket { id: format!("#{}", issue.number), title: issue.title, description: issue.body.unwrap_or_default() })
+}
+
+/// Jira issue `key`
+async fn fetch_jira(issues: &IssuesConfig, key: &str) -> Result<Ticket, String> {
+    #[derive(Deserialize)]
+    struct Issue {
+        key: String,
+        fields: Fields,
+    }
+    #[derive(Deserialize)]
+    struct Fields {
+        summary: String,
+        description: Option<String>,
+    }
+    let site = issues.jira_url.as_deref().ok_or("set [issues] jira_url")?.trim_end_matches('/');
+    let host = site.split_once("://").map_or(site, |(_, rest)| rest).split(['/', ':']).next().unwrap_or(site);
+    let http = forge::http_client(host).map_err(|e| e.to_string())?;
+    let mut request = http.get(format!("{}/rest/api/2/issue/{}?fields=summary,description", site, key));
+    let token = issues
+        .token
+        .clone()
+        .filter(|token| !token.is_empty())
+        .or_else(|| std::env::var(JIRA_TOKEN_VARIABLE).ok().filter(|token| !token.is_empty()));
+    request = match (token, &issues.jira_email) {
+        (Some(token), Some(email)) => request.basic_auth(email, Some(token)),
+        (Some(token), None) => request.bearer_auth(token),
+        (None, _) => request,
+    };
+    let response = forge::send(host, request).await.map_err(|e| e.to_string())?;
+    let issue: Issue = response
+        .json()
+        .await
+        .map_err(|source| ForgeError::RequestFailed { host: host.to_string(), source }.to_string())?;
+    Ok(Ticket { id: issue.key, title: issue.fields.summary, description: issue.fields.description.unwrap_or_default() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ticket_in_branch() {
+        let jira = IssuesConfig { tracker: IssueTracker::Jira, ..Default::default() };
+        assert_eq!(ticket_in_branch(&jira, "feature/PROJ-123-login").as_deref(), Some("PROJ-123"));
+        assert_eq!(ticket_in_branch(&jira, "ABC2-7").as_deref(), Some("ABC2-7"));
+        assert_eq!(ticket_in_branch(&jira, "feature/proj-123-login"), None);
+        let projects = IssuesConfig { jira_projects: vec!["PROJ".to_string()], ..jira };
+        assert_eq!(ticket_in_branch(&projects, "fix/utf-8-and-proj-42").as_deref(), Some("PROJ-42"));
+        assert_eq!(ticket_in_branch(&projects, "fix/UTF-8-names"), None);
+
+        let github = IssuesConfig { tracker: IssueTracker::GitHub, ..Default::default() };
+        assert_eq!(ticket_in_branch(&github, "123-fix-login").as_deref(), Some("123"));
+        assert_eq!(ticket_in_branch(&github, "fix/issue-45-crash").as_deref(), Some("45"));
+        assert_eq!(ticket_in_branch(&github, "gh-7").as_deref(), Some("7"));
+        assert_eq!(ticket_in_branch(&github, "release/1.2"), None);
+        assert_eq!(ticket_in_branch(&github, "feature/login"), None);
+    }
+
+    #[test]
+    fn test_prompt_section_truncates_the_description() {
+        let ticket = Ticket { id: "PROJ-1".to_string(), title: "Login".to_string(), description: "x".repeat(MAX_DESCRIPTION_CHARS + 10) };
+        let section = ticket.prompt_section();
+        assert!(section.starts_with("Ticket:\n<untrusted-ticket-"));
+        assert!(section.contains("PROJ-1: Login\n\n"));
+        assert!(section.contains("[description truncated]"));
+    }
+}
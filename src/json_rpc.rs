@@ -0,0 +1,121 @@
+// git-enhancer/src/json_rpc.rs
+//! JSON-RPC 2.0 over stdio, the transport of `serve`.
+//!
+//! Every message is one line of JSON on stdin; responses are written as one
+//! line each to stdout, in the order of the requests. Notifications (requests
+//! without an `id`) are handled but get no response. Logging goes to stderr,
+//! so nothing but responses ever reaches stdout. The server stops at the end
+//! of its input.
+
+use serde_json::{Value, json};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+/// The method itself failed, e.g. the model could not be reached
+pub const SERVER_ERROR: i64 = -32000;
+
+/// An error response
+#[derive(Debug, Clone, PartialEq)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into() }
+    }
+
+    /// The error for a method this server doesn't have
+    pub fn method_not_found(method: &str) -> Self {
+        RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", method))
+    }
+}
+
+/// What answers the requests
+pub trait Handler {
+    /// The result of `method` called with `params` (`null` when there are none)
+    async fn handle(&mut self, method: &str, params: Value) -> Result<Value, RpcError>;
+}
+
+/// Answers the requests on stdin until it ends
+pub async fn serve(handler: &mut impl Handler) -> std::io::Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => dispatch(handler, message).await,
+            Err(e) => Some(error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string()))),
+        };
+        if let Some(response) = response {
+            let mut text = response.to_string();
+            text.push('\n');
+            stdout.write_all(text.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+/// The response to one message, if it needs one
+async fn dispatch(handler: &mut impl Handler, message: Value) -> Option<Value> {
+    let Value::Object(mut message) = message else {
+        return Some(error_response(Value::Null, RpcError::new(INVALID_REQUEST, "expected a JSON-RPC request object")));
+    };
+    let id = message.remove("id");
+    let method = match message.remove("method") {
+        Some(Value::String(method)) => method,
+        // A response to a request of ours; this server sends none
+        None if message.contains_key("result") || message.contains_key("error") => return None,
+        _ => return Some(error_response(id.unwrap_or(Value::Null), RpcError::new(INVALID_REQUEST, "missing 'method'"))),
+    };
+    if message.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return id.map(|id| error_response(id, RpcError::new(INVALID_REQUEST, "'jsonrpc' must be \"2.0\"")));
+    }
+    let params = message.remove("params").unwrap_or(Value::Null);
+    tracing::debug!("JSON-RPC {} {}", method, params);
+    let result = handler.handle(&method, params).await;
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => error_response(id, error),
+    })
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": error.code, "message": error.message } })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    impl Handler for Echo {
+        async fn handle(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+            match method {
+                "echo" => Ok(params),
+                _ => Err(RpcError::method_not_found(method)),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let response = dispatch(&mut Echo, json!({ "jsonrpc": "2.0", "id": 1, "method": "echo", "params": [1] })).await;
+        assert_eq!(response, Some(json!({ "jsonrpc": "2.0", "id": 1, "result": [1] })));
+        let response = dispatch(&mut Echo, json!({ "jsonrpc": "2.0", "id": "a", "method": "nope" })).await.unwrap();
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(dispatch(&mut Echo, json!({ "jsonrpc": "2.0", "method": "echo" })).await, None);
+        assert_eq!(dispatch(&mut Echo, json!({ "jsonrpc": "2.0", "id": 2, "result": {} })).await, None);
+        let response = dispatch(&mut Echo, json!([1, 2])).await.unwrap();
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+    }
+}
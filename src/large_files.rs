@@ -0,0 +1,197 @@
+// git-enhancer/src/large_files.rs
+//! Advice on large files about to enter the history.
+//!
+//! Before `commit --ai` generates a message, newly added files above
+//! `[commit] large_file_bytes` are listed with their sizes. Once committed they
+//! stay in every clone for good, so the user can move them to Git LFS, unstage
+//! them, or commit them anyway; the model explains the tradeoff for these files.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::ai_utils::{ChatMessage, OpenAIChatRequest, clean_ai_output, send_chat_request};
+use crate::commit_commands::format_size;
+use crate::config::AppConfig;
+use crate::errors::{AIError, AppError};
+use crate::git_backend::GitBackend;
+use crate::git_commands::map_output_to_git_command_error;
+use crate::git_context::GitContext;
+use crate::prompt::{self, Choice};
+use crate::types::GitStatus;
+use crate::ui;
+
+const TRADEOFF_PROMPT: &str = "The user is about to commit the large files listed below to a git \
+    repository. In one short paragraph, explain the tradeoff for these specific files: committed \
+    as they are, they stay in the history of every clone forever, even if deleted later; tracked \
+    with Git LFS, only small pointers enter the history, but everyone needs Git LFS installed and \
+    the server has to support it; unstaged, they stay out of this commit. Recommend one option \
+    based on the kind of files. Do not use headings or lists.";
+
+/// A newly added file above the size limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct LargeFile {
+    /// Relative to the repository root
+    path: String,
+    bytes: u64,
+}
+
+/// Warns about large new files in `status` and lets the user decide what to do with them.
+///
+/// Returns whether the index was changed, in which case the status has to be read again.
+pub async fn advise(
+    config: &AppConfig,
+    git_context: &GitContext,
+    backend: &dyn GitBackend,
+    status: &GitStatus,
+) -> Result<bool, AppError> {
+    let limit = config.commit.large_file_bytes();
+    if limit == 0 {
+        return Ok(false);
+    }
+    let large: Vec<LargeFile> = status
+        .staged
+        .iter()
+        .filter(|file| file.status_code == "A")
+        .filter_map(|file| {
+            // `:<path>` is the staged blob, whatever the working tree holds now
+            let bytes = backend.object_size(&format!(":{}", file.path)).ok()?;
+            (bytes > limit).then(|| LargeFile { path: file.path.clone(), bytes })
+        })
+        .collect();
+    if large.is_empty() {
+        return Ok(false);
+    }
+
+    ui::warning(&format!(
+        "these new files are larger than {} and will be in the history of every clone:",
+        format_size(limit)
+    ));
+    for file in &large {
+        eprintln!("  {} ({})", file.path, format_size(file.bytes));
+    }
+    if !prompt::is_interactive() {
+        return Ok(false);
+    }
+    let lfs_installed = is_lfs_installed();
+    match explain_tradeoff(config, &large, lfs_installed).await {
+        Ok(explanation) => eprintln!("\n{}\n", explanation.trim()),
+        Err(AIError::Cancelled) => return Err(AIError::Cancelled.into()),
+        Err(e) => tracing::debug!("Could not explain the large file tradeoff: {}", e),
+    }
+
+    let mut choices = vec![
+        Choice::new('c', "commit them anyway"),
+        Choice::new('u', "unstage them"),
+        Choice::new('a', "abort"),
+    ];
+    if lfs_installed {
+        choices.insert(1, Choice::new('l', "track them with Git LFS"));
+    }
+    let root = git_context.root()?;
+    let paths: Vec<&str> = large.iter().map(|file| file.path.as_str()).collect();
+    match choices[prompt::select("", &choices, 0)?].key {
+        'l' => {
+            let patterns = lfs_patterns(&paths);
+            let mut track = vec!["lfs", "track"];
+            track.extend(patterns.iter().map(String::as_str));
+            run_git(root, &track)?;
+            run_git(root, &["add", ".gitattributes"])?;
+            // Re-adding goes through the LFS filter, which stages pointers instead
+            run_git(root, &[&["rm", "--cached", "--quiet", "--"][..], &paths].concat())?;
+            run_git(root, &[&["add", "--"][..], &paths].concat())?;
+            ui::status(&format!("Tracking {} with Git LFS", patterns.join(", ")));
+            Ok(true)
+        }
+        'u' => {
+            run_git(root, &[&["rm", "--cached", "--quiet", "--"][..], &paths].concat())?;
+            Ok(true)
+        }
+        'a' => Err(AppError::Generic("Commit aborted".to_string())),
+        _ => Ok(false),
+    }
+}
+
+/// `git lfs track` patterns for `paths`: `*.<ext>` where there is an extension,
+/// the path itself otherwise
+fn lfs_patterns(paths: &[&str]) -> Vec<String> {
+    let mut patterns: Vec<String> = Vec::new();
+    for path in paths {
+        let pattern = match Path::new(path).extension() {
+            Some(extension) => format!("*.{}", extension.to_string_lossy()),
+            None => path.to_string(),
+        };
+        if !patterns.contains(&pattern) {
+            patterns.push(pattern);
+        }
+    }
+    patterns
+}
+
+fn is_lfs_installed() -> bool {
+    Command::new("git")
+        .args(["lfs", "version"])
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+fn run_git(root: &Path, args: &[&str]) -> Result<(), AppError> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(root)
+        .output()
+        .map_err(|e| AppError::Io(format!("running git {}", args.join(" ")), e))?;
+    if !output.status.success() {
+        return Err(map_output_to_git_command_error(&format!("git {}", args.join(" ")), output).into());
+    }
+    Ok(())
+}
+
+/// Asks the model to weigh committing, LFS and unstaging for these files.
+async fn explain_tradeoff(config: &AppConfig, large: &[LargeFile], lfs_installed: bool) -> Result<String, AIError> {
+    let parameters = config.model_parameters("explain");
+    // File names are repository content
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, true)?;
+    let mut files = String::new();
+    for file in large {
+        files.push_str(&format!("- {} ({})\n", file.path, format_size(file.bytes)));
+    }
+    let lfs = if lfs_installed { "installed" } else { "not installed" };
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: TRADEOFF_PROMPT.to_string() },
+            ChatMessage {
+                role: "user".to_string(),
+                content: format!("Files:\n{}\nGit LFS is {} on this machine.", files, lfs),
+            },
+        ],
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    let response = send_chat_request(config, &parameters.api_url, &request).await?;
+    let text = response
+        .choices
+        .first()
+        .map(|choice| clean_ai_output(&choice.message.content))
+        .ok_or(AIError::NoChoiceInResponse)?;
+    if text.trim().is_empty() {
+        return Err(AIError::EmptyMessage);
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lfs_patterns_group_by_extension() {
+        assert_eq!(
+            lfs_patterns(&["assets/intro.mp4", "assets/outro.mp4", "data/model.bin", "tools/blob"]),
+            vec!["*.mp4", "*.bin", "tools/blob"]
+        );
+    }
+}
@@ -0,0 +1,93 @@
+// git-enhancer/src/lib.rs
+//! The engine behind the `git-enhancer` command, usable from other Rust tools.
+//!
+//! The binary is a thin shell over this crate: it takes git-enhancer's own
+//! options out of the command line and hands the rest to the subcommand
+//! handlers, or to git. Editors, bots and other tools can call the same
+//! pieces directly:
+//!
+//! * [`config`] loads `~/.config/gitie` (with `gitie.*` git settings and the
+//!   system policy applied) into an [`config::AppConfig`], including the
+//!   compiled prompts of [`prompt_template`].
+//! * [`ai_utils`] talks to OpenAI-compatible chat and embedding endpoints,
+//!   with retries, timeouts and cancellation.
+//! * [`diff`] parses unified diffs; [`git_backend`] reads the repository data
+//!   prompts are built from, by running git or in-process with the `git2`
+//!   feature.
+//! * [`commit`] generates commit messages through a stable API (see below);
+//!   [`commit_commands`] is the `commit` subcommand around it and
+//!   [`ai_explainer`] explains git commands and their output.
+//! * [`errors`] has the error types every fallible function returns.
+//!
+//! Handlers print to stdout and may ask questions on the terminal, as the
+//! command line does; [`output::OutputFormat::Json`] makes their output
+//! machine-readable and [`prompt::set_mode`] answers questions unattended.
+//!
+//! Only [`commit`] follows semantic versioning; the other modules are the
+//! internals of the command line and may change in any release.
+
+pub mod aliases;
+pub mod ai_explainer;
+pub mod ai_utils;
+pub mod branches_commands;
+pub mod cli;
+pub mod commit;
+pub mod commit_commands;
+pub mod config;
+pub mod config_commands;
+pub mod crash;
+pub mod diff;
+pub mod doctor_commands;
+pub mod errors;
+pub mod export_commands;
+pub mod forge;
+pub mod git_backend;
+pub mod git_commands;
+pub mod git_context;
+pub mod gitea;
+pub mod github;
+pub mod glossary;
+pub mod guard;
+pub mod history_index;
+pub mod interaction_log;
+pub mod interrupt;
+pub mod issue_tracker;
+pub mod log_summary_commands;
+pub mod next_commands;
+pub mod notify;
+pub mod output;
+pub mod policy;
+pub mod pr_commands;
+pub mod prompt;
+pub mod prompt_template;
+pub mod review;
+pub mod search_commands;
+pub mod serve_commands;
+pub mod stats_commands;
+pub mod style;
+pub mod suggestions;
+pub mod task_commands;
+pub mod teach;
+pub mod team_commands;
+pub mod types;
+pub mod ui;
+pub mod usage_stats;
+
+mod ai_hooks;
+mod audit;
+mod capture;
+mod clipboard;
+mod clock;
+mod editor_server;
+mod history_selection;
+mod json_rpc;
+mod large_files;
+mod markdown;
+mod mcp;
+mod post_generate;
+mod progress;
+mod remotes;
+mod safety;
+mod scope;
+mod state_file;
+mod untrusted;
@@ -0,0 +1,277 @@
+// git-enhancer/src/log_summary_commands.rs
+//! The `summarize-log` and `changelog` subcommands.
+//!
+//! A range of thousands of commits doesn't fit in a model's context window, so
+//! long ranges are summarized hierarchically: the commits are split into chunks
+//! that are summarized independently (several requests at a time), groups of
+//! those summaries are merged until few enough remain, and one last request
+//! writes the summary or changelog from them. Short ranges go straight to the
+//! last step.
+
+use crate::ai_utils::{AiReply, ChatMessage, OpenAIChatRequest, OpenAIUsage, clean_ai_output, run_concurrently, send_chat_request};
+use crate::cli::LogSummaryArgs;
+use crate::config::AppConfig;
+use crate::errors::{AIError, AppError, GitError};
+use crate::git_commands::execute_git_command_and_capture_output;
+use crate::git_context::GitContext;
+use crate::history_selection::Selection;
+use crate::output;
+use crate::progress::Progress;
+use crate::scope;
+use crate::untrusted::{self, UNTRUSTED_CONTENT_RULES};
+
+const CHUNK_PROMPT: &str = "Below is a batch of consecutive git commits (hash, author, subject \
+    and body), newest first, taken from a longer history. Summarize what changed in this batch as \
+    a concise bullet list grouped by theme. Keep the concrete names of features, components and \
+    fixes, and call out breaking changes explicitly. Do not add an introduction.";
+
+const MERGE_PROMPT: &str = "Below are summaries of consecutive batches of git commits, newest \
+    first. Merge them into one concise bullet list grouped by theme, keeping the concrete names \
+    of features, components and fixes and every breaking change, and dropping repetition. Do not \
+    add an introduction.";
+
+const SUMMARY_PROMPT: &str = "Below is the history of a range of commits in a git repository, \
+    either as the commits themselves or as summaries of batches of them, newest first. Write a \
+    summary for a developer catching up on this range: a short overview paragraph, then the main \
+    themes as bullet points with the most important changes under each. Only use the information \
+    given.";
+
+const CHANGELOG_PROMPT: &str = "Below is the history of a range of commits in a git repository, \
+    either as the commits themselves or as summaries of batches of them, newest first. Write the \
+    changelog for a release covering this range in Markdown, with the sections Added, Changed, \
+    Fixed and Removed (leave out empty ones) and one bullet per user-visible change, written for \
+    users rather than developers. Leave out purely internal changes such as refactoring, tests \
+    and CI. Only use the information given.";
+
+/// Summaries merged by one request
+const MERGE_FAN_IN: usize = 10;
+
+/// Characters of a commit body included; subjects are always complete
+const MAX_BODY_CHARS: usize = 500;
+
+/// What the final request writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Summary,
+    Changelog,
+}
+
+/// Handles the `summarize-log` subcommand
+///
+/// # Arguments
+///
+/// * `args` - Commits to cover and chunk size from CLI
+/// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub async fn handle_summarize_log(args: LogSummaryArgs, config: &AppConfig, git_context: &GitContext) -> Result<(), AppError> {
+    git_context.require_repository()?;
+    let selection = Selection::check(&args.history, "HEAD")?;
+    summarize_range(&args, &selection, Outcome::Summary, config, git_context).await
+}
+
+/// Handles the `changelog` subcommand
+///
+/// Without a range, covers the commits since the latest tag (all of them if
+/// there is no tag yet).
+///
+/// # Arguments
+///
+/// * `args` - Commits to cover and chunk size from CLI
+/// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub async fn handle_changelog(args: LogSummaryArgs, config: &AppConfig, git_context: &GitContext) -> Result<(), AppError> {
+    git_context.require_repository()?;
+    let default_range = match args.history.range {
+        Some(_) => "HEAD".to_string(),
+        None => match git(&["describe", "--tags", "--abbrev=0"]) {
+            Ok(tag) => format!("{}..HEAD", tag.trim()),
+            Err(_) => "HEAD".to_string(),
+        },
+    };
+    let selection = Selection::check(&args.history, &default_range)?;
+    summarize_range(&args, &selection, Outcome::Changelog, config, git_context).await
+}
+
+async fn summarize_range(
+    args: &LogSummaryArgs,
+    selection: &Selection,
+    outcome: Outcome,
+    config: &AppConfig,
+    git_context: &GitContext,
+) -> Result<(), AppError> {
+    let scope = scope::resolve(git_context, config.scope.as_deref())?;
+    let mut log_args = vec!["log".to_string(), "--no-merges".to_string(), "--format=%x1e%h%x1f%an%x1f%s%x1f%b".to_string()];
+    log_args.extend(selection.log_args(scope.as_ref()));
+    let commits = parse_commits(&git(&log_args)?);
+    let (kind, empty) = match outcome {
+        Outcome::Summary => ("log-summary", format!("No commits {}.", selection)),
+        Outcome::Changelog => ("changelog", format!("No changes {}.", selection)),
+    };
+    let chunk_size = args.chunk_size.max(1);
+    let data = serde_json::json!({
+        "range": selection.range,
+        "since": selection.since,
+        "until": selection.until,
+        "author_patterns": selection.authors,
+        "paths": selection.paths,
+        "scope": scope.as_ref().map(ToString::to_string),
+        "commits": commits.len(),
+        "chunks": commits.len().div_ceil(chunk_size),
+    });
+
+    let reply = if commits.is_empty() {
+        AiReply::local(empty)
+    } else {
+        map_reduce(config, &commits, chunk_size, outcome).await?
+    };
+    output::print_ai_reply_with_data(&config.output, kind, &reply, Some(&data), 0)
+}
+
+/// Summarizes `commits` chunk by chunk, merges the chunk summaries level by
+/// level and writes the `outcome` from what remains. Usage is added up over
+/// every request.
+async fn map_reduce(config: &AppConfig, commits: &[String], chunk_size: usize, outcome: Outcome) -> Result<AiReply, AppError> {
+    let mut usage = None;
+    let final_input = if commits.len() <= chunk_size {
+        untrusted::fence("commits", &commits.join("\n"))
+    } else {
+        let chunks: Vec<&[String]> = commits.chunks(chunk_size).collect();
+        let mut summaries = run_level(config, "Summarizing commits", CHUNK_PROMPT, "commits", &chunks, &mut usage).await?;
+        while summaries.len() > MERGE_FAN_IN {
+            let groups: Vec<&[String]> = summaries.chunks(MERGE_FAN_IN).collect();
+            summaries = run_level(config, "Merging summaries", MERGE_PROMPT, "summaries", &groups, &mut usage).await?;
+        }
+        untrusted::fence("summaries", &summaries.join("\n\n"))
+    };
+    let prompt = match outcome {
+        Outcome::Summary => SUMMARY_PROMPT,
+        Outcome::Changelog => CHANGELOG_PROMPT,
+    };
+    let mut reply = ask(config, prompt, final_input).await?;
+    add_usage(&mut usage, reply.usage.take());
+    reply.usage = usage;
+    Ok(reply)
+}
+
+/// One request per group, up to `ai.max_concurrency` at a time, with progress on stderr
+async fn run_level(
+    config: &AppConfig,
+    label: &str,
+    prompt: &str,
+    content_label: &str,
+    groups: &[&[String]],
+    usage: &mut Option<OpenAIUsage>,
+) -> Result<Vec<String>, AppError> {
+    let progress = Progress::start(label, groups.len());
+    let replies = run_concurrently(config.ai.max_concurrency, groups, |group| {
+        let progress = &progress;
+        async move {
+            let reply = ask(config, prompt, untrusted::fence(content_label, &group.join("\n\n"))).await;
+            progress.advance(1);
+            reply
+        }
+    })
+    .await;
+    drop(progress);
+    let mut texts = Vec::with_capacity(replies.len());
+    for reply in replies {
+        let reply = reply?;
+        add_usage(usage, reply.usage);
+        texts.push(reply.text);
+    }
+    Ok(texts)
+}
+
+async fn ask(config: &AppConfig, system_prompt: &str, content: String) -> Result<AiReply, AIError> {
+    let parameters = config.model_parameters("explain");
+    // Commit messages are repository content
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, true)?;
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: format!("{}\n\n{}", system_prompt, UNTRUSTED_CONTENT_RULES) },
+            ChatMessage { role: "user".to_string(), content },
+        ],
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    let response = send_chat_request(config, &parameters.api_url, &request).await?;
+    let choice = response.choices.first().ok_or(AIError::NoChoiceInResponse)?;
+    let text = clean_ai_output(&choice.message.content);
+    if text.trim().is_empty() {
+        return Err(AIError::EmptyMessage);
+    }
+    Ok(AiReply { text, model: response.model.clone(), usage: Some(response.usage.clone()) })
+}
+
+fn add_usage(total: &mut Option<OpenAIUsage>, usage: Option<OpenAIUsage>) {
+    match (total.as_mut(), usage) {
+        (Some(total), Some(usage)) => *total += usage,
+        (None, usage) => *total = usage,
+        (Some(_), None) => {}
+    }
+}
+
+/// One line per commit from `git log --format=%x1e%h%x1f%an%x1f%s%x1f%b`:
+/// `<hash> <subject> (<author>)`, followed by the shortened body if there is one
+fn parse_commits(output: &str) -> Vec<String> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut fields = record.splitn(4, '\x1f');
+            let (hash, author, subject) = (fields.next()?, fields.next()?, fields.next()?);
+            let body: String = fields.next().unwrap_or_default().trim().chars().take(MAX_BODY_CHARS).collect();
+            let mut commit = format!("{} {} ({})", hash, subject, author);
+            if !body.is_empty() {
+                commit.push_str("\n  ");
+                commit.push_str(&body.replace('\n', "\n  "));
+            }
+            Some(commit)
+        })
+        .collect()
+}
+
+fn git<S: AsRef<str>>(args: &[S]) -> Result<String, AppError> {
+    let args: Vec<String> = args.iter().map(|arg| arg.as_ref().to_string()).collect();
+    let output = execute_git_command_and_capture_output(&args)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: format!("git {}", args.join(" ")),
+            status_code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+        .into());
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_commits_keeps_subject_author_and_short_body() {
+        let long_body = "x".repeat(MAX_BODY_CHARS + 50);
+        let output = format!(
+            "\x1eabc1234\x1fAnn\x1fFix pager\x1f\n\x1edef5678\x1fBob\x1fAdd docs\x1fExplain setup\nand usage\n\x1e0a1b2c3\x1fCy\x1fLong\x1f{}\n",
+            long_body
+        );
+        let commits = parse_commits(&output);
+        assert_eq!(commits.len(), 3);
+        assert_eq!(commits[0], "abc1234 Fix pager (Ann)");
+        assert_eq!(commits[1], "def5678 Add docs (Bob)\n  Explain setup\n  and usage");
+        assert_eq!(commits[2].len(), "0a1b2c3 Long (Cy)\n  ".len() + MAX_BODY_CHARS);
+    }
+}
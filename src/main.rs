@@ -1,154 +1,507 @@
-use clap::Parser;
-use std::env;
-
-mod ai_explainer;
-mod ai_utils;
-mod cli;
-mod commit_commands;
-mod config;
-mod errors;
-mod git_commands;
-mod types;
+use std::time::Instant;
+use tracing_subscriber::EnvFilter;
 
-// CLI and core types
-use crate::cli::{args_contain_help, CommitArgs, EnhancerSubCommand, GitEnhancerArgs};
+use git_enhancer::{crash, guard, interrupt, notify, output, prompt, prompt_template, style, suggestions, teach, ui, usage_stats};
 
-/// Checks if the `--ai` flag is present in the provided arguments
-fn args_contain_ai(args: &[String]) -> bool {
-    args.iter().any(|arg| arg == "--ai")
-}
-use crate::git_commands::{execute_git_command_and_capture_output, passthrough_to_git, map_output_to_git_command_error, is_git_available, is_in_git_repository};
-use crate::commit_commands::{handle_commit, handle_commit_passthrough};
-use config::AppConfig;
-use errors::{AppError, GitError, AIError};
+// CLI and core types
+use git_enhancer::aliases::Aliases;
+use git_enhancer::cli::{leading_global_args, parse_command_line, CommandKind, CommandLine, EnhancerSubCommand, GlobalArgs};
+use git_enhancer::git_commands::{execute_git_command_and_capture_output, passthrough_to_git};
+use git_enhancer::git_context::GitContext;
+use git_enhancer::commit_commands::handle_commit;
+use git_enhancer::config_commands::handle_config;
+use git_enhancer::doctor_commands::handle_doctor;
+use git_enhancer::export_commands::handle_export_history;
+use git_enhancer::branches_commands::handle_branches_cleanup;
+use git_enhancer::log_summary_commands::{handle_changelog, handle_summarize_log};
+use git_enhancer::next_commands::handle_next;
+use git_enhancer::pr_commands::handle_pr_describe;
+use git_enhancer::search_commands::handle_search;
+use git_enhancer::serve_commands::handle_serve;
+use git_enhancer::stats_commands::handle_stats;
+use git_enhancer::task_commands::handle_run_task;
+use git_enhancer::team_commands::handle_team_summary;
+use git_enhancer::config::AppConfig;
+use git_enhancer::errors::{AIError, AppError, ConfigError, GitError};
+use interrupt::exit_code;
+use output::{ColorMode, ResultRecord, OutputFile, OutputFormat};
+use prompt::AnswerMode;
+use style::Stream;
 
 // External dependencies
-use ai_explainer::{explain_git_command, explain_git_command_output};
-use ai_utils::{OpenAIChatCompletionResponse, OpenAIChatRequest, ChatMessage}; 
-
-
+use git_enhancer::ai_explainer::{explain_command_run, explain_git_command, explain_git_command_output, ExplanationLevel};
 
 fn main() {
+    crash::install_hook();
+    let mut raw_cli_args: Vec<String> = std::env::args().skip(1).collect();
+    // `GITIE_NO_AI=1` is a leading `--no-ai` for scripts that can't add one
+    if std::env::var("GITIE_NO_AI").is_ok_and(|value| !value.is_empty() && value != "0") {
+        raw_cli_args.insert(0, "--no-ai".to_string());
+    }
+    let leading = leading_global_args(&raw_cli_args).unwrap_or_else(|e| exit_with_usage_error(&e));
+    // Aliases come from the configuration, so `--config` has to be in place first
+    apply_config_option(&leading);
+    let aliases = if leading.no_ai { Aliases::default() } else { Aliases::from_env() };
+    let line = parse_command_line(&raw_cli_args, &aliases).unwrap_or_else(|e| exit_with_usage_error(&e));
+    let verbosity = line.global.verbosity();
+    ui::set_quiet(verbosity.quiet);
+    let color_flag = line.global.color_mode();
+    if let Some(mode) = color_flag {
+        style::set_mode(mode);
+    }
+    if line.global.no_input {
+        prompt::set_mode(AnswerMode::Defaults);
+    }
+    if line.global.yes {
+        prompt::set_mode(AnswerMode::AssumeYes);
+    }
+    // Only warnings by default: passthrough commands must leave stderr exactly as
+    // git wrote it. `-v`/`-q` take precedence over `RUST_LOG`.
+    let filter = match verbosity.log_filter() {
+        Some(level) => EnvFilter::new(level),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn")),
+    };
     tracing_subscriber::fmt()
         .with_writer(std::io::stderr)
+        .with_ansi(style::enabled(Stream::Stderr))
+        .with_env_filter(filter)
         .init();
-    let result = tokio::runtime::Builder::new_multi_thread()
-        .enable_all()
-        .build()
-        .unwrap()
-        .block_on(run_app());
+    // Repository selection sets environment variables inherited by every git child
+    // process, so it has to happen while the process is still single-threaded.
+    let result = apply_repository_options(&line.global)
+        .and_then(|()| {
+            if is_plain_passthrough(&line) {
+                tracing::debug!("Plain passthrough, skipping the configuration and the async runtime");
+                return passthrough_to_git(&line.git_args());
+            }
+            tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(async {
+                    interrupt::install_handler()
+                        .map_err(|e| AppError::Io("installing the Ctrl-C handler".to_string(), e))?;
+                    run_app(line, color_flag).await
+                })
+        });
 
     if let Err(e) = result {
-        tracing::error!("Application failed: {}", e);
-        let exit_code = match e {
-            AppError::Git(GitError::PassthroughFailed { status_code, .. }) => {
-                status_code.unwrap_or(128) 
-            }
-            AppError::Git(GitError::CommandFailed { status_code, .. }) => {
-                status_code.unwrap_or(128)
+        // Git already reported its own failure on the inherited stderr, and a
+        // cancelled request its `[cancelled]` marker
+        if !matches!(e, AppError::Git(GitError::PassthroughFailed { .. }) | AppError::AI(AIError::Cancelled)) {
+            ui::error(&e.to_string());
+            if let AppError::AI(ai_error) = &e
+                && let Some(hint) = ai_error.hint()
+            {
+                ui::hint(&hint);
             }
-            _ => 1, 
-        };
-        std::process::exit(exit_code);
+        }
+        std::process::exit(exit_code_for(&e));
     }
 }
 
-async fn run_app() -> Result<(), AppError> {
-    let config = AppConfig::load()?;
+/// Reports a command line clap rejected and exits the way git does for usage errors.
+fn exit_with_usage_error(error: &clap::Error) -> ! {
+    // clap's own rendering names its internal parser; the first line says it all
+    let message = error.to_string();
+    let message = message.lines().next().unwrap_or_default();
+    ui::error(message.strip_prefix("error: ").unwrap_or(message));
+    std::process::exit(129);
+}
+
+/// Exports `--config <path>` as `GITIE_CONFIG`, made absolute first so that a
+/// later `-C` doesn't change which file it names.
+fn apply_config_option(options: &GlobalArgs) {
+    if let Some(path) = &options.config {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.into());
+        // SAFETY: called from `main` before any threads are started
+        unsafe { std::env::set_var("GITIE_CONFIG", path) };
+    }
+}
+
+/// The status this process exits with when `error` ends it.
+fn exit_code_for(error: &AppError) -> i32 {
+    match error {
+        AppError::Git(GitError::PassthroughFailed { status_code, .. }) => {
+            status_code.unwrap_or(128) 
+        }
+        AppError::Git(GitError::CommandFailed { status_code, .. }) => {
+            status_code.unwrap_or(128)
+        }
+        AppError::AI(AIError::Cancelled) => interrupt::INTERRUPTED_EXIT_CODE,
+        _ => 1, 
+    }
+}
+
+/// The name `stats` counts a run of `command` under: the subcommand, without its arguments
+fn command_name(command: &EnhancerSubCommand, command_line: &[String]) -> String {
+    let name = command_line.first().cloned().unwrap_or_default();
+    match command {
+        EnhancerSubCommand::Commit(commit_args) if commit_args.ai => format!("{} --ai", name),
+        _ => name,
+    }
+}
+
+/// Applies leading `-C`, `--git-dir` and `--work-tree` options the way git does, so that
+/// repository checks, internal git commands and passthrough all see the same repository.
+///
+/// `-C` changes the working directory of this process; the other two are exported as
+/// `GIT_DIR` / `GIT_WORK_TREE`, which also covers values already set in the environment.
+fn apply_repository_options(options: &GlobalArgs) -> Result<(), AppError> {
+    for directory in options.directories.iter().filter(|d| !d.is_empty()) {
+        std::env::set_current_dir(directory)
+            .map_err(|e| AppError::Io(format!("changing to directory '{}'", directory), e))?;
+    }
+    // SAFETY: called from `main` before the async runtime starts any threads
+    unsafe {
+        if let Some(git_dir) = &options.git_dir {
+            std::env::set_var("GIT_DIR", git_dir);
+        }
+        if let Some(work_tree) = &options.work_tree {
+            std::env::set_var("GIT_WORK_TREE", work_tree);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `git <args>` can go straight to git: `--no-ai`, or no AI, no
+/// git-enhancer subcommand, and neither the guard nor teach mode would step in.
+///
+/// Everyday commands such as `status` then cost no more than running git,
+/// since neither the configuration nor the async runtime is set up. Without
+/// the runtime Ctrl-C is ignored rather than handled, which needs Unix.
+fn is_plain_passthrough(line: &CommandLine) -> bool {
+    let args = line.git_args();
+    cfg!(unix)
+        && (line.global.no_ai
+            || (!line.global.affects_features()
+                && !needs_configuration(line)
+                && !guard::may_intervene(line)
+                && !teach::may_show_tip(&args)))
+}
+
+/// Whether `line` uses AI or a gitie subcommand, which need the configuration;
+/// everything else is passed through to git.
+fn needs_configuration(line: &CommandLine) -> bool {
+    if line.global.ai || line.global.explain {
+        return true;
+    }
+    match &line.kind {
+        CommandKind::Enhancer(EnhancerSubCommand::Commit(commit_args)) => commit_args.ai,
+        // Reports a broken configuration itself
+        CommandKind::Enhancer(EnhancerSubCommand::Doctor) => false,
+        CommandKind::Enhancer(_) => true,
+        CommandKind::Help | CommandKind::Git => false,
+    }
+}
+
+/// Parses a prompt given with `--prompt-file` or `--system-prompt`; `origin`
+/// names it in errors.
+fn compile_prompt_override(source: &str, origin: &str) -> Result<prompt_template::PromptTemplate, ConfigError> {
+    prompt_template::PromptTemplate::compile(source, &prompt_template::variables_for(None))
+        .map_err(|problems| ConfigError::Invalid(origin.to_string(), problems))
+}
+
+/// The text of a `--context`: the argument itself, or what stdin holds for `-`
+fn read_context(context: &str) -> Result<String, AppError> {
+    if context != "-" {
+        return Ok(context.to_string());
+    }
+    let mut text = String::new();
+    std::io::Read::read_to_string(&mut std::io::stdin(), &mut text)
+        .map_err(|e| AppError::Io("reading the context from stdin".to_string(), e))?;
+    Ok(text)
+}
+
+async fn run_app(line: CommandLine, color_flag: Option<ColorMode>) -> Result<(), AppError> {
+    let GlobalArgs {
+        json: json_output,
+        porcelain: porcelain_output,
+        run: run_suggestion,
+        glossary,
+        force_yes,
+        output: output_path,
+        append: append_output,
+        timeout,
+        copy: copy_output,
+        model: model_override,
+        temperature: temperature_override,
+        lang: lang_override,
+        level: level_override,
+        prompt_file,
+        system_prompt: inline_prompt,
+        scope,
+        context,
+        ..
+    } = line.global.clone();
+
+    if line.global.no_ai {
+        return passthrough_to_git(&line.git_args());
+    }
+
+    // `gitie-config` manages the configuration file itself, so it has to work even
+    // when the current configuration fails to load (and outside of a repository).
+    if !line.global.ai
+        && !line.global.explain
+        && let CommandKind::Enhancer(EnhancerSubCommand::Config(config_args)) = &line.kind
+    {
+        return handle_config(config_args.clone());
+    }
+
+    let mut config = match AppConfig::load() {
+        Ok(config) => config,
+        // Plain git commands must keep working while the configuration is broken
+        Err(e) if !needs_configuration(&line) => {
+            tracing::debug!("Configuration not loaded, passing through without AI: {}", e);
+            AppConfig::without_ai()
+        }
+        Err(e) => return Err(e.into()),
+    };
+    // One run's choice must not get around a model the administrator locked
+    for (value_given, flag, key) in [
+        (model_override.is_some(), "--model", "ai.model_name"),
+        (temperature_override.is_some(), "--temperature", "ai.temperature"),
+    ] {
+        if value_given && config.policy.is_locked(key) {
+            return Err(AppError::Generic(format!("{} cannot be used: {} is locked by the system policy", flag, key)));
+        }
+    }
+    if let Some(temperature) = temperature_override
+        && !(0.0..=2.0).contains(&temperature)
+    {
+        return Err(AppError::Generic(format!("invalid --temperature value '{}' (expected 0 to 2)", temperature)));
+    }
+    config.model_override = model_override;
+    config.temperature_override = temperature_override;
+    if let Some(language) = lang_override {
+        config.explain.language = Some(language);
+    }
+    if glossary {
+        config.explain.glossary = true;
+    }
+    if let Some(name) = level_override {
+        config.explain.level = ExplanationLevel::from_name(&name).ok_or_else(|| {
+            AppError::Generic(format!(
+                "invalid --level value '{}' (expected beginner, normal or expert)",
+                name
+            ))
+        })?;
+    }
+    config.prompt_override = match (prompt_file, inline_prompt) {
+        (Some(_), Some(_)) => {
+            return Err(AppError::Generic(
+                "--prompt-file and --system-prompt cannot be used together".to_string(),
+            ));
+        }
+        (Some(path), None) => {
+            let source = std::fs::read_to_string(&path).map_err(|e| ConfigError::FileRead(path.clone(), e))?;
+            Some(compile_prompt_override(&source, &path)?)
+        }
+        (None, Some(inline)) => Some(compile_prompt_override(&inline, "--system-prompt")?),
+        (None, None) => None,
+    };
+    match (json_output, porcelain_output) {
+        (true, true) => {
+            return Err(AppError::Generic(
+                "--json and --porcelain cannot be used together".to_string(),
+            ));
+        }
+        (true, false) => config.output.format = OutputFormat::Json,
+        (false, true) => config.output.format = OutputFormat::Porcelain,
+        (false, false) => {}
+    }
+    config.output.copy = copy_output;
+    config.guard.force_yes = force_yes;
+    config.scope = scope;
+    config.context = context.iter().map(|text| read_context(text)).collect::<Result<_, _>>()?;
+    if let Some(seconds) = timeout {
+        config.ai.timeouts.deadline = Some(seconds.parse().ok().filter(|&seconds| seconds > 0).ok_or_else(|| {
+            AppError::Generic(format!("invalid --timeout value '{}' (expected a number of seconds)", seconds))
+        })?);
+    }
+    // `-o -` is stdout, like the default
+    config.output.file = output_path
+        .filter(|path| path != "-")
+        .map(|path| OutputFile { path: path.into(), append: append_output });
+    // `--color` wins over the configuration
+    if color_flag.is_none() {
+        style::set_mode(config.output.color);
+    }
+
+    let result = run_command(line, &config, run_suggestion).await;
+    if config.output.format.is_machine_readable()
+        && let Err(e) = &result
+        // A failing git command is reported by git itself, or by the record that
+        // was already printed for it
+        && !matches!(e, AppError::Git(GitError::PassthroughFailed { .. }))
+    {
+        output::print_record(config.output.format, &ResultRecord::failure(e, exit_code_for(e)))?;
+    }
+    result
+}
+
+async fn run_command(line: CommandLine, config: &AppConfig, run_suggestion: bool) -> Result<(), AppError> {
+    let git_context = GitContext::default();
     // First check if git is available
-    if !is_git_available()? {
-        tracing::error!("Error: Git is not available on this system.");
+    if !git_context.is_git_available()? {
         return Err(AppError::Io("Git command not found or not executable".to_string(), std::io::Error::new(std::io::ErrorKind::NotFound, "Git not available")));
     }
-    
-    // Then check if we're in a git repository
-    if !is_in_git_repository()? {
-        tracing::error!("Error: Not a git repository (or any of the parent directories).");
-        return Err(GitError::NotARepository.into());
-    }
+    // A repository is only required by the commands that read one (see
+    // `GitContext::require_repository`); explanations and passthrough work anywhere.
 
-    let raw_cli_args: Vec<String> = std::env::args().skip(1).collect();
-    // 1. Check for help flags first
-    if args_contain_help(&raw_cli_args) {
-        let ai_flag_present = args_contain_ai(&raw_cli_args);
-        if ai_flag_present {
-            tracing::info!("Help flag detected with --ai. Explaining Git command output...");
-            let mut command_to_execute_for_help = raw_cli_args.clone();
-            command_to_execute_for_help.retain(|arg| arg != "--ai");
-
-            // After removing the --ai flag:
-            // - For `git-enhancer --ai --help` -> `--help` remains in the command
-            // - For `git-enhancer --ai commit --help` -> `commit --help` remains
-            // The help flags (-h/--help) are NOT removed by the retain operation,
-            // only the --ai flag is removed
-            // Since help flags always remain, we'll never have an empty command
-            let cmd_output = execute_git_command_and_capture_output(&command_to_execute_for_help)?;
-            let mut text_to_explain = cmd_output.stdout;
-            if !cmd_output.status.success() && !cmd_output.stderr.is_empty() {
-                text_to_explain.push_str("\n--- Stderr ---\n");
-                text_to_explain.push_str(&cmd_output.stderr);
+    let mut git_args = line.git_args();
+    match line.kind {
+        // 1. --explain describes the command without running it, even one that
+        //    shares its name with a git-enhancer subcommand
+        //    (`--explain commit -m "..."` explains `git commit`)
+        _ if line.global.explain => {
+            tracing::info!("--explain flag detected. Explaining Git command...");
+            usage_stats::record(config, usage_stats::Event::Command { name: "explain".to_string() });
+            if git_args.is_empty() {
+                // Handle `git-enhauser --explain` (with no actual command)
+                // Default to explaining "git --help"
+                tracing::debug!("No specific command with --explain, explaining 'git --help'.");
+                git_args.push("--help".to_string());
             }
-            match explain_git_command_output(&config, &text_to_explain).await {
-                Ok(explanation) => println!("{}", explanation),
+            let started = Instant::now();
+            match explain_git_command(config, &git_args).await {
+                Ok(explanation) => {
+                    notify::if_slow(&config.notify, started.elapsed(), "Explanation ready");
+                    output::print_ai_reply(&config.output, "explanation", &explanation, 0)?;
+                    if run_suggestion && !config.output.format.is_machine_readable() {
+                        suggestions::offer_to_run(&explanation.text)?;
+                    }
+                }
                 Err(e) => return Err(AppError::AI(e)),
             }
-        } else {
+        }
+        // 2. --ai runs the command and explains what it printed; for a help
+        //    request that is git's help text
+        CommandKind::Help if line.global.ai => {
+            tracing::info!("Help flag detected with --ai. Explaining Git command output...");
+            run_and_explain(&git_args, true, config, run_suggestion).await?;
+        }
+        CommandKind::Help => {
             // No --ai, just passthrough the help request to git
             tracing::info!("Help flag detected without --ai. Passing to git.");
-            passthrough_to_git(&raw_cli_args)?;
+            passthrough_to_git(&git_args)?;
         }
-    } else {
-        // 2. Not a help request, try parsing as git-enhancer subcommand or global AI explanation
-        let mut enhancer_parser_args = vec!["git-enhancer-dummy".to_string()]; // Dummy executable name for clap
-        enhancer_parser_args.extend_from_slice(&raw_cli_args);
-
-        match GitEnhancerArgs::try_parse_from(&enhancer_parser_args) {
-            Ok(parsed_enhancer_args) => {
-                // Successfully parsed as a git-enhancer specific command
-                match parsed_enhancer_args.command {
-                    EnhancerSubCommand::Commit(commit_args) => {
-                        // This handles `git-enhauser commit --ai` as well as `git-enhauser commit -m "message"`
-                        // The `handle_commit` function itself checks `commit_args.ai`
-                        tracing::info!("Parsed as git-enhancer commit subcommand. Delegating to handle_commit.");
-                        handle_commit(commit_args, &config).await?;
-                    }
-                    // Future: Add other EnhancerSubCommand arms here if they are added to cli.rs
+        _ if line.global.ai && git_args.is_empty() => {
+            // `git-enhauser --ai` explains git's own help
+            run_and_explain(&["--help".to_string()], true, config, run_suggestion).await?;
+        }
+        _ if line.global.ai => {
+            tracing::info!("--ai flag detected. Running the Git command and explaining its output...");
+            guard::check(config, &git_context, &line).await?;
+            run_and_explain(&git_args, false, config, run_suggestion).await?;
+        }
+        // 3. git-enhancer's own subcommands
+        CommandKind::Enhancer(command) => {
+            let name = command_name(&command, &line.command);
+            usage_stats::record(config, usage_stats::Event::Command { name });
+            match command {
+                EnhancerSubCommand::Commit(commit_args) => {
+                    // This handles `git-enhauser commit --ai` as well as `git-enhauser commit -m "message"`
+                    // The `handle_commit` function itself checks `commit_args.ai`
+                    tracing::info!("Parsed as git-enhancer commit subcommand. Delegating to handle_commit.");
+                    handle_commit(*commit_args, config, &git_context).await?;
                 }
-            }
-            Err(_) => {
-                // Failed to parse as a specific git-enhancer subcommand.
-                // This could be a global --ai explanation request for a generic git command (e.g. `git-enhauser --ai status`),
-                // or just a command to passthrough (e.g. `git-enhauser status`).
-                let ai_flag_present = args_contain_ai(&raw_cli_args);
-                if ai_flag_present {
-                    tracing::info!("Not a specific git-enhancer subcommand, but --ai flag detected. Explaining Git command...");
-                    let mut command_to_explain = raw_cli_args.clone();
-                    command_to_explain.retain(|arg| arg != "--ai"); // Remove all occurrences of --ai
-
-                    if command_to_explain.is_empty() {
-                        // Handle `git-enhauser --ai` (with no actual command after removing --ai)
-                        // Default to explaining "git --help"
-                        tracing::debug!("No specific command with global --ai, explaining 'git --help'.");
-                        command_to_explain.push("--help".to_string());
-                    }
-                    match explain_git_command(&config, &command_to_explain).await {
-                        Ok(explanation) => println!("{}", explanation),
-                        Err(e) => return Err(AppError::AI(e)),
-                    }
-                } else {
-                    // No --ai, not a known enhancer subcommand. Pass through to git.
-                    // e.g., `git-enhauser status`
-                    tracing::info!("Not a recognized git-enhancer subcommand and no --ai. Passing to git.");
-                    passthrough_to_git(&raw_cli_args)?;
+                EnhancerSubCommand::Config(config_args) => {
+                    handle_config(config_args)?;
                 }
+                EnhancerSubCommand::Next(next_args) => {
+                    handle_next(next_args, config, &git_context).await?;
+                }
+                EnhancerSubCommand::TeamSummary(team_args) => {
+                    handle_team_summary(team_args, config, &git_context).await?;
+                }
+                EnhancerSubCommand::BranchesCleanup(cleanup_args) => {
+                    handle_branches_cleanup(cleanup_args, config, &git_context).await?;
+                }
+                EnhancerSubCommand::Doctor => {
+                    handle_doctor().await?;
+                }
+                EnhancerSubCommand::Search(search_args) => {
+                    handle_search(search_args, config, &git_context).await?;
+                }
+                EnhancerSubCommand::SummarizeLog(log_args) => {
+                    handle_summarize_log(log_args, config, &git_context).await?;
+                }
+                EnhancerSubCommand::Changelog(log_args) => {
+                    handle_changelog(log_args, config, &git_context).await?;
+                }
+                EnhancerSubCommand::Run(task_args) => {
+                    handle_run_task(task_args, config, &git_context).await?;
+                }
+                EnhancerSubCommand::PrDescribe(pr_args) => {
+                    handle_pr_describe(pr_args, config, &git_context).await?;
+                }
+                EnhancerSubCommand::ExportHistory(export_args) => {
+                    handle_export_history(export_args, config)?;
+                }
+                EnhancerSubCommand::Serve(serve_args) => {
+                    handle_serve(serve_args, config).await?;
+                }
+                EnhancerSubCommand::Stats => {
+                    handle_stats(config)?;
+                }
+                // Future: Add other EnhancerSubCommand arms here if they are added to cli.rs
+            }
+        }
+        // 4. Everything else is git's, e.g. `git-enhauser status`
+        CommandKind::Git => {
+            tracing::info!("Not a git-enhancer subcommand and no --ai. Passing to git.");
+            guard::check(config, &git_context, &line).await?;
+            let tip = teach::start(config, &git_args);
+            passthrough_to_git(&git_args)?;
+            if let Some(tip) = tip {
+                tip.show().await;
             }
         }
     }
     Ok(())
 }
 
-
-
-
-
-
+/// Runs `git <git_args>` with its output captured, then prints an explanation
+/// of that output in its place. Exits with git's status, not the explanation's.
+///
+/// # Arguments
+///
+/// * `git_args` - The git command to run
+/// * `is_help` - Whether the command prints git's help text rather than repository content
+/// * `config` - Application configuration
+/// * `run_suggestion` - Whether to offer to run the commands the explanation suggests
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success, or an error when git or the explanation failed
+async fn run_and_explain(git_args: &[String], is_help: bool, config: &AppConfig, run_suggestion: bool) -> Result<(), AppError> {
+    usage_stats::record(config, usage_stats::Event::Command { name: "explain-output".to_string() });
+    let cmd_output = execute_git_command_and_capture_output(git_args)?;
+    let mut text_to_explain = cmd_output.stdout;
+    if !cmd_output.stderr.is_empty() {
+        text_to_explain.push_str("\n--- Stderr ---\n");
+        text_to_explain.push_str(&cmd_output.stderr);
+    }
+    // The explanation replaces git's output, but not its exit status
+    let status_code = exit_code(cmd_output.status);
+    let started = Instant::now();
+    let explanation = if is_help {
+        explain_git_command_output(config, &text_to_explain).await
+    } else {
+        explain_command_run(config, git_args, &text_to_explain).await
+    }
+    .map_err(AppError::AI)?;
+    notify::if_slow(&config.notify, started.elapsed(), "Explanation ready");
+    output::print_ai_reply(&config.output, "explanation", &explanation, status_code.unwrap_or(128))?;
+    if run_suggestion && !config.output.format.is_machine_readable() {
+        suggestions::offer_to_run(&explanation.text)?;
+    }
+    if !cmd_output.status.success() {
+        return Err(AppError::Git(GitError::PassthroughFailed {
+            command: format!("git {}", git_args.join(" ")),
+            status_code,
+        }));
+    }
+    Ok(())
+}
@@ -0,0 +1,256 @@
+// git-enhancer/src/markdown.rs
+//! Fenced code blocks in AI responses: finding them and highlighting them.
+//!
+//! Models mostly answer with shell commands and patches, so highlighting is a
+//! small lexer for those two; other languages are only set apart from the prose.
+
+use crate::style::Style;
+
+/// A fenced code block, numbered from 1 in the order it appears
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// The info string after the opening fence, e.g. `bash`
+    pub language: Option<String>,
+    pub code: String,
+}
+
+/// How the lines of a block are colored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Syntax {
+    Shell,
+    Diff,
+    Other,
+}
+
+impl Syntax {
+    fn of(language: Option<&str>) -> Self {
+        match language.map(str::to_ascii_lowercase).as_deref() {
+            None | Some("sh" | "bash" | "zsh" | "shell" | "console" | "git") => Syntax::Shell,
+            Some("diff" | "patch") => Syntax::Diff,
+            Some(_) => Syntax::Other,
+        }
+    }
+}
+
+/// Returns the language of a fence line (```` ```bash ````), or `None` if it isn't one.
+fn fence(line: &str) -> Option<Option<String>> {
+    let info = line.trim().strip_prefix("```")?.trim();
+    Some((!info.is_empty()).then(|| info.to_string()))
+}
+
+/// Extracts the fenced code blocks from `text`, empty ones included so that
+/// positions match the numbers shown by `style`.
+pub fn code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<CodeBlock> = None;
+    for line in text.lines() {
+        match (fence(line), current.take()) {
+            (Some(_), Some(block)) => {
+                blocks.push(CodeBlock { code: block.code.trim().to_string(), ..block })
+            }
+            (Some(language), None) => current = Some(CodeBlock { language, code: String::new() }),
+            (None, Some(mut block)) => {
+                block.code.push_str(line);
+                block.code.push('\n');
+                current = Some(block);
+            }
+            (None, None) => {}
+        }
+    }
+    blocks
+}
+
+/// The commands in the shell code blocks of `text`, one per command line.
+///
+/// Prompt markers (`$ `) and comment lines are dropped and lines continued with
+/// a trailing backslash are joined.
+pub fn shell_commands(text: &str) -> Vec<String> {
+    let mut commands = Vec::new();
+    for block in code_blocks(text) {
+        if Syntax::of(block.language.as_deref()) != Syntax::Shell {
+            continue;
+        }
+        let mut pending = String::new();
+        for line in block.code.lines() {
+            let line = line.trim();
+            let line = line.strip_prefix("$ ").unwrap_or(line);
+            if pending.is_empty() && (line.is_empty() || line.starts_with('#')) {
+                continue;
+            }
+            match line.strip_suffix('\\') {
+                Some(continued) => {
+                    pending.push_str(continued.trim_end());
+                    pending.push(' ');
+                }
+                None => {
+                    pending.push_str(line);
+                    commands.push(std::mem::take(&mut pending));
+                }
+            }
+        }
+        if !pending.trim().is_empty() {
+            commands.push(pending.trim().to_string());
+        }
+    }
+    commands
+}
+
+/// Styles markdown for a color terminal: bold headings, and code blocks framed
+/// with their number (matching `code_blocks`) and highlighted.
+pub fn style(text: &str) -> String {
+    let mut lines = Vec::new();
+    let mut block: Option<Syntax> = None;
+    let mut number = 0;
+    for line in text.lines() {
+        match (fence(line), block) {
+            (Some(_), Some(_)) => {
+                block = None;
+                lines.push(Style::Dim.apply("└─"));
+            }
+            (Some(language), None) => {
+                block = Some(Syntax::of(language.as_deref()));
+                number += 1;
+                let label = match language {
+                    Some(language) => format!("┌─ [{}] {}", number, language),
+                    None => format!("┌─ [{}]", number),
+                };
+                lines.push(Style::Dim.apply(&label));
+            }
+            (None, Some(syntax)) => lines.push(highlight_line(syntax, line)),
+            (None, None) if line.starts_with('#') => lines.push(Style::Bold.apply(line)),
+            (None, None) => lines.push(line.to_string()),
+        }
+    }
+    lines.join("\n")
+}
+
+fn highlight_line(syntax: Syntax, line: &str) -> String {
+    match syntax {
+        Syntax::Diff => highlight_diff_line(line),
+        Syntax::Shell => highlight_shell_line(line),
+        Syntax::Other => line.to_string(),
+    }
+}
+
+fn highlight_diff_line(line: &str) -> String {
+    let style = if line.starts_with("+++") || line.starts_with("---") {
+        Style::Bold
+    } else if line.starts_with('+') {
+        Style::Green
+    } else if line.starts_with('-') {
+        Style::Red
+    } else if line.starts_with("@@") {
+        Style::Cyan
+    } else {
+        return line.to_string();
+    };
+    style.apply(line)
+}
+
+/// Colors comments, quoted strings, options and the command name of a shell line.
+fn highlight_shell_line(line: &str) -> String {
+    let indent_len = line.len() - line.trim_start().len();
+    let (indent, rest) = line.split_at(indent_len);
+    if rest.starts_with('#') {
+        return format!("{}{}", indent, Style::Dim.apply(rest));
+    }
+
+    let mut out = String::from(indent);
+    let mut first_word = true;
+    let mut chars = rest.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            out.push(c);
+            continue;
+        }
+        // A token runs to the next unquoted whitespace
+        let mut end = start + c.len_utf8();
+        let mut quote = matches!(c, '"' | '\'').then_some(c);
+        while let Some(&(index, next)) = chars.peek() {
+            if quote.is_none() && next.is_whitespace() {
+                break;
+            }
+            if Some(next) == quote {
+                quote = None;
+            } else if quote.is_none() && matches!(next, '"' | '\'') {
+                quote = Some(next);
+            }
+            end = index + next.len_utf8();
+            chars.next();
+        }
+        let token = &rest[start..end];
+        let styled = if token.starts_with('#') {
+            // A trailing comment takes the rest of the line
+            let comment = &rest[start..];
+            out.push_str(&Style::Dim.apply(comment));
+            return out;
+        } else if first_word {
+            Style::Bold.apply(token)
+        } else if token.starts_with('"') || token.starts_with('\'') {
+            Style::Yellow.apply(token)
+        } else if token.starts_with('-') {
+            Style::Cyan.apply(token)
+        } else {
+            token.to_string()
+        };
+        out.push_str(&styled);
+        // `git` is followed by its subcommand, which is part of the command name
+        first_word = first_word && (token == "git" || token == "$" || token == "sudo");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_blocks_keep_language_and_position() {
+        let text = "Run:\n```bash\ngit status\n```\n```\n\n```\nthen\n```diff\n-a\n+b\n```";
+        assert_eq!(
+            code_blocks(text),
+            vec![
+                CodeBlock { language: Some("bash".to_string()), code: "git status".to_string() },
+                CodeBlock { language: None, code: String::new() },
+                CodeBlock { language: Some("diff".to_string()), code: "-a\n+b".to_string() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shell_commands_come_from_shell_blocks_only() {
+        let text = "```bash\n# check first\n$ git status\ngit log \\\n  --oneline\n```\n\
+                    ```diff\n-a\n```\n```\ngit fetch\n```";
+        assert_eq!(shell_commands(text), vec!["git status", "git log --oneline", "git fetch"]);
+    }
+
+    #[test]
+    fn test_style_frames_and_numbers_code_blocks() {
+        let styled = style("# Fix\n```bash\ngit add -p \"a b\" # review\n```");
+        assert_eq!(
+            styled,
+            [
+                Style::Bold.apply("# Fix"),
+                Style::Dim.apply("┌─ [1] bash"),
+                format!(
+                    "{} {} {} {} {}",
+                    Style::Bold.apply("git"),
+                    Style::Bold.apply("add"),
+                    Style::Cyan.apply("-p"),
+                    Style::Yellow.apply("\"a b\""),
+                    Style::Dim.apply("# review")
+                ),
+                Style::Dim.apply("└─"),
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_diff_lines_are_colored_by_kind() {
+        assert_eq!(highlight_diff_line("+added"), Style::Green.apply("+added"));
+        assert_eq!(highlight_diff_line("-removed"), Style::Red.apply("-removed"));
+        assert_eq!(highlight_diff_line("@@ -1 +1 @@"), Style::Cyan.apply("@@ -1 +1 @@"));
+        assert_eq!(highlight_diff_line(" context"), " context");
+    }
+}
@@ -0,0 +1,260 @@
+// git-enhancer/src/mcp.rs
+//! The Model Context Protocol server of `serve --mcp`.
+//!
+//! Desktop AI assistants and IDE agents start `git-enhancer serve --mcp` in a
+//! repository and call its tools to read the repository and to have commit
+//! messages written, with git-enhancer's configuration, prompts and policy.
+//! The tools only read; committing is left to the client.
+
+use serde_json::{Value, json};
+
+use crate::commit_commands::generate_staged_message;
+use crate::config::AppConfig;
+use crate::errors::AppError;
+use crate::git_commands::execute_git_command_and_capture_output;
+use crate::git_context::GitContext;
+use crate::json_rpc::{Handler, INVALID_PARAMS, RpcError};
+
+/// Protocol revisions this server speaks, newest first
+const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+/// Commits `get_log` returns when no count is given, and at most
+const DEFAULT_LOG_COUNT: u64 = 20;
+const MAX_LOG_COUNT: u64 = 500;
+
+/// Characters of tool output returned; clients pass it on to a model
+const MAX_OUTPUT_CHARS: usize = 100_000;
+
+const INSTRUCTIONS: &str = "Tools for the git repository the server runs in. Use get_status, \
+    get_staged_diff and get_log to see its state, and generate_commit_message for a message \
+    describing the staged changes. The tools never change the repository.";
+
+/// Answers MCP requests for the repository in the working directory
+pub struct McpServer<'a> {
+    config: &'a AppConfig,
+}
+
+impl<'a> McpServer<'a> {
+    pub fn new(config: &'a AppConfig) -> Self {
+        McpServer { config }
+    }
+
+    async fn call_tool(&self, name: &str, arguments: &Value) -> Result<String, ToolError> {
+        // The repository may change between calls, e.g. another branch is checked out
+        let git_context = GitContext::default();
+        match name {
+            "get_status" => {
+                git_context.require_repository()?;
+                git(&["status", "--short", "--branch"], &[])
+            }
+            "get_staged_diff" => {
+                git_context.require_repository()?;
+                git(&["diff", "--cached"], &string_list(arguments, "paths")?)
+            }
+            "get_log" => {
+                let count = match arguments.get("max_count") {
+                    None | Some(Value::Null) => DEFAULT_LOG_COUNT,
+                    Some(count) => count
+                        .as_u64()
+                        .filter(|count| (1..=MAX_LOG_COUNT).contains(count))
+                        .ok_or_else(|| ToolError::Invalid(format!("'max_count' must be a number from 1 to {}", MAX_LOG_COUNT)))?,
+                };
+                let range = optional_string(arguments, "range")?;
+                // A range is never an option; this keeps `--output=...` and the like out
+                if let Some(range) = range.as_deref().filter(|range| range.starts_with('-')) {
+                    return Err(ToolError::Invalid(format!("'{}' is not a revision range", range)));
+                }
+                let paths = string_list(arguments, "paths")?;
+                git_context.require_repository()?;
+                let count = format!("--max-count={}", count);
+                let mut args = vec!["log", "--format=medium", "--stat", count.as_str()];
+                args.extend(range.as_deref());
+                git(&args, &paths)
+            }
+            "generate_commit_message" => {
+                let ticket = optional_string(arguments, "ticket")?;
+                let reply = generate_staged_message(self.config, &git_context, ticket.as_deref()).await?;
+                Ok(reply.text)
+            }
+            _ => Err(ToolError::Unknown(name.to_string())),
+        }
+    }
+}
+
+impl Handler for McpServer<'_> {
+    async fn handle(&mut self, method: &str, params: Value) -> Result<Value, RpcError> {
+        match method {
+            "initialize" => {
+                let requested = params.get("protocolVersion").and_then(Value::as_str);
+                let version = requested
+                    .filter(|requested| PROTOCOL_VERSIONS.contains(requested))
+                    .unwrap_or(PROTOCOL_VERSIONS[0]);
+                Ok(json!({
+                    "protocolVersion": version,
+                    "capabilities": { "tools": { "listChanged": false } },
+                    "serverInfo": { "name": "git-enhancer", "version": env!("CARGO_PKG_VERSION") },
+                    "instructions": INSTRUCTIONS,
+                }))
+            }
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tool_definitions() })),
+            "tools/call" => {
+                let name = params
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing tool 'name'"))?;
+                let arguments = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+                match self.call_tool(name, &arguments).await {
+                    Ok(text) => Ok(tool_result(&text, false)),
+                    Err(ToolError::Unknown(name)) => Err(RpcError::new(INVALID_PARAMS, format!("unknown tool '{}'", name))),
+                    // Failures the model can react to are results, not protocol errors
+                    Err(error) => Ok(tool_result(&error.to_string(), true)),
+                }
+            }
+            // Notifications such as `notifications/initialized` need nothing
+            method if method.starts_with("notifications/") => Ok(Value::Null),
+            _ => Err(RpcError::method_not_found(method)),
+        }
+    }
+}
+
+/// Why a tool call failed
+#[derive(Debug)]
+enum ToolError {
+    Unknown(String),
+    Invalid(String),
+    /// git's error output
+    Git(String),
+    Failed(AppError),
+}
+
+impl std::fmt::Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolError::Unknown(name) => write!(f, "unknown tool '{}'", name),
+            ToolError::Invalid(message) => write!(f, "invalid arguments: {}", message),
+            ToolError::Git(message) => write!(f, "{}", message),
+            ToolError::Failed(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl From<AppError> for ToolError {
+    fn from(error: AppError) -> Self {
+        ToolError::Failed(error)
+    }
+}
+
+fn tool_result(text: &str, is_error: bool) -> Value {
+    let mut text = text.to_string();
+    if let Some((end, _)) = text.char_indices().nth(MAX_OUTPUT_CHARS) {
+        text.truncate(end);
+        text.push_str("\n[output truncated]");
+    }
+    if text.is_empty() {
+        text = "(no output)".to_string();
+    }
+    json!({ "content": [{ "type": "text", "text": text }], "isError": is_error })
+}
+
+fn tool_definitions() -> Value {
+    let paths = json!({
+        "type": "array",
+        "items": { "type": "string" },
+        "description": "Limit the output to these paths (git pathspecs)",
+    });
+    json!([
+        {
+            "name": "get_status",
+            "description": "The current branch, its upstream, and the staged, changed and untracked files (git status --short --branch).",
+            "inputSchema": { "type": "object", "properties": {} },
+        },
+        {
+            "name": "get_staged_diff",
+            "description": "The changes staged for the next commit, as a unified diff (git diff --cached).",
+            "inputSchema": { "type": "object", "properties": { "paths": paths } },
+        },
+        {
+            "name": "get_log",
+            "description": "Commits with their messages and changed files (git log --stat), newest first.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "range": { "type": "string", "description": "Revision range such as main..HEAD or v1.2.0..; default HEAD" },
+                    "max_count": { "type": "integer", "minimum": 1, "maximum": MAX_LOG_COUNT, "description": "Number of commits; default 20" },
+                    "paths": paths,
+                },
+            },
+        },
+        {
+            "name": "generate_commit_message",
+            "description": "A commit message for the staged changes, written with the repository's git-enhancer configuration. Does not commit.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "ticket": { "type": "string", "description": "Ticket the change is for, e.g. PROJ-123; found in the branch name by default" },
+                },
+            },
+        },
+    ])
+}
+
+fn optional_string(arguments: &Value, name: &str) -> Result<Option<String>, ToolError> {
+    match arguments.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(value)) => Ok(Some(value.clone())),
+        Some(_) => Err(ToolError::Invalid(format!("'{}' must be a string", name))),
+    }
+}
+
+fn string_list(arguments: &Value, name: &str) -> Result<Vec<String>, ToolError> {
+    match arguments.get(name) {
+        None | Some(Value::Null) => Ok(Vec::new()),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|item| item.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| ToolError::Invalid(format!("'{}' must be a list of strings", name))),
+        Some(_) => Err(ToolError::Invalid(format!("'{}' must be a list of strings", name))),
+    }
+}
+
+/// The output of `git <args> -- <paths>`, or git's error
+fn git(args: &[&str], paths: &[String]) -> Result<String, ToolError> {
+    let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    if !paths.is_empty() {
+        args.push("--".to_string());
+        args.extend(paths.iter().cloned());
+    }
+    let output = execute_git_command_and_capture_output(&args)?;
+    if !output.status.success() {
+        return Err(ToolError::Git(format!("git {} failed: {}", args.join(" "), output.stderr.trim())));
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_initialize_and_tool_errors() {
+        let config = AppConfig::without_ai();
+        let mut server = McpServer::new(&config);
+        let result = server.handle("initialize", json!({ "protocolVersion": "2024-11-05" })).await.unwrap();
+        assert_eq!(result["protocolVersion"], "2024-11-05");
+        let result = server.handle("initialize", json!({ "protocolVersion": "1999-01-01" })).await.unwrap();
+        assert_eq!(result["protocolVersion"], PROTOCOL_VERSIONS[0]);
+        let tools = server.handle("tools/list", Value::Null).await.unwrap();
+        assert_eq!(tools["tools"].as_array().unwrap().len(), 4);
+
+        let error = server.handle("tools/call", json!({ "name": "rm_rf" })).await.unwrap_err();
+        assert_eq!(error.code, INVALID_PARAMS);
+        let result = server
+            .handle("tools/call", json!({ "name": "get_log", "arguments": { "range": "--output=/tmp/x" } }))
+            .await
+            .unwrap();
+        assert_eq!(result["isError"], true);
+        assert!(result["content"][0]["text"].as_str().unwrap().contains("is not a revision range"));
+    }
+}
@@ -0,0 +1,334 @@
+//! The `next` subcommand.
+
+use crate::ai_utils::{AiReply, ChatMessage, OpenAIChatRequest, clean_ai_output, send_chat_request};
+use crate::cli::NextArgs;
+use crate::config::AppConfig;
+use crate::errors::{AIError, AppError, GitError};
+use crate::git_backend::open_backend;
+use crate::git_commands::execute_git_command_and_capture_output;
+use crate::git_context::GitContext;
+use crate::output;
+use crate::types::InProgressOperation;
+
+const PHRASING_PROMPT: &str = "Rewrite the following checklist of next steps in a git repository \
+    so it reads naturally and briefly. Keep every item, keep their order and numbering, and keep \
+    every command, file name and number exactly as given. Do not add items, advice or headings.";
+
+/// Branch and working tree state from `git status --porcelain=v2 --branch --show-stash`
+#[derive(Debug, Default, PartialEq)]
+struct StatusSummary {
+    /// Current branch; `None` when HEAD is detached
+    branch: Option<String>,
+    /// The branch has no commits yet
+    unborn: bool,
+    upstream: Option<String>,
+    ahead: u32,
+    behind: u32,
+    staged: usize,
+    unstaged: usize,
+    untracked: usize,
+    conflicted: Vec<String>,
+    stashes: usize,
+    /// Remote to publish a branch to (`origin` when there are several); not part of the status
+    remote: Option<String>,
+}
+
+/// Handles the `next` subcommand
+///
+/// Builds the checklist from the repository state; the AI, when asked for with
+/// `--ai`, only rewords it.
+///
+/// # Arguments
+///
+/// * `args` - Next arguments from CLI
+/// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub async fn handle_next(args: NextArgs, config: &AppConfig, git_context: &GitContext) -> Result<(), AppError> {
+    git_context.require_repository()?;
+    let status_args: Vec<String> = ["status", "--porcelain=v2", "--branch", "--show-stash", "-z"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    let output = execute_git_command_and_capture_output(&status_args)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: "git status --porcelain=v2".to_string(),
+            status_code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+        .into());
+    }
+    let mut summary = parse_porcelain_v2(&output.stdout);
+    let remotes = execute_git_command_and_capture_output(&["remote".to_string()])?.stdout;
+    summary.remote = remotes
+        .lines()
+        .find(|remote| *remote == "origin")
+        .or_else(|| remotes.lines().next())
+        .map(str::to_string);
+    let operation = open_backend().repository_state()?.operation;
+    let checklist = format_checklist(&next_steps(&summary, operation));
+
+    let reply = if args.ai {
+        rephrase(config, &checklist).await?
+    } else {
+        AiReply::local(&checklist)
+    };
+    output::print_ai_reply(&config.output, "next", &reply, 0)
+}
+
+/// Parses `git status --porcelain=v2 --branch --show-stash -z` output.
+fn parse_porcelain_v2(output: &str) -> StatusSummary {
+    let mut summary = StatusSummary::default();
+    let mut entries = output.split('\0').filter(|entry| !entry.is_empty());
+    while let Some(entry) = entries.next() {
+        let (kind, rest) = entry.split_once(' ').unwrap_or((entry, ""));
+        match kind {
+            "#" => parse_header(&mut summary, rest),
+            "1" | "2" => {
+                let xy = rest.as_bytes();
+                if xy.first().is_some_and(|&x| x != b'.') {
+                    summary.staged += 1;
+                }
+                if xy.get(1).is_some_and(|&y| y != b'.') {
+                    summary.unstaged += 1;
+                }
+                if kind == "2" {
+                    // Renames and copies are followed by the original path
+                    entries.next();
+                }
+            }
+            "u" => {
+                // `u XY sub m1 m2 m3 mW h1 h2 h3 <path>`
+                if let Some(path) = rest.splitn(10, ' ').nth(9) {
+                    summary.conflicted.push(path.to_string());
+                }
+            }
+            "?" => summary.untracked += 1,
+            _ => {}
+        }
+    }
+    summary
+}
+
+fn parse_header(summary: &mut StatusSummary, header: &str) {
+    let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+    match key {
+        "branch.oid" => summary.unborn = value == "(initial)",
+        "branch.head" if value != "(detached)" => summary.branch = Some(value.to_string()),
+        "branch.upstream" => summary.upstream = Some(value.to_string()),
+        "branch.ab" => {
+            for count in value.split_whitespace() {
+                if let Some(ahead) = count.strip_prefix('+') {
+                    summary.ahead = ahead.parse().unwrap_or(0);
+                } else if let Some(behind) = count.strip_prefix('-') {
+                    summary.behind = behind.parse().unwrap_or(0);
+                }
+            }
+        }
+        "stash" => summary.stashes = value.parse().unwrap_or(0),
+        _ => {}
+    }
+}
+
+/// The steps to take, most urgent first.
+fn next_steps(summary: &StatusSummary, operation: Option<InProgressOperation>) -> Vec<String> {
+    let mut steps = Vec::new();
+    if !summary.conflicted.is_empty() {
+        steps.push(format!(
+            "Resolve the conflicts in {}, then mark them resolved with `git add <file>`",
+            summary.conflicted.join(", ")
+        ));
+    }
+    match operation {
+        Some(InProgressOperation::Rebase) => {
+            steps.push("Finish the rebase with `git rebase --continue` (or give up with `git rebase --abort`)".to_string())
+        }
+        Some(InProgressOperation::Merge) => {
+            steps.push("Conclude the merge with `git commit` (or give up with `git merge --abort`)".to_string())
+        }
+        Some(InProgressOperation::CherryPick) => steps.push(
+            "Finish the cherry-pick with `git cherry-pick --continue` (or give up with `git cherry-pick --abort`)".to_string(),
+        ),
+        Some(InProgressOperation::Revert) => steps.push(
+            "Finish the revert with `git revert --continue` (or give up with `git revert --abort`)".to_string(),
+        ),
+        Some(InProgressOperation::Bisect) => steps.push(
+            "Mark the checked-out commit with `git bisect good` or `git bisect bad`, or end with `git bisect reset`".to_string(),
+        ),
+        None => {}
+    }
+    if summary.branch.is_none() && operation.is_none() {
+        steps.push("HEAD is detached; keep any new commits on a branch with `git switch -c <name>`".to_string());
+    }
+    // While an operation is in progress, its own step covers committing
+    if operation.is_none() {
+        if summary.staged > 0 {
+            steps.push(format!("Commit the {} with `git commit`", count(summary.staged, "staged file")));
+        }
+        if summary.unstaged > 0 {
+            steps.push(format!(
+                "Stage the changes in {} with `git add -p`, or discard them with `git restore <file>`",
+                count(summary.unstaged, "modified file")
+            ));
+        }
+        if summary.untracked > 0 {
+            steps.push(format!(
+                "Add the {} with `git add`, or list them in .gitignore",
+                count(summary.untracked, "untracked file")
+            ));
+        }
+    }
+    if let Some(upstream) = &summary.upstream {
+        match (summary.ahead, summary.behind) {
+            (0, 0) => {}
+            (0, behind) => steps.push(format!("Pull {} from {} with `git pull`", count(behind as usize, "new commit"), upstream)),
+            (ahead, 0) => steps.push(format!("Push {} to {} with `git push`", count(ahead as usize, "commit"), upstream)),
+            (ahead, behind) => steps.push(format!(
+                "Your branch and {} have diverged ({} ahead, {} behind); integrate with `git pull --rebase` or `git pull --no-rebase`, then push",
+                upstream, ahead, behind
+            )),
+        }
+    } else if let (Some(branch), Some(remote)) = (&summary.branch, &summary.remote)
+        && !summary.unborn
+    {
+        steps.push(format!("Publish the branch with `git push -u {} {}`", remote, branch));
+    }
+    if summary.stashes > 0 {
+        steps.push(format!(
+            "Review the {} with `git stash list`; apply or drop what you no longer need",
+            count(summary.stashes, "stash entry")
+        ));
+    }
+    steps
+}
+
+fn format_checklist(steps: &[String]) -> String {
+    if steps.is_empty() {
+        return "Nothing to do: the working tree is clean and the branch is up to date.".to_string();
+    }
+    steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| format!("{}. {}", index + 1, step))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// `1 staged file`, `3 staged files`; `entry` becomes `entries`
+fn count(n: usize, noun: &str) -> String {
+    match (n, noun.strip_suffix('y')) {
+        (1, _) => format!("1 {}", noun),
+        (_, Some(stem)) => format!("{} {}ies", n, stem),
+        (_, None) => format!("{} {}s", n, noun),
+    }
+}
+
+/// Has the model reword the checklist; the facts in it come from git.
+async fn rephrase(config: &AppConfig, checklist: &str) -> Result<AiReply, AIError> {
+    let parameters = config.model_parameters("explain");
+    // File names from the status are repository content
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, true)?;
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: PHRASING_PROMPT.to_string() },
+            ChatMessage { role: "user".to_string(), content: checklist.to_string() },
+        ],
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    let response = send_chat_request(config, &parameters.api_url, &request).await?;
+    let choice = response.choices.first().ok_or(AIError::NoChoiceInResponse)?;
+    let text = clean_ai_output(&choice.message.content);
+    if text.trim().is_empty() {
+        return Err(AIError::EmptyMessage);
+    }
+    Ok(AiReply { text, model: response.model.clone(), usage: Some(response.usage.clone()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_porcelain_v2() {
+        let output = "# branch.oid 1234\0# branch.head feature\0# branch.upstream origin/feature\0\
+                      # branch.ab +2 -1\0# stash 3\0\
+                      1 M. N... 100644 100644 100644 aaa bbb src/lib.rs\0\
+                      1 .M N... 100644 100644 100644 aaa bbb README.md\0\
+                      2 RM N... 100644 100644 100644 aaa bbb R100 new.rs\0old.rs\0\
+                      u UU N... 100644 100644 100644 100644 aaa bbb ccc both modified.rs\0\
+                      ? notes.txt\0";
+        assert_eq!(
+            parse_porcelain_v2(output),
+            StatusSummary {
+                branch: Some("feature".to_string()),
+                unborn: false,
+                upstream: Some("origin/feature".to_string()),
+                ahead: 2,
+                behind: 1,
+                staged: 2,
+                unstaged: 2,
+                untracked: 1,
+                conflicted: vec!["both modified.rs".to_string()],
+                stashes: 3,
+                remote: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_next_steps_put_conflicts_and_operations_first() {
+        let summary = StatusSummary {
+            branch: Some("main".to_string()),
+            upstream: Some("origin/main".to_string()),
+            ahead: 3,
+            staged: 1,
+            conflicted: vec!["a.rs".to_string()],
+            ..StatusSummary::default()
+        };
+        assert_eq!(
+            next_steps(&summary, Some(InProgressOperation::Rebase)),
+            vec![
+                "Resolve the conflicts in a.rs, then mark them resolved with `git add <file>`",
+                "Finish the rebase with `git rebase --continue` (or give up with `git rebase --abort`)",
+                "Push 3 commits to origin/main with `git push`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_next_steps_for_clean_and_unpublished_branches() {
+        let clean = StatusSummary {
+            branch: Some("main".to_string()),
+            upstream: Some("origin/main".to_string()),
+            ..StatusSummary::default()
+        };
+        assert_eq!(
+            format_checklist(&next_steps(&clean, None)),
+            "Nothing to do: the working tree is clean and the branch is up to date."
+        );
+
+        let unpublished = StatusSummary {
+            branch: Some("topic".to_string()),
+            untracked: 2,
+            stashes: 1,
+            remote: Some("origin".to_string()),
+            ..StatusSummary::default()
+        };
+        assert_eq!(
+            format_checklist(&next_steps(&unpublished, None)),
+            "1. Add the 2 untracked files with `git add`, or list them in .gitignore\n\
+             2. Publish the branch with `git push -u origin topic`\n\
+             3. Review the 1 stash entry with `git stash list`; apply or drop what you no longer need"
+        );
+    }
+}
@@ -0,0 +1,106 @@
+// git-enhancer/src/notify.rs
+//! Desktop notifications for AI requests that took long enough for the user
+//! to switch to another window.
+//!
+//! Notifications are best effort: if no notifier is installed the terminal
+//! bell is rung instead, which most terminals turn into an urgency hint.
+
+use serde::Deserialize;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+// 桌面通知相关的配置
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NotifyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Only requests that took at least this long are announced
+    #[serde(default = "default_after_seconds")]
+    pub after_seconds: u64,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        NotifyConfig {
+            enabled: false,
+            after_seconds: default_after_seconds(),
+        }
+    }
+}
+
+fn default_after_seconds() -> u64 {
+    10
+}
+
+impl NotifyConfig {
+    /// Whether a request that took `elapsed` should be announced
+    fn applies_to(&self, elapsed: Duration) -> bool {
+        self.enabled && elapsed >= Duration::from_secs(self.after_seconds)
+    }
+}
+
+/// Announces `message` (e.g. "Commit message ready") if the request took
+/// `elapsed` and notifications are enabled. Nothing happens without a terminal
+/// on stderr, since then nobody is sitting in front of the command.
+pub fn if_slow(config: &NotifyConfig, elapsed: Duration, message: &str) {
+    if !config.applies_to(elapsed) || !io::stderr().is_terminal() {
+        return;
+    }
+    tracing::debug!("Request took {:.1}s; sending a notification", elapsed.as_secs_f64());
+    if let Err(e) = send(message) {
+        tracing::debug!("No desktop notifier available ({}); ringing the bell", e);
+        let mut stderr = io::stderr().lock();
+        let _ = stderr.write_all(b"\x07");
+        let _ = stderr.flush();
+    }
+}
+
+fn send(message: &str) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("osascript");
+        command.arg("-e").arg(applescript(message));
+        command
+    } else {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name", "gitie", "gitie", message]);
+        command
+    };
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!("notifier exited with {}", status)))
+    }
+}
+
+/// The AppleScript that shows `message` in Notification Center.
+fn applescript(message: &str) -> String {
+    let quoted = message.replace('\\', "\\\\").replace('"', "\\\"");
+    format!("display notification \"{}\" with title \"gitie\"", quoted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_slow_requests_are_announced_when_enabled() {
+        let config = NotifyConfig { enabled: true, after_seconds: 5 };
+        assert!(config.applies_to(Duration::from_secs(5)));
+        assert!(!config.applies_to(Duration::from_millis(4_900)));
+        assert!(!NotifyConfig::default().applies_to(Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn test_applescript_quotes_message() {
+        assert_eq!(
+            applescript(r#"Say "hi" \ bye"#),
+            r#"display notification "Say \"hi\" \\ bye" with title "gitie""#
+        );
+    }
+}
@@ -0,0 +1,446 @@
+//! Printing results as text, JSON or porcelain, to stdout, a file or the clipboard.
+
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+use crate::ai_utils::{AiReply, OpenAIUsage};
+use crate::clipboard;
+use crate::errors::AppError;
+use crate::interrupt::ForegroundChild;
+use crate::markdown;
+use crate::style::{self, Stream};
+use crate::ui;
+
+/// How AI-generated text is rendered on stdout
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// Markdown as produced by the model, lightly styled when color is enabled
+    #[default]
+    Markdown,
+    /// Markdown syntax stripped, for terminals or tools that don't render it
+    Plain,
+    /// A JSON object per result, for scripts and editor integrations
+    Json,
+    /// Line-oriented `key value` records, for shell scripts
+    Porcelain,
+}
+
+impl OutputFormat {
+    /// Whether results are records for programs rather than text for people
+    pub fn is_machine_readable(self) -> bool {
+        matches!(self, OutputFormat::Json | OutputFormat::Porcelain)
+    }
+}
+
+/// When to emit ANSI colors
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    /// Only when stdout is a terminal
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    /// Parses the value of `--color` / `[output] color`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(ColorMode::Auto),
+            "always" => Some(ColorMode::Always),
+            "never" => Some(ColorMode::Never),
+            _ => None,
+        }
+    }
+}
+
+// 输出相关的配置
+#[derive(Deserialize, Debug, Clone)]
+pub struct OutputConfig {
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub color: ColorMode,
+    /// Page long output through git's pager when stdout is a terminal
+    #[serde(default = "default_pager")]
+    pub pager: bool,
+    /// Also put results on the clipboard; set by `--copy`, not a configuration key
+    #[serde(skip)]
+    pub copy: bool,
+    /// Write results here instead of stdout; set by `-o`/`--output`, not a configuration key
+    #[serde(skip)]
+    pub file: Option<OutputFile>,
+}
+
+/// Destination given with `-o <file>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputFile {
+    pub path: PathBuf,
+    /// Add to the end of the file (`--append`) instead of replacing it
+    pub append: bool,
+}
+
+impl Default for OutputConfig {
+    fn default() -> Self {
+        OutputConfig {
+            format: OutputFormat::default(),
+            color: ColorMode::default(),
+            pager: default_pager(),
+            copy: false,
+            file: None,
+        }
+    }
+}
+
+fn default_pager() -> bool {
+    true
+}
+
+/// Version of the JSON and porcelain records below; bumped only for incompatible changes
+pub const RECORD_SCHEMA_VERSION: u32 = 1;
+
+/// A single result in the JSON or porcelain format.
+///
+/// Every JSON record carries all keys, with `null` for the ones that don't apply, so
+/// consumers can rely on the shape. `exit_code` is the status gitie exits with.
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ResultRecord<'a> {
+    pub schema_version: u32,
+    /// What the record holds, e.g. `"explanation"` or `"commit"`
+    pub kind: &'a str,
+    /// `"ok"` or `"error"`
+    pub status: &'a str,
+    pub exit_code: i32,
+    pub content: Option<&'a str>,
+    pub model: Option<&'a str>,
+    pub usage: Option<&'a OpenAIUsage>,
+    /// Structured details some kinds add next to the text, e.g. per-author counts
+    pub data: Option<&'a serde_json::Value>,
+    pub error: Option<String>,
+}
+
+impl<'a> ResultRecord<'a> {
+    /// A record for an AI reply that ends with `exit_code`
+    pub fn reply(kind: &'a str, reply: &'a AiReply, exit_code: i32) -> Self {
+        ResultRecord {
+            schema_version: RECORD_SCHEMA_VERSION,
+            kind,
+            status: if exit_code == 0 { "ok" } else { "error" },
+            exit_code,
+            content: Some(&reply.text),
+            model: Some(reply.model.as_str()).filter(|model| !model.is_empty()),
+            usage: reply.usage.as_ref(),
+            data: None,
+            error: None,
+        }
+    }
+
+    /// Adds structured `data` to the record
+    pub fn with_data(self, data: Option<&'a serde_json::Value>) -> Self {
+        ResultRecord { data, ..self }
+    }
+
+    /// A record for a command that failed before producing a result
+    pub fn failure(error: &AppError, exit_code: i32) -> Self {
+        ResultRecord {
+            schema_version: RECORD_SCHEMA_VERSION,
+            kind: "error",
+            status: "error",
+            exit_code,
+            content: None,
+            model: None,
+            usage: None,
+            data: None,
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+impl ResultRecord<'_> {
+    /// The record as porcelain: one `key value` line per field, in a fixed order.
+    ///
+    /// Fields that don't apply are left out; `data` is compact JSON on one line.
+    /// Multi-line content and errors become
+    /// one line per text line with the same key, so `awk '$1 == "content"'` and
+    /// `sed -n 's/^content //p'` recover the text.
+    pub fn to_porcelain(&self) -> String {
+        let mut lines = vec![
+            format!("version {}", self.schema_version),
+            format!("kind {}", self.kind),
+            format!("status {}", self.status),
+            format!("exit-code {}", self.exit_code),
+        ];
+        if let Some(model) = self.model {
+            lines.push(format!("model {}", model));
+        }
+        if let Some(usage) = self.usage {
+            lines.push(format!(
+                "usage {} {} {}",
+                usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+            ));
+        }
+        if let Some(data) = self.data {
+            lines.push(format!("data {}", data));
+        }
+        for (key, text) in [("content", self.content), ("error", self.error.as_deref())] {
+            for line in text.into_iter().flat_map(str::lines) {
+                lines.push(format!("{} {}", key, line));
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// The record in `format`, JSON unless porcelain was asked for.
+    fn format_as(&self, format: OutputFormat) -> Result<String, AppError> {
+        match format {
+            OutputFormat::Porcelain => Ok(self.to_porcelain()),
+            _ => serde_json::to_string(self)
+                .map_err(|e| AppError::Generic(format!("Failed to serialize JSON output: {}", e))),
+        }
+    }
+}
+
+/// Writes one record on stdout: a single line of JSON, or a porcelain block.
+pub fn print_record(format: OutputFormat, record: &ResultRecord) -> Result<(), AppError> {
+    let text = record.format_as(format)?;
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", text).map_err(|e| AppError::Io("writing output".to_string(), e))
+}
+
+/// Prints an AI reply according to the `[output]` settings.
+///
+/// `kind` names the result (e.g. `"explanation"`) and `exit_code` is the status the
+/// command will exit with; both are only visible in JSON mode.
+pub fn print_ai_reply(
+    config: &OutputConfig,
+    kind: &str,
+    reply: &AiReply,
+    exit_code: i32,
+) -> Result<(), AppError> {
+    print_ai_reply_with_data(config, kind, reply, None, exit_code)
+}
+
+/// Like `print_ai_reply`, with structured `data` added to JSON and porcelain records.
+pub fn print_ai_reply_with_data(
+    config: &OutputConfig,
+    kind: &str,
+    reply: &AiReply,
+    data: Option<&serde_json::Value>,
+    exit_code: i32,
+) -> Result<(), AppError> {
+    if config.copy {
+        copy_to_clipboard(&reply.text);
+    }
+    if let Some(file) = &config.file {
+        let contents = match config.format {
+            OutputFormat::Json | OutputFormat::Porcelain => {
+                ResultRecord::reply(kind, reply, exit_code).with_data(data).format_as(config.format)?
+            }
+            // Files get the text itself: no colors, and markdown is what editors expect
+            OutputFormat::Markdown => reply.text.clone(),
+            OutputFormat::Plain => strip_markdown(&reply.text),
+        };
+        return write_to_file(file, &contents);
+    }
+    if config.format.is_machine_readable() {
+        return print_record(config.format, &ResultRecord::reply(kind, reply, exit_code).with_data(data));
+    }
+    let stdout_is_terminal = io::stdout().is_terminal();
+    let use_color = style::enabled(Stream::Stdout);
+    let rendered = render(config.format, use_color, &reply.text);
+
+    if config.pager && stdout_is_terminal {
+        match page(&rendered) {
+            Ok(()) => return Ok(()),
+            Err(e) => tracing::debug!("Pager unavailable, printing directly: {}", e),
+        }
+    }
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", rendered).map_err(|e| AppError::Io("writing output".to_string(), e))
+}
+
+/// Writes a result to the `-o` file, followed by a newline.
+pub fn write_to_file(file: &OutputFile, contents: &str) -> Result<(), AppError> {
+    let description = format!("writing output to '{}'", file.path.display());
+    let mut handle = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(file.append)
+        .truncate(!file.append)
+        .open(&file.path)
+        .map_err(|e| AppError::Io(description.clone(), e))?;
+    writeln!(handle, "{}", contents).map_err(|e| AppError::Io(description, e))?;
+    ui::status(&format!("Wrote {}", file.path.display()));
+    Ok(())
+}
+
+/// Copies `text` for `--copy`. Failing to copy only warns: the text was printed anyway.
+pub fn copy_to_clipboard(text: &str) {
+    match clipboard::copy(text) {
+        Ok(method) => ui::status(&format!("Copied to the clipboard ({})", method)),
+        Err(e) => ui::warning(&format!("could not copy to the clipboard: {}", e)),
+    }
+}
+
+fn render(format: OutputFormat, use_color: bool, text: &str) -> String {
+    match format {
+        OutputFormat::Json | OutputFormat::Porcelain => text.to_string(),
+        OutputFormat::Plain => strip_markdown(text),
+        OutputFormat::Markdown if use_color => markdown::style(text),
+        OutputFormat::Markdown => text.to_string(),
+    }
+}
+
+/// Removes the most common markdown syntax: heading markers, emphasis and code fences.
+fn strip_markdown(text: &str) -> String {
+    text.lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .map(|line| {
+            let line = line.trim_start_matches('#').trim_start_matches(' ');
+            line.replace("**", "").replace("__", "").replace('`', "")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Sends text through the pager git would use (`GIT_PAGER`, `core.pager`, `PAGER`, `less`).
+fn page(text: &str) -> io::Result<()> {
+    let pager = Command::new("git")
+        .args(["var", "GIT_PAGER"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "less".to_string());
+    if pager.is_empty() || pager == "cat" {
+        return Err(io::Error::other("pager disabled"));
+    }
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    // Same defaults git uses: quit if one screen, keep colors, don't clear the screen
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let mut child = command.spawn()?;
+    let _foreground = ForegroundChild::register(&child);
+    if let Some(mut stdin) = child.stdin.take() {
+        // The user quitting the pager early closes the pipe; that's not an error
+        match writeln!(stdin, "{}", text) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+    }
+    child.wait()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_reply_record_has_a_stable_shape() {
+        let reply = AiReply {
+            text: "line \"one\"\nline two".to_string(),
+            model: "llama3".to_string(),
+            usage: Some(OpenAIUsage { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 }),
+        };
+        assert_eq!(
+            serde_json::to_string(&ResultRecord::reply("explanation", &reply, 0)).unwrap(),
+            r#"{"schema_version":1,"kind":"explanation","status":"ok","exit_code":0,"content":"line \"one\"\nline two","model":"llama3","usage":{"prompt_tokens":10,"completion_tokens":5,"total_tokens":15},"data":null,"error":null}"#
+        );
+        let local = AiReply::local("nothing to explain");
+        let record = ResultRecord::reply("explanation", &local, 1);
+        assert_eq!(record.status, "error");
+        assert_eq!(record.model, None);
+        assert_eq!(record.usage, None);
+    }
+
+    #[test]
+    fn test_porcelain_record_has_one_field_per_line() {
+        let reply = AiReply {
+            text: "Fix pager\n\nDetails".to_string(),
+            model: "llama3".to_string(),
+            usage: Some(OpenAIUsage { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 }),
+        };
+        assert_eq!(
+            ResultRecord::reply("commit", &reply, 0).to_porcelain(),
+            "version 1\nkind commit\nstatus ok\nexit-code 0\nmodel llama3\nusage 10 5 15\n\
+             content Fix pager\ncontent \ncontent Details"
+        );
+        let data = serde_json::json!({"authors": [{"name": "Ann", "commits": 2}]});
+        let local = AiReply::local("Ann fixed the pager");
+        assert_eq!(
+            ResultRecord::reply("team-summary", &local, 0).with_data(Some(&data)).to_porcelain(),
+            "version 1\nkind team-summary\nstatus ok\nexit-code 0\n\
+             data {\"authors\":[{\"commits\":2,\"name\":\"Ann\"}]}\ncontent Ann fixed the pager"
+        );
+        let error = AppError::Generic("boom".to_string());
+        assert_eq!(
+            ResultRecord::failure(&error, 1).to_porcelain(),
+            "version 1\nkind error\nstatus error\nexit-code 1\nerror Application error: boom"
+        );
+    }
+
+    #[test]
+    fn test_json_failure_record_carries_the_error() {
+        let error = AppError::Generic("boom".to_string());
+        assert_eq!(
+            serde_json::to_string(&ResultRecord::failure(&error, 1)).unwrap(),
+            r#"{"schema_version":1,"kind":"error","status":"error","exit_code":1,"content":null,"model":null,"usage":null,"data":null,"error":"Application error: boom"}"#
+        );
+    }
+
+    #[test]
+    fn test_write_to_file_overwrites_or_appends() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("test_temp_data")
+            .join("output")
+            .join("write_to_file");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.md");
+        std::fs::write(&path, "old\n").unwrap();
+
+        let mut file = OutputFile { path: path.clone(), append: false };
+        write_to_file(&file, "first").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first\n");
+        file.append = true;
+        write_to_file(&file, "second").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "first\nsecond\n");
+    }
+
+    #[test]
+    fn test_render_plain_strips_markdown() {
+        let text = "## Summary\n\nUse **git status** to see `changes`.\n```bash\ngit status\n```";
+        assert_eq!(
+            render(OutputFormat::Plain, true, text),
+            "Summary\n\nUse git status to see changes.\ngit status"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_styles_headings_only_with_color() {
+        let text = "# Title\nbody";
+        assert_eq!(render(OutputFormat::Markdown, false, text), text);
+        assert_eq!(
+            render(OutputFormat::Markdown, true, text),
+            "\x1b[1m# Title\x1b[0m\nbody"
+        );
+    }
+
+    #[test]
+    fn test_output_config_parses_from_toml() {
+        let config: OutputConfig =
+            toml::from_str("format = \"plain\"\ncolor = \"never\"\npager = false\n").unwrap();
+        assert_eq!(config.format, OutputFormat::Plain);
+        assert_eq!(config.color, ColorMode::Never);
+        assert!(!config.pager);
+    }
+}
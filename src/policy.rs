@@ -0,0 +1,192 @@
+//! Organisation policy for AI usage, read from the system policy file.
+
+use serde::Deserialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::errors::{AIError, ConfigError};
+
+/// System-wide policy file maintained by administrators. Users cannot relocate or override it.
+pub const SYSTEM_POLICY_FILE: &str = "/etc/gitie/policy.toml";
+
+/// Restrictions on AI usage imposed by an organisation
+///
+/// Example `/etc/gitie/policy.toml`:
+///
+/// ```toml
+/// [ai]
+/// enabled = true
+/// send_diffs = false                      # explanations only
+/// allowed_endpoints = ["https://llm.corp.example/v1/chat/completions"]
+/// allowed_models = ["corp-model"]
+///
+/// [locked.ai]                             # forced onto every user's configuration
+/// api_url = "https://llm.corp.example/v1/chat/completions"
+/// ```
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Policy {
+    #[serde(default)]
+    pub ai: AIPolicy,
+
+    /// Configuration values (same layout as `config.toml`) that take precedence over
+    /// the user's config file and git config
+    #[serde(default)]
+    pub locked: toml::Table,
+}
+
+/// The `[ai]` section of the policy file
+#[derive(Deserialize, Debug, Clone)]
+pub struct AIPolicy {
+    /// When false, every AI feature is disabled
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// When false, repository content such as diffs must never be sent to a model
+    #[serde(default = "default_true")]
+    pub send_diffs: bool,
+    /// If non-empty, the only API endpoints that may be contacted
+    #[serde(default)]
+    pub allowed_endpoints: Vec<String>,
+    /// If non-empty, the only models that may be requested
+    #[serde(default)]
+    pub allowed_models: Vec<String>,
+}
+
+impl Default for AIPolicy {
+    fn default() -> Self {
+        AIPolicy {
+            enabled: true,
+            send_diffs: true,
+            allowed_endpoints: Vec::new(),
+            allowed_models: Vec::new(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Policy {
+    /// Loads the policy at `path`. A missing file means no restrictions.
+    pub fn load_from(path: &Path) -> Result<Self, ConfigError> {
+        let path_str = path.to_string_lossy().to_string();
+        match fs::read_to_string(path) {
+            Ok(content) => {
+                tracing::debug!("Applying AI usage policy from {}", path.display());
+                toml::from_str(&content).map_err(|e| ConfigError::TomlParse(path_str, e))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Policy::default()),
+            Err(e) => Err(ConfigError::FileRead(path_str, e)),
+        }
+    }
+
+    /// Whether `[locked]` forces the dotted configuration `key`, e.g. `ai.model_name`
+    pub fn is_locked(&self, key: &str) -> bool {
+        let mut table = &self.locked;
+        let mut parts = key.split('.').peekable();
+        while let Some(part) = parts.next() {
+            match table.get(part) {
+                Some(_) if parts.peek().is_none() => return true,
+                Some(toml::Value::Table(inner)) => table = inner,
+                _ => return false,
+            }
+        }
+        false
+    }
+
+    /// Checks whether an AI request is permitted before it is sent.
+    ///
+    /// `includes_repository_content` should be true whenever the prompt carries diffs
+    /// or file contents rather than just a command or its help text.
+    pub fn check_request(
+        &self,
+        api_url: &str,
+        model: &str,
+        includes_repository_content: bool,
+    ) -> Result<(), AIError> {
+        if !self.ai.enabled {
+            return Err(AIError::DisabledByPolicy(
+                "AI features are disabled by the system policy".to_string(),
+            ));
+        }
+        if includes_repository_content && !self.ai.send_diffs {
+            return Err(AIError::DisabledByPolicy(
+                "sending repository content to an AI model is not allowed by the system policy"
+                    .to_string(),
+            ));
+        }
+        if !self.ai.allowed_endpoints.is_empty()
+            && !self.ai.allowed_endpoints.iter().any(|e| e == api_url)
+        {
+            return Err(AIError::DisabledByPolicy(format!(
+                "endpoint '{}' is not in the policy's allowed_endpoints",
+                api_url
+            )));
+        }
+        if !self.ai.allowed_models.is_empty() && !self.ai.allowed_models.iter().any(|m| m == model) {
+            return Err(AIError::DisabledByPolicy(format!(
+                "model '{}' is not in the policy's allowed_models",
+                model
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(toml_text: &str) -> Policy {
+        toml::from_str(toml_text).unwrap()
+    }
+
+    #[test]
+    fn test_missing_policy_file_allows_everything() {
+        let policy = Policy::load_from(Path::new("/nonexistent/gitie/policy.toml")).unwrap();
+        assert!(policy.ai.enabled && policy.ai.send_diffs);
+        assert!(policy.check_request("http://any", "any-model", true).is_ok());
+    }
+
+    #[test]
+    fn test_disabled_ai_blocks_all_requests() {
+        let policy = policy("[ai]\nenabled = false\n");
+        assert!(matches!(
+            policy.check_request("http://any", "m", false),
+            Err(AIError::DisabledByPolicy(_))
+        ));
+    }
+
+    #[test]
+    fn test_send_diffs_false_allows_explanations_only() {
+        let policy = policy("[ai]\nsend_diffs = false\n");
+        assert!(policy.check_request("http://any", "m", false).is_ok());
+        assert!(policy.check_request("http://any", "m", true).is_err());
+    }
+
+    #[test]
+    fn test_allowlists_pin_endpoint_and_model() {
+        let policy = policy(
+            r#"[ai]
+allowed_endpoints = ["https://llm.corp/v1/chat/completions"]
+allowed_models = ["corp-model"]
+"#,
+        );
+        assert!(
+            policy
+                .check_request("https://llm.corp/v1/chat/completions", "corp-model", true)
+                .is_ok()
+        );
+        assert!(
+            policy
+                .check_request("https://api.openai.com/v1/chat/completions", "corp-model", true)
+                .is_err()
+        );
+        assert!(
+            policy
+                .check_request("https://llm.corp/v1/chat/completions", "other", true)
+                .is_err()
+        );
+    }
+}
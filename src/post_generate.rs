@@ -0,0 +1,95 @@
+// git-enhancer/src/post_generate.rs
+//! `post_generate_cmd`: user scripts that rewrite what the model wrote.
+//!
+//! A task's `post_generate_cmd` is run with `sh -c`, gets the cleaned-up AI
+//! output on stdin and prints the text to use instead, so organisations can
+//! rewrite messages, filter words or add ticket IDs without changes here:
+//!
+//! ```toml
+//! [commit]
+//! post_generate_cmd = "sed -e 's/^Fix /fix: /'"
+//! ```
+//!
+//! The script runs in the working directory (or the repository given to the
+//! library API), with `GITIE_TASK` set to the
+//! task (`commit`, `explain`, `review` or a `run` task's name). Its stderr goes
+//! to the terminal. A script that fails or prints nothing fails the command:
+//! the output must not silently skip a filter the organisation relies on.
+
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::errors::AIError;
+
+/// `text` piped through `command`, or `text` itself when there is no command.
+///
+/// The trailing newlines the script prints are dropped.
+pub fn apply(command: Option<&str>, task: &str, text: &str) -> Result<String, AIError> {
+    apply_in(None, command, task, text)
+}
+
+/// `apply`, with the script run in `dir` instead of the current directory
+pub fn apply_in(dir: Option<&Path>, command: Option<&str>, task: &str, text: &str) -> Result<String, AIError> {
+    let Some(command) = command.filter(|command| !command.trim().is_empty()) else {
+        return Ok(text.to_string());
+    };
+    tracing::debug!("Piping the {} output through `{}`", task, command);
+    let failed = |reason: String| AIError::PostGenerateFailed { command: command.to_string(), reason };
+    let output = run_script(dir, command, &[("GITIE_TASK", task)], text).map_err(failed)?;
+    let output = output.trim_end_matches(['\n', '\r']);
+    if output.trim().is_empty() {
+        return Err(failed("it printed nothing".to_string()));
+    }
+    Ok(output.to_string())
+}
+
+/// The output of `sh -c <command>` run in `dir` (the current directory when
+/// `None`) given `input` on stdin, with `envs` set; why it failed otherwise.
+pub(crate) fn run_script(dir: Option<&Path>, command: &str, envs: &[(&str, &str)], input: &str) -> Result<String, String> {
+    let mut script = Command::new("sh");
+    if let Some(dir) = dir {
+        script.current_dir(dir);
+    }
+    let mut child = script
+        .args(["-c", command])
+        .envs(envs.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let input = input.to_string();
+    // Written from another thread, so a script that prints before it has read
+    // everything can't block on a full pipe
+    let writer = std::thread::spawn(move || match stdin.write_all(input.as_bytes()) {
+        // Scripts that ignore their input close it early
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => Ok(()),
+        result => result,
+    });
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if let Ok(Err(e)) = writer.join() {
+        return Err(format!("could not write its input: {}", e));
+    }
+    if !output.status.success() {
+        return Err(output.status.to_string());
+    }
+    String::from_utf8(output.stdout).map_err(|_| "its output is not UTF-8".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_pipes_text_through_the_command() {
+        assert_eq!(apply(None, "commit", "Fix bug").unwrap(), "Fix bug");
+        assert_eq!(apply(Some("sed 's/^Fix /fix: /'"), "commit", "Fix bug\n\nDetails").unwrap(), "fix: bug\n\nDetails");
+        assert_eq!(apply(Some("printf '%s ' \"$GITIE_TASK\"; cat"), "review", "ok").unwrap(), "review ok");
+        assert_eq!(apply(Some("echo replaced"), "commit", &"x".repeat(1 << 20)).unwrap(), "replaced");
+
+        let error = apply(Some("exit 3"), "commit", "Fix bug").unwrap_err();
+        assert!(matches!(&error, AIError::PostGenerateFailed { command, .. } if command == "exit 3"));
+        assert!(apply(Some("true"), "commit", "Fix bug").is_err());
+    }
+}
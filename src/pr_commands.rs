@@ -0,0 +1,243 @@
+// git-enhancer/src/pr_commands.rs
+//! The `pr-describe` subcommand.
+//!
+//! Writes a pull request title and description for the current branch from its
+//! commits and its diff against the base branch. With `--create` the branch is
+//! pushed if the remote doesn't have it yet, and the pull request is opened on
+//! the forge hosting the remote (see `forge`) with the description, labels and
+//! reviewers.
+
+use serde::Deserialize;
+
+use crate::ai_utils::{AiReply, ChatMessage, OpenAIChatRequest, send_json_request};
+use crate::branches_commands::default_branch;
+use crate::cli::PrDescribeArgs;
+use crate::config::AppConfig;
+use crate::errors::{AIError, AppError, GitError};
+use crate::forge::{self, NewPullRequest, PullRequest};
+use crate::git_commands::{execute_git_command_and_capture_output, passthrough_to_git};
+use crate::git_context::GitContext;
+use crate::issue_tracker::{self, Ticket};
+use crate::output;
+use crate::prompt;
+use crate::scope;
+use crate::ui;
+use crate::untrusted::{self, UNTRUSTED_CONTENT_RULES};
+
+const PR_PROMPT: &str = "Below are the commits and the diff of a branch that is about to be proposed \
+    as a pull request. Reply with a JSON object with two keys: \"title\", a concise pull request title \
+    in the imperative mood of at most 72 characters, and \"body\", the description in Markdown: a short \
+    summary of what changes and why, a bullet list of the main changes, and anything reviewers should \
+    pay attention to, such as breaking changes or migrations. Only use the information given. Reply \
+    with the JSON object only.";
+
+/// Characters of the diff sent along with the commits; the commits and the
+/// diffstat cover the rest
+const MAX_DIFF_CHARS: usize = 30_000;
+
+/// What the model writes
+#[derive(Deserialize, Debug)]
+struct PrDescription {
+    title: String,
+    body: String,
+}
+
+/// Handles the `pr-describe` subcommand
+///
+/// # Arguments
+///
+/// * `args` - Base branch and `--create` options from CLI
+/// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub async fn handle_pr_describe(args: PrDescribeArgs, config: &AppConfig, git_context: &GitContext) -> Result<(), AppError> {
+    git_context.require_repository()?;
+    let branch = git_context
+        .current_branch()
+        .ok_or_else(|| AppError::Generic("HEAD is detached; check out the branch to describe first".to_string()))?
+        .to_string();
+    let base = match &args.base {
+        Some(base) => base.clone(),
+        None => default_branch(config)?,
+    };
+    let scope = scope::resolve(git_context, config.scope.as_deref())?;
+    let scoped = |args: &[&str]| -> Vec<String> {
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        if let Some(scope) = &scope {
+            args.extend(["--".to_string(), scope.pathspec()]);
+        }
+        args
+    };
+    let commits = git(&scoped(&["log", "--no-merges", "--format=%h %s%n%b", &format!("{}..HEAD", base)]))?;
+    if commits.trim().is_empty() {
+        return Err(AppError::Generic(format!("'{}' has no commits that are not in '{}'", branch, base)));
+    }
+    let range = format!("{}...HEAD", base);
+    let stat = git(&scoped(&["diff", "--stat", &range]))?;
+    let diff = git(&scoped(&["diff", &range]))?;
+
+    let ticket = issue_tracker::ticket_context(config, git_context, args.ticket.as_deref()).await;
+    let (description, reply) = describe(config, &commits, &stat, &diff, ticket.as_ref()).await?;
+    let reply = AiReply { text: format!("{}\n\n{}", description.title, description.body), ..reply };
+    let mut data = serde_json::json!({
+        "base": base,
+        "head": branch,
+        "title": description.title,
+        "body": description.body,
+        "ticket": ticket.as_ref().map(|ticket| &ticket.id),
+        "url": null,
+    });
+    if !args.create {
+        return output::print_ai_reply_with_data(&config.output, "pr-description", &reply, Some(&data), 0);
+    }
+    // A machine-readable record is only printed once the pull request exists
+    if config.output.format.is_machine_readable() {
+        let pull_request = open_pull_request(&args, config, &branch, &base, &description).await?;
+        data["url"] = pull_request.html_url.into();
+        return output::print_ai_reply_with_data(&config.output, "pr-description", &reply, Some(&data), 0);
+    }
+    output::print_ai_reply_with_data(&config.output, "pr-description", &reply, Some(&data), 0)?;
+    if !prompt::confirm(&format!("Open this pull request from '{}' into '{}'?", branch, base), true)? {
+        return Ok(());
+    }
+    let pull_request = open_pull_request(&args, config, &branch, &base, &description).await?;
+    ui::status(&format!("Opened pull request #{}: {}", pull_request.number, pull_request.html_url));
+    Ok(())
+}
+
+async fn describe(
+    config: &AppConfig,
+    commits: &str,
+    stat: &str,
+    diff: &str,
+    ticket: Option<&Ticket>,
+) -> Result<(PrDescription, AiReply), AIError> {
+    let parameters = config.model_parameters("commit");
+    // Commits and diff are repository content
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, true)?;
+    let mut system_prompt = PR_PROMPT.to_string();
+    if let Some(language) = &config.commit.language {
+        system_prompt.push_str(&format!("\n\nWrite the title and the body in {}.", language));
+    }
+    if ticket.is_some() {
+        system_prompt.push_str(&format!("\n\n{}", issue_tracker::TICKET_RULES));
+    }
+    system_prompt.push_str(&format!("\n\n{}", UNTRUSTED_CONTENT_RULES));
+    let mut diff = diff.to_string();
+    if let Some((end, _)) = diff.char_indices().nth(MAX_DIFF_CHARS) {
+        diff.truncate(end);
+        diff.push_str("\n[diff truncated]");
+    }
+    let mut content = format!(
+        "{}\n\n{}\n\n{}",
+        untrusted::fence("commits", commits),
+        untrusted::fence("diffstat", stat),
+        untrusted::fence("diff", &diff)
+    );
+    if let Some(ticket) = ticket {
+        content = format!("{}\n{}", ticket.prompt_section(), content);
+    }
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: system_prompt },
+            ChatMessage { role: "user".to_string(), content },
+        ],
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    send_json_request(config, &parameters.api_url, &request).await
+}
+
+/// Pushes `branch` if needed and opens the pull request, then adds labels and reviewers.
+///
+/// The pull request exists once it has been created, so failing to add labels
+/// or reviewers only warns.
+async fn open_pull_request(
+    args: &PrDescribeArgs,
+    config: &AppConfig,
+    branch: &str,
+    base: &str,
+    description: &PrDescription,
+) -> Result<PullRequest, AppError> {
+    let remote = push_remote(branch);
+    let url = git(&["remote".to_string(), "get-url".to_string(), remote.clone()])?.trim().to_string();
+    // Before pushing, so a missing token doesn't leave a pushed branch behind
+    let client = forge::connect(config, &remote, &url).await?;
+    push_if_needed(&remote, branch)?;
+
+    let base = base.strip_prefix(&format!("{}/", remote)).unwrap_or(base);
+    let pull_request = client
+        .create_pull_request(&NewPullRequest {
+            title: &description.title,
+            body: &description.body,
+            head: branch,
+            base,
+            draft: args.draft,
+        })
+        .await?;
+    if !args.labels.is_empty()
+        && let Err(e) = client.add_labels(pull_request.number, &args.labels).await
+    {
+        ui::warning(&format!("could not add the labels: {}", e));
+    }
+    if !args.reviewers.is_empty()
+        && let Err(e) = client.request_reviewers(pull_request.number, &args.reviewers).await
+    {
+        ui::warning(&format!("could not request the reviews: {}", e));
+    }
+    Ok(pull_request)
+}
+
+/// The remote `branch` is pushed to: `branch.<name>.pushRemote`, `remote.pushDefault`,
+/// `branch.<name>.remote`, else `origin`
+fn push_remote(branch: &str) -> String {
+    [format!("branch.{}.pushRemote", branch), "remote.pushDefault".to_string(), format!("branch.{}.remote", branch)]
+        .into_iter()
+        .find_map(|key| {
+            git(&["config".to_string(), "--get".to_string(), key])
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty() && value != ".")
+        })
+        .unwrap_or_else(|| "origin".to_string())
+}
+
+/// Pushes `branch` to `remote` unless the remote-tracking branch already has HEAD.
+///
+/// Sets the upstream when the branch has none, as `git push -u` does.
+fn push_if_needed(remote: &str, branch: &str) -> Result<(), AppError> {
+    let tracking = format!("refs/remotes/{}/{}", remote, branch);
+    let pushed = git(&["merge-base".to_string(), "--is-ancestor".to_string(), "HEAD".to_string(), tracking]).is_ok();
+    if pushed {
+        return Ok(());
+    }
+    let mut push = vec!["push".to_string()];
+    if git(&["config".to_string(), "--get".to_string(), format!("branch.{}.remote", branch)]).is_err() {
+        push.push("--set-upstream".to_string());
+    }
+    push.extend([remote.to_string(), branch.to_string()]);
+    passthrough_to_git(&push)
+}
+
+/// The stdout of a successful `git <args>`
+fn git<S: AsRef<str>>(args: &[S]) -> Result<String, AppError> {
+    let args: Vec<String> = args.iter().map(|arg| arg.as_ref().to_string()).collect();
+    let output = execute_git_command_and_capture_output(&args)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: format!("git {}", args.join(" ")),
+            status_code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+        .into());
+    }
+    Ok(output.stdout)
+}
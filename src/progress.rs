@@ -0,0 +1,148 @@
+// git-enhancer/src/progress.rs
+//! Spinner shown on stderr while waiting for the model, and a counter for
+//! commands that work through many requests.
+
+use std::cell::Cell;
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::ui;
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const FRAME_INTERVAL: Duration = Duration::from_millis(100);
+/// Moves to the start of the line and erases it
+const CLEAR_LINE: &str = "\r\x1b[2K";
+
+/// Set while a `Progress` line is shown; spinners stay hidden so they don't fight over the line
+static PROGRESS_SHOWN: AtomicBool = AtomicBool::new(false);
+
+/// Animates a status line until dropped, then erases it.
+///
+/// Does nothing with `--quiet` or when stderr is not a terminal, so logs and
+/// redirected output stay clean.
+pub struct Spinner {
+    /// `true` once stopped; the lock also keeps a frame from being drawn after the line is cleared
+    stopped: Option<Arc<Mutex<bool>>>,
+}
+
+impl Spinner {
+    /// Starts the spinner. Must be called from inside the tokio runtime.
+    pub fn start(model: &str) -> Self {
+        if ui::is_quiet() || !io::stderr().is_terminal() || PROGRESS_SHOWN.load(Ordering::SeqCst) {
+            return Spinner { stopped: None };
+        }
+        let stopped = Arc::new(Mutex::new(false));
+        let task_stopped = Arc::clone(&stopped);
+        let model = model.to_string();
+        let started = Instant::now();
+        tokio::spawn(async move {
+            for frame in 0.. {
+                {
+                    let stopped = task_stopped.lock().unwrap_or_else(|e| e.into_inner());
+                    if *stopped {
+                        break;
+                    }
+                    let mut stderr = io::stderr().lock();
+                    let _ = write!(stderr, "{}{}", CLEAR_LINE, status_line(frame, &model, started.elapsed()));
+                    let _ = stderr.flush();
+                }
+                tokio::time::sleep(FRAME_INTERVAL).await;
+            }
+        });
+        Spinner { stopped: Some(stopped) }
+    }
+
+    /// Erases the status line so a message can be printed on a line of its own;
+    /// the next frame draws it again below.
+    pub fn clear(&self) {
+        if let Some(stopped) = &self.stopped {
+            let _guard = stopped.lock().unwrap_or_else(|e| e.into_inner());
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "{}", CLEAR_LINE);
+            let _ = stderr.flush();
+        }
+    }
+}
+
+impl Drop for Spinner {
+    fn drop(&mut self) {
+        if let Some(stopped) = &self.stopped {
+            *stopped.lock().unwrap_or_else(|e| e.into_inner()) = true;
+            self.clear();
+        }
+    }
+}
+
+fn status_line(frame: usize, model: &str, elapsed: Duration) -> String {
+    format!(
+        "{} Waiting for {} ({:.1}s)",
+        FRAMES[frame % FRAMES.len()],
+        model,
+        elapsed.as_secs_f32()
+    )
+}
+
+/// A `<label>: <done>/<total>` line on stderr, redrawn as work completes and
+/// erased when dropped. Silent under the same conditions as the spinner, and
+/// replaces the spinners of the requests made meanwhile.
+pub struct Progress {
+    label: String,
+    total: usize,
+    done: Cell<usize>,
+    visible: bool,
+}
+
+impl Progress {
+    pub fn start(label: &str, total: usize) -> Self {
+        let progress = Progress {
+            label: label.to_string(),
+            total,
+            done: Cell::new(0),
+            visible: !ui::is_quiet() && io::stderr().is_terminal(),
+        };
+        PROGRESS_SHOWN.store(progress.visible, Ordering::SeqCst);
+        progress.draw();
+        progress
+    }
+
+    /// Counts `count` more items as done.
+    pub fn advance(&self, count: usize) {
+        self.done.set(self.done.get() + count);
+        self.draw();
+    }
+
+    fn draw(&self) {
+        if self.visible {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "{}{}: {}/{}", CLEAR_LINE, self.label, self.done.get(), self.total);
+            let _ = stderr.flush();
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if self.visible {
+            PROGRESS_SHOWN.store(false, Ordering::SeqCst);
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "{}", CLEAR_LINE);
+            let _ = stderr.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_line_shows_model_and_elapsed_time() {
+        assert_eq!(
+            status_line(0, "llama3", Duration::from_millis(2340)),
+            "⠋ Waiting for llama3 (2.3s)"
+        );
+        assert_eq!(status_line(11, "m", Duration::ZERO), "⠙ Waiting for m (0.0s)");
+    }
+}
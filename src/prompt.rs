@@ -0,0 +1,194 @@
+// git-enhancer/src/prompt.rs
+//! Questions asked on the terminal.
+//!
+//! Every prompt goes through `select` or `confirm` so they share one look and
+//! one set of keys: the highlighted letter or the choice's number picks it,
+//! Enter takes the default and `?` lists the choices. Without a terminal on
+//! stdin, or with `--no-input`, the default is taken without asking; `--yes`
+//! answers "yes" to confirmations and takes the default of selections.
+
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::errors::AppError;
+use crate::style::{self, Stream, Style};
+
+/// How questions are answered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnswerMode {
+    /// Ask when stdin is a terminal
+    Interactive,
+    /// `--yes`
+    AssumeYes,
+    /// `--no-input`
+    Defaults,
+}
+
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_mode(mode: AnswerMode) {
+    let value = match mode {
+        AnswerMode::Interactive => 0,
+        AnswerMode::AssumeYes => 1,
+        AnswerMode::Defaults => 2,
+    };
+    MODE.store(value, Ordering::Relaxed);
+}
+
+fn mode() -> AnswerMode {
+    match MODE.load(Ordering::Relaxed) {
+        1 => AnswerMode::AssumeYes,
+        2 => AnswerMode::Defaults,
+        _ => AnswerMode::Interactive,
+    }
+}
+
+/// Whether questions are actually shown to the user
+pub fn is_interactive() -> bool {
+    mode() == AnswerMode::Interactive && io::stdin().is_terminal()
+}
+
+/// One of the answers offered by `select`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Choice<'a> {
+    /// Letter that picks this choice; highlighted where it appears in the label
+    pub key: char,
+    pub label: &'a str,
+}
+
+impl<'a> Choice<'a> {
+    pub const fn new(key: char, label: &'a str) -> Self {
+        Choice { key, label }
+    }
+}
+
+/// Asks `question` and returns the index of the chosen entry of `choices`.
+pub fn select(question: &str, choices: &[Choice], default: usize) -> Result<usize, AppError> {
+    if !is_interactive() {
+        return Ok(default);
+    }
+    let line = format!("{} {} ", question, choice_line(choices, default));
+    ask_until_answered(line.trim_start(), choices, default)
+}
+
+/// Asks a yes/no `question`; `default` is what Enter (or no terminal) answers.
+pub fn confirm(question: &str, default: bool) -> Result<bool, AppError> {
+    match mode() {
+        AnswerMode::AssumeYes => return Ok(true),
+        AnswerMode::Defaults => return Ok(default),
+        AnswerMode::Interactive if !io::stdin().is_terminal() => return Ok(default),
+        AnswerMode::Interactive => {}
+    }
+    let choices = [Choice::new('y', "yes"), Choice::new('n', "no")];
+    let line = format!("{} [{}] ", question, if default { "Y/n" } else { "y/N" });
+    Ok(ask_until_answered(&line, &choices, if default { 0 } else { 1 })? == 0)
+}
+
+fn ask_until_answered(line: &str, choices: &[Choice], default: usize) -> Result<usize, AppError> {
+    loop {
+        let Some(answer) = ask(line)? else {
+            // End of input: nobody is there to answer
+            return Ok(default);
+        };
+        if answer.trim() == "?" {
+            for (index, choice) in choices.iter().enumerate() {
+                eprintln!("  {} {} - {}", index + 1, choice.key, choice.label);
+            }
+            continue;
+        }
+        match parse_answer(&answer, choices, default) {
+            Some(index) => return Ok(index),
+            None => eprintln!("Please answer with one of the letters shown, or ? for help."),
+        }
+    }
+}
+
+/// Asks for free-form text; `None` when nobody can answer (see `is_interactive`)
+/// or at end of input.
+pub fn input(question: &str) -> Result<Option<String>, AppError> {
+    if !is_interactive() {
+        return Ok(None);
+    }
+    Ok(ask(&format!("{} ", question))?.map(|answer| answer.trim().to_string()))
+}
+
+/// Prints `line` on stderr and reads one answer; `None` at end of input.
+fn ask(line: &str) -> Result<Option<String>, AppError> {
+    eprint!("{}", line);
+    io::stderr()
+        .flush()
+        .map_err(|e| AppError::Io("flushing stderr".to_string(), e))?;
+    let mut answer = String::new();
+    let read = io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .map_err(|e| AppError::Io("reading the answer".to_string(), e))?;
+    Ok((read > 0).then_some(answer))
+}
+
+/// `[s]tage, [c]ontinue, [a]bort? [c]`, with the keys highlighted when colors are on.
+fn choice_line(choices: &[Choice], default: usize) -> String {
+    let labels: Vec<String> = choices.iter().map(label_with_key).collect();
+    let default_key = choices.get(default).map_or(String::new(), |choice| choice.key.to_string());
+    format!("{}? [{}]", labels.join(", "), default_key)
+}
+
+fn label_with_key(choice: &Choice) -> String {
+    let key = style::paint(Stream::Stderr, Style::Bold, &format!("[{}]", choice.key));
+    match choice.label.find(choice.key) {
+        Some(0) => format!("{}{}", key, &choice.label[choice.key.len_utf8()..]),
+        _ => format!("{} {}", key, choice.label),
+    }
+}
+
+/// Maps an answer to a choice: empty is the default, otherwise a key, a full
+/// label or a 1-based number. `None` when nothing matches.
+fn parse_answer(answer: &str, choices: &[Choice], default: usize) -> Option<usize> {
+    let answer = answer.trim().to_lowercase();
+    if answer.is_empty() {
+        return Some(default);
+    }
+    if let Ok(number) = answer.parse::<usize>() {
+        return (1..=choices.len()).contains(&number).then(|| number - 1);
+    }
+    choices.iter().position(|choice| {
+        answer == choice.key.to_string() || answer == choice.label.to_lowercase()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHOICES: [Choice<'static>; 3] = [
+        Choice::new('s', "stage them too"),
+        Choice::new('c', "continue"),
+        Choice::new('a', "abort"),
+    ];
+
+    #[test]
+    fn test_parse_answer_accepts_keys_labels_and_numbers() {
+        assert_eq!(parse_answer("\n", &CHOICES, 1), Some(1));
+        assert_eq!(parse_answer("S\n", &CHOICES, 1), Some(0));
+        assert_eq!(parse_answer("abort", &CHOICES, 1), Some(2));
+        assert_eq!(parse_answer("3", &CHOICES, 1), Some(2));
+        assert_eq!(parse_answer("4", &CHOICES, 1), None);
+        assert_eq!(parse_answer("x", &CHOICES, 1), None);
+    }
+
+    #[test]
+    fn test_choice_line_marks_keys_and_default() {
+        // Colors are off when stderr isn't a terminal, as under `cargo test`
+        if style::enabled(Stream::Stderr) {
+            return;
+        }
+        assert_eq!(
+            choice_line(&CHOICES, 1),
+            "[s]tage them too, [c]ontinue, [a]bort? [c]"
+        );
+        assert_eq!(
+            choice_line(&[Choice::new('y', "yes"), Choice::new('n', "no, skip it")], 0),
+            "[y]es, [n]o, skip it? [y]"
+        );
+    }
+}
@@ -0,0 +1,131 @@
+// git-enhancer/src/prompt_template.rs
+//! Placeholders in prompt files.
+//!
+//! A prompt may refer to settings as `{{name}}`, e.g. "Write the message in
+//! {{language}}.", which are filled in for every request. Templates are parsed
+//! once when the configuration is loaded, so a typo such as `{{langauge}}` or
+//! a missing `}}` is reported then, with its line number, instead of reaching
+//! the model as part of the prompt. A literal `{{` is written `\{{`.
+
+/// Placeholders every prompt may use
+const COMMON_VARIABLES: &[&str] = &["language"];
+
+/// Placeholders only the explanation prompt may use
+const EXPLANATION_VARIABLES: &[&str] = &["level"];
+
+/// Placeholders only user-defined tasks (see `task_commands`) may use
+const TASK_VARIABLES: &[&str] = &["args"];
+
+/// The placeholders a prompt for `task` (`"commit"`, `"explanation"` or a
+/// user-defined `"task"`) may use. A prompt given on the command line replaces
+/// the commit or explanation prompt, so `None` allows the placeholders of both.
+pub fn variables_for(task: Option<&str>) -> Vec<&'static str> {
+    let mut variables = COMMON_VARIABLES.to_vec();
+    match task {
+        None | Some("explanation") => variables.extend(EXPLANATION_VARIABLES),
+        Some("task") => variables.extend(TASK_VARIABLES),
+        _ => {}
+    }
+    variables
+}
+
+/// A parsed prompt, ready to be rendered
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Placeholder(String),
+}
+
+impl PromptTemplate {
+    /// Parses `source`, allowing the placeholders in `variables`
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PromptTemplate, Vec<String>>` - The template, or every problem
+    ///   found as "line N: ..."
+    pub fn compile(source: &str, variables: &[&str]) -> Result<Self, Vec<String>> {
+        let mut segments = Vec::new();
+        let mut problems = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+        let mut line = 1;
+
+        while let Some(start) = rest.find("{{") {
+            if rest[..start].ends_with('\\') {
+                text.push_str(&rest[..start - 1]);
+                text.push_str("{{");
+                line += rest[..start].matches('\n').count();
+                rest = &rest[start + 2..];
+                continue;
+            }
+            text.push_str(&rest[..start]);
+            line += rest[..start].matches('\n').count();
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}").filter(|end| !after[..*end].contains(['\n', '{'])) else {
+                problems.push(format!("line {}: `{{{{` is not closed with `}}}}` (write `\\{{{{` for a literal `{{{{`)", line));
+                text.push_str("{{");
+                rest = after;
+                continue;
+            };
+            let name = after[..end].trim();
+            if name.is_empty() {
+                problems.push(format!("line {}: empty placeholder `{{{{}}}}`", line));
+            } else if !variables.contains(&name) {
+                problems.push(format!(
+                    "line {}: unknown placeholder `{{{{{}}}}}` (available: {})",
+                    line,
+                    name,
+                    variables.join(", ")
+                ));
+            } else {
+                if !text.is_empty() {
+                    segments.push(Segment::Text(std::mem::take(&mut text)));
+                }
+                segments.push(Segment::Placeholder(name.to_string()));
+            }
+            rest = &after[end + 2..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+
+        if problems.is_empty() { Ok(PromptTemplate { segments }) } else { Err(problems) }
+    }
+
+    /// The prompt with every placeholder replaced by `value(name)`
+    pub fn render(&self, value: impl Fn(&str) -> String) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Text(text) => text.clone(),
+                Segment::Placeholder(name) => value(name),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_renders_placeholders_and_reports_errors_by_line() {
+        let variables = variables_for(Some("explanation"));
+        let template = PromptTemplate::compile("Answer in {{ language }}.\nKeep \\{{braces}} for a {{level}} reader.", &variables).unwrap();
+        let rendered = template.render(|name| if name == "language" { "German".to_string() } else { "beginner".to_string() });
+        assert_eq!(rendered, "Answer in German.\nKeep {{braces}} for a beginner reader.");
+
+        let problems = PromptTemplate::compile("ok\n{{langauge}}\n\nthen {{level}} and {{}}\n{{ unclosed", &variables_for(Some("commit"))).unwrap_err();
+        assert_eq!(problems.len(), 4);
+        assert!(problems[0].starts_with("line 2: unknown placeholder `{{langauge}}` (available: language)"));
+        assert!(problems[1].starts_with("line 4: unknown placeholder `{{level}}`"));
+        assert_eq!(problems[2], "line 4: empty placeholder `{{}}`");
+        assert!(problems[3].starts_with("line 5: `{{` is not closed"));
+    }
+}
@@ -0,0 +1,170 @@
+// git-enhancer/src/remotes.rs
+//! Remote and tracking branch context for explaining `git push`.
+//!
+//! Which remote and branch a push affects depends on arguments, tracking
+//! configuration and `push.default`, and whether it is rejected depends on
+//! what the remote already has. This collects those facts from the local
+//! repository, so the explanation can name the exact remote and branch and say
+//! whether a non-fast-forward rejection is coming. Remote-tracking refs are as
+//! of the last fetch; nothing here talks to the network.
+
+use crate::git_commands::execute_git_command_and_capture_output;
+
+/// `git push` options that take the next argument as their value
+const PUSH_OPTIONS_WITH_VALUE: &[&str] = &["-o", "--push-option", "--repo", "--receive-pack", "--exec"];
+
+/// Where a push sends one local ref
+#[derive(Debug, PartialEq, Eq)]
+struct PushTarget {
+    /// Local ref being pushed; `None` deletes `remote_branch`
+    local: Option<String>,
+    remote_branch: String,
+}
+
+/// Context for explaining `git <args>`, or `None` when it isn't a push or
+/// isn't run inside a repository.
+pub fn push_context(args: &[String]) -> Option<String> {
+    if args.first().map(String::as_str) != Some("push") {
+        return None;
+    }
+    let remote_list = git(&["remote", "-v"])?;
+    let remote_names: Vec<&str> = remote_list.lines().filter_map(|line| line.split_whitespace().next()).collect();
+    let (remote, targets) = parse_push_operands(&args[1..]);
+
+    let mut context = String::from("Repository context (remote-tracking branches are as of the last fetch):\n");
+    if remote_list.is_empty() {
+        context.push_str("- No remotes are configured.\n");
+    }
+    for line in remote_list.lines().filter(|line| line.ends_with("(push)")) {
+        context.push_str(&format!("- Remote: {}\n", line.trim_end_matches(" (push)")));
+    }
+    let branch = git(&["symbolic-ref", "--short", "-q", "HEAD"]).filter(|branch| !branch.is_empty());
+    match &branch {
+        Some(branch) => context.push_str(&format!("- Current branch: {}\n", branch)),
+        None => context.push_str("- HEAD is detached\n"),
+    }
+    if let Some(upstream) = git(&["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{upstream}"]) {
+        context.push_str(&format!("- Upstream of the current branch: {}\n", upstream));
+    }
+    let push_default = git(&["config", "--get", "push.default"]).unwrap_or_else(|| "simple (git's default)".to_string());
+    context.push_str(&format!("- push.default: {}\n", push_default));
+
+    let comparisons: Vec<(String, String)> = match (&remote, targets.is_empty()) {
+        // `git push` and `git push <remote>` push the current branch per push.default
+        (_, true) => {
+            let mut push = git(&["rev-parse", "--abbrev-ref", "--symbolic-full-name", "@{push}"]);
+            // Another remote than the configured one gets the branch under the same name
+            if let (Some(remote), Some(branch)) = (&remote, &branch)
+                && !push.as_ref().is_some_and(|push| push.starts_with(&format!("{}/", remote)))
+            {
+                push = remote_names.contains(&remote.as_str()).then(|| format!("{}/{}", remote, branch));
+            }
+            push.map(|push| {
+                context.push_str(&format!("- The current branch pushes to: {}\n", push));
+                vec![("HEAD".to_string(), push)]
+            })
+            .unwrap_or_default()
+        }
+        (Some(remote), false) if remote_names.contains(&remote.as_str()) => targets
+            .iter()
+            .filter_map(|target| {
+                let tracking = format!("{}/{}", remote, target.remote_branch);
+                match &target.local {
+                    Some(local) => Some((local.clone(), tracking)),
+                    None => {
+                        context.push_str(&format!("- This deletes the branch {} on {}\n", target.remote_branch, remote));
+                        None
+                    }
+                }
+            })
+            .collect(),
+        // A URL, or a remote that doesn't exist: no tracking branches to compare with
+        _ => Vec::new(),
+    };
+    for (local, tracking) in comparisons {
+        let range = format!("{}...{}", local, tracking);
+        let counts = git(&["rev-list", "--left-right", "--count", &range]);
+        match counts.as_deref().and_then(parse_counts) {
+            Some((ahead, 0)) => context.push_str(&format!(
+                "- {} is {} commit(s) ahead of {}; the push is a fast-forward\n",
+                local, ahead, tracking
+            )),
+            Some((ahead, behind)) => context.push_str(&format!(
+                "- {} is {} commit(s) ahead of and {} behind {}; without --force the push will be rejected as non-fast-forward\n",
+                local, ahead, behind, tracking
+            )),
+            None => context.push_str(&format!(
+                "- {} does not exist yet; the push creates it unless it appeared on the remote since the last fetch\n",
+                tracking
+            )),
+        }
+    }
+    Some(context)
+}
+
+/// Splits `git push` arguments (after `push`) into the remote and the targets of its refspecs.
+fn parse_push_operands(args: &[String]) -> (Option<String>, Vec<PushTarget>) {
+    let mut operands = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            operands.extend(args.by_ref().cloned());
+        } else if PUSH_OPTIONS_WITH_VALUE.contains(&arg.as_str()) {
+            args.next();
+        } else if !arg.starts_with('-') {
+            operands.push(arg.clone());
+        }
+    }
+    let mut operands = operands.into_iter();
+    let remote = operands.next();
+    let targets = operands
+        .map(|refspec| {
+            let refspec = refspec.trim_start_matches('+');
+            let (local, remote_branch) = refspec.split_once(':').unwrap_or((refspec, refspec));
+            PushTarget {
+                local: (!local.is_empty()).then(|| local.to_string()),
+                remote_branch: remote_branch.trim_start_matches("refs/heads/").to_string(),
+            }
+        })
+        .collect();
+    (remote, targets)
+}
+
+/// `2\t1` from `git rev-list --left-right --count` as (ahead, behind)
+fn parse_counts(output: &str) -> Option<(u32, u32)> {
+    let mut counts = output.split_whitespace().map(|count| count.parse().ok());
+    Some((counts.next()??, counts.next()??))
+}
+
+/// The trimmed stdout of a successful `git <args>`
+fn git(args: &[&str]) -> Option<String> {
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let output = execute_git_command_and_capture_output(&args).ok()?;
+    output.status.success().then(|| output.stdout.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_push_operands() {
+        assert_eq!(parse_push_operands(&args(&["--force-with-lease"])), (None, vec![]));
+        assert_eq!(
+            parse_push_operands(&args(&["-o", "ci.skip", "-u", "upstream", "feature", "+HEAD:main", ":old"])),
+            (
+                Some("upstream".to_string()),
+                vec![
+                    PushTarget { local: Some("feature".to_string()), remote_branch: "feature".to_string() },
+                    PushTarget { local: Some("HEAD".to_string()), remote_branch: "main".to_string() },
+                    PushTarget { local: None, remote_branch: "old".to_string() },
+                ]
+            )
+        );
+        assert_eq!(parse_counts("2\t1\n"), Some((2, 1)));
+    }
+}
@@ -0,0 +1,63 @@
+// git-enhancer/src/review.rs
+//! AI code review of a diff.
+//!
+//! Uses the `[review]` model settings, so reviews can go to a stronger model
+//! than commit messages. The review lists concrete problems (bugs, missing
+//! error handling, security issues, unclear code) with the file and line they
+//! are in, or says that it found none.
+
+use crate::ai_utils::{AiReply, ChatMessage, OpenAIChatRequest, clean_ai_output, send_chat_request};
+use crate::config::AppConfig;
+use crate::errors::AIError;
+use crate::post_generate;
+use crate::untrusted::{self, UNTRUSTED_CONTENT_RULES};
+
+const REVIEW_PROMPT: &str = "You are reviewing a change to a code base, given as a unified diff. \
+    List the concrete problems it introduces: bugs, unhandled errors and edge cases, security \
+    issues, and code that is hard to understand. For each, give the file and the line in the new \
+    version, what is wrong and how to fix it, most important first. Don't comment on style that a \
+    formatter would fix, and don't praise. If you find no problems, say so in one sentence.";
+
+/// Characters of a diff sent for review
+const MAX_DIFF_CHARS: usize = 60_000;
+
+/// Returns an AI review of the changes in `diff`.
+pub async fn review_diff(config: &AppConfig, diff: &str) -> Result<AiReply, AIError> {
+    if diff.trim().is_empty() {
+        return Ok(AiReply::local("There are no changes to review."));
+    }
+    let parameters = config.model_parameters("review");
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, true)?;
+    let mut diff = diff.to_string();
+    if let Some((end, _)) = diff.char_indices().nth(MAX_DIFF_CHARS) {
+        diff.truncate(end);
+        diff.push_str("\n[diff truncated]");
+    }
+    let mut system_prompt = REVIEW_PROMPT.to_string();
+    if let Some(language) = &config.explain.language {
+        system_prompt.push_str(&format!("\n\nWrite the review in {}.", language));
+    }
+    system_prompt.push_str(&format!("\n\n{}", UNTRUSTED_CONTENT_RULES));
+    let mut user_prompt = untrusted::background(&config.context).unwrap_or_default();
+    user_prompt.push_str(&untrusted::fence("diff", &diff));
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: system_prompt },
+            ChatMessage { role: "user".to_string(), content: user_prompt },
+        ],
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    let response = send_chat_request(config, &parameters.api_url, &request).await?;
+    let choice = response.choices.first().ok_or(AIError::NoChoiceInResponse)?;
+    let text = clean_ai_output(&choice.message.content);
+    if text.trim().is_empty() {
+        return Err(AIError::EmptyMessage);
+    }
+    let text = post_generate::apply(config.post_generate_cmd("review"), "review", &text)?;
+    Ok(AiReply { text, model: response.model.clone(), usage: Some(response.usage.clone()) })
+}
@@ -0,0 +1,112 @@
+// git-enhancer/src/safety.rs
+//! Recognizes shell commands that can lose work.
+//!
+//! This is a list of well-known dangerous forms, not a sandbox: it exists so that
+//! a command suggested by the model is never run on a single keypress when it
+//! could discard changes, rewrite shared history or delete files.
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+/// How risky a command is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Risk {
+    Safe,
+    /// Can lose work; the reason is shown to the user
+    Destructive(&'static str),
+}
+
+lazy_static! {
+    static ref DESTRUCTIVE_PATTERNS: Vec<(Regex, &'static str)> = [
+        (r"^git\s+reset\b.*\s--(hard|merge|keep)\b", "discards uncommitted changes"),
+        (r"^git\s+clean\b.*\s(-[a-zA-Z]*f|--force\b)", "deletes untracked files"),
+        (r"^git\s+push\b.*\s(--force\b|--force-with-lease\b|-[a-zA-Z]*f\b|\+\S)", "overwrites commits on the remote"),
+        (r"^git\s+push\b.*\s(--delete\b|-d\b|:\S)", "deletes a remote branch or tag"),
+        (r"^git\s+(checkout|switch)\b.*\s(-f|--force|--discard-changes)\b", "discards uncommitted changes"),
+        (r"^git\s+checkout\b.*\s--\s", "discards uncommitted changes to the listed paths"),
+        (r"^git\s+checkout\s+\.$", "discards all uncommitted changes"),
+        (r"^git\s+branch\b.*\s(-D|--delete\s+--force|-d\s+-f)\b", "deletes a branch even if it is not merged"),
+        (r"^git\s+stash\s+(drop|clear)\b", "deletes stashed changes"),
+        (r"^git\s+(filter-branch|filter-repo)\b", "rewrites the whole history"),
+        (r"^git\s+reflog\s+(expire|delete)\b", "removes the record of earlier states"),
+        (r"^git\s+gc\b.*--prune", "permanently removes unreachable commits"),
+        (r"^git\s+update-ref\s+-d\b", "deletes a ref"),
+        (r"^git\s+tag\s+(-d|--delete)\b", "deletes a tag"),
+        (r"^rm\s", "deletes files"),
+        (r"^(sudo|dd|mkfs|shred)\b", "can damage the system"),
+    ]
+    .into_iter()
+    .map(|(pattern, reason)| (Regex::new(pattern).unwrap(), reason))
+    .collect();
+    /// Splits a command line at `;`, `&&`, `||` and `|`
+    static ref COMMAND_SEPARATOR: Regex = Regex::new(r"\s*(?:;|&&|\|\||\|)\s*").unwrap();
+}
+
+/// Classifies a command line, looking at every command in a `;`/`&&`/`|` chain.
+pub fn classify(command_line: &str) -> Risk {
+    for command in COMMAND_SEPARATOR.split(command_line.trim()) {
+        let command = command.trim();
+        for (pattern, reason) in DESTRUCTIVE_PATTERNS.iter() {
+            if pattern.is_match(command) {
+                return Risk::Destructive(reason);
+            }
+        }
+        if restores_worktree(command) {
+            return Risk::Destructive("discards uncommitted changes");
+        }
+    }
+    Risk::Safe
+}
+
+/// `git restore` touches the working tree unless only `--staged` is given
+fn restores_worktree(command: &str) -> bool {
+    let mut words = command.split_whitespace();
+    if words.next() != Some("git") || words.next() != Some("restore") {
+        return false;
+    }
+    let options: Vec<&str> = words.filter(|word| word.starts_with('-')).collect();
+    let staged = options.iter().any(|option| *option == "--staged" || *option == "-S");
+    let worktree = options.iter().any(|option| *option == "--worktree" || *option == "-W");
+    worktree || !staged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destructive_commands_are_recognized() {
+        for command in [
+            "git reset --hard HEAD~1",
+            "git clean -fdx",
+            "git push --force origin main",
+            "git push -f",
+            "git push origin +main",
+            "git push origin --delete old",
+            "git checkout -- src/lib.rs",
+            "git restore src/lib.rs",
+            "git branch -D feature",
+            "git stash clear",
+            "git status && git reset --hard",
+            "rm -rf target",
+        ] {
+            assert!(matches!(classify(command), Risk::Destructive(_)), "{}", command);
+        }
+    }
+
+    #[test]
+    fn test_everyday_commands_are_safe() {
+        for command in [
+            "git status",
+            "git reset HEAD~1",
+            "git restore --staged src/lib.rs",
+            "git push origin main",
+            "git branch -d merged",
+            "git checkout -b feature",
+            "git log --oneline | head",
+            "git clean -n",
+        ] {
+            assert_eq!(classify(command), Risk::Safe, "{}", command);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+// git-enhancer/src/scope.rs
+//! Limiting the AI context to one directory of a monorepo.
+//!
+//! The scope comes from `--scope <dir>`, relative to the current directory, or
+//! from `scope = "<dir>"` in a `.gitie.toml` at the repository root, relative to
+//! the root. Commit message generation then only sees the staged changes inside
+//! it, and `team-summary` only the commits that touch it.
+
+use serde::Deserialize;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Component, Path};
+
+use crate::errors::{AppError, ConfigError};
+use crate::git_context::GitContext;
+
+/// Settings checked into the repository, read from its root
+pub const REPOSITORY_CONFIG_FILE_NAME: &str = ".gitie.toml";
+
+#[derive(Deserialize, Debug, Default)]
+struct RepositoryConfig {
+    /// Default scope, relative to the repository root
+    scope: Option<String>,
+}
+
+/// A directory of the repository, relative to its root with `/` separators
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scope(String);
+
+impl Scope {
+    /// The scope as a pathspec that means the same from any directory
+    pub fn pathspec(&self) -> String {
+        format!(":(top){}", self.0)
+    }
+
+    /// Whether `path`, relative to the repository root, is inside the scope
+    pub fn contains(&self, path: &str) -> bool {
+        path.strip_prefix(self.0.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    }
+}
+
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The scope of this run: the `--scope` directory if given, otherwise the
+/// repository's `.gitie.toml` default.
+///
+/// `None` when neither sets one, or when the scope is the whole repository.
+pub fn resolve(git_context: &GitContext, flag: Option<&str>) -> Result<Option<Scope>, AppError> {
+    let root = git_context.root()?.to_path_buf();
+    let (base, directory) = match flag {
+        Some(directory) => (git_context.working_dir()?, directory.to_string()),
+        None => match read_repository_config(&root)?.scope {
+            Some(directory) => (root.clone(), directory),
+            None => return Ok(None),
+        },
+    };
+    let invalid = || AppError::Generic(format!("scope '{}' is not a directory in this repository", directory));
+    let path = base.join(&directory).canonicalize().map_err(|_| invalid())?;
+    let root = root.canonicalize().map_err(|e| AppError::Io(format!("resolving '{}'", root.display()), e))?;
+    if !path.is_dir() {
+        return Err(invalid());
+    }
+    let relative = path.strip_prefix(&root).map_err(|_| invalid())?;
+    Ok(to_scope(relative))
+}
+
+/// `relative` with `/` separators; `None` for the repository root itself
+fn to_scope(relative: &Path) -> Option<Scope> {
+    let parts: Vec<String> = relative
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect();
+    (!parts.is_empty()).then(|| Scope(parts.join("/")))
+}
+
+fn read_repository_config(root: &Path) -> Result<RepositoryConfig, ConfigError> {
+    let path = root.join(REPOSITORY_CONFIG_FILE_NAME);
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(RepositoryConfig::default()),
+        Err(e) => return Err(ConfigError::FileRead(path.to_string_lossy().to_string(), e)),
+    };
+    toml::from_str(&content).map_err(|e| ConfigError::TomlParse(path.to_string_lossy().to_string(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scope_contains_whole_path_components() {
+        let scope = to_scope(Path::new("packages/api")).unwrap();
+        assert_eq!(scope.pathspec(), ":(top)packages/api");
+        assert!(scope.contains("packages/api"));
+        assert!(scope.contains("packages/api/src/lib.rs"));
+        assert!(!scope.contains("packages/api-client/src/lib.rs"));
+        assert!(!scope.contains("packages/web/index.ts"));
+        assert_eq!(to_scope(Path::new("")), None);
+    }
+}
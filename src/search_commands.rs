@@ -0,0 +1,88 @@
+// git-enhancer/src/search_commands.rs
+//! The `search` subcommand.
+//!
+//! Finds commits whose messages are closest in meaning to a query, so "where
+//! did we stop retrying uploads" finds "Drop the upload backoff" even without a
+//! shared word. The index of embedded messages is updated first (see
+//! `history_index`), which only embeds commits made since the last search.
+
+use crate::ai_utils::AiReply;
+use crate::cli::SearchArgs;
+use crate::config::AppConfig;
+use crate::errors::{AppError, GitError};
+use crate::git_commands::execute_git_command_and_capture_output;
+use crate::git_context::GitContext;
+use crate::history_index;
+use crate::output;
+use crate::ui;
+
+/// Handles the `search` subcommand
+///
+/// # Arguments
+///
+/// * `args` - Search arguments from CLI
+/// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub async fn handle_search(args: SearchArgs, config: &AppConfig, git_context: &GitContext) -> Result<(), AppError> {
+    git_context.require_repository()?;
+    let query = args.query.join(" ");
+    if query.trim().is_empty() && !args.update {
+        return Err(AppError::Generic("search needs a query, e.g. `git-enhancer search retry uploads`".to_string()));
+    }
+
+    let path = history_index::index_path()?;
+    let index = history_index::update(config, &path).await?;
+    if args.update {
+        ui::status(&format!("{} commit(s) indexed", index.entries.len()));
+        return Ok(());
+    }
+
+    let vector = history_index::embed_query(config, &query).await?;
+    let matches = history_index::nearest(&index, &vector, args.limit);
+    let oids: Vec<&str> = matches.iter().map(|(entry, _)| entry.oid.as_str()).collect();
+    let summaries = describe_commits(&oids)?;
+    let results: Vec<serde_json::Value> = matches
+        .iter()
+        .zip(&summaries)
+        .map(|((entry, score), summary)| serde_json::json!({ "commit": entry.oid, "summary": summary, "score": score }))
+        .collect();
+    let text = if matches.is_empty() {
+        "No indexed commits.".to_string()
+    } else {
+        matches
+            .iter()
+            .zip(&summaries)
+            .map(|((_, score), summary)| format!("{}  ({:.2})", summary, score))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let data = serde_json::json!({ "query": query, "results": results });
+    output::print_ai_reply_with_data(&config.output, "search", &AiReply::local(text), Some(&data), 0)
+}
+
+/// `<short hash> <date> <subject>` for each of `oids`, in the same order
+fn describe_commits(oids: &[&str]) -> Result<Vec<String>, AppError> {
+    if oids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut args: Vec<String> = ["log", "--no-walk=unsorted", "--date=short", "--format=%h %ad %s"]
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect();
+    args.extend(oids.iter().map(|oid| oid.to_string()));
+    let output = execute_git_command_and_capture_output(&args)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: "git log --no-walk".to_string(),
+            status_code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+        .into());
+    }
+    Ok(output.stdout.lines().map(str::to_string).collect())
+}
@@ -0,0 +1,44 @@
+// git-enhancer/src/serve_commands.rs
+//! The `serve` subcommand: git-enhancer as a long-running server for other tools.
+//!
+//! `serve --mcp` speaks the Model Context Protocol on stdin and stdout (see
+//! `mcp`), so AI assistants and IDE agents can use git-enhancer as their git
+//! backend. `serve --stdio` speaks a smaller JSON-RPC protocol for editor
+//! plugins (see `editor_server`). The configuration is loaded once, when the
+//! server starts.
+
+use crate::cli::ServeArgs;
+use crate::ai_utils;
+use crate::config::AppConfig;
+use crate::editor_server::EditorServer;
+use crate::errors::AppError;
+use crate::json_rpc;
+use crate::mcp::McpServer;
+use crate::prompt::{self, AnswerMode};
+
+/// Handles the `serve` subcommand
+///
+/// # Arguments
+///
+/// * `args` - Protocol to speak from CLI
+/// * `config` - Application configuration
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success once the input ends, or an error
+pub async fn handle_serve(args: ServeArgs, config: &AppConfig) -> Result<(), AppError> {
+    // stdin carries requests: nobody is there to answer questions
+    prompt::set_mode(AnswerMode::Defaults);
+    let served = if args.stdio {
+        let start_dir = std::env::current_dir().map_err(|e| AppError::Io("reading the working directory".to_string(), e))?;
+        tracing::info!("Serving the editor protocol on stdio");
+        // Editors wait for the first answer; have the connection ready
+        ai_utils::warm_up(config, "commit");
+        json_rpc::serve(&mut EditorServer::new(config, start_dir)).await
+    } else {
+        debug_assert!(args.mcp, "clap requires --mcp or --stdio");
+        tracing::info!("Serving the Model Context Protocol on stdio");
+        json_rpc::serve(&mut McpServer::new(config)).await
+    };
+    served.map_err(|e| AppError::Io("serving requests on stdio".to_string(), e))
+}
@@ -0,0 +1,123 @@
+// git-enhancer/src/state_file.rs
+//! Safe writes to git-enhancer's own files.
+//!
+//! Several git-enhancer processes can run at once, e.g. an editor plugin next to
+//! a terminal. Read-modify-write changes to a file such as `config.toml` hold an
+//! advisory lock on `<file>.lock` while they run, and every write goes to a
+//! temporary file that is renamed over the target, so readers see either the old
+//! or the new content and never a half-written file.
+
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// An exclusive lock on a state file, released when dropped
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Waits until no other process holds the lock for `path`, then takes it.
+    pub fn acquire(path: &Path) -> io::Result<FileLock> {
+        let lock_path = sibling(path, ".lock");
+        if let Some(dir) = lock_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&lock_path)?;
+        file.lock()?;
+        Ok(FileLock { _file: file })
+    }
+}
+
+/// Replaces the content of `path` with `contents` in one step.
+///
+/// A symlink at `path` is kept and the file it points to replaced, and the
+/// file keeps its permissions, so a `config.toml` holding an API key stays
+/// private.
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    // A file that doesn't exist yet has nothing to resolve
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let permissions = fs::metadata(&path).map(|metadata| metadata.permissions()).ok();
+    let temp_path = sibling(&path, &format!(".tmp-{}", std::process::id()));
+    let written = File::create(&temp_path).and_then(|mut file| {
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(contents.as_ref())?;
+        file.sync_all()
+    });
+    match written.and_then(|_| fs::rename(&temp_path, &path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Appends `line` and a newline to the log at `path`, creating it if needed.
+///
+/// The line is written in one write under the lock, so lines of concurrent
+/// processes can't interleave.
+pub fn append_line(path: &Path, line: &str) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let line = format!("{}\n", line);
+    let _lock = FileLock::acquire(path)?;
+    let mut log = OpenOptions::new().create(true).append(true).open(path)?;
+    log.write_all(line.as_bytes())
+}
+
+/// `path` with `suffix` appended to its file name
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::TryLockError;
+
+    #[test]
+    fn test_lock_excludes_others_and_writes_replace_the_file() {
+        let dir = std::env::temp_dir().join(format!("gitie-state-file-test-{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let lock = FileLock::acquire(&path).unwrap();
+        let other = File::open(dir.join("config.toml.lock")).unwrap();
+        assert!(matches!(other.try_lock(), Err(TryLockError::WouldBlock)));
+        drop(lock);
+        assert!(other.try_lock().is_ok());
+
+        write_atomic(&path, "old").unwrap();
+        write_atomic(&path, "new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let mut names: Vec<_> = fs::read_dir(&dir).unwrap().map(|entry| entry.unwrap().file_name()).collect();
+        names.sort();
+        assert_eq!(names, vec!["config.toml", "config.toml.lock"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_writes_keep_symlinks_and_permissions() {
+        use std::os::unix::fs::{PermissionsExt, symlink};
+
+        let dir = std::env::temp_dir().join(format!("gitie-state-file-link-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("dotfiles")).unwrap();
+        let target = dir.join("dotfiles").join("config.toml");
+        let link = dir.join("config.toml");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_atomic(&link, "new").unwrap();
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(fs::metadata(&target).unwrap().permissions().mode() & 0o777, 0o600);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,37 @@
+// git-enhancer/src/stats_commands.rs
+//! The `stats` subcommand.
+//!
+//! Summarises the local usage statistics (see `usage_stats`): how often each
+//! subcommand ran, how many proposed commit messages were accepted, edited or
+//! rejected, and how long AI requests took.
+
+use crate::ai_utils::AiReply;
+use crate::config::AppConfig;
+use crate::errors::AppError;
+use crate::output;
+use crate::usage_stats;
+
+/// Handles the `stats` subcommand
+///
+/// # Arguments
+///
+/// * `config` - Application configuration
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub fn handle_stats(config: &AppConfig) -> Result<(), AppError> {
+    let summary = usage_stats::summarize().map_err(|e| AppError::Io("reading the usage statistics".to_string(), e))?;
+    let mut text = summary.render();
+    if text.is_empty() {
+        text = if config.stats.enabled {
+            "No usage recorded yet.".to_string()
+        } else {
+            "No usage recorded; set [stats] enabled = true to record it. Statistics never leave this machine.".to_string()
+        };
+    } else if let Ok(path) = usage_stats::stats_path() {
+        text.push_str(&format!("\n\nRecorded in {}; delete it to start over.", path.display()));
+    }
+    let data = serde_json::to_value(&summary).map_err(|e| AppError::Generic(format!("could not serialize the statistics: {}", e)))?;
+    output::print_ai_reply_with_data(&config.output, "stats", &AiReply::local(text), Some(&data), 0)
+}
@@ -0,0 +1,123 @@
+// git-enhancer/src/style.rs
+//! Decides whether output is colored and applies the colors.
+//!
+//! Everything that emits ANSI styling goes through here so that `--color`,
+//! `[output] color`, `NO_COLOR` and `CLICOLOR_FORCE` are honored the same way
+//! for error messages and rendered AI text.
+
+use std::io::{self, IsTerminal};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::output::ColorMode;
+
+/// The effective color mode; starts as `Auto` until the flag or the configuration sets it
+static MODE: AtomicU8 = AtomicU8::new(0);
+
+/// Where styled text is written; colors are only automatic on a terminal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Bold,
+    Dim,
+    Red,
+    Green,
+    Yellow,
+    Cyan,
+}
+
+impl Style {
+    fn code(self) -> &'static str {
+        match self {
+            Style::Bold => "1",
+            Style::Dim => "2",
+            Style::Red => "1;31",
+            Style::Green => "32",
+            Style::Yellow => "1;33",
+            Style::Cyan => "36",
+        }
+    }
+
+    /// Wraps `text` in this style's escape codes, regardless of any setting.
+    pub fn apply(self, text: &str) -> String {
+        format!("\x1b[{}m{}\x1b[0m", self.code(), text)
+    }
+}
+
+pub fn set_mode(mode: ColorMode) {
+    let value = match mode {
+        ColorMode::Auto => 0,
+        ColorMode::Always => 1,
+        ColorMode::Never => 2,
+    };
+    MODE.store(value, Ordering::Relaxed);
+}
+
+fn mode() -> ColorMode {
+    match MODE.load(Ordering::Relaxed) {
+        1 => ColorMode::Always,
+        2 => ColorMode::Never,
+        _ => ColorMode::Auto,
+    }
+}
+
+/// Whether text written to `stream` should be colored.
+pub fn enabled(stream: Stream) -> bool {
+    let is_terminal = match stream {
+        Stream::Stdout => io::stdout().is_terminal(),
+        Stream::Stderr => io::stderr().is_terminal(),
+    };
+    resolve(
+        mode(),
+        std::env::var("NO_COLOR").ok().as_deref(),
+        std::env::var("CLICOLOR_FORCE").ok().as_deref(),
+        is_terminal,
+    )
+}
+
+/// An explicit `always`/`never` wins; in `auto` mode a non-empty `NO_COLOR`
+/// disables colors and `CLICOLOR_FORCE` (other than `0`) forces them off a terminal.
+fn resolve(mode: ColorMode, no_color: Option<&str>, clicolor_force: Option<&str>, is_terminal: bool) -> bool {
+    match mode {
+        ColorMode::Always => true,
+        ColorMode::Never => false,
+        ColorMode::Auto if no_color.is_some_and(|value| !value.is_empty()) => false,
+        ColorMode::Auto if clicolor_force.is_some_and(|value| value != "0") => true,
+        ColorMode::Auto => is_terminal,
+    }
+}
+
+/// Styles `text` for `stream` if colors are enabled there.
+pub fn paint(stream: Stream, style: Style, text: &str) -> String {
+    if enabled(stream) {
+        style.apply(text)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_honors_flag_then_environment_then_terminal() {
+        assert!(resolve(ColorMode::Always, Some("1"), None, false));
+        assert!(!resolve(ColorMode::Never, None, Some("1"), true));
+        assert!(!resolve(ColorMode::Auto, Some("1"), Some("1"), true));
+        assert!(resolve(ColorMode::Auto, Some(""), None, true));
+        assert!(resolve(ColorMode::Auto, None, Some("1"), false));
+        assert!(!resolve(ColorMode::Auto, None, Some("0"), false));
+        assert!(!resolve(ColorMode::Auto, None, None, false));
+    }
+
+    #[test]
+    fn test_style_apply_wraps_text_in_escape_codes() {
+        assert_eq!(Style::Bold.apply("x"), "\x1b[1mx\x1b[0m");
+        assert_eq!(Style::Red.apply("error:"), "\x1b[1;31merror:\x1b[0m");
+    }
+}
@@ -0,0 +1,100 @@
+// git-enhancer/src/suggestions.rs
+//! Running a command suggested in an AI answer (`--run`).
+//!
+//! Nothing runs without the user picking a command and confirming it at a
+//! terminal; `--yes` does not apply here. Commands that can lose work need the
+//! confirmation typed out in full.
+
+use std::process::Command as StdCommand;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use crate::errors::{AppError, GitError};
+use crate::git_commands::run_attached_to_terminal;
+use crate::interrupt::exit_code;
+use crate::markdown;
+use crate::prompt;
+use crate::safety::{self, Risk};
+use crate::style::{self, Stream, Style};
+use crate::ui;
+
+/// What has to be typed to run a destructive command
+const OVERRIDE_PHRASE: &str = "run anyway";
+
+lazy_static! {
+    /// Placeholders the model leaves for the user to fill in, such as `<branch>`
+    static ref PLACEHOLDER: Regex = Regex::new(r"<[A-Za-z][A-Za-z0-9_ -]*>").unwrap();
+}
+
+/// Lists the shell commands found in `text` and runs the one the user picks.
+pub fn offer_to_run(text: &str) -> Result<(), AppError> {
+    let commands = markdown::shell_commands(text);
+    if commands.is_empty() {
+        ui::status("The answer doesn't suggest any command to run.");
+        return Ok(());
+    }
+    if !prompt::is_interactive() {
+        ui::warning("--run needs a terminal to confirm the command; nothing was run");
+        return Ok(());
+    }
+
+    eprintln!("\nSuggested commands:");
+    for (index, command) in commands.iter().enumerate() {
+        let note = match safety::classify(command) {
+            Risk::Safe => String::new(),
+            Risk::Destructive(reason) => {
+                format!("  {}", style::paint(Stream::Stderr, Style::Red, &format!("({})", reason)))
+            }
+        };
+        eprintln!("  [{}] {}{}", index + 1, command, note);
+    }
+    let Some(command) = pick(&commands)? else {
+        return Ok(());
+    };
+
+    if PLACEHOLDER.is_match(command) {
+        ui::warning("this command has placeholders to fill in; edit it and run it yourself");
+        return Ok(());
+    }
+    let confirmed = match safety::classify(command) {
+        Risk::Safe => prompt::confirm(&format!("Run `{}`?", command), false)?,
+        Risk::Destructive(reason) => {
+            eprintln!("`{}` {}.", command, reason);
+            let answer = prompt::input(&format!("Type '{}' to run it:", OVERRIDE_PHRASE))?;
+            answer.as_deref() == Some(OVERRIDE_PHRASE)
+        }
+    };
+    if !confirmed {
+        eprintln!("Not running it.");
+        return Ok(());
+    }
+
+    tracing::debug!("Running suggested command: {}", command);
+    let status = run_attached_to_terminal(StdCommand::new("sh").arg("-c").arg(command))
+        .map_err(|e| AppError::Io(format!("running '{}'", command), e))?;
+    if !status.success() {
+        return Err(AppError::Git(GitError::PassthroughFailed {
+            command: command.clone(),
+            status_code: exit_code(status),
+        }));
+    }
+    Ok(())
+}
+
+/// Asks for a command number until a valid one or nothing is entered.
+fn pick(commands: &[String]) -> Result<Option<&String>, AppError> {
+    let question = format!("Run which command? [1-{}, Enter to skip]", commands.len());
+    loop {
+        let Some(answer) = prompt::input(&question)? else {
+            return Ok(None);
+        };
+        if answer.is_empty() {
+            return Ok(None);
+        }
+        match answer.parse::<usize>() {
+            Ok(number) if (1..=commands.len()).contains(&number) => return Ok(commands.get(number - 1)),
+            _ => eprintln!("Please enter a number between 1 and {}.", commands.len()),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+// git-enhancer/src/task_commands.rs
+//! The `run` subcommand: AI tasks defined by the user.
+//!
+//! Each `<name>.toml` in `~/.config/gitie/tasks/` is a task that `run <name>`
+//! sends to the model: a prompt template and the input it is given, such as
+//! the staged diff or a range of the log. New workflows ("find typos in what
+//! I'm about to commit", "draft a migration note for these commits") need only
+//! a file, no code:
+//!
+//! ```toml
+//! description = "Find typos in the staged changes"
+//! input = "staged-diff"
+//! prompt = "List the spelling mistakes in added lines, as `file: wrong -> right`."
+//! ```
+//!
+//! A task can pipe the model's answer through a script of its own with
+//! `post_generate_cmd` (see `post_generate`).
+
+use serde::Deserialize;
+use std::fs;
+use std::io::{self, ErrorKind, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+
+use crate::ai_utils::{AiReply, ChatMessage, OpenAIChatRequest, clean_ai_output, send_chat_request};
+use crate::cli::RunTaskArgs;
+use crate::config::{self, AppConfig};
+use crate::errors::{AIError, AppError, ConfigError, GitError};
+use crate::git_commands::execute_git_command_and_capture_output;
+use crate::git_context::GitContext;
+use crate::output;
+use crate::post_generate;
+use crate::prompt_template::{self, PromptTemplate};
+use crate::scope;
+use crate::ui;
+use crate::untrusted::{self, UNTRUSTED_CONTENT_RULES};
+
+/// Directory under the user configuration directory that holds the task files
+pub const TASKS_DIR_NAME: &str = "tasks";
+
+/// Commits a `log` task covers when no range is given
+const DEFAULT_LOG_COUNT: &str = "-20";
+
+/// What a task sends to the model along with its prompt
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+enum TaskInput {
+    /// `git diff --cached`
+    StagedDiff,
+    /// `git diff`: changes not staged yet
+    Diff,
+    /// `git log` of the range given as the task's arguments (the last 20 commits by default)
+    Log,
+    /// Whatever is piped to git-enhancer
+    Stdin,
+    /// Nothing but the prompt, e.g. when `{{args}}` carries the question
+    #[default]
+    None,
+}
+
+/// A task file as written
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct TaskFile {
+    description: Option<String>,
+    #[serde(default)]
+    input: TaskInput,
+    prompt: String,
+    post_generate_cmd: Option<String>,
+}
+
+/// A task file, parsed and with its prompt compiled
+#[derive(Debug)]
+struct Task {
+    description: Option<String>,
+    input: TaskInput,
+    prompt: PromptTemplate,
+    post_generate_cmd: Option<String>,
+}
+
+/// Handles the `run` subcommand
+///
+/// Without a task name, lists the tasks that are defined.
+///
+/// # Arguments
+///
+/// * `args` - Task name and its arguments from CLI
+/// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub async fn handle_run_task(args: RunTaskArgs, config: &AppConfig, git_context: &GitContext) -> Result<(), AppError> {
+    // The user's tasks, also when `--config` names another configuration file
+    let dir = config::home_config_dir().join(TASKS_DIR_NAME);
+    let Some(name) = args.task else {
+        return list_tasks(&dir, config);
+    };
+    let task = load_task(&dir, &name)?;
+    let input = read_input(task.input, &args.args, config, git_context)?;
+    let data = serde_json::json!({ "task": name, "args": args.args });
+    let reply = match input {
+        Some(input) if input.trim().is_empty() => AiReply::local(format!("Nothing to run '{}' on: the {} is empty.", name, describe(task.input))),
+        input => ask(config, &name, &task, &args.args.join(" "), input).await?,
+    };
+    output::print_ai_reply_with_data(&config.output, "task", &reply, Some(&data), 0)
+}
+
+/// One line per task file in `dir`: its name and description
+fn list_tasks(dir: &Path, config: &AppConfig) -> Result<(), AppError> {
+    let names = task_names(dir)?;
+    let text = if names.is_empty() {
+        format!("No tasks yet. Add one as <name>.toml in {} and run it with `git-enhancer run <name>`.", dir.display())
+    } else {
+        names
+            .iter()
+            .map(|name| match load_task(dir, name) {
+                Ok(task) => format!("{}  {}", name, task.description.as_deref().unwrap_or("")).trim_end().to_string(),
+                Err(_) => format!("{}  (invalid, see `git-enhancer run {}`)", name, name),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+    let data = serde_json::json!({ "tasks": names });
+    output::print_ai_reply_with_data(&config.output, "task", &AiReply::local(text), Some(&data), 0)
+}
+
+/// Names of the `*.toml` files in `dir`, sorted; none when `dir` doesn't exist
+fn task_names(dir: &Path) -> Result<Vec<String>, AppError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(AppError::Io(format!("reading {}", dir.display()), e)),
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            (path.extension()? == "toml").then(|| path.file_stem()?.to_str().map(str::to_string))?
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn load_task(dir: &Path, name: &str) -> Result<Task, AppError> {
+    if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+        return Err(AppError::Generic(format!("invalid task name '{}'", name)));
+    }
+    let path: PathBuf = dir.join(format!("{}.toml", name));
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            let names = task_names(dir)?;
+            let available = if names.is_empty() { "none".to_string() } else { names.join(", ") };
+            return Err(AppError::Generic(format!("no task '{}' in {} (available: {})", name, dir.display(), available)));
+        }
+        Err(e) => return Err(ConfigError::FileRead(path.to_string_lossy().to_string(), e).into()),
+    };
+    Ok(parse_task(&path, &text)?)
+}
+
+/// Parses a task file, reporting problems in its prompt with their line in the prompt
+fn parse_task(path: &Path, text: &str) -> Result<Task, ConfigError> {
+    let file: TaskFile = toml::from_str(text).map_err(|e| ConfigError::TomlParse(path.to_string_lossy().to_string(), e))?;
+    let prompt = PromptTemplate::compile(&file.prompt, &prompt_template::variables_for(Some("task"))).map_err(|problems| {
+        ConfigError::Invalid(
+            path.to_string_lossy().to_string(),
+            problems.into_iter().map(|problem| format!("prompt {}", problem)).collect(),
+        )
+    })?;
+    Ok(Task { description: file.description, input: file.input, prompt, post_generate_cmd: file.post_generate_cmd })
+}
+
+/// The input for a task, or `None` for tasks that take none
+fn read_input(input: TaskInput, args: &[String], config: &AppConfig, git_context: &GitContext) -> Result<Option<String>, AppError> {
+    let mut git_args: Vec<String> = match input {
+        TaskInput::None => return Ok(None),
+        TaskInput::Stdin => {
+            if io::stdin().is_terminal() {
+                ui::status("Reading the task's input from stdin (end it with Ctrl-D)");
+            }
+            let mut text = String::new();
+            io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|e| AppError::Io("reading stdin".to_string(), e))?;
+            return Ok(Some(text));
+        }
+        TaskInput::StagedDiff => vec!["diff".to_string(), "--cached".to_string()],
+        TaskInput::Diff => vec!["diff".to_string()],
+        TaskInput::Log => {
+            let mut log_args = vec!["log".to_string(), "--no-merges".to_string(), "--format=medium".to_string()];
+            if args.is_empty() {
+                log_args.push(DEFAULT_LOG_COUNT.to_string());
+            } else {
+                log_args.extend(args.iter().cloned());
+            }
+            log_args
+        }
+    };
+    git_context.require_repository()?;
+    if let Some(scope) = scope::resolve(git_context, config.scope.as_deref())? {
+        git_args.extend(["--".to_string(), scope.pathspec()]);
+    }
+    let output = execute_git_command_and_capture_output(&git_args)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: format!("git {}", git_args.join(" ")),
+            status_code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        }
+        .into());
+    }
+    Ok(Some(output.stdout))
+}
+
+fn describe(input: TaskInput) -> &'static str {
+    match input {
+        TaskInput::StagedDiff => "staged diff",
+        TaskInput::Diff => "unstaged diff",
+        TaskInput::Log => "log",
+        TaskInput::Stdin => "input on stdin",
+        TaskInput::None => "input",
+    }
+}
+
+async fn ask(config: &AppConfig, name: &str, task: &Task, args: &str, input: Option<String>) -> Result<AiReply, AIError> {
+    let parameters = config.model_parameters("task");
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, input.is_some())?;
+    let prompt = task.prompt.render(|name| match name {
+        "language" => config.explain.language.clone().unwrap_or_else(|| "English".to_string()),
+        "args" => args.to_string(),
+        _ => String::new(),
+    });
+    let messages = match input {
+        Some(input) => vec![
+            ChatMessage { role: "system".to_string(), content: format!("{}\n\n{}", prompt, UNTRUSTED_CONTENT_RULES) },
+            ChatMessage { role: "user".to_string(), content: untrusted::fence(describe(task.input), &input) },
+        ],
+        None => vec![ChatMessage { role: "user".to_string(), content: prompt }],
+    };
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages,
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    let response = send_chat_request(config, &parameters.api_url, &request).await?;
+    let choice = response.choices.first().ok_or(AIError::NoChoiceInResponse)?;
+    let text = clean_ai_output(&choice.message.content);
+    if text.trim().is_empty() {
+        return Err(AIError::EmptyMessage);
+    }
+    let text = post_generate::apply(task.post_generate_cmd.as_deref(), name, &text)?;
+    Ok(AiReply { text, model: response.model.clone(), usage: Some(response.usage.clone()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_task_reads_input_and_checks_the_prompt() {
+        let path = Path::new("tasks/typos.toml");
+        let task = parse_task(path, "description = \"Typos\"\ninput = \"staged-diff\"\nprompt = \"Find typos. {{args}}\"\n").unwrap();
+        assert_eq!(task.input, TaskInput::StagedDiff);
+        assert_eq!(task.prompt.render(|_| "in docs".to_string()), "Find typos. in docs");
+        assert_eq!(parse_task(path, "prompt = \"Hi\"").unwrap().input, TaskInput::None);
+
+        assert!(matches!(parse_task(path, "input = \"tape\"\nprompt = \"Hi\""), Err(ConfigError::TomlParse(..))));
+        assert!(matches!(parse_task(path, "prompt = \"Hi\"\nmodel = \"x\""), Err(ConfigError::TomlParse(..))));
+        match parse_task(path, "prompt = \"\"\"\nok\n{{level}}\"\"\"") {
+            Err(ConfigError::Invalid(_, problems)) => assert!(problems[0].starts_with("prompt line 2: unknown placeholder `{{level}}`")),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
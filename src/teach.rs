@@ -0,0 +1,162 @@
+// git-enhancer/src/teach.rs
+//! Teach mode: a short tip after a command passed through to git.
+//!
+//! The tip is requested while git runs, so it costs no waiting when the model
+//! is quick, and is dropped if it isn't ready shortly after git finishes. At
+//! most one tip is shown per `teach_interval_minutes`.
+
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::task::JoinHandle;
+
+use crate::ai_utils::{ChatMessage, OpenAIChatRequest, clean_ai_output, send_background_chat_request};
+use crate::config::{self, AppConfig, ConfigPaths};
+use crate::state_file::write_atomic;
+use crate::style::{self, Stream, Style};
+use crate::ui;
+
+/// How long to wait for a tip once git has finished
+const TIP_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+/// Records when the last tip was shown, in seconds since the epoch
+const LAST_TIP_FILE_NAME: &str = "last-tip";
+
+/// The model's answer when it has nothing worth saying
+const NO_TIP: &str = "NONE";
+
+const TIP_PROMPT: &str = "The user just ran the git command below successfully. Reply with one \
+    concise, practical tip of at most two sentences that is directly relevant to it, such as a \
+    newer equivalent command (`git switch -c` for `git checkout -b`), a safer option or a related \
+    time saver. Do not explain the command itself and do not use headings. If there is no \
+    genuinely useful tip, reply with NONE.";
+
+/// A tip being fetched in the background
+pub struct PendingTip(JoinHandle<Option<String>>);
+
+/// Starts fetching a tip for `git <args>` if teach mode is on and a tip is due.
+pub fn start(config: &AppConfig, args: &[String]) -> Option<PendingTip> {
+    if !config.explain.teach_mode || !could_show_tip(args) {
+        return None;
+    }
+    let interval = Duration::from_secs(config.explain.teach_interval_minutes() * 60);
+    if !is_due(read_last_tip(), SystemTime::now(), interval) {
+        return None;
+    }
+    let parameters = config.model_parameters("explain");
+    if let Err(e) = config.policy.check_request(&parameters.api_url, &parameters.model, false) {
+        tracing::debug!("No tip: {}", e);
+        return None;
+    }
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: TIP_PROMPT.to_string() },
+            ChatMessage { role: "user".to_string(), content: format!("git {}", args.join(" ")) },
+        ],
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    let config = config.clone();
+    Some(PendingTip(tokio::spawn(async move {
+        match send_background_chat_request(&config, &parameters.api_url, &request).await {
+            Ok(response) => response.choices.first().map(|choice| clean_ai_output(&choice.message.content)),
+            Err(e) => {
+                tracing::debug!("No tip: {}", e);
+                None
+            }
+        }
+    })))
+}
+
+/// Whether a tip could follow `git <args>`, judged from the config file alone.
+///
+/// Cheap enough to decide whether the configuration has to be loaded at all
+/// for a command passed through to git.
+pub fn may_show_tip(args: &[String]) -> bool {
+    could_show_tip(args)
+        && config::peek_setting(&ConfigPaths::from_env(), "explain.teach_mode").and_then(|value| value.as_bool()) == Some(true)
+}
+
+fn could_show_tip(args: &[String]) -> bool {
+    !args.is_empty() && !ui::is_quiet() && io::stderr().is_terminal()
+}
+
+impl PendingTip {
+    /// Prints the tip if it arrives in time and says something.
+    pub async fn show(self) {
+        let mut handle = self.0;
+        let tip = match tokio::time::timeout(TIP_GRACE_PERIOD, &mut handle).await {
+            Ok(Ok(tip)) => tip,
+            Ok(Err(e)) => {
+                tracing::debug!("Tip task failed: {}", e);
+                None
+            }
+            Err(_) => {
+                tracing::debug!("Tip not ready in time; dropping it");
+                handle.abort();
+                None
+            }
+        };
+        let Some(tip) = tip.map(|tip| tip.trim().to_string()) else {
+            return;
+        };
+        if tip.is_empty() || tip.trim_end_matches('.') == NO_TIP {
+            return;
+        }
+        eprintln!("\n{} {}", style::paint(Stream::Stderr, Style::Cyan, "tip:"), tip);
+        record_last_tip(SystemTime::now());
+    }
+}
+
+/// Whether enough time has passed since the tip shown at `last`
+fn is_due(last: Option<SystemTime>, now: SystemTime, interval: Duration) -> bool {
+    match last {
+        Some(last) => now.duration_since(last).is_ok_and(|elapsed| elapsed >= interval),
+        None => true,
+    }
+}
+
+/// Kept with the other state under the data directory, not next to a
+/// configuration file given with `--config`
+fn last_tip_path() -> Option<PathBuf> {
+    Some(dirs::data_dir()?.join("gitie").join(LAST_TIP_FILE_NAME))
+}
+
+fn read_last_tip() -> Option<SystemTime> {
+    let seconds = fs::read_to_string(last_tip_path()?).ok()?.trim().parse().ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds))
+}
+
+fn record_last_tip(now: SystemTime) {
+    let seconds = now.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+    let Some(path) = last_tip_path() else {
+        return;
+    };
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| write_atomic(&path, seconds.to_string()));
+    if let Err(e) = written {
+        tracing::debug!("Could not record the tip time: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tips_are_rate_limited() {
+        let hour = Duration::from_secs(3600);
+        let now = UNIX_EPOCH + Duration::from_secs(100_000);
+        assert!(is_due(None, now, hour));
+        assert!(is_due(Some(now - hour), now, hour));
+        assert!(!is_due(Some(now - Duration::from_secs(60)), now, hour));
+        // A clock that went backwards doesn't unlock a tip
+        assert!(!is_due(Some(now + hour), now, hour));
+    }
+}
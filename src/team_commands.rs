@@ -0,0 +1,249 @@
+//! The `team-summary` subcommand.
+
+use serde::Serialize;
+use std::io::{self, BufRead};
+
+use crate::ai_utils::{AiReply, ChatMessage, OpenAIChatRequest, clean_ai_output, send_chat_request};
+use crate::cli::TeamSummaryArgs;
+use crate::config::AppConfig;
+use crate::errors::{AIError, AppError, GitError};
+use crate::git_commands::stream_git_command_output;
+use crate::git_context::GitContext;
+use crate::history_selection::Selection;
+use crate::output;
+use crate::scope;
+
+const TEAM_SUMMARY_PROMPT: &str = "You write sprint review digests for a team lead. Below are \
+    the commits of each contributor in a time range, grouped by author with their commit counts \
+    and line changes. For every contributor, in the order given, write a heading with their name \
+    followed by two to four bullet points summarizing what they worked on, grouping related \
+    commits into themes instead of repeating commit subjects. Only use the information given.";
+
+/// Start of the range when neither a range nor `--since` is given
+const DEFAULT_SINCE: &str = "2 weeks ago";
+
+/// Commit subjects listed per author in the prompt; the counts cover all commits
+const MAX_SUBJECTS_PER_AUTHOR: usize = 60;
+
+/// Bytes of `git log` output kept in memory; the rest is read back from disk
+const LOG_MEMORY_LIMIT: usize = 8 * 1024 * 1024;
+
+/// One contributor's commits in the range
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+struct AuthorActivity {
+    name: String,
+    email: String,
+    commits: usize,
+    insertions: u64,
+    deletions: u64,
+    /// Commit subjects, newest first
+    subjects: Vec<String>,
+}
+
+/// Handles the `team-summary` subcommand
+///
+/// Counts come from `git log` (honoring `.mailmap`); the AI writes the digest.
+/// Without a range or `--since`, the last two weeks are covered.
+///
+/// # Arguments
+///
+/// * `args` - Team summary arguments from CLI
+/// * `config` - Application configuration
+/// * `git_context` - Git and repository facts of this run
+///
+/// # Returns
+///
+/// * `Result<(), AppError>` - Success or an error
+pub async fn handle_team_summary(args: TeamSummaryArgs, config: &AppConfig, git_context: &GitContext) -> Result<(), AppError> {
+    git_context.require_repository()?;
+    let mut history = args.history;
+    if history.range.is_none() && history.since.is_none() {
+        history.since = Some(DEFAULT_SINCE.to_string());
+    }
+    let selection = Selection::check(&history, "HEAD")?;
+    let mut log_args = vec![
+        "log".to_string(),
+        "--no-merges".to_string(),
+        "--shortstat".to_string(),
+        "--format=%x1e%aN%x1f%aE%x1f%s".to_string(),
+    ];
+    let scope = scope::resolve(git_context, config.scope.as_deref())?;
+    log_args.extend(selection.log_args(scope.as_ref()));
+    let mut output = stream_git_command_output(&log_args, LOG_MEMORY_LIMIT)?;
+    if !output.status.success() {
+        return Err(GitError::CommandFailed {
+            command: "git log --shortstat".to_string(),
+            status_code: output.status.code(),
+            stdout: output.stdout.text().unwrap_or_default(),
+            stderr: output.stderr,
+        }
+        .into());
+    }
+    let authors = output
+        .stdout
+        .reader()
+        .and_then(group_by_author)
+        .map_err(|e| AppError::Io("reading the output of git log".to_string(), e))?;
+    let data = serde_json::json!({
+        "range": selection.range,
+        "since": selection.since,
+        "until": selection.until,
+        "author_patterns": selection.authors,
+        "paths": selection.paths,
+        "scope": scope.as_ref().map(ToString::to_string),
+        "authors": authors,
+    });
+
+    let reply = if authors.is_empty() {
+        AiReply::local(format!("No commits {}.", selection))
+    } else {
+        write_digest(config, &selection.to_string(), &authors).await?
+    };
+    output::print_ai_reply_with_data(&config.output, "team-summary", &reply, Some(&data), 0)
+}
+
+/// Groups `git log --shortstat --format=%x1e%aN%x1f%aE%x1f%s` output by author
+/// email, most active first.
+///
+/// Reads one commit at a time, so the log never has to fit in memory.
+fn group_by_author(mut output: impl BufRead) -> io::Result<Vec<AuthorActivity>> {
+    let mut authors: Vec<AuthorActivity> = Vec::new();
+    let mut buffer = Vec::new();
+    loop {
+        buffer.clear();
+        if output.read_until(b'\x1e', &mut buffer)? == 0 {
+            break;
+        }
+        let record = String::from_utf8_lossy(&buffer);
+        let record = record.trim_end_matches('\x1e');
+        if record.trim().is_empty() {
+            continue;
+        }
+        let mut lines = record.lines();
+        let mut fields = lines.next().unwrap_or_default().splitn(3, '\x1f');
+        let (Some(name), Some(email), Some(subject)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        let (insertions, deletions) = lines.find(|line| !line.trim().is_empty()).map_or((0, 0), parse_shortstat);
+
+        let index = match authors.iter().position(|author| author.email.eq_ignore_ascii_case(email)) {
+            Some(index) => index,
+            None => {
+                authors.push(AuthorActivity {
+                    name: name.to_string(),
+                    email: email.to_string(),
+                    commits: 0,
+                    insertions: 0,
+                    deletions: 0,
+                    subjects: Vec::new(),
+                });
+                authors.len() - 1
+            }
+        };
+        let author = &mut authors[index];
+        author.commits += 1;
+        author.insertions += insertions;
+        author.deletions += deletions;
+        author.subjects.push(subject.to_string());
+    }
+    // Stable, so equally active authors keep the log's order
+    authors.sort_by_key(|author| std::cmp::Reverse(author.commits));
+    Ok(authors)
+}
+
+/// Insertions and deletions from a line like ` 3 files changed, 10 insertions(+), 2 deletions(-)`
+fn parse_shortstat(line: &str) -> (u64, u64) {
+    let mut insertions = 0;
+    let mut deletions = 0;
+    for part in line.split(',') {
+        let mut words = part.split_whitespace();
+        let (Some(number), Some(what)) = (words.next(), words.next()) else {
+            continue;
+        };
+        let number = number.parse().unwrap_or(0);
+        if what.starts_with("insertion") {
+            insertions = number;
+        } else if what.starts_with("deletion") {
+            deletions = number;
+        }
+    }
+    (insertions, deletions)
+}
+
+/// The prompt for the commits `selection` (in words) covers
+fn build_team_summary_prompt(selection: &str, authors: &[AuthorActivity]) -> String {
+    let mut prompt = format!("Commits {}:\n", selection);
+    for author in authors {
+        prompt.push_str(&format!(
+            "\n## {} <{}>: {} commits, +{} -{} lines\n",
+            author.name, author.email, author.commits, author.insertions, author.deletions
+        ));
+        for subject in author.subjects.iter().take(MAX_SUBJECTS_PER_AUTHOR) {
+            prompt.push_str(&format!("- {}\n", subject));
+        }
+        if author.subjects.len() > MAX_SUBJECTS_PER_AUTHOR {
+            prompt.push_str(&format!("- ... and {} more\n", author.subjects.len() - MAX_SUBJECTS_PER_AUTHOR));
+        }
+    }
+    prompt
+}
+
+async fn write_digest(config: &AppConfig, selection: &str, authors: &[AuthorActivity]) -> Result<AiReply, AIError> {
+    let parameters = config.model_parameters("explain");
+    // Commit subjects and author names are repository content
+    config
+        .policy
+        .check_request(&parameters.api_url, &parameters.model, true)?;
+    let request = OpenAIChatRequest {
+        model: parameters.model,
+        messages: vec![
+            ChatMessage { role: "system".to_string(), content: TEAM_SUMMARY_PROMPT.to_string() },
+            ChatMessage { role: "user".to_string(), content: build_team_summary_prompt(selection, authors) },
+        ],
+        temperature: Some(parameters.temperature),
+        stream: false,
+        max_tokens: parameters.max_tokens,
+    };
+    let response = send_chat_request(config, &parameters.api_url, &request).await?;
+    let choice = response.choices.first().ok_or(AIError::NoChoiceInResponse)?;
+    let text = clean_ai_output(&choice.message.content);
+    if text.trim().is_empty() {
+        return Err(AIError::EmptyMessage);
+    }
+    Ok(AiReply { text, model: response.model.clone(), usage: Some(response.usage.clone()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_by_author_sums_commits_and_lines() {
+        let output = "\x1eAnn\x1fann@example.com\x1fFix pager\n\n 1 file changed, 3 insertions(+)\n\
+                      \x1eBob\x1fbob@example.com\x1fAdd docs\n\n 2 files changed, 10 insertions(+), 1 deletion(-)\n\
+                      \x1eAnn\x1fANN@example.com\x1fRemove dead code\n\n 1 file changed, 7 deletions(-)\n\
+                      \x1eBob\x1fbob@example.com\x1fEmpty commit\n\
+                      \x1eBob\x1fbob@example.com\x1fTypo\n\n 1 file changed, 1 insertion(+), 1 deletion(-)\n";
+        let authors = group_by_author(output.as_bytes()).unwrap();
+        assert_eq!(
+            authors.iter().map(|a| (a.name.as_str(), a.commits, a.insertions, a.deletions)).collect::<Vec<_>>(),
+            vec![("Bob", 3, 11, 2), ("Ann", 2, 3, 7)]
+        );
+        assert_eq!(authors[1].subjects, vec!["Fix pager", "Remove dead code"]);
+    }
+
+    #[test]
+    fn test_team_summary_prompt_caps_subjects() {
+        let author = AuthorActivity {
+            name: "Ann".to_string(),
+            email: "ann@example.com".to_string(),
+            commits: MAX_SUBJECTS_PER_AUTHOR + 2,
+            insertions: 5,
+            deletions: 1,
+            subjects: (0..MAX_SUBJECTS_PER_AUTHOR + 2).map(|i| format!("Change {}", i)).collect(),
+        };
+        let prompt = build_team_summary_prompt("since 2 weeks ago", &[author]);
+        assert!(prompt.starts_with("Commits since 2 weeks ago:\n\n## Ann <ann@example.com>: 62 commits, +5 -1 lines\n"));
+        assert!(prompt.ends_with("- ... and 2 more\n"));
+    }
+}
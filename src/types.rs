@@ -1,3 +1,4 @@
+
 use std::process::ExitStatus;
 use std::collections::HashMap;
 
@@ -100,7 +101,7 @@ pub struct GitCommit {
 }
 
 /// Represents the status of files in a Git repository
-#[derive(Debug, Default)]
+#[derive(Debug, Default, PartialEq)]
 pub struct GitStatus {
     /// Files that are staged for commit
     pub staged: Vec<GitFileStatus>,
@@ -110,13 +111,16 @@ pub struct GitStatus {
     
     /// Files that are untracked
     pub untracked: Vec<String>,
+
+    /// Files with unresolved merge conflicts
+    pub conflicted: Vec<String>,
     
     /// Current branch name
     pub current_branch: Option<String>,
 }
 
 /// Represents the status of a specific file in Git
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct GitFileStatus {
     /// Path to the file
     pub path: String,
@@ -177,4 +181,55 @@ pub struct GitConfig {
     
     /// Structured access to common sections
     pub sections: HashMap<String, HashMap<String, String>>,
-}
\ No newline at end of file
+}
+/// One line of `git blame` output
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitBlameLine {
+    /// Hash of the commit that last changed the line
+    pub commit: String,
+
+    /// Author of that commit
+    pub author: String,
+
+    /// 1-based line number in the current file
+    pub line_number: usize,
+
+    /// The line's content, without the trailing newline
+    pub content: String,
+}
+
+/// A multi-step operation the repository is in the middle of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InProgressOperation {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    Bisect,
+}
+
+impl std::fmt::Display for InProgressOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            InProgressOperation::Merge => "merge",
+            InProgressOperation::Rebase => "rebase",
+            InProgressOperation::CherryPick => "cherry-pick",
+            InProgressOperation::Revert => "revert",
+            InProgressOperation::Bisect => "bisect",
+        })
+    }
+}
+
+/// Repository state that commands may need to adapt to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RepositoryState {
+    /// HEAD points at a commit rather than a branch
+    pub detached_head: bool,
+
+    /// The operation in progress, if any
+    pub operation: Option<InProgressOperation>,
+
+    /// The message git prepared for the commit concluding the operation (`MERGE_MSG`),
+    /// without comment lines
+    pub prepared_message: Option<String>,
+}
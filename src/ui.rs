@@ -0,0 +1,77 @@
+// git-enhancer/src/ui.rs
+//! Messages meant for the user, as opposed to `tracing` logs.
+//!
+//! Logs are for debugging and follow `-v`/`RUST_LOG`; these follow git's
+//! conventions instead (`error: ...`, `warning: ...`) and only `--quiet` affects them.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::style::{self, Stream, Style};
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// How chatty the process is, from the leading `-q`/`-v` options
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Verbosity {
+    pub quiet: bool,
+    /// Number of `-v` given
+    pub verbose: u8,
+}
+
+impl Verbosity {
+    /// The `tracing` filter for these options, or `None` to leave it to `RUST_LOG`.
+    pub fn log_filter(&self) -> Option<&'static str> {
+        match (self.quiet, self.verbose) {
+            (true, _) => Some("error"),
+            (false, 0) => None,
+            (false, 1) => Some("info"),
+            (false, 2) => Some("debug"),
+            (false, _) => Some("trace"),
+        }
+    }
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Whether progress and status output should be left out
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Reports what is happening, e.g. a retried request. Suppressed by `--quiet`.
+pub fn status(message: &str) {
+    if !is_quiet() {
+        eprintln!("{}", message);
+    }
+}
+
+/// Reports something the user should know about even with `--quiet`.
+pub fn warning(message: &str) {
+    eprintln!("{} {}", style::paint(Stream::Stderr, Style::Yellow, "warning:"), message);
+}
+
+/// Suggests how to fix the problem just reported, like git's `hint:` lines.
+pub fn hint(message: &str) {
+    eprintln!("{} {}", style::paint(Stream::Stderr, Style::Yellow, "hint:"), message);
+}
+
+/// Reports the error that ends the command.
+pub fn error(message: &str) {
+    eprintln!("{} {}", style::paint(Stream::Stderr, Style::Red, "error:"), message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_filter_follows_verbosity() {
+        assert_eq!(Verbosity::default().log_filter(), None);
+        assert_eq!(Verbosity { quiet: false, verbose: 1 }.log_filter(), Some("info"));
+        assert_eq!(Verbosity { quiet: false, verbose: 2 }.log_filter(), Some("debug"));
+        assert_eq!(Verbosity { quiet: false, verbose: 5 }.log_filter(), Some("trace"));
+        assert_eq!(Verbosity { quiet: true, verbose: 2 }.log_filter(), Some("error"));
+    }
+}
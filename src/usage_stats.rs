@@ -0,0 +1,249 @@
+// git-enhancer/src/usage_stats.rs
+//! Local usage statistics, shown by the `stats` subcommand.
+//!
+//! With `[stats] enabled = true`, git-enhancer appends a line to `stats.jsonl`
+//! under `~/.local/share/gitie` (the platform's data directory) for every
+//! subcommand it runs, every AI request (its model, how long it took and
+//! whether it succeeded) and every proposed commit message (accepted, edited
+//! or rejected). Only names, times and outcomes are kept: no arguments,
+//! prompts, replies or repository content. Nothing ever leaves the machine;
+//! `stats` summarises the file and deleting it starts over. Commands passed
+//! straight through to git are not counted. Recording never fails the command.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::AppConfig;
+use crate::interaction_log::Decision;
+use crate::state_file;
+
+const STATS_FILE_NAME: &str = "stats.jsonl";
+
+// 本地使用统计的配置
+#[derive(Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct StatsConfig {
+    /// Record local usage statistics for `stats`
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// Something worth counting
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// A git-enhancer subcommand, e.g. `commit --ai` or `explain`
+    Command { name: String },
+    /// A request to the model
+    AiRequest { model: String, latency_ms: u64, ok: bool },
+    /// What the user did with a proposal of `task`
+    Suggestion { task: String, decision: Decision },
+}
+
+/// One line of the statistics file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Record {
+    /// Unix time it happened
+    time: u64,
+    #[serde(flatten)]
+    event: Event,
+}
+
+/// Appends `event` to the statistics file when they are enabled.
+pub fn record(config: &AppConfig, event: Event) {
+    if !config.stats.enabled {
+        return;
+    }
+    let record = Record { time: unix_now(), event };
+    let appended = serde_json::to_string(&record)
+        .map_err(io::Error::other)
+        .and_then(|line| state_file::append_line(&stats_path()?, &line));
+    if let Err(e) = appended {
+        tracing::debug!("Could not write to the statistics file: {}", e);
+    }
+}
+
+/// Records a request to `model` that took `latency`
+pub fn record_ai_request(config: &AppConfig, model: &str, latency: Duration, ok: bool) {
+    let latency_ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+    record(config, Event::AiRequest { model: model.to_string(), latency_ms, ok });
+}
+
+/// Where the statistics are kept
+pub fn stats_path() -> io::Result<PathBuf> {
+    Ok(dirs::data_dir()
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "no data directory"))?
+        .join("gitie")
+        .join(STATS_FILE_NAME))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// What the statistics file adds up to
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Summary {
+    /// Unix time of the first record
+    pub since: Option<u64>,
+    /// Runs of each subcommand
+    pub commands: BTreeMap<String, u64>,
+    /// Outcomes of the proposals of each task
+    pub suggestions: BTreeMap<String, Outcomes>,
+    pub requests: u64,
+    pub failed_requests: u64,
+    /// Latency of successful requests, in milliseconds
+    pub latency_ms: Option<Percentiles>,
+}
+
+/// How the proposals of a task ended
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+pub struct Outcomes {
+    pub accepted: u64,
+    pub edited: u64,
+    pub rejected: u64,
+}
+
+impl Outcomes {
+    fn total(&self) -> u64 {
+        self.accepted + self.edited + self.rejected
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct Percentiles {
+    pub p50: u64,
+    pub p90: u64,
+    pub p99: u64,
+}
+
+/// The summary of the statistics recorded so far; empty when there are none.
+///
+/// Lines that don't parse, e.g. one cut short by a full disk, are skipped.
+pub fn summarize() -> io::Result<Summary> {
+    let text = match fs::read_to_string(stats_path()?) {
+        Ok(text) => text,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Summary::default()),
+        Err(e) => return Err(e),
+    };
+    let records = text.lines().filter_map(|line| serde_json::from_str::<Record>(line).ok());
+    Ok(summarize_records(records))
+}
+
+fn summarize_records(records: impl Iterator<Item = Record>) -> Summary {
+    let mut summary = Summary::default();
+    let mut latencies = Vec::new();
+    for record in records {
+        summary.since = Some(summary.since.map_or(record.time, |since| since.min(record.time)));
+        match record.event {
+            Event::Command { name } => *summary.commands.entry(name).or_default() += 1,
+            Event::AiRequest { latency_ms, ok, .. } => {
+                summary.requests += 1;
+                if ok {
+                    latencies.push(latency_ms);
+                } else {
+                    summary.failed_requests += 1;
+                }
+            }
+            Event::Suggestion { task, decision } => {
+                let outcomes = summary.suggestions.entry(task).or_default();
+                match decision {
+                    Decision::Accepted => outcomes.accepted += 1,
+                    Decision::Edited => outcomes.edited += 1,
+                    Decision::Rejected => outcomes.rejected += 1,
+                }
+            }
+        }
+    }
+    latencies.sort_unstable();
+    // Nearest-rank percentiles
+    let percentile = |p: usize| latencies[(latencies.len() * p).div_ceil(100).max(1) - 1];
+    if !latencies.is_empty() {
+        summary.latency_ms = Some(Percentiles { p50: percentile(50), p90: percentile(90), p99: percentile(99) });
+    }
+    summary
+}
+
+impl Summary {
+    /// The summary as text for the terminal
+    pub fn render(&self) -> String {
+        let Some(since) = self.since else {
+            return String::new();
+        };
+        let days = unix_now().saturating_sub(since) / 86_400 + 1;
+        let mut lines = vec![format!("Usage over the last {} day{}", days, if days == 1 { "" } else { "s" })];
+
+        if !self.commands.is_empty() {
+            lines.push(String::new());
+            lines.push("Commands".to_string());
+            let mut commands: Vec<_> = self.commands.iter().collect();
+            commands.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            let width = commands.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+            lines.extend(commands.iter().map(|(name, count)| format!("  {:<width$}  {}", name, count, width = width)));
+        }
+        if !self.suggestions.is_empty() {
+            lines.push(String::new());
+            lines.push("AI suggestions".to_string());
+            for (task, outcomes) in &self.suggestions {
+                let total = outcomes.total();
+                let share = |count: u64| count * 100 / total.max(1);
+                lines.push(format!(
+                    "  {}: {} proposed, {} accepted ({}%), {} edited ({}%), {} rejected ({}%)",
+                    task,
+                    total,
+                    outcomes.accepted,
+                    share(outcomes.accepted),
+                    outcomes.edited,
+                    share(outcomes.edited),
+                    outcomes.rejected,
+                    share(outcomes.rejected)
+                ));
+            }
+        }
+        if self.requests > 0 {
+            lines.push(String::new());
+            lines.push(format!("AI requests: {}, {} failed", self.requests, self.failed_requests));
+            if let Some(latency) = &self.latency_ms {
+                let seconds = |ms: u64| format!("{:.1}s", ms as f64 / 1000.0);
+                lines.push(format!(
+                    "  latency p50 {}, p90 {}, p99 {}",
+                    seconds(latency.p50),
+                    seconds(latency.p90),
+                    seconds(latency.p99)
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_records() {
+        let mut records = vec![
+            Record { time: 200, event: Event::Command { name: "commit --ai".to_string() } },
+            Record { time: 100, event: Event::Command { name: "commit --ai".to_string() } },
+            Record { time: 300, event: Event::Command { name: "explain".to_string() } },
+            Record { time: 300, event: Event::Suggestion { task: "commit".to_string(), decision: Decision::Accepted } },
+            Record { time: 300, event: Event::Suggestion { task: "commit".to_string(), decision: Decision::Rejected } },
+            Record { time: 300, event: Event::AiRequest { model: "m".to_string(), latency_ms: 9_000, ok: false } },
+        ];
+        records.extend((1..=10).map(|i| Record { time: 300, event: Event::AiRequest { model: "m".to_string(), latency_ms: i * 100, ok: true } }));
+        let summary = summarize_records(records.into_iter());
+        assert_eq!(summary.since, Some(100));
+        assert_eq!(summary.commands["commit --ai"], 2);
+        assert_eq!(summary.suggestions["commit"], Outcomes { accepted: 1, edited: 0, rejected: 1 });
+        assert_eq!((summary.requests, summary.failed_requests), (11, 1));
+        assert_eq!(summary.latency_ms, Some(Percentiles { p50: 500, p90: 900, p99: 1000 }));
+
+        let line = serde_json::to_string(&Record { time: 1, event: Event::Command { name: "stats".to_string() } }).unwrap();
+        assert_eq!(line, r#"{"time":1,"event":"command","name":"stats"}"#);
+        assert_eq!(summarize_records(std::iter::empty()).render(), "");
+    }
+}
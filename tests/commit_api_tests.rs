@@ -0,0 +1,96 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use git_enhancer::commit::{self, Config, GenerateOptions};
+use git_enhancer::errors::{AppError, GitError};
+
+// The library API must work without the binary: a repository, a configuration
+// and a chat endpoint are all it needs.
+
+fn test_dir(test_name: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("test_integration_temp_data")
+        .join(test_name);
+    if path.exists() {
+        fs::remove_dir_all(&path).unwrap_or_else(|_| panic!("Failed to remove old test dir: {:?}", path));
+    }
+    fs::create_dir_all(path.join("repo")).unwrap_or_else(|_| panic!("Failed to create test dir: {:?}", path));
+    fs::canonicalize(&path).unwrap()
+}
+
+fn git(repo: &Path, args: &[&str]) {
+    let output = Command::new("git").args(args).current_dir(repo).output().expect("Failed to run git");
+    assert!(output.status.success(), "git {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+}
+
+/// Answers one chat completion request with `message`, returning the endpoint URL
+fn serve_one_reply(message: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/v1/chat/completions", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                content_length = value.trim().parse().unwrap();
+            }
+            if line.trim().is_empty() {
+                break;
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        let reply = serde_json::json!({
+            "id": "1", "object": "chat.completion", "created": 0, "model": "mock-model",
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": message }, "finish_reason": "stop" }],
+            "usage": { "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 },
+        })
+        .to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            reply.len(),
+            reply
+        );
+        reader.get_mut().write_all(response.as_bytes()).unwrap();
+    });
+    url
+}
+
+#[test]
+fn test_generate_returns_a_draft_for_the_staged_changes() {
+    let dir = test_dir("commit_api_generate");
+    let repo = dir.join("repo");
+    git(&repo, &["init", "-q"]);
+    let assets = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets");
+    let config_dir = dir.join("config");
+    fs::create_dir_all(&config_dir).unwrap();
+    for prompt in ["commit-prompt", "explanation-prompt"] {
+        fs::copy(assets.join(prompt), config_dir.join(prompt)).unwrap();
+    }
+    let url = serve_one_reply("Add greeting\n\nSay hello to new users.");
+    let config_file = config_dir.join("ci.toml");
+    fs::write(&config_file, format!("[ai]\napi_url = \"{}\"\nmodel_name = \"mock-model\"\n", url)).unwrap();
+    let config = Config::from_file(&config_file).expect("config should load");
+
+    let before = std::env::current_dir().unwrap();
+    match commit::generate(&repo, GenerateOptions::new().config(config.clone())) {
+        Err(AppError::Git(GitError::NoStagedChanges)) => {}
+        other => panic!("expected NoStagedChanges, got {:?}", other),
+    }
+
+    fs::write(repo.join("hello.txt"), "hello\n").unwrap();
+    git(&repo, &["add", "hello.txt"]);
+    let draft = commit::generate(&repo, GenerateOptions::new().config(config)).expect("a draft");
+    assert_eq!(draft.subject(), "Add greeting");
+    assert_eq!(draft.body(), "Say hello to new users.");
+    assert_eq!(draft.model, "mock-model");
+    assert_eq!(draft.usage.map(|usage| usage.total_tokens), Some(15));
+    assert_eq!(std::env::current_dir().unwrap(), before);
+}
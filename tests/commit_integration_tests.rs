@@ -1,3 +1,6 @@
+// Fixtures that no test uses yet, kept as upstream wrote them
+#![allow(dead_code, clippy::expect_fun_call)]
+
 use std::env;
 use std::fs;
 use std::path::PathBuf;
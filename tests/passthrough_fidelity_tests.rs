@@ -0,0 +1,226 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+// Passthrough must be indistinguishable from running git directly: same stdout,
+// same stderr, same exit code.
+
+fn test_dir(test_name: &str) -> PathBuf {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("test_integration_temp_data")
+        .join(test_name);
+    if path.exists() {
+        fs::remove_dir_all(&path)
+            .unwrap_or_else(|_| panic!("Failed to remove old test dir: {:?}", path));
+    }
+    fs::create_dir_all(path.join("home"))
+        .unwrap_or_else(|_| panic!("Failed to create test dir: {:?}", path));
+    fs::canonicalize(&path).unwrap()
+}
+
+// Both binaries get the same isolated environment so only gitie itself can differ
+fn command_in(program: &str, dir: &Path, home: &Path) -> Command {
+    let assets = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("assets");
+    let mut command = Command::new(program);
+    command
+        .current_dir(dir)
+        .env("HOME", home)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        // The test directories live inside this crate's own checkout
+        .env("GIT_CEILING_DIRECTORIES", home.parent().unwrap())
+        .env("GIT_AUTHOR_NAME", "Test User")
+        .env("GIT_AUTHOR_EMAIL", "test@example.com")
+        .env("GIT_COMMITTER_NAME", "Test User")
+        .env("GIT_COMMITTER_EMAIL", "test@example.com")
+        .env("GIT_AUTHOR_DATE", "2024-01-01T00:00:00Z")
+        .env("GIT_COMMITTER_DATE", "2024-01-01T00:00:00Z")
+        .env("GITIE_ASSETS_CONFIG", assets.join("config.example.toml"))
+        .env("GITIE_ASSETS_COMMIT_PROMPT", assets.join("commit-prompt"))
+        .env("GITIE_ASSETS_EXPLANATION_PROMPT", assets.join("explanation-prompt"))
+        .env_remove("RUST_LOG")
+        .env_remove("GIT_DIR")
+        .env_remove("GIT_WORK_TREE");
+    command
+}
+
+fn run(program: &str, dir: &Path, home: &Path, args: &[&str]) -> Output {
+    command_in(program, dir, home)
+        .args(args)
+        .output()
+        .unwrap_or_else(|e| panic!("Failed to run {} {:?}: {}", program, args, e))
+}
+
+fn assert_same_as_git(dir: &Path, home: &Path, args: &[&str]) {
+    let expected = run("git", dir, home, args);
+    let actual = run(env!("CARGO_BIN_EXE_git-enhancer"), dir, home, args);
+    assert_eq!(
+        actual.status.code(),
+        expected.status.code(),
+        "exit code differs for {:?}",
+        args
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&actual.stdout),
+        String::from_utf8_lossy(&expected.stdout),
+        "stdout differs for {:?}",
+        args
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&actual.stderr),
+        String::from_utf8_lossy(&expected.stderr),
+        "stderr differs for {:?}",
+        args
+    );
+}
+
+fn setup_repo(test_name: &str) -> (PathBuf, PathBuf) {
+    let root = test_dir(test_name);
+    let home = root.join("home");
+    let repo = root.join("repo");
+    fs::create_dir_all(&repo).unwrap();
+    for args in [
+        &["init", "-q", "-b", "main"][..],
+        &["commit", "-q", "--allow-empty", "-m", "Initial"],
+    ] {
+        assert!(run("git", &repo, &home, args).status.success(), "git {:?} failed", args);
+    }
+    fs::write(repo.join("file.txt"), "hello\n").unwrap();
+    // Run gitie once so first-run initialization doesn't show up in the comparisons
+    run(env!("CARGO_BIN_EXE_git-enhancer"), &repo, &home, &["status"]);
+    (repo, home)
+}
+
+#[test]
+fn test_passthrough_matches_git_for_successful_commands() {
+    let (repo, home) = setup_repo("passthrough_success");
+    for args in [
+        &["status"][..],
+        &["status", "--porcelain=v1"],
+        &["log", "--oneline"],
+        &["branch", "--list"],
+        &["rev-parse", "HEAD"],
+        &["diff", "--stat"],
+    ] {
+        assert_same_as_git(&repo, &home, args);
+    }
+}
+
+#[test]
+fn test_passthrough_matches_git_for_failing_commands() {
+    let (repo, home) = setup_repo("passthrough_failure");
+    for args in [
+        &["nosuchcommand"][..],
+        &["rev-parse", "--verify", "no-such-ref"],
+        &["config", "--get", "no.such-key"],
+        &["log", "no-such-branch"],
+        &["ls-files", "--error-unmatch", "file.txt"],
+    ] {
+        assert_same_as_git(&repo, &home, args);
+    }
+}
+
+#[test]
+fn test_config_is_gits_own_command() {
+    let (repo, home) = setup_repo("passthrough_config");
+    // git-enhancer's settings are under `gitie-config`; these are git's, whether
+    // or not this git knows the `set` and `get` subcommands
+    for args in [&["config", "set", "ai.temperature", "0.3"][..], &["config", "get", "ai.temperature"]] {
+        assert_same_as_git(&repo, &home, args);
+    }
+}
+
+#[test]
+fn test_commit_passthrough_matches_git() {
+    let (repo, home) = setup_repo("passthrough_commit");
+    // Nothing staged: git commit fails with its own status and message
+    assert_same_as_git(&repo, &home, &["commit", "-m", "Nothing staged"]);
+    assert_same_as_git(&repo, &home, &["commit", "--dry-run"]);
+    assert_same_as_git(&repo, &home, &["commit", "--no-verify", "-m", "Nothing staged"]);
+}
+
+#[test]
+fn test_passthrough_works_outside_a_repository() {
+    let root = test_dir("passthrough_outside_repo");
+    let home = root.join("home");
+    let outside = root.join("outside");
+    fs::create_dir_all(&outside).unwrap();
+    run(env!("CARGO_BIN_EXE_git-enhancer"), &outside, &home, &["--version"]);
+    assert_same_as_git(&outside, &home, &["--version"]);
+    assert_same_as_git(&outside, &home, &["-v"]);
+    assert_same_as_git(&outside, &home, &["status"]);
+    // Plain passthrough doesn't touch the configuration
+    assert!(!home.join(".config/gitie").exists());
+}
+
+#[test]
+fn test_passthrough_ignores_a_broken_configuration() {
+    let (repo, home) = setup_repo("passthrough_broken_config");
+    // Passthrough doesn't set up the configuration directory, so create it here
+    fs::create_dir_all(home.join(".config/gitie")).unwrap();
+    fs::write(home.join(".config/gitie/config.toml"), "[ai\nmodel_name = ").unwrap();
+    assert_same_as_git(&repo, &home, &["status"]);
+    assert_same_as_git(&repo, &home, &["commit", "--dry-run"]);
+    assert_same_as_git(&repo, &home, &["log", "--help"]);
+
+    // AI features still report the problem
+    let output = run(env!("CARGO_BIN_EXE_git-enhancer"), &repo, &home, &["status", "--ai"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("config.toml"));
+}
+
+#[test]
+fn test_no_ai_passes_everything_to_git() {
+    let (repo, home) = setup_repo("passthrough_no_ai");
+    // A broken configuration shows it isn't read
+    fs::create_dir_all(home.join(".config/gitie")).unwrap();
+    fs::write(home.join(".config/gitie/config.toml"), "[ai\nmodel_name = ").unwrap();
+    let expected = run("git", &repo, &home, &["status", "--ai"]);
+    let actual = run(env!("CARGO_BIN_EXE_git-enhancer"), &repo, &home, &["--no-ai", "status", "--ai"]);
+    assert_eq!((actual.status.code(), actual.stderr), (expected.status.code(), expected.stderr));
+
+    let actual = command_in(env!("CARGO_BIN_EXE_git-enhancer"), &repo, &home)
+        .env("GITIE_NO_AI", "1")
+        .args(["commit", "--ai", "--dry-run"])
+        .output()
+        .unwrap();
+    let expected = run("git", &repo, &home, &["commit", "--ai", "--dry-run"]);
+    assert_eq!((actual.status.code(), actual.stderr), (expected.status.code(), expected.stderr));
+}
+
+#[test]
+fn test_commit_reads_its_message_from_stdin() {
+    let (repo, home) = setup_repo("passthrough_commit_stdin");
+    let commit_from_stdin = |program: &str| {
+        let mut child = command_in(program, &repo, &home)
+            .args(["commit", "-q", "--allow-empty", "-F", "-"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"Piped subject\n\nPiped body\n").unwrap();
+        assert!(child.wait().unwrap().success(), "{} commit -F - failed", program);
+        String::from_utf8(run("git", &repo, &home, &["log", "-1", "--format=%B"]).stdout).unwrap()
+    };
+    assert_eq!(commit_from_stdin(env!("CARGO_BIN_EXE_git-enhancer")), commit_from_stdin("git"));
+    assert_eq!(commit_from_stdin(env!("CARGO_BIN_EXE_git-enhancer")), "Piped subject\n\nPiped body\n\n");
+}
+
+#[test]
+fn test_arguments_after_double_dash_are_git_s() {
+    let (repo, home) = setup_repo("passthrough_double_dash");
+    // Files named like git-enhancer's options, and one with a space
+    for name in ["--ai", "--json", "a b.txt"] {
+        fs::write(repo.join(name), "x\n").unwrap();
+    }
+    for args in [
+        &["status", "--short", "--", "--ai", "--json", "a b.txt"][..],
+        &["log", "--oneline", "--", "--ai"],
+        &["add", "--dry-run", "--", "--ai", "a b.txt"],
+        &["diff", "--stat", "--", "--explain"],
+        &["commit", "--dry-run", "-m", "--ai", "--", "--json"],
+        &["commit", "-q", "-m", "Untracked -- --ai", "--", "--json"],
+    ] {
+        assert_same_as_git(&repo, &home, args);
+    }
+}